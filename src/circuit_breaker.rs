@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use reqwest::Url;
+
+use crate::Error;
+
+#[derive(Debug, Clone, Copy)]
+struct HostFailures {
+    consecutive: u32,
+    last: Instant,
+}
+
+type HostTable = Arc<Mutex<HashMap<String, HostFailures>>>;
+
+/// The host table used by [CircuitBreaker::shared], so that failures observed by any script
+/// count towards the same hosts.
+static SHARED_HOSTS: LazyLock<HostTable> = LazyLock::new(|| Arc::new(Mutex::new(HashMap::new())));
+
+/// Tracks consecutive request failures per host. After `max_failures` consecutive failures to a
+/// host, the circuit for that host is open and further requests are refused until `cooldown`
+/// has passed since the last failure. The next request after the cooldown is then let through,
+/// closing the circuit again on success or re-opening it on failure.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    max_failures: u32,
+    cooldown: Duration,
+    hosts: HostTable,
+}
+
+impl CircuitBreaker {
+    /// Create a breaker with its own, unshared, failure counters.
+    pub fn new(max_failures: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            max_failures,
+            cooldown,
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Create a breaker using failure counters shared by all shared breakers in this process.
+    pub fn shared(max_failures: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            max_failures,
+            cooldown,
+            hosts: SHARED_HOSTS.clone(),
+        }
+    }
+
+    /// Check whether a request for `url` may be made, returning [Error::CircuitOpenError] if the
+    /// circuit for its host is open.
+    pub fn check(&self, url: &str) -> Result<(), Error> {
+        let host = host_of(url);
+        let hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+        match hosts.get(&host) {
+            Some(failures)
+                if failures.consecutive >= self.max_failures
+                    && failures.last.elapsed() < self.cooldown =>
+            {
+                debug!("circuit breaker: refusing request to {host}");
+                Err(Error::CircuitOpenError(host))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the outcome of a request for `url`.
+    pub fn record(&self, url: &str, success: bool) {
+        let host = host_of(url);
+
+        let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+
+        if success {
+            hosts.remove(&host);
+        } else {
+            let failures = hosts.entry(host.clone()).or_insert(HostFailures {
+                consecutive: 0,
+                last: Instant::now(),
+            });
+
+            failures.consecutive += 1;
+            failures.last = Instant::now();
+
+            if failures.consecutive == self.max_failures {
+                warn!(
+                    "circuit breaker: {} consecutive failures for {host}, pausing requests \
+                     for {:?}",
+                    failures.consecutive, self.cooldown
+                );
+            }
+        }
+    }
+}
+
+/// The host of `url`, or the entire URL if it has no host.
+fn host_of(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_lowercase()))
+        .unwrap_or_else(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(50));
+
+        assert!(breaker.check("http://a.test/x").is_ok());
+
+        breaker.record("http://a.test/x", false);
+        assert!(breaker.check("http://a.test/y").is_ok());
+
+        breaker.record("http://a.test/y", false);
+        assert!(matches!(
+            breaker.check("http://A.test/z"),
+            Err(Error::CircuitOpenError(host)) if host == "a.test"
+        ));
+
+        // Other hosts are unaffected
+        assert!(breaker.check("http://b.test/").is_ok());
+
+        // Clones share the counters
+        assert!(breaker.clone().check("http://a.test/").is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.check("http://a.test/").is_ok());
+
+        // A failure after the cooldown re-opens the circuit immediately
+        breaker.record("http://a.test/", false);
+        assert!(breaker.check("http://a.test/").is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        breaker.record("http://a.test/", true);
+        breaker.record("http://a.test/", false);
+        assert!(breaker.check("http://a.test/").is_ok());
+    }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("https://Example.com:8080/a?b"), "example.com");
+        assert_eq!(host_of("not a url"), "not a url");
+    }
+}
@@ -0,0 +1,437 @@
+use std::{fs, net::IpAddr};
+
+use chrono::{DateTime, Utc};
+use im::Vector;
+use log::debug;
+use reqwest::Url;
+
+use crate::{Error, util::home::home_dir};
+
+/// A cookie as stored in a [CookieJar].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Expiry as a unix timestamp, or None for session cookies.
+    pub expires: Option<i64>,
+    pub name: String,
+    pub value: String,
+}
+
+impl Cookie {
+    fn is_expired_at(&self, now: i64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+
+        let domain_matches = host == self.domain
+            || (self.include_subdomains && is_subdomain_of(&host, &self.domain));
+
+        let path_matches = url.path() == self.path
+            || (url.path().starts_with(&self.path)
+                && (self.path.ends_with('/') || url.path()[self.path.len()..].starts_with('/')));
+
+        domain_matches && path_matches && (!self.secure || url.scheme() == "https")
+    }
+
+    /// Parse a `Set-Cookie` header value received in response to a request for `url`. Returns
+    /// None if the header is malformed, or if its `Domain` attribute names neither the host of
+    /// `url` nor a domain it is a subdomain of, or names a public suffix such as `co.uk` (RFC
+    /// 6265, section 5.3).
+    fn parse_set_cookie(url: &Url, header: &str, now: i64) -> Option<Cookie> {
+        let mut parts = header.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+
+        if name.trim().is_empty() {
+            return None;
+        }
+
+        let default_path = match url.path().rfind('/') {
+            Some(0) | None => "/".to_string(),
+            Some(index) => url.path()[..index].to_string(),
+        };
+
+        let host = url.host_str()?.to_lowercase();
+
+        let mut cookie = Cookie {
+            domain: host.clone(),
+            include_subdomains: false,
+            path: default_path,
+            secure: false,
+            expires: None,
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        };
+
+        let mut max_age = None;
+
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => (attribute.trim().to_lowercase(), ""),
+            };
+
+            match key.as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_lowercase();
+
+                    if domain != host && !is_subdomain_of(&host, &domain) {
+                        return None;
+                    }
+
+                    // A public suffix is only accepted as the host itself, as a host-only cookie
+                    if psl::suffix_str(&domain) == Some(domain.as_str()) {
+                        if domain != host {
+                            return None;
+                        }
+                    } else {
+                        cookie.domain = domain;
+                        cookie.include_subdomains = true;
+                    }
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "expires" => {
+                    if let Ok(expires) = DateTime::parse_from_rfc2822(value) {
+                        cookie.expires = Some(expires.timestamp());
+                    }
+                }
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                _ => (),
+            }
+        }
+
+        // Max-Age takes precedence over Expires (RFC 6265, section 5.3)
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(now + max_age);
+        }
+
+        Some(cookie)
+    }
+
+    fn to_netscape_line(&self) -> String {
+        let bool_str = |value: bool| if value { "TRUE" } else { "FALSE" };
+
+        format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            if self.include_subdomains { "." } else { "" },
+            self.domain,
+            bool_str(self.include_subdomains),
+            self.path,
+            bool_str(self.secure),
+            self.expires.unwrap_or(0),
+            self.name,
+            self.value,
+        )
+    }
+
+    fn from_netscape_line(line: &str) -> Option<Cookie> {
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        if fields.len() != 7 {
+            return None;
+        }
+
+        let expires = fields[4].parse::<i64>().ok()?;
+
+        Some(Cookie {
+            domain: fields[0].trim_start_matches('.').to_lowercase(),
+            include_subdomains: fields[1] == "TRUE",
+            path: fields[2].to_string(),
+            secure: fields[3] == "TRUE",
+            expires: if expires == 0 { None } else { Some(expires) },
+            name: fields[5].to_string(),
+            value: fields[6].to_string(),
+        })
+    }
+}
+
+/// Whether `host` is a subdomain of `domain`. IP addresses have no subdomains.
+fn is_subdomain_of(host: &str, domain: &str) -> bool {
+    host.parse::<IpAddr>().is_err() && host.ends_with(&format!(".{domain}"))
+}
+
+/// A collection of cookies received from and sent to servers, optionally persisted to a file
+/// in the Netscape `cookies.txt` format.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vector<Cookie>,
+    path: Option<String>,
+}
+
+impl CookieJar {
+    /// Create an empty jar that is not persisted.
+    pub fn new() -> CookieJar {
+        CookieJar::default()
+    }
+
+    /// Open a jar persisted to the file at `path`, where `${HOME}` is replaced with the path of
+    /// the user's home directory. Cookies are loaded from the file if it exists, skipping any
+    /// that have expired, and the jar is saved back to the same file by [CookieJar::save].
+    pub fn open(path: &str) -> Result<CookieJar, Error> {
+        let path = match home_dir() {
+            Ok(home) => path.replace("${HOME}", &home),
+            Err(_) => path.to_string(),
+        };
+
+        let now = Utc::now().timestamp();
+
+        let cookies = match fs::read_to_string(&path) {
+            Ok(text) => text
+                .lines()
+                .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+                .filter_map(Cookie::from_netscape_line)
+                .filter(|cookie| !cookie.is_expired_at(now))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vector::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        debug!("cookie jar: loaded {} cookie(s) from {path}", cookies.len());
+
+        Ok(CookieJar {
+            cookies,
+            path: Some(path),
+        })
+    }
+
+    /// A copy of the jar that is not persisted, so that cookies stored in the copy are not
+    /// saved to the file of this jar.
+    pub fn unpersisted(&self) -> CookieJar {
+        CookieJar {
+            cookies: self.cookies.clone(),
+            path: None,
+        }
+    }
+
+    pub fn cookies(&self) -> &Vector<Cookie> {
+        &self.cookies
+    }
+
+    /// Store the cookies given by the (newline-separated) `Set-Cookie` header values received in
+    /// response to a request for `url`. Cookies that are already expired are removed from the jar.
+    pub fn store(&self, url: &str, set_cookie: &str) -> CookieJar {
+        let Ok(url) = Url::parse(url) else {
+            return self.clone();
+        };
+
+        let now = Utc::now().timestamp();
+        let mut cookies = self.cookies.clone();
+
+        for cookie in set_cookie
+            .lines()
+            .filter_map(|header| Cookie::parse_set_cookie(&url, header, now))
+        {
+            cookies.retain(|existing| {
+                (&existing.domain, &existing.path, &existing.name)
+                    != (&cookie.domain, &cookie.path, &cookie.name)
+            });
+
+            if !cookie.is_expired_at(now) {
+                cookies.push_back(cookie);
+            }
+        }
+
+        CookieJar {
+            cookies,
+            ..self.clone()
+        }
+    }
+
+    /// Get the value of the `Cookie` header to send with a request for `url`, if any.
+    pub fn header(&self, url: &str) -> Option<String> {
+        let url = Url::parse(url).ok()?;
+        let now = Utc::now().timestamp();
+
+        let pairs = self
+            .cookies
+            .iter()
+            .filter(|cookie| !cookie.is_expired_at(now) && cookie.matches(&url))
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>();
+
+        if pairs.is_empty() {
+            None
+        } else {
+            Some(pairs.join("; "))
+        }
+    }
+
+    /// Save the jar to its file, if it was opened using [CookieJar::open].
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(path) = &self.path {
+            let mut text = "# Netscape HTTP Cookie File\n".to_string();
+
+            for cookie in self.cookies.iter() {
+                text.push_str(&cookie.to_netscape_line());
+                text.push('\n');
+            }
+
+            fs::write(path, text)?;
+
+            debug!(
+                "cookie jar: saved {} cookie(s) to {path}",
+                self.cookies.len()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("scrapeycat-test-{name}-{}.txt", process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_store_and_header() {
+        let jar = CookieJar::new()
+            .store("http://example.com/a/b", "a=1\nb=2; Path=/; Max-Age=60")
+            .store("http://example.com/", "c=3; Domain=example.com; Secure");
+
+        assert_eq!(jar.cookies().len(), 3);
+        assert_eq!(
+            jar.header("http://example.com/a/c"),
+            Some("a=1; b=2".to_string())
+        );
+        assert_eq!(jar.header("http://example.com/"), Some("b=2".to_string()));
+        assert_eq!(jar.header("http://example.com/ab"), Some("b=2".to_string()));
+        assert_eq!(
+            jar.header("https://www.example.com/"),
+            Some("c=3".to_string())
+        );
+        assert_eq!(
+            jar.header("https://example.com/a"),
+            Some("a=1; b=2; c=3".to_string())
+        );
+        assert_eq!(jar.header("http://example.org/"), None);
+        assert_eq!(jar.header("not a url"), None);
+    }
+
+    #[test]
+    fn test_store_domain() {
+        let jar = CookieJar::new().store("http://www.example.com/", "a=1; Domain=.Example.com");
+
+        assert_eq!(
+            jar.header("http://other.example.com/"),
+            Some("a=1".to_string())
+        );
+
+        // Domains other than the host and the domains it is a subdomain of are rejected
+        for (url, domain) in [
+            ("http://example.com/", "example.org"),
+            ("http://example.com/", "ample.com"),
+            ("http://example.com/", "www.example.com"),
+            ("http://127.0.0.1/", "0.0.1"),
+        ] {
+            let jar = CookieJar::new().store(url, &format!("a=1; Domain={domain}"));
+            assert!(jar.cookies().is_empty(), "{domain}");
+        }
+
+        // Public suffixes are rejected, unless they are the host itself
+        assert!(
+            CookieJar::new()
+                .store("http://example.com/", "a=1; Domain=com")
+                .cookies()
+                .is_empty()
+        );
+        assert!(
+            CookieJar::new()
+                .store("http://example.co.uk/", "a=1; Domain=co.uk")
+                .cookies()
+                .is_empty()
+        );
+
+        let jar = CookieJar::new().store("http://localhost/", "a=1; Domain=localhost");
+
+        assert_eq!(jar.header("http://localhost/"), Some("a=1".to_string()));
+    }
+
+    #[test]
+    fn test_store_replace_and_expire() {
+        let jar = CookieJar::new()
+            .store("http://example.com/", "a=1\nb=2")
+            .store("http://example.com/", "a=3");
+
+        assert_eq!(
+            jar.header("http://example.com/"),
+            Some("b=2; a=3".to_string())
+        );
+
+        let jar = jar.store("http://example.com/", "b=; Max-Age=0").store(
+            "http://example.com/",
+            "a=; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+        );
+
+        assert_eq!(jar.header("http://example.com/"), None);
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn test_save_and_open() {
+        let path = temp_path("cookie-jar");
+        let _ = fs::remove_file(&path);
+
+        let jar = CookieJar::open(&path).unwrap();
+        assert!(jar.cookies().is_empty());
+
+        jar.store(
+            "https://example.com/",
+            "session=abc; Domain=example.com; Max-Age=3600; Secure\ntheme=dark",
+        )
+        .save()
+        .unwrap();
+
+        let jar = CookieJar::open(&path).unwrap();
+
+        assert_eq!(jar.cookies().len(), 2);
+        assert_eq!(
+            jar.header("https://www.example.com/"),
+            Some("session=abc".to_string())
+        );
+        assert_eq!(
+            jar.header("https://example.com/"),
+            Some("session=abc; theme=dark".to_string())
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_skips_expired() {
+        let path = temp_path("cookie-jar-expired");
+
+        fs::write(
+            &path,
+            "# Netscape HTTP Cookie File\n\
+            example.com\tFALSE\t/\tFALSE\t1\told\tx\n\
+            example.com\tFALSE\t/\tFALSE\t0\tsession\ty\n\
+            example.com\tFALSE\t/\tFALSE\t99999999999\tfresh\tz\n\
+            malformed line\n",
+        )
+        .unwrap();
+
+        let jar = CookieJar::open(&path).unwrap();
+
+        assert_eq!(
+            jar.header("http://example.com/"),
+            Some("session=y; fresh=z".to_string())
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
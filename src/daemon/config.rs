@@ -5,6 +5,8 @@ pub struct Config {
     pub script_dirs: Vec<String>,
     pub script_names: Vec<String>,
     pub suites: Option<Vec<Suite>>,
+    /// The file in which to keep the times at which jobs were last run.
+    pub state_file: Option<String>,
 }
 
 impl Config {
@@ -17,6 +19,11 @@ impl Config {
             script_dirs,
             script_names,
             suites,
+            state_file: None,
         }
     }
+
+    pub fn with_state_file(self, state_file: Option<String>) -> Self {
+        Config { state_file, ..self }
+    }
 }
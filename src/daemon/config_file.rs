@@ -1,6 +1,6 @@
 #![expect(dead_code)]
 
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fs, net::SocketAddr, time::Duration};
 
 use serde::Deserialize;
 
@@ -10,6 +10,7 @@ use crate::{
         config::Config,
         suite::{Job, Suite},
     },
+    scrapelang::program::parse_resolve_addr,
 };
 
 use super::cron::CronSpec;
@@ -21,22 +22,31 @@ pub struct ConfigFile {
 
 impl ConfigFile {
     pub fn get_version(path: &str) -> Result<usize, Error> {
-        match toml::from_str::<ConfigFile>(fs::read_to_string(path)?.as_str())
+        ConfigFile::get_version_from_str(fs::read_to_string(path)?.as_str())
+    }
+
+    pub fn get_version_from_str(text: &str) -> Result<usize, Error> {
+        match toml::from_str::<ConfigFile>(text)
             .map_err(|e| Error::ParseError(e.to_string()))?
             .config_version
         {
-            version @ 1 => Ok(version),
+            version @ (1 | 2) => Ok(version),
             _ => Err(Error::UnsupportedConfigVersionError),
         }
     }
 
     pub fn config_from_file(path: &str) -> Result<Config, Error> {
-        match ConfigFile::get_version(path)? {
-            1 => Ok(
-                toml::from_str::<ConfigFileV1>(fs::read_to_string(path)?.as_str())
-                    .map_err(|e| Error::ParseError(e.to_string()))?
-                    .try_into()?,
-            ),
+        ConfigFile::config_from_str(fs::read_to_string(path)?.as_str())
+    }
+
+    pub fn config_from_str(text: &str) -> Result<Config, Error> {
+        match ConfigFile::get_version_from_str(text)? {
+            1 => Ok(toml::from_str::<ConfigFileV1>(text)
+                .map_err(|e| Error::ParseError(e.to_string()))?
+                .try_into()?),
+            2 => Ok(toml::from_str::<ConfigFileV2>(text)
+                .map_err(|e| Error::ParseError(e.to_string()))?
+                .try_into()?),
             _ => Err(Error::UnsupportedConfigVersionError),
         }
     }
@@ -53,6 +63,7 @@ struct ConfigFileV1 {
 #[derive(Debug, Clone, Deserialize)]
 struct SuiteV1 {
     jobs: Vec<JobV1>,
+    isolate: Option<bool>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -63,6 +74,13 @@ struct JobV1 {
     kwargs: Option<HashMap<String, String>>,
     schedule: String,
     dedup: bool,
+    summarize: Option<bool>,
+    allow_overlap: Option<bool>,
+    on_error: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    catchup: Option<bool>,
+    /// The file in which to keep the cookies of the job across runs.
+    cookie_jar: Option<String>,
 }
 
 impl TryFrom<ConfigFileV1> for Config {
@@ -76,17 +94,10 @@ impl TryFrom<ConfigFileV1> for Config {
                 let mut jobs = vec![];
 
                 for job in suite.jobs {
-                    jobs.push(Job::new(
-                        job.name.unwrap_or("unnamed".to_string()),
-                        job.script,
-                        job.args,
-                        job.kwargs,
-                        job.schedule.parse::<CronSpec>()?,
-                        job.dedup,
-                    )?);
+                    jobs.push(build_job(job, &DefaultsV2::default())?);
                 }
 
-                suites.push(Suite::new(name, jobs));
+                suites.push(Suite::new(name, jobs).with_isolated(suite.isolate.unwrap_or(false)));
             }
 
             Some(suites)
@@ -98,6 +109,149 @@ impl TryFrom<ConfigFileV1> for Config {
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct ConfigFileV2 {
+    config_version: usize,
+    script_dirs: Vec<String>,
+    script_names: Vec<String>,
+    /// The file in which to keep the times at which jobs were last run.
+    state_file: Option<String>,
+    defaults: Option<DefaultsV2>,
+    suites: Option<HashMap<String, SuiteV2>>,
+}
+
+/// Defaults applied to all jobs, either globally or for the jobs of a single suite.
+#[derive(Debug, Clone, Default, Deserialize)]
+struct DefaultsV2 {
+    user_agent: Option<String>,
+    /// Timeout for a single run of a job, in seconds.
+    timeout: Option<u64>,
+    headers: Option<HashMap<String, String>>,
+    /// Average number of requests per second allowed for all jobs of a suite together.
+    requests_per_second: Option<f64>,
+    /// Addresses to connect to for the given hosts instead of resolving them.
+    resolve: Option<HashMap<String, String>>,
+    /// Environment variables readable by scripts, as names or prefixes followed by `*`.
+    allowed_env: Option<Vec<String>>,
+}
+
+impl DefaultsV2 {
+    /// Combine with `overrides`, whose settings take precedence. Headers and address overrides
+    /// are combined individually.
+    fn merge(&self, overrides: &DefaultsV2) -> DefaultsV2 {
+        DefaultsV2 {
+            user_agent: overrides.user_agent.clone().or(self.user_agent.clone()),
+            timeout: overrides.timeout.or(self.timeout),
+            headers: merge_maps(&self.headers, &overrides.headers),
+            requests_per_second: overrides.requests_per_second.or(self.requests_per_second),
+            resolve: merge_maps(&self.resolve, &overrides.resolve),
+            allowed_env: overrides.allowed_env.clone().or(self.allowed_env.clone()),
+        }
+    }
+
+    /// The headers to send with every request, including `User-Agent` if a user agent is set.
+    fn request_headers(&self) -> HashMap<String, String> {
+        let mut headers = self.headers.clone().unwrap_or_default();
+
+        if let Some(user_agent) = &self.user_agent {
+            headers.insert("User-Agent".to_string(), user_agent.clone());
+        }
+
+        headers
+    }
+
+    /// The addresses to connect to for the given hosts instead of resolving them.
+    fn resolve_addrs(&self) -> Result<HashMap<String, SocketAddr>, Error> {
+        self.resolve
+            .iter()
+            .flatten()
+            .map(|(host, addr)| Ok((host.clone(), parse_resolve_addr(addr)?)))
+            .collect()
+    }
+}
+
+/// Combine `map` with `overrides`, whose entries take precedence.
+fn merge_maps(
+    map: &Option<HashMap<String, String>>,
+    overrides: &Option<HashMap<String, String>>,
+) -> Option<HashMap<String, String>> {
+    match (map, overrides) {
+        (Some(map), Some(overrides)) => {
+            let mut map = map.clone();
+            map.extend(overrides.clone());
+            Some(map)
+        }
+        (map, None) => map.clone(),
+        (None, overrides) => overrides.clone(),
+    }
+}
+
+/// Build a job from its config, with `defaults` applied. Headers of the job itself take
+/// precedence over the default headers.
+fn build_job(job: JobV1, defaults: &DefaultsV2) -> Result<Job, Error> {
+    let mut headers = defaults.request_headers();
+    headers.extend(job.headers.unwrap_or_default());
+
+    Ok(Job::new(
+        job.name.unwrap_or("unnamed".to_string()),
+        job.script,
+        job.args,
+        job.kwargs,
+        job.schedule.parse::<CronSpec>()?,
+        job.dedup,
+    )?
+    .with_summarize(job.summarize.unwrap_or(false))
+    .with_allow_overlap(job.allow_overlap.unwrap_or(false))
+    .with_on_error(job.on_error)
+    .with_headers(headers)
+    .with_resolve(defaults.resolve_addrs()?)
+    .with_allowed_env(defaults.allowed_env.clone())
+    .with_timeout(defaults.timeout.map(Duration::from_secs))
+    .with_catchup(job.catchup.unwrap_or(false))
+    .with_cookie_jar(job.cookie_jar))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SuiteV2 {
+    defaults: Option<DefaultsV2>,
+    jobs: Vec<JobV1>,
+    isolate: Option<bool>,
+}
+
+impl TryFrom<ConfigFileV2> for Config {
+    type Error = Error;
+
+    fn try_from(value: ConfigFileV2) -> Result<Self, Error> {
+        let global_defaults = value.defaults.unwrap_or_default();
+
+        let suites = if let Some(config_suites) = value.suites {
+            let mut suites = vec![];
+
+            for (name, suite) in config_suites {
+                let defaults = global_defaults.merge(&suite.defaults.unwrap_or_default());
+                let mut jobs = vec![];
+
+                for job in suite.jobs {
+                    jobs.push(build_job(job, &defaults)?);
+                }
+
+                suites.push(
+                    Suite::new(name, jobs)
+                        .with_isolated(suite.isolate.unwrap_or(false))
+                        .with_requests_per_second(defaults.requests_per_second)?,
+                );
+            }
+
+            Some(suites)
+        } else {
+            None
+        };
+
+        Ok(Config::new(value.script_dirs, value.script_names, suites)
+            .with_state_file(value.state_file))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::env;
@@ -123,11 +277,23 @@ config_version = 1
 script_dirs = [".", "${HOME}/.scrapeycat/scripts"]
 script_names = ["${NAME}", "${NAME}.scrape"]
 
-[suites.default]
-jobs = [
-    { name = "x", script = "print", args = ["hi", "bye"], schedule = "0 12 * * *", dedup = false },
-    { script = "foo", kwargs = { foo = "bar" }, schedule = "*/5 * * * *", dedup = true },
-]
+[[suites.default.jobs]]
+name = "x"
+script = "print"
+args = ["hi", "bye"]
+schedule = "0 12 * * *"
+dedup = false
+
+[[suites.default.jobs]]
+script = "foo"
+kwargs = { foo = "bar" }
+schedule = "*/5 * * * *"
+dedup = true
+summarize = true
+allow_overlap = true
+on_error = "notify"
+headers = { "User-Agent" = "cat" }
+cookie_jar = "${HOME}/.scrapeycat/foo-cookies.txt"
 "#;
         let config: ConfigFile = toml::from_str(config_text).unwrap();
         assert_eq!(config.config_version, 1);
@@ -159,6 +325,11 @@ jobs = [
         assert!(suite_default.jobs[0].kwargs.is_none());
         assert_eq!(suite_default.jobs[0].schedule, "0 12 * * *");
         assert!(!suite_default.jobs[0].dedup);
+        assert!(suite_default.jobs[0].summarize.is_none());
+        assert!(suite_default.jobs[0].allow_overlap.is_none());
+        assert!(suite_default.jobs[0].on_error.is_none());
+        assert!(suite_default.jobs[0].headers.is_none());
+        assert!(suite_default.jobs[0].cookie_jar.is_none());
 
         assert_eq!(&suite_default.jobs[1].name, &None::<String>);
         assert_eq!(&suite_default.jobs[1].script, "foo");
@@ -171,6 +342,20 @@ jobs = [
         );
         assert_eq!(suite_default.jobs[1].schedule, "*/5 * * * *");
         assert!(suite_default.jobs[1].dedup);
+        assert_eq!(suite_default.jobs[1].summarize, Some(true));
+        assert_eq!(suite_default.jobs[1].allow_overlap, Some(true));
+        assert_eq!(suite_default.jobs[1].on_error, Some("notify".to_string()));
+        assert_eq!(
+            suite_default.jobs[1].headers,
+            Some(HashMap::from([(
+                "User-Agent".to_string(),
+                "cat".to_string()
+            )]))
+        );
+        assert_eq!(
+            suite_default.jobs[1].cookie_jar,
+            Some("${HOME}/.scrapeycat/foo-cookies.txt".to_string())
+        );
     }
 
     #[test]
@@ -181,9 +366,14 @@ script_dirs = ["/var/scraper"]
 script_names = ["${NAME}.txt"]
 
 [suites.common]
-jobs = [
-    { script = "get-temperature", args = ["stockholm"], schedule = "*/10 * * * *", dedup = false },
-]
+isolate = true
+
+[[suites.common.jobs]]
+script = "get-temperature"
+args = ["stockholm"]
+schedule = "*/10 * * * *"
+dedup = false
+headers = { "Accept-Language" = "sv" }
 "#;
         let config: Config = toml::from_str::<ConfigFileV1>(config_text)
             .unwrap()
@@ -194,7 +384,15 @@ jobs = [
         assert_eq!(config.script_names, vec!["${NAME}.txt".to_string()]);
         assert_eq!(config.suites.as_ref().unwrap().len(), 1);
         assert_eq!(config.suites.as_ref().unwrap()[0].name(), "common");
+        assert!(config.suites.as_ref().unwrap()[0].is_isolated());
         assert_eq!(config.suites.as_ref().unwrap()[0].jobs().count(), 1);
+
+        let job = config.suites.as_ref().unwrap()[0].jobs().next().unwrap();
+
+        assert_eq!(
+            job.headers(),
+            &HashMap::from([("Accept-Language".to_string(), "sv".to_string())])
+        );
     }
 
     #[test]
@@ -214,6 +412,11 @@ jobs = [
                 .is_ok_and(|version| version == 1)
         );
 
+        assert!(
+            ConfigFile::get_version(asset_path!("valid/v2_defaults.toml"))
+                .is_ok_and(|version| version == 2)
+        );
+
         assert!(ConfigFile::get_version(asset_path!("invalid/empty_file.toml")).is_err());
         assert!(ConfigFile::get_version(asset_path!("invalid/gibberish.toml")).is_err());
         assert!(ConfigFile::get_version(asset_path!("invalid/small_parse_error.toml")).is_err());
@@ -332,4 +535,232 @@ jobs = [
             ConfigFile::config_from_file(asset_path!("invalid/bad_version_empty_c.toml")).is_err()
         );
     }
+
+    #[test]
+    fn test_config_from_file_v2_defaults() {
+        let config = ConfigFile::config_from_file(asset_path!("valid/v2_defaults.toml")).unwrap();
+
+        assert_eq!(config.script_dirs, vec!["/v2_defaults".to_string()]);
+        assert_eq!(config.script_names, vec!["${NAME}.txt".to_string()]);
+
+        let suites = config.suites.unwrap();
+        assert_eq!(suites.len(), 1);
+
+        let jobs = suites[0].jobs().collect::<Vec<_>>();
+        assert_eq!(jobs.len(), 2);
+
+        for job in jobs {
+            assert_eq!(
+                job.headers(),
+                &HashMap::from([
+                    ("User-Agent".to_string(), "scrapeycat/2".to_string()),
+                    ("Accept-Language".to_string(), "en".to_string()),
+                ])
+            );
+            assert_eq!(job.timeout(), Some(Duration::from_secs(30)));
+        }
+    }
+
+    #[test]
+    fn test_config_from_file_v2_suite_overrides() {
+        let config =
+            ConfigFile::config_from_file(asset_path!("valid/v2_suite_overrides.toml")).unwrap();
+
+        let suites = config.suites.unwrap();
+        assert_eq!(suites.len(), 3);
+
+        let suites_map: HashMap<&str, &Suite> =
+            HashMap::from_iter(suites.iter().map(|suite| (suite.name(), suite)));
+
+        let job = suites_map["plain"].jobs().next().unwrap();
+        assert_eq!(job.script_name(), "foo");
+        assert_eq!(
+            job.headers(),
+            &HashMap::from([
+                ("User-Agent".to_string(), "scrapeycat/2".to_string()),
+                ("Accept-Language".to_string(), "en".to_string()),
+            ])
+        );
+        assert_eq!(job.timeout(), Some(Duration::from_secs(30)));
+
+        let job = suites_map["swedish"].jobs().next().unwrap();
+        assert_eq!(job.script_name(), "bar");
+        assert_eq!(
+            job.headers(),
+            &HashMap::from([
+                ("User-Agent".to_string(), "scrapeycat/2".to_string()),
+                ("Accept-Language".to_string(), "sv".to_string()),
+                ("DNT".to_string(), "1".to_string()),
+            ])
+        );
+        assert_eq!(job.timeout(), Some(Duration::from_secs(30)));
+
+        let job = suites_map["patient"].jobs().next().unwrap();
+        assert_eq!(job.script_name(), "baz");
+        assert_eq!(
+            job.headers(),
+            &HashMap::from([
+                ("User-Agent".to_string(), "patient-cat".to_string()),
+                ("Accept-Language".to_string(), "en".to_string()),
+            ])
+        );
+        assert_eq!(job.timeout(), Some(Duration::from_secs(300)));
+
+        // Headers of the job itself take precedence over the defaults
+        let job = suites_map["patient"].jobs().nth(1).unwrap();
+        assert_eq!(job.script_name(), "qux");
+        assert_eq!(
+            job.headers(),
+            &HashMap::from([
+                ("User-Agent".to_string(), "qux-cat".to_string()),
+                ("Accept-Language".to_string(), "en".to_string()),
+            ])
+        );
+        assert_eq!(job.timeout(), Some(Duration::from_secs(300)));
+    }
+
+    #[test]
+    fn test_config_from_file_v2_no_defaults() {
+        let config =
+            ConfigFile::config_from_file(asset_path!("valid/v2_no_defaults.toml")).unwrap();
+
+        let suites = config.suites.unwrap();
+        let job = suites[0].jobs().next().unwrap();
+
+        assert_eq!(job.script_name(), "foo");
+        assert!(job.headers().is_empty());
+        assert!(job.timeout().is_none());
+    }
+
+    #[test]
+    fn test_config_from_file_v2_rate_limit() {
+        let config = ConfigFile::config_from_file(asset_path!("valid/v2_rate_limit.toml")).unwrap();
+
+        let suites = config.suites.unwrap();
+        let suites_map: HashMap<&str, &Suite> =
+            HashMap::from_iter(suites.iter().map(|suite| (suite.name(), suite)));
+
+        assert_eq!(
+            suites_map["default"]
+                .rate_limiter()
+                .map(|limiter| limiter.requests_per_second()),
+            Some(2.0)
+        );
+        assert_eq!(
+            suites_map["slow"]
+                .rate_limiter()
+                .map(|limiter| limiter.requests_per_second()),
+            Some(0.5)
+        );
+
+        let config =
+            ConfigFile::config_from_file(asset_path!("valid/v2_no_defaults.toml")).unwrap();
+        assert!(config.suites.unwrap()[0].rate_limiter().is_none());
+
+        assert!(matches!(
+            ConfigFile::config_from_file(asset_path!("invalid/v2_bad_rate_limit.toml")),
+            Err(Error::ValueOutOfRangeError)
+        ));
+    }
+
+    #[test]
+    fn test_config_from_file_v2_resolve() {
+        let config = ConfigFile::config_from_file(asset_path!("valid/v2_resolve.toml")).unwrap();
+
+        let suites = config.suites.unwrap();
+        let suites_map: HashMap<&str, &Suite> =
+            HashMap::from_iter(suites.iter().map(|suite| (suite.name(), suite)));
+
+        let resolve = |suite: &str| {
+            let mut resolve = suites_map[suite]
+                .jobs()
+                .next()
+                .unwrap()
+                .resolve()
+                .iter()
+                .map(|(host, addr)| format!("{host}={addr}"))
+                .collect::<Vec<_>>();
+            resolve.sort();
+            resolve
+        };
+
+        assert_eq!(
+            resolve("default"),
+            vec!["example.com=203.0.113.7:0", "example.org=203.0.113.8:8443"]
+        );
+
+        // Suite defaults override global defaults per host
+        assert_eq!(
+            resolve("staging"),
+            vec![
+                "example.com=[2001:db8::1]:8080",
+                "example.org=203.0.113.8:8443"
+            ]
+        );
+
+        assert!(matches!(
+            ConfigFile::config_from_file(asset_path!("invalid/v2_bad_resolve.toml")),
+            Err(Error::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_from_file_v2_allowed_env() {
+        let config =
+            ConfigFile::config_from_file(asset_path!("valid/v2_allowed_env.toml")).unwrap();
+
+        let suites = config.suites.unwrap();
+        let suites_map: HashMap<&str, &Suite> =
+            HashMap::from_iter(suites.iter().map(|suite| (suite.name(), suite)));
+
+        let allowed_env = |suite: &str| {
+            suites_map[suite]
+                .jobs()
+                .next()
+                .unwrap()
+                .allowed_env()
+                .cloned()
+        };
+
+        assert_eq!(
+            allowed_env("default"),
+            Some(vec!["SCRAPEYCAT_*".to_string(), "GITHUB_TOKEN".to_string()])
+        );
+        assert_eq!(
+            allowed_env("weather"),
+            Some(vec!["WEATHER_API_KEY".to_string()])
+        );
+
+        let config =
+            ConfigFile::config_from_file(asset_path!("valid/v2_no_defaults.toml")).unwrap();
+        assert!(
+            config.suites.unwrap()[0]
+                .jobs()
+                .next()
+                .unwrap()
+                .allowed_env()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_config_from_file_v2_catchup() {
+        let config = ConfigFile::config_from_file(asset_path!("valid/v2_catchup.toml")).unwrap();
+
+        assert_eq!(
+            config.state_file,
+            Some("${HOME}/.scrapeycat/state.json".to_string())
+        );
+        assert_eq!(
+            config.suites.unwrap()[0]
+                .jobs()
+                .map(|job| job.is_catchup())
+                .collect::<Vec<_>>(),
+            vec![true, false]
+        );
+
+        let config =
+            ConfigFile::config_from_file(asset_path!("valid/v2_no_defaults.toml")).unwrap();
+        assert!(config.state_file.is_none());
+    }
 }
@@ -1,5 +1,6 @@
-use std::str::FromStr;
+use std::{fmt::Display, str::FromStr};
 
+use chrono::{DateTime, Datelike, Local, TimeDelta, Timelike};
 use winnow::Parser;
 
 use crate::{
@@ -45,10 +46,43 @@ impl<const L: u8, const H: u8> CronSpecItem<L, H> {
                 .join("|"),
         }
     }
+
+    /// Whether the value `n` is among the values denoted by this item.
+    pub fn matches(&self, n: u8) -> bool {
+        match self {
+            CronSpecItem::Any => true,
+            CronSpecItem::AnyStepped(step) => n
+                .checked_sub(L)
+                .is_some_and(|offset| offset % step.get() == 0),
+            CronSpecItem::Single(single) => n == single.get(),
+            CronSpecItem::SingleStepped(start, step) => n
+                .checked_sub(start.get())
+                .is_some_and(|offset| offset % step.get() == 0),
+            CronSpecItem::Range(range) => range.get().contains(&n),
+            CronSpecItem::RangeStepped(range, step) => {
+                range.get().contains(&n) && (n - range.get().start()).is_multiple_of(step.get())
+            }
+        }
+    }
+
+    /// Whether a field whose first item is `self` counts as unrestricted, i.e. starts with `*`.
+    fn is_star(&self) -> bool {
+        matches!(self, CronSpecItem::Any | CronSpecItem::AnyStepped(_))
+    }
 }
 
+fn any_matches<const L: u8, const H: u8>(items: &[CronSpecItem<L, H>], n: u32) -> bool {
+    items.iter().any(|item| item.matches(n as u8))
+}
+
+/// How far [CronSpec::next_after] and [CronSpec::prev_before] look for a time at which a
+/// schedule is due, which is long enough to find e.g. the next February 29th.
+const SEARCH_LIMIT: TimeDelta = TimeDelta::days(8 * 366);
+
 #[derive(Debug, Clone)]
 pub struct CronSpec {
+    /// The spec as written, e.g. `*/5 * * * *` or `@daily`.
+    text: String,
     minute: Vec<CronSpecItem<0, 59>>,
     hour: Vec<CronSpecItem<0, 23>>,
     day_of_month: Vec<CronSpecItem<1, 31>>,
@@ -57,6 +91,77 @@ pub struct CronSpec {
 }
 
 impl CronSpec {
+    /// Whether the schedule is due at the minute of `when`. As in standard cron, if both the day
+    /// of month and the day of week are restricted, i.e. neither field starts with `*`, a day
+    /// matching either field is due. Otherwise a day must match both fields.
+    pub fn matches(&self, when: &DateTime<Local>) -> bool {
+        self.matches_hour(when) && any_matches(&self.minute, when.minute())
+    }
+
+    /// Whether the schedule is due at any minute of the hour of `when`.
+    fn matches_hour(&self, when: &DateTime<Local>) -> bool {
+        let day_of_month = any_matches(&self.day_of_month, when.day());
+        let day_of_week = any_matches(&self.day_of_week, when.weekday().number_from_monday());
+
+        let day = if self.day_of_month.first().is_some_and(CronSpecItem::is_star)
+            || self.day_of_week.first().is_some_and(CronSpecItem::is_star)
+        {
+            day_of_month && day_of_week
+        } else {
+            day_of_month || day_of_week
+        };
+
+        day && any_matches(&self.hour, when.hour()) && any_matches(&self.month, when.month())
+    }
+
+    /// The first minute after `from` at which the schedule is due, or None if it is not due
+    /// within the next eight years, as for a spec such as `0 0 31 2 *`. Time is stepped through
+    /// in real time, skipping whole hours in which the schedule is not due, so that across
+    /// daylight saving time changes the result is the first minute for which
+    /// [CronSpec::matches] is true.
+    pub fn next_after(&self, from: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut when = from.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = from + SEARCH_LIMIT;
+
+        while when <= limit {
+            if !self.matches_hour(&when) {
+                when += TimeDelta::minutes(60 - when.minute() as i64);
+            } else if any_matches(&self.minute, when.minute()) {
+                return Some(when);
+            } else {
+                when += TimeDelta::minutes(1);
+            }
+        }
+
+        None
+    }
+
+    /// The last minute before `before` at which the schedule was due, or None if it was not due
+    /// within the previous eight years. Like [CronSpec::next_after], but looking back in time.
+    pub fn prev_before(&self, before: DateTime<Local>) -> Option<DateTime<Local>> {
+        let minute = before.with_second(0)?.with_nanosecond(0)?;
+
+        let mut when = if minute == before {
+            minute - TimeDelta::minutes(1)
+        } else {
+            minute
+        };
+
+        let limit = before - SEARCH_LIMIT;
+
+        while when >= limit {
+            if !self.matches_hour(&when) {
+                when -= TimeDelta::minutes(when.minute() as i64 + 1);
+            } else if any_matches(&self.minute, when.minute()) {
+                return Some(when);
+            } else {
+                when -= TimeDelta::minutes(1);
+            }
+        }
+
+        None
+    }
+
     pub fn to_regex_pattern(&self) -> String {
         format!(
             "({})({})({})({})({})",
@@ -89,11 +194,36 @@ impl CronSpec {
     }
 }
 
+impl Display for CronSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
 impl FromStr for CronSpec {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        parse::parse_cronspec.parse(s).map_err(|e| {
+        let text = s.trim().to_string();
+
+        let s = if s.trim().starts_with('@') {
+            match s.trim() {
+                "@yearly" | "@annually" => "0 0 1 1 *",
+                "@monthly" => "0 0 1 * *",
+                "@weekly" => "0 0 * * 0",
+                "@daily" | "@midnight" => "0 0 * * *",
+                "@hourly" => "0 * * * *",
+                name => {
+                    return Err(Error::ParseError(format!(
+                        "Invalid cron spec: unknown macro `{name}`"
+                    )));
+                }
+            }
+        } else {
+            s
+        };
+
+        let spec = parse::parse_cronspec.parse(s).map_err(|e| {
             Error::ParseError(format!(
                 r#"Invalid cron spec:
 -------------------------------
@@ -101,7 +231,9 @@ impl FromStr for CronSpec {
 -------------------------------
 "#
             ))
-        })
+        })?;
+
+        Ok(CronSpec { text, ..spec })
     }
 }
 
@@ -284,6 +416,79 @@ mod parse {
         Ok(result)
     }
 
+    /// Map day-of-week items parsed in the domain 0-7 onto the domain 1-7, where both 0 and 7
+    /// denote Sunday. Items including 0 are split into a separate item for Sunday (7) and an
+    /// item covering the remaining days, if any.
+    fn normalize_day_of_week(items: Vec<CronSpecItem<0, 7>>) -> Vec<CronSpecItem<1, 7>> {
+        let sunday = || CronSpecItem::Single(7.try_into().expect("7 is within 1-7"));
+
+        items
+            .into_iter()
+            .flat_map(|item| match item {
+                CronSpecItem::Any => vec![CronSpecItem::Any],
+                CronSpecItem::AnyStepped(step) => vec![CronSpecItem::AnyStepped(
+                    step.get().try_into().expect("step is within 1-7"),
+                )],
+                CronSpecItem::Single(n) if n.get() == 0 => vec![sunday()],
+                CronSpecItem::Single(n) => vec![CronSpecItem::Single(
+                    n.get().try_into().expect("nonzero day is within 1-7"),
+                )],
+                CronSpecItem::SingleStepped(n, step) if n.get() == 0 => vec![
+                    sunday(),
+                    CronSpecItem::SingleStepped(
+                        step.get().try_into().expect("step is within 1-7"),
+                        step.get().try_into().expect("step is within 1-7"),
+                    ),
+                ],
+                CronSpecItem::SingleStepped(n, step) => vec![CronSpecItem::SingleStepped(
+                    n.get().try_into().expect("nonzero day is within 1-7"),
+                    step.get().try_into().expect("step is within 1-7"),
+                )],
+                CronSpecItem::Range(range) => {
+                    let (start, end) = (*range.get().start(), *range.get().end());
+
+                    if start == 0 {
+                        let mut result = vec![sunday()];
+
+                        if end > 0 {
+                            result.push(CronSpecItem::Range(
+                                (1..=end).try_into().expect("range is within 1-7"),
+                            ));
+                        }
+
+                        result
+                    } else {
+                        vec![CronSpecItem::Range(
+                            (start..=end).try_into().expect("range is within 1-7"),
+                        )]
+                    }
+                }
+                CronSpecItem::RangeStepped(range, step) => {
+                    let (start, end) = (*range.get().start(), *range.get().end());
+                    let step_within_1_7 = || step.get().try_into().expect("step is within 1-7");
+
+                    if start == 0 {
+                        let mut result = vec![sunday()];
+
+                        if step.get() <= end {
+                            result.push(CronSpecItem::RangeStepped(
+                                (step.get()..=end).try_into().expect("range is within 1-7"),
+                                step_within_1_7(),
+                            ));
+                        }
+
+                        result
+                    } else {
+                        vec![CronSpecItem::RangeStepped(
+                            (start..=end).try_into().expect("range is within 1-7"),
+                            step_within_1_7(),
+                        )]
+                    }
+                }
+            })
+            .collect()
+    }
+
     pub fn parse_cronspec(input: &mut &str) -> ModalResult<CronSpec> {
         let (_, minute, _, hour, _, day_of_month, _, month, _, day_of_week, _) = (
             multispace0,
@@ -295,12 +500,15 @@ mod parse {
             multispace1,
             cronspec_item.context(StrContext::Label("month")),
             multispace1,
-            cronspec_item.context(StrContext::Label("day of week")),
+            cronspec_item::<0, 7>
+                .map(normalize_day_of_week)
+                .context(StrContext::Label("day of week")),
             multispace0,
         )
             .parse_next(input)?;
 
         Ok(CronSpec {
+            text: String::new(),
             minute,
             hour,
             day_of_month,
@@ -315,8 +523,11 @@ mod tests {
     use std::ops::Bound;
 
     use bolero::{TypeGenerator, check, produce};
+    use chrono::{Local, TimeZone};
     use regex::Regex;
 
+    use crate::daemon::suite::Job;
+
     use super::*;
 
     #[derive(Debug, Clone, TypeGenerator)]
@@ -395,7 +606,7 @@ mod tests {
         month: Vec<Item<1, 12>>,
 
         #[generator(produce::<Vec<_>>().with().len(1..=3))]
-        day_of_week: Vec<Item<1, 7>>,
+        day_of_week: Vec<Item<0, 7>>,
     }
 
     impl ValidSpec {
@@ -588,9 +799,89 @@ mod tests {
         assert!("* * * 100 *".parse::<CronSpec>().is_err());
     }
 
+    #[test]
+    fn test_parse_sunday_as_zero() {
+        let day_of_week = |spec: &str| spec.parse::<CronSpec>().unwrap().day_of_week;
+
+        assert_eq!(
+            day_of_week("* * * * 0"),
+            vec![CronSpecItem::Single(7.try_into().unwrap())]
+        );
+        assert_eq!(
+            day_of_week("* * * * 7"),
+            vec![CronSpecItem::Single(7.try_into().unwrap())]
+        );
+        assert_eq!(
+            day_of_week("* * * * 0-6"),
+            vec![
+                CronSpecItem::Single(7.try_into().unwrap()),
+                CronSpecItem::Range((1..=6).try_into().unwrap())
+            ]
+        );
+        assert_eq!(
+            day_of_week("* * * * 0-0"),
+            vec![CronSpecItem::Single(7.try_into().unwrap())]
+        );
+        assert_eq!(
+            day_of_week("* * * * 0-6/2,5"),
+            vec![
+                CronSpecItem::Single(7.try_into().unwrap()),
+                CronSpecItem::RangeStepped((2..=6).try_into().unwrap(), 2.try_into().unwrap()),
+                CronSpecItem::Single(5.try_into().unwrap())
+            ]
+        );
+        assert_eq!(
+            day_of_week("* * * * 0/3"),
+            vec![
+                CronSpecItem::Single(7.try_into().unwrap()),
+                CronSpecItem::SingleStepped(3.try_into().unwrap(), 3.try_into().unwrap())
+            ]
+        );
+
+        assert_eq!(
+            "* * * * 0".parse::<CronSpec>().unwrap().to_regex_pattern(),
+            "(..)(..)(..)(..)(07)"
+        );
+        assert_eq!(
+            "* * * * 0-6"
+                .parse::<CronSpec>()
+                .unwrap()
+                .to_regex_pattern(),
+            "(..)(..)(..)(..)(07|01|02|03|04|05|06)"
+        );
+        assert_eq!(
+            "* * * * 0-6/2"
+                .parse::<CronSpec>()
+                .unwrap()
+                .to_regex_pattern(),
+            "(..)(..)(..)(..)(07|02|04|06)"
+        );
+
+        // 2024-06-02 was a Sunday, 2024-06-03 a Monday
+        let sunday = Local.with_ymd_and_hms(2024, 6, 2, 12, 0, 0).unwrap();
+        let monday = Local.with_ymd_and_hms(2024, 6, 3, 12, 0, 0).unwrap();
+
+        let job = |spec: &str| {
+            Job::new(
+                "x",
+                "x",
+                None,
+                None,
+                spec.parse::<CronSpec>().unwrap(),
+                false,
+            )
+            .unwrap()
+        };
+
+        assert!(job("0 12 * * 0").is_due_at(sunday));
+        assert!(!job("0 12 * * 0").is_due_at(monday));
+        assert!(job("0 12 * * 0-6").is_due_at(sunday));
+        assert!(job("0 12 * * 0-6").is_due_at(monday));
+        assert!(job("0 12 * * 7").is_due_at(sunday));
+    }
+
     #[test]
     fn test_parse_invalid_day_of_week() {
-        assert!("* * * * 0".parse::<CronSpec>().is_err());
         assert!("* * * * 8".parse::<CronSpec>().is_err());
         assert!("* * * * 100".parse::<CronSpec>().is_err());
     }
@@ -643,6 +934,250 @@ mod tests {
         assert!("* * * * 6-5".parse::<CronSpec>().is_err());
     }
 
+    #[test]
+    fn test_parse_macros() {
+        let pattern = |spec: &str| spec.parse::<CronSpec>().unwrap().to_regex_pattern();
+
+        assert_eq!(pattern("@yearly"), pattern("0 0 1 1 *"));
+        assert_eq!(pattern("@annually"), pattern("0 0 1 1 *"));
+        assert_eq!(pattern("@monthly"), pattern("0 0 1 * *"));
+        assert_eq!(pattern("@weekly"), pattern("0 0 * * 0"));
+        assert_eq!(pattern("@daily"), pattern("0 0 * * *"));
+        assert_eq!(pattern("@midnight"), pattern("0 0 * * *"));
+        assert_eq!(pattern("@hourly"), pattern("0 * * * *"));
+        assert_eq!(pattern("  @daily "), pattern("0 0 * * *"));
+
+        assert!("@reboot".parse::<CronSpec>().is_err());
+        assert!("@Daily".parse::<CronSpec>().is_err());
+        assert!("@".parse::<CronSpec>().is_err());
+        assert!("@daily * * * * *".parse::<CronSpec>().is_err());
+    }
+
+    #[test]
+    fn test_cronspec_matches_agrees_with_regex() {
+        // Both agree whenever the day of month and day of week are not both restricted
+        let specs = [
+            "* * * * *",
+            "*/7 */5 * * *",
+            "3/20 1-20/6 * * *",
+            "0,30 12 */3 2/4 *",
+            "15 6-8 * * 1-5",
+            "0 0 * * 0/3",
+            "59 23 31 12 *",
+        ];
+
+        let start = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        for spec in specs {
+            let cronspec = spec.parse::<CronSpec>().unwrap();
+            let regex = Regex::new(&format!("^{}$", cronspec.to_regex_pattern())).unwrap();
+
+            for minutes in (0..366 * 24 * 60).step_by(37) {
+                let when = start + chrono::Duration::minutes(minutes);
+
+                assert_eq!(
+                    cronspec.matches(&when),
+                    regex.is_match(&Job::format_datetime(when)),
+                    "{spec} at {when}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_cronspec_display() {
+        assert_eq!(
+            "*/5 * * * *".parse::<CronSpec>().unwrap().to_string(),
+            "*/5 * * * *"
+        );
+        assert_eq!(
+            " @daily ".parse::<CronSpec>().unwrap().to_string(),
+            "@daily"
+        );
+    }
+
+    #[test]
+    fn test_cronspec_next_after() {
+        let noon = "0 12 * * *".parse::<CronSpec>().unwrap();
+
+        assert_eq!(
+            noon.next_after(Local.with_ymd_and_hms(2024, 6, 1, 9, 30, 15).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap())
+        );
+
+        // Strictly after
+        assert_eq!(
+            noon.next_after(Local.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 2, 12, 0, 0).unwrap())
+        );
+
+        assert_eq!(
+            "0 0 31 2 *"
+                .parse::<CronSpec>()
+                .unwrap()
+                .next_after(Local.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cronspec_next_after_daily() {
+        let daily = "@daily".parse::<CronSpec>().unwrap();
+
+        assert_eq!(
+            daily.next_after(Local.with_ymd_and_hms(2024, 6, 1, 23, 59, 59).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 2, 0, 0, 0).unwrap())
+        );
+        assert_eq!(
+            daily.next_after(Local.with_ymd_and_hms(2024, 6, 2, 0, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 3, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_cronspec_next_after_step() {
+        let quarterly = "*/15 * * * *".parse::<CronSpec>().unwrap();
+
+        assert_eq!(
+            quarterly.next_after(Local.with_ymd_and_hms(2024, 6, 1, 10, 7, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 10, 15, 0).unwrap())
+        );
+        assert_eq!(
+            quarterly.next_after(Local.with_ymd_and_hms(2024, 6, 1, 10, 15, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 10, 30, 0).unwrap())
+        );
+        assert_eq!(
+            quarterly.next_after(Local.with_ymd_and_hms(2024, 6, 1, 10, 50, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 11, 0, 0).unwrap())
+        );
+
+        // Steps within a restricted range of hours
+        assert_eq!(
+            "*/15 9-10 * * *"
+                .parse::<CronSpec>()
+                .unwrap()
+                .next_after(Local.with_ymd_and_hms(2024, 6, 1, 10, 50, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 2, 9, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_cronspec_next_after_month_boundary() {
+        assert_eq!(
+            "0 0 1 * *"
+                .parse::<CronSpec>()
+                .unwrap()
+                .next_after(Local.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap())
+        );
+
+        // Months without a 31st are skipped
+        assert_eq!(
+            "30 8 31 * *"
+                .parse::<CronSpec>()
+                .unwrap()
+                .next_after(Local.with_ymd_and_hms(2024, 4, 1, 0, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 5, 31, 8, 30, 0).unwrap())
+        );
+
+        assert_eq!(
+            "* * * * *"
+                .parse::<CronSpec>()
+                .unwrap()
+                .next_after(Local.with_ymd_and_hms(2024, 12, 31, 23, 59, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap())
+        );
+
+        assert_eq!(
+            "0 0 29 2 *"
+                .parse::<CronSpec>()
+                .unwrap()
+                .next_after(Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2028, 2, 29, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_cronspec_next_after_day_of_month_or_day_of_week() {
+        // On the 13th, and on Fridays
+        let spec = "0 9 13 * 5".parse::<CronSpec>().unwrap();
+
+        assert_eq!(
+            spec.next_after(Local.with_ymd_and_hms(2024, 10, 5, 0, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 10, 11, 9, 0, 0).unwrap())
+        );
+        assert_eq!(
+            spec.next_after(Local.with_ymd_and_hms(2024, 10, 11, 9, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 10, 13, 9, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_cronspec_next_after_agrees_with_matches() {
+        let specs = [
+            "* * * * *",
+            "*/7 */5 * * *",
+            "3/20 1-20/6 * * *",
+            "0,30 12 */3 2/4 *",
+            "15 6-8 * * 1-5",
+            "0 0 * * 0/3",
+            "0 9 13 * 5",
+        ];
+
+        let start = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        for spec in specs {
+            let cronspec = spec.parse::<CronSpec>().unwrap();
+            let mut next = cronspec.next_after(start).unwrap();
+
+            for minutes in 1..60 * 24 * 60 {
+                let when = start + chrono::Duration::minutes(minutes);
+
+                if when > next {
+                    next = cronspec.next_after(next).unwrap();
+                }
+
+                assert_eq!(cronspec.matches(&when), when == next, "{spec} at {when}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_cronspec_prev_before() {
+        let hourly = "@hourly".parse::<CronSpec>().unwrap();
+
+        assert_eq!(
+            hourly.prev_before(Local.with_ymd_and_hms(2024, 6, 1, 10, 5, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap())
+        );
+        assert_eq!(
+            hourly.prev_before(Local.with_ymd_and_hms(2024, 6, 1, 10, 0, 30).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap())
+        );
+
+        // Strictly before
+        assert_eq!(
+            hourly.prev_before(Local.with_ymd_and_hms(2024, 6, 1, 10, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap())
+        );
+
+        assert_eq!(
+            "@daily"
+                .parse::<CronSpec>()
+                .unwrap()
+                .prev_before(Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()),
+            Some(Local.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap())
+        );
+
+        assert_eq!(
+            "0 0 31 2 *"
+                .parse::<CronSpec>()
+                .unwrap()
+                .prev_before(Local.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap()),
+            None
+        );
+    }
+
     #[test]
     fn test_cronspec_to_regex() {
         assert!(
@@ -0,0 +1,128 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use tokio::task;
+
+use chrono::{DateTime, Local, TimeZone};
+use log::debug;
+
+use crate::{Error, util::home::substitute_home};
+
+/// Where the daemon keeps the times at which jobs were last run, unless the config says
+/// otherwise.
+pub const DEFAULT_STATE_FILE: &str = "${HOME}/.scrapeycat/daemon-state.json";
+
+/// The times at which jobs were last run, keyed by `suite.job` and stored as unix timestamps in
+/// a JSON file, so that runs missed while the daemon was not running can be caught up on after
+/// restarting it.
+#[derive(Debug, Clone, Default)]
+pub struct LastRuns {
+    path: Option<String>,
+    runs: BTreeMap<String, i64>,
+}
+
+impl LastRuns {
+    /// Last run times kept in memory only.
+    pub fn new() -> LastRuns {
+        LastRuns::default()
+    }
+
+    /// Load the last run times stored in the file at `path`, where `${HOME}` is replaced with
+    /// the path of the user's home directory. A missing file holds no last run times, and is
+    /// created when first recording one.
+    pub fn load(path: &str) -> Result<LastRuns, Error> {
+        let path = substitute_home(path)?;
+
+        let runs = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| Error::JsonParseError(format!("{path}: {e}")))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(LastRuns {
+            path: Some(path),
+            runs,
+        })
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<DateTime<Local>> {
+        self.runs
+            .get(job_id)
+            .and_then(|timestamp| Local.timestamp_opt(*timestamp, 0).single())
+    }
+
+    /// Record that the job was run at `when`. The time is only stored in the file, if any, by
+    /// [LastRuns::save].
+    pub fn record(&mut self, job_id: &str, when: DateTime<Local>) {
+        self.runs.insert(job_id.to_string(), when.timestamp());
+    }
+
+    /// Store all last run times in the file, if loaded from one. The file is written on a
+    /// blocking thread, so as not to hold up other tasks.
+    pub async fn save(&self) -> Result<(), Error> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+
+        let text =
+            serde_json::to_string(&self.runs).map_err(|e| Error::JsonParseError(e.to_string()))?;
+
+        task::spawn_blocking(move || {
+            if let Some(dir) = Path::new(&path).parent() {
+                fs::create_dir_all(dir)?;
+            }
+
+            fs::write(&path, text)?;
+
+            debug!("daemon::LastRuns: saved {path}");
+            Ok(())
+        })
+        .await
+        .map_err(|e| Error::IOError(io::Error::other(e)))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_last_runs() {
+        let path = env::temp_dir()
+            .join(format!("scrapeycat-test-last-runs-{}.json", process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let _ = fs::remove_file(&path);
+
+        let when = Local.with_ymd_and_hms(2024, 6, 1, 9, 0, 3).unwrap();
+
+        let mut last_runs = LastRuns::load(&path).unwrap();
+        assert!(last_runs.get("default.news").is_none());
+
+        last_runs.record("default.news", when);
+        assert_eq!(last_runs.get("default.news"), Some(when));
+
+        // Not persisted until saved
+        assert!(LastRuns::load(&path).unwrap().get("default.news").is_none());
+
+        last_runs.save().await.unwrap();
+        assert_eq!(
+            LastRuns::load(&path).unwrap().get("default.news"),
+            Some(when)
+        );
+
+        fs::write(&path, "{").unwrap();
+        assert!(LastRuns::load(&path).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
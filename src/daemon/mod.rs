@@ -1,28 +1,44 @@
 pub mod config;
 pub mod config_file;
 pub mod cron;
+pub mod last_runs;
 pub mod suite;
 
 use std::{
     collections::{HashMap, HashSet},
-    fs,
-    hash::{DefaultHasher, Hash, Hasher},
-    sync::{Arc, RwLock},
+    fs, io,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
     time::Duration,
 };
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, TimeDelta, Timelike};
 use flagset::{FlagSet, flags};
-use log::{debug, error, warn};
+use im::Vector;
+use last_runs::{DEFAULT_STATE_FILE, LastRuns};
+use log::{debug, error, info, warn};
 use suite::{Job, Suite};
-use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::{
+    runtime::{Builder, Handle},
+    sync::{
+        Semaphore,
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        watch,
+    },
+    task::JoinHandle,
+};
 
 use crate::{
     Error,
-    daemon::config::Config,
-    effect::{EffectInvocation, EffectOptions, EffectSignature},
-    scrapelang::program::{ScriptLoaderPointer, run},
-    scraper::ReqwestHttpDriver,
+    daemon::{config::Config, config_file::ConfigFile},
+    effect::{self, EffectInvocation, EffectRegistry},
+    rate_limiter::RateLimiter,
+    scrapelang::program::{RunOptions, ScriptLoaderPointer, run_with_options},
+    scraper::{HttpDriver, ReqwestHttpDriver},
+    util::home::substitute_home,
 };
 
 flags! {
@@ -38,7 +54,7 @@ flags! {
 async fn effects_handler(
     id: String,
     mut effects_receiver: UnboundedReceiver<EffectInvocation>,
-    effects: HashMap<String, EffectSignature>,
+    effects: EffectRegistry,
     options: FlagSet<EffectsHandlerOptions>,
 ) {
     let mut dedup_seen: HashSet<u64> = HashSet::new();
@@ -49,10 +65,7 @@ async fn effects_handler(
                 debug!("daemon::effects_handler: ({id}) {invocation:?}");
 
                 if options.contains(EffectsHandlerOptions::Deduplicate) {
-                    let mut hasher = DefaultHasher::new();
-                    invocation.hash(&mut hasher);
-
-                    let invocation_hash = hasher.finish();
+                    let invocation_hash = invocation.dedup_hash();
 
                     if dedup_seen.contains(&invocation_hash) {
                         debug!("daemon::effects_handler: ({id}) deduplicated");
@@ -64,11 +77,7 @@ async fn effects_handler(
 
                 match effects.get(invocation.name()) {
                     Some(function) => {
-                        if let Some(error) = function(
-                            invocation.args(),
-                            invocation.kwargs(),
-                            EffectOptions::default().into(),
-                        ) {
+                        if let Some(error) = effect::invoke_effect(function, &invocation).await {
                             error!(
                                 "daemon::effects_handler: \
                                 error invoking effect `{}`: {error} (args: {:?}, kwargs: {:?})",
@@ -89,55 +98,104 @@ async fn effects_handler(
     }
 }
 
-pub async fn run_config(config: Config, effects: HashMap<String, EffectSignature>) {
-    debug!("daemon::run_config({config:?}, {effects:?})");
+/// Collapse the effect invocations buffered during one run of a job into one summary
+/// invocation per effect name, e.g. three `print` invocations become a single `print`
+/// invocation with the argument "3 new items: a, b, c". Keyword arguments are taken from the
+/// first invocation of each effect.
+fn summarize_invocations(invocations: Vec<EffectInvocation>) -> Vec<EffectInvocation> {
+    let mut groups: Vec<(String, Vec<EffectInvocation>)> = vec![];
+
+    for invocation in invocations {
+        match groups
+            .iter_mut()
+            .find(|(name, _)| name == invocation.name())
+        {
+            Some((_, group)) => group.push(invocation),
+            None => groups.push((invocation.name().to_string(), vec![invocation])),
+        }
+    }
 
-    fn substitute_variables(text: String, path: &str) -> String {
-        text.replace("${NAME}", path).replace(
-            "${HOME}",
-            dirs::home_dir()
-                .expect("Should be able to find user's home directory path")
-                .to_str()
-                .expect("Home directory path should be valid unicode"),
-        )
+    groups
+        .into_iter()
+        .map(|(name, mut group)| {
+            if group.len() == 1 {
+                group.remove(0)
+            } else {
+                EffectInvocation::new(
+                    name,
+                    vec![format!(
+                        "{} new items: {}",
+                        group.len(),
+                        group
+                            .iter()
+                            .map(|invocation| invocation.args().join(" "))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )],
+                    group[0].kwargs().clone(),
+                )
+            }
+        })
+        .collect()
+}
+
+/// Shared script directories and script names, as given by the current config.
+type ScriptPaths = Arc<RwLock<(Vec<String>, Vec<String>)>>;
+
+fn config_script_loader(script_paths: ScriptPaths) -> ScriptLoaderPointer {
+    fn substitute_variables(text: String, path: &str) -> Result<String, Error> {
+        substitute_home(&text.replace("${NAME}", path))
     }
 
-    if let Some(suites) = config.suites {
-        let script_dirs = config.script_dirs;
-        let script_names = config.script_names;
+    Arc::new(RwLock::new(move |path: &str| {
+        debug!("daemon::config_script_loader({path})");
 
-        let script_loader = move |path: &str| {
-            debug!("daemon::run_config::script_loader({path})");
+        let (script_dirs, script_names) = script_paths
+            .read()
+            .map_err(|_| Error::ScriptLoaderLockingError)?
+            .clone();
 
-            if let Some(script) = script_dirs
-                .iter()
-                .flat_map(|dir| script_names.iter().map(move |name| (dir, name)))
-                .filter_map(|(dir, name)| {
-                    debug!(
-                        "daemon::run_config::script_loader({path}) try {}",
-                        substitute_variables(format!("{dir}/{name}"), path)
-                    );
+        let candidates = script_dirs
+            .iter()
+            .flat_map(|dir| script_names.iter().map(move |name| (dir, name)))
+            .map(|(dir, name)| substitute_variables(format!("{dir}/{name}"), path))
+            .collect::<Result<Vec<_>, _>>()?;
 
-                    fs::read_to_string(substitute_variables(format!("{dir}/{name}"), path)).ok()
-                })
-                .next()
-            {
-                debug!(
-                    "daemon::run_config::script_loader({path}) -> Ok ({} bytes)",
-                    script.len()
-                );
-                Ok(script)
-            } else {
-                debug!("daemon::run_config::script_loader({path}) -> Not found");
-                Err(Error::ScriptNotFoundError(path.to_string()))
-            }
-        };
+        if let Some(script) = candidates
+            .iter()
+            .filter_map(|candidate| {
+                debug!("daemon::config_script_loader({path}) try {candidate}");
+
+                fs::read_to_string(candidate).ok()
+            })
+            .next()
+        {
+            debug!(
+                "daemon::config_script_loader({path}) -> Ok ({} bytes)",
+                script.len()
+            );
+            Ok(script)
+        } else {
+            debug!("daemon::config_script_loader({path}) -> Not found");
+            Err(Error::ScriptNotFoundError(path.to_string()))
+        }
+    }))
+}
+
+pub async fn run_config(config: Config, effects: EffectRegistry, shutdown: watch::Receiver<bool>) {
+    debug!("daemon::run_config({config:?}, {effects:?})");
+
+    if let Some(suites) = config.suites {
+        let script_paths = Arc::new(RwLock::new((config.script_dirs, config.script_names)));
 
-        run_forever(
+        run_forever_with_last_runs::<ReqwestHttpDriver>(
             suites,
-            Arc::new(RwLock::new(script_loader)),
+            config_script_loader(script_paths),
             effects,
             LocalMinuteIntervalClock,
+            NoConfigWatcher,
+            load_last_runs(config.state_file.as_deref()),
+            shutdown,
         )
         .await
     } else {
@@ -145,26 +203,195 @@ pub async fn run_config(config: Config, effects: HashMap<String, EffectSignature
     }
 }
 
+/// Run the config in the file at `path`, picking up any changes made to the file while the
+/// daemon is running.
+pub async fn run_config_file(path: &str, effects: EffectRegistry, shutdown: watch::Receiver<bool>) {
+    debug!("daemon::run_config_file({path}, {effects:?})");
+
+    let watcher = match ConfigFileWatcher::new(path) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            error!("daemon::run_config_file: {e}");
+            return;
+        }
+    };
+
+    let config = watcher.config.clone();
+
+    if config.suites.is_none() {
+        warn!("daemon::run_config_file: daemon asked to run config containing no suite(s).")
+    }
+
+    run_forever_with_last_runs::<ReqwestHttpDriver>(
+        config.suites.unwrap_or_default(),
+        config_script_loader(watcher.script_paths.clone()),
+        effects,
+        LocalMinuteIntervalClock,
+        watcher,
+        load_last_runs(config.state_file.as_deref()),
+        shutdown,
+    )
+    .await
+}
+
+/// Load the times at which jobs were last run from `state_file`, or from [DEFAULT_STATE_FILE]
+/// if None, falling back to keeping them in memory only if the file cannot be read.
+fn load_last_runs(state_file: Option<&str>) -> LastRuns {
+    let state_file = state_file.unwrap_or(DEFAULT_STATE_FILE);
+
+    LastRuns::load(state_file).unwrap_or_else(|e| {
+        error!("daemon: error loading {state_file}, runs missed will not be caught up on: {e}");
+        LastRuns::new()
+    })
+}
+
+/// Describe the jobs of `suites` for `scrapeycat daemon --list`: one line per job giving its
+/// name, script and schedule, followed by the next `count` times after `now` at which it is due.
+pub fn format_schedule(suites: &[Suite], now: DateTime<Local>, count: usize) -> String {
+    let mut suites = suites.iter().collect::<Vec<_>>();
+    suites.sort_by_key(|suite| suite.name());
+
+    let mut text = String::new();
+
+    for suite in suites {
+        for job in suite.jobs() {
+            text.push_str(&format!(
+                "{}.{} (script `{}`, schedule `{}`)\n",
+                suite.name(),
+                job.name(),
+                job.script_name(),
+                job.schedule(),
+            ));
+
+            let times = std::iter::successors(job.schedule().next_after(now), |when| {
+                job.schedule().next_after(*when)
+            });
+
+            let mut due = false;
+
+            for when in times.take(count) {
+                text.push_str(&format!("    {}\n", when.format("%Y-%m-%d %H:%M")));
+                due = true;
+            }
+
+            if !due {
+                text.push_str("    never\n");
+            }
+        }
+    }
+
+    text
+}
+
+/// Trait for picking up changes to the config while [run_forever] is running.
+pub trait ConfigWatcher {
+    /// Check for changes to the config.
+    ///
+    /// This method is called at least once per interval, and should return the new list of
+    /// suites if the config has changed since the last call, or None if it is unchanged.
+    fn poll(&mut self) -> Option<Vec<Suite>>;
+}
+
+/// A watcher for configs that never change.
+#[derive(Default)]
+pub struct NoConfigWatcher;
+
+impl ConfigWatcher for NoConfigWatcher {
+    fn poll(&mut self) -> Option<Vec<Suite>> {
+        None
+    }
+}
+
+/// A watcher polling a config file for changes to its contents.
+///
+/// If the changed file cannot be parsed, the error is logged and the previous config is kept.
+pub struct ConfigFileWatcher {
+    path: String,
+    contents: String,
+    config: Config,
+    script_paths: ScriptPaths,
+}
+
+impl ConfigFileWatcher {
+    pub fn new(path: impl Into<String>) -> Result<Self, Error> {
+        let path = path.into();
+        let contents = fs::read_to_string(&path)?;
+        let config = ConfigFile::config_from_str(&contents)?;
+        let script_paths = Arc::new(RwLock::new((
+            config.script_dirs.clone(),
+            config.script_names.clone(),
+        )));
+
+        Ok(ConfigFileWatcher {
+            path,
+            contents,
+            config,
+            script_paths,
+        })
+    }
+}
+
+impl ConfigWatcher for ConfigFileWatcher {
+    fn poll(&mut self) -> Option<Vec<Suite>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!(
+                    "daemon::ConfigFileWatcher: error reading {}: {e}",
+                    self.path
+                );
+                return None;
+            }
+        };
+
+        if contents == self.contents {
+            return None;
+        }
+
+        // Remember the contents either way, to only report a broken config once
+        self.contents = contents;
+
+        match ConfigFile::config_from_str(&self.contents) {
+            Ok(config) => {
+                debug!("daemon::ConfigFileWatcher: reloaded {}", self.path);
+
+                match self.script_paths.write() {
+                    Ok(mut script_paths) => {
+                        *script_paths = (config.script_dirs.clone(), config.script_names.clone())
+                    }
+                    Err(e) => {
+                        error!("daemon::ConfigFileWatcher: {e}");
+                        return None;
+                    }
+                }
+
+                self.config = config;
+                Some(self.config.suites.clone().unwrap_or_default())
+            }
+            Err(e) => {
+                error!(
+                    "daemon::ConfigFileWatcher: error reloading {}, keeping previous config: {e}",
+                    self.path
+                );
+                None
+            }
+        }
+    }
+}
+
 /// Trait for the clock of the main daemon loop in [run_forever].
 pub trait Clock {
-    /// Get the tick interval.
+    /// Get the longest time to sleep at once.
     ///
-    /// The daemon will check for due jobs once per tick, but note that jobs are always
-    /// scheduled at one-minute granularity.
+    /// The daemon sleeps until the next time at which a job is due, but wakes up at least
+    /// once per interval to check for changes to the config.
     fn interval(&mut self) -> Duration;
 
     /// Check the clock.
     ///
-    /// This method is called exactly once per interval.
+    /// This method is called exactly once per wakeup.
     fn now(&mut self) -> Option<DateTime<Local>>;
 
-    /// Peek at the clock to ensure we're not oversleeping.
-    ///
-    /// This method may be called multiple times per interval and/or in the middle of
-    /// an interval. The distinction between [Clock::now] and [Clock::peek] is useful
-    /// for creating different types of mock clocks in testing.
-    fn peek(&mut self) -> Option<DateTime<Local>>;
-
     /// Sleep for some time.
     #[allow(async_fn_in_trait)]
     async fn sleep(&mut self, time: Duration);
@@ -183,62 +410,406 @@ impl Clock for LocalMinuteIntervalClock {
         Some(Local::now())
     }
 
-    fn peek(&mut self) -> Option<DateTime<Local>> {
-        Some(Local::now())
-    }
-
     async fn sleep(&mut self, time: Duration) {
         tokio::time::sleep(time).await
     }
 }
 
-// TODO: it would be cool if the daemon could pick up changes to the config automatically
-pub async fn run_forever(
-    suites: Vec<Suite>,
-    script_loader: ScriptLoaderPointer,
-    effects: HashMap<String, EffectSignature>,
-    mut clock: impl Clock,
-) {
-    debug!("daemon::run_forever({suites:?}, {effects:?})");
+/// A job spawned by [spawn_jobs], along with its effects handler and what it shares with the
+/// other jobs of its suite.
+#[derive(Debug)]
+struct RunningJob {
+    /// The name of the suite of the job.
+    suite: String,
+    job: Job,
+    /// The sender for the effects handler of the job.
+    effect_sender: UnboundedSender<EffectInvocation>,
+    /// The handle of the effects handler of the job.
+    effects_handler: JoinHandle<()>,
+    /// Whether a run of the job is in flight.
+    in_flight: Arc<AtomicBool>,
+    /// The runtime of the suite of the job, if isolated.
+    runtime: Option<SuiteRuntime>,
+    /// The rate limiter shared by the jobs of the suite of the job, if any.
+    rate_limiter: Option<RateLimiter>,
+}
 
-    let interval = clock.interval();
+/// A runtime of its own for running the jobs of an isolated suite, driven by a dedicated thread
+/// so that runs blocking or crashing the thread do not affect the jobs of other suites. The
+/// thread exits once all clones have been dropped and all runs spawned have finished.
+#[derive(Debug, Clone)]
+struct SuiteRuntime {
+    handle: Handle,
+    keepalive: mpsc::Sender<()>,
+}
+
+impl SuiteRuntime {
+    fn new(suite_name: &str) -> Result<SuiteRuntime, Error> {
+        let (handle_tx, handle_rx) = std::sync::mpsc::channel();
+        let (keepalive, mut keepalive_rx) = mpsc::channel::<()>(1);
+
+        thread::Builder::new()
+            .name(format!("suite-{suite_name}"))
+            .spawn(
+                move || match Builder::new_current_thread().enable_all().build() {
+                    Ok(runtime) => {
+                        let _ = handle_tx.send(Ok(runtime.handle().clone()));
+
+                        // Never receives a value, returns once all senders have been dropped
+                        runtime.block_on(keepalive_rx.recv());
+                    }
+                    Err(e) => {
+                        let _ = handle_tx.send(Err(e));
+                    }
+                },
+            )?;
 
-    let jobs = suites
+        let handle = handle_rx
+            .recv()
+            .map_err(|e| Error::IOError(io::Error::other(e)))??;
+
+        Ok(SuiteRuntime { handle, keepalive })
+    }
+
+    /// Spawn `future` on the runtime, keeping the runtime running until it has finished.
+    fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let keepalive = self.keepalive.clone();
+
+        self.handle.spawn(async move {
+            let _keepalive = keepalive;
+            future.await
+        })
+    }
+}
+
+/// The options of the effects handler for the effects of `job`.
+fn effects_handler_options(job: &Job) -> FlagSet<EffectsHandlerOptions> {
+    let mut options: FlagSet<_> = EffectsHandlerOptions::Default.into();
+
+    if job.is_dedup() {
+        options |= EffectsHandlerOptions::Deduplicate;
+    }
+
+    options
+}
+
+/// Spawn an effects handler for each job of the given suites, and a runtime for each isolated
+/// suite.
+fn spawn_jobs(suites: &[Suite], effects: &EffectRegistry) -> Vec<RunningJob> {
+    suites
         .iter()
         .flat_map(|suite| {
-            suite.jobs().enumerate().map(|(nth, job)| {
-                let mut options: FlagSet<_> = EffectsHandlerOptions::Default.into();
-
-                if job.is_dedup() {
-                    options |= EffectsHandlerOptions::Deduplicate;
-                }
+            let runtime = if suite.is_isolated() {
+                SuiteRuntime::new(suite.name())
+                    .inspect_err(|e| {
+                        error!(
+                            "daemon::spawn_jobs: unable to isolate suite {}, running it with \
+                             the others: {e}",
+                            suite.name()
+                        )
+                    })
+                    .ok()
+            } else {
+                None
+            };
 
+            suite.jobs().enumerate().map(move |(nth, job)| {
                 let (tx, rx) = mpsc::unbounded_channel::<EffectInvocation>();
-                (
-                    suite.name(),
-                    job,
-                    tx,
-                    tokio::spawn(effects_handler(
+
+                RunningJob {
+                    suite: suite.name().to_string(),
+                    job: job.clone(),
+                    effect_sender: tx,
+                    effects_handler: tokio::spawn(effects_handler(
                         format!("{}.{}-{}", suite.name(), nth, job.script_name()),
                         rx,
                         effects.clone(),
-                        options,
+                        effects_handler_options(job),
                     )),
-                )
+                    in_flight: Arc::new(AtomicBool::new(false)),
+                    runtime: runtime.clone(),
+                    rate_limiter: suite.rate_limiter().cloned(),
+                }
             })
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+/// How long [run_forever] waits for runs in flight and effects handlers to finish on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wait until `shutdown` is set to true. Never returns if the sender has been dropped without
+/// requesting shutdown.
+async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    if shutdown.wait_for(|requested| *requested).await.is_err() {
+        std::future::pending::<()>().await
+    }
+}
+
+/// Await `run`, failing with [Error::TimeoutError] if `timeout` is given and passes first.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    run: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, run)
+            .await
+            .unwrap_or(Err(Error::TimeoutError(timeout.as_secs()))),
+        None => run.await,
+    }
+}
+
+/// Run `job` once, sending its effects to `effect_sender`, or a summary of them if the job
+/// summarizes its effects.
+fn run_job<H: HttpDriver + Send + Sync + 'static>(
+    job: &Job,
+    rate_limiter: Option<RateLimiter>,
+    script_loader: ScriptLoaderPointer,
+    effect_sender: UnboundedSender<EffectInvocation>,
+    effects: EffectRegistry,
+) -> impl Future<Output = Result<Vector<String>, Error>> + Send + 'static {
+    let script_name = job.script_name().to_string();
+    let args = job.args().clone();
+    let kwargs = job.kwargs().clone();
+    let options = RunOptions {
+        headers: job.headers().clone(),
+        rate_limiter,
+        memory_cache: None,
+        resolve: job.resolve().clone(),
+        allowed_env: job.allowed_env().cloned(),
+        cookie_jar: job.cookie_jar().map(str::to_string),
+        effects,
+    };
+    let timeout = job.timeout();
+    let summarize = job.is_summarize();
+
+    async move {
+        if summarize {
+            let (buffer_tx, mut buffer_rx) = mpsc::unbounded_channel::<EffectInvocation>();
+
+            let result = with_timeout(
+                timeout,
+                run_with_options::<H>(
+                    &script_name,
+                    args,
+                    kwargs,
+                    options,
+                    script_loader,
+                    buffer_tx,
+                ),
+            )
+            .await;
+
+            let mut buffered = vec![];
+
+            while let Ok(invocation) = buffer_rx.try_recv() {
+                buffered.push(invocation);
+            }
+
+            for invocation in summarize_invocations(buffered) {
+                let _ = effect_sender.send(invocation);
+            }
+
+            result
+        } else {
+            with_timeout(
+                timeout,
+                run_with_options::<H>(
+                    &script_name,
+                    args,
+                    kwargs,
+                    options,
+                    script_loader,
+                    effect_sender,
+                ),
+            )
+            .await
+        }
+    }
+}
+
+/// Run the job named `job_id`, given as `suite.job`, of the given suites once right away, as
+/// the daemon would run it when due, and wait for its effects to be handled.
+pub async fn run_job_once<H: HttpDriver + Send + Sync + 'static>(
+    suites: &[Suite],
+    job_id: &str,
+    script_loader: ScriptLoaderPointer,
+    effects: EffectRegistry,
+) -> Result<Vector<String>, Error> {
+    debug!("daemon::run_job_once({job_id}, {effects:?})");
+
+    let (suite, job) = suites
+        .iter()
+        .flat_map(|suite| suite.jobs().map(move |job| (suite, job)))
+        .find(|(suite, job)| format!("{}.{}", suite.name(), job.name()) == job_id)
+        .ok_or(Error::JobNotFoundError)?;
+
+    let (effect_tx, effect_rx) = mpsc::unbounded_channel::<EffectInvocation>();
+
+    let handler = tokio::spawn(effects_handler(
+        job_id.to_string(),
+        effect_rx,
+        effects.clone(),
+        effects_handler_options(job),
+    ));
+
+    // The run owns the only sender, so the effects handler exits once the run is done and all
+    // effects sent have been handled
+    let result = run_job::<H>(
+        job,
+        suite.rate_limiter().cloned(),
+        script_loader,
+        effect_tx,
+        effects,
+    )
+    .await;
+
+    let _ = handler.await;
+
+    result
+}
+
+/// Run the job named `job_id`, given as `suite.job`, of `config` once right away, as for
+/// `scrapeycat daemon --run`.
+pub async fn run_config_job(
+    config: Config,
+    job_id: &str,
+    effects: EffectRegistry,
+) -> Result<Vector<String>, Error> {
+    let script_paths = Arc::new(RwLock::new((config.script_dirs, config.script_names)));
+
+    run_job_once::<ReqwestHttpDriver>(
+        &config.suites.unwrap_or_default(),
+        job_id,
+        config_script_loader(script_paths),
+        effects,
+    )
+    .await
+}
+
+/// How long ago a missed run of a job may have been due for the job to be caught up on when the
+/// daemon starts, so that restarting after a long downtime does not start every job at once.
+const CATCHUP_MAX_AGE: TimeDelta = TimeDelta::days(1);
+
+/// How many runs caught up on may be in flight at once, so that restarting the daemon does not
+/// start every job with catch-up enabled at the same time.
+const CATCHUP_CONCURRENCY: usize = 2;
+
+/// Whether `job`, last run at `last_run`, missed a run due within [CATCHUP_MAX_AGE] before the
+/// minute of `now`.
+fn missed_run(job: &Job, last_run: Option<DateTime<Local>>, now: DateTime<Local>) -> bool {
+    let (Some(last_run), Some(minute)) = (
+        last_run,
+        now.with_second(0).and_then(|now| now.with_nanosecond(0)),
+    ) else {
+        return false;
+    };
+
+    job.schedule()
+        .prev_before(minute)
+        .is_some_and(|missed| missed > last_run && now - missed <= CATCHUP_MAX_AGE)
+}
+
+/// Run the jobs of the given suites on schedule until the clock runs out or shutdown is
+/// requested by sending `true` on the `shutdown` channel.
+///
+/// Between runs, the daemon sleeps until the next time at which any job is due, or for at most
+/// the interval of the clock. On waking up, each job due at any minute since the previous wakeup
+/// is run once, so jobs are not skipped if the clock oversleeps. On the first wakeup, jobs due in
+/// the current minute are run.
+///
+/// On shutdown, or once the clock runs out, no further runs are started, and any runs in flight
+/// and the effects handlers are given [SHUTDOWN_TIMEOUT] to finish handling all effects sent
+/// before returning.
+pub async fn run_forever<H: HttpDriver + Send + Sync + 'static>(
+    suites: Vec<Suite>,
+    script_loader: ScriptLoaderPointer,
+    effects: EffectRegistry,
+    clock: impl Clock,
+    watcher: impl ConfigWatcher,
+    shutdown: watch::Receiver<bool>,
+) {
+    run_forever_with_last_runs::<H>(
+        suites,
+        script_loader,
+        effects,
+        clock,
+        watcher,
+        LastRuns::new(),
+        shutdown,
+    )
+    .await
+}
+
+/// Like [run_forever], but recording the times at which jobs with catch-up enabled are run in
+/// `last_runs`. On the first wakeup, each such job that missed a run since it was last run is
+/// run once, provided the run missed was due within [CATCHUP_MAX_AGE].
+pub async fn run_forever_with_last_runs<H: HttpDriver + Send + Sync + 'static>(
+    suites: Vec<Suite>,
+    script_loader: ScriptLoaderPointer,
+    effects: EffectRegistry,
+    mut clock: impl Clock,
+    mut watcher: impl ConfigWatcher,
+    mut last_runs: LastRuns,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    debug!("daemon::run_forever({suites:?}, {effects:?})");
+
+    let interval = clock.interval();
+
+    let mut jobs = spawn_jobs(&suites, &effects);
+
+    // Handles of runs in flight and of effects handlers of jobs replaced by a reload
+    let mut pending: Vec<JoinHandle<()>> = vec![];
 
     debug!("daemon::run_forever: jobs ({}): {jobs:?}", jobs.len());
 
-    loop {
-        let datetime_top = clock.now();
+    // The time of the previous wakeup, at or before which all due runs have been started
+    let mut checked_until: Option<DateTime<Local>> = None;
+
+    let catchup_permits = Arc::new(Semaphore::new(CATCHUP_CONCURRENCY));
 
-        if datetime_top.is_none() {
+    loop {
+        if *shutdown.borrow() {
             break;
         }
 
-        for (suite, job, effect_tx, _) in &jobs {
+        pending.retain(|handle| !handle.is_finished());
+
+        if let Some(suites) = watcher.poll() {
+            // Dropping the old jobs closes their effect channels, so the old effects handlers
+            // exit once any runs still in flight have finished sending their effects.
+            let old_jobs = std::mem::replace(&mut jobs, spawn_jobs(&suites, &effects));
+
+            pending.extend(old_jobs.into_iter().map(|running| running.effects_handler));
+
+            debug!(
+                "daemon::run_forever: reloaded jobs ({}): {jobs:?}",
+                jobs.len()
+            );
+        }
+
+        let Some(now) = clock.now() else {
+            break;
+        };
+
+        let since = checked_until.unwrap_or(now - TimeDelta::minutes(1));
+        let mut recorded = false;
+
+        for RunningJob {
+            suite,
+            job,
+            effect_sender: effect_tx,
+            in_flight,
+            runtime,
+            rate_limiter,
+            ..
+        } in &jobs
+        {
             debug!(
                 "daemon::run_forever::loop: check {}.{}-{}",
                 suite,
@@ -246,7 +817,32 @@ pub async fn run_forever(
                 job.script_name()
             );
 
-            if job.is_due_at(datetime_top.expect("`datetime_top` cannot be None")) {
+            let job_id = format!("{}.{}", suite, job.name());
+
+            let catchup = checked_until.is_none()
+                && job.is_catchup()
+                && missed_run(job, last_runs.get(&job_id), now);
+
+            if catchup {
+                info!("daemon::run_forever::loop: catching up on missed run of {job_id}");
+            }
+
+            if job.is_due_between(since, now) || catchup {
+                if !job.is_allow_overlap() && in_flight.swap(true, Ordering::SeqCst) {
+                    debug!(
+                        "daemon::run_forever::loop: skip {}.{}-{} (previous run still in flight)",
+                        suite,
+                        job.name(),
+                        job.script_name()
+                    );
+                    continue;
+                }
+
+                if job.is_catchup() {
+                    last_runs.record(&job_id, now);
+                    recorded = true;
+                }
+
                 debug!(
                     "daemon::run_forever::loop: execute {}.{}-{}",
                     suite,
@@ -254,34 +850,61 @@ pub async fn run_forever(
                     job.script_name()
                 );
 
-                let task_script_name = job.script_name().to_string();
-                let task_args = job.args().clone();
-                let task_kwargs = job.kwargs().clone();
-                let task_effect_sender = effect_tx.clone();
-                let task_script_loader = script_loader.clone();
-
-                let handle = tokio::spawn(async move {
-                    run::<ReqwestHttpDriver>(
-                        &task_script_name,
-                        task_args,
-                        task_kwargs,
-                        task_script_loader,
-                        task_effect_sender,
-                    )
-                    .await
-                });
+                let run = run_job::<H>(
+                    job,
+                    rate_limiter.clone(),
+                    script_loader.clone(),
+                    effect_tx.clone(),
+                    effects.clone(),
+                );
+
+                let permits = catchup.then(|| catchup_permits.clone());
+
+                let task = async move {
+                    let _permit = match permits {
+                        Some(permits) => permits.acquire_owned().await.ok(),
+                        None => None,
+                    };
+
+                    run.await
+                };
+
+                let handle = match runtime {
+                    Some(runtime) => runtime.spawn(task),
+                    None => tokio::spawn(task),
+                };
 
                 let err_script_id = job.script_name().to_string();
+                let err_job_id = job_id.clone();
+                let err_on_error = job.on_error().map(|name| name.to_string());
+                let err_effect_sender = effect_tx.clone();
+                let task_in_flight = in_flight.clone();
 
-                tokio::spawn(async move {
-                    match handle.await {
+                pending.push(tokio::spawn(async move {
+                    let error_text = match handle.await {
                         Ok(result) => match result {
-                            Ok(_) => (),
-                            Err(e) => error!("daemon::run_forever::loop: ({err_script_id}) {e}"),
+                            Ok(_) => None,
+                            Err(e) => Some(e.to_string()),
                         },
-                        Err(e) => error!("daemon::run_forever::loop: ({err_script_id}) {e}"),
+                        Err(e) => Some(e.to_string()),
+                    };
+
+                    if let Some(error_text) = error_text {
+                        error!("daemon::run_forever::loop: ({err_script_id}) {error_text}");
+
+                        if let Some(on_error) = err_on_error {
+                            let _ = err_effect_sender.send(EffectInvocation::new(
+                                on_error,
+                                vec![format!(
+                                    "Job `{err_job_id}` ({err_script_id}) failed: {error_text}"
+                                )],
+                                HashMap::new(),
+                            ));
+                        }
                     }
-                });
+
+                    task_in_flight.store(false, Ordering::SeqCst);
+                }));
             } else {
                 debug!(
                     "daemon::run_forever::loop: skip {}.{}-{}",
@@ -292,19 +915,46 @@ pub async fn run_forever(
             }
         }
 
-        clock.sleep(interval / 2).await;
+        checked_until = Some(now);
+
+        if recorded && let Err(e) = last_runs.save().await {
+            error!("daemon::run_forever::loop: error recording last runs: {e}");
+        }
 
-        let datetime_middle = clock.peek();
+        let next_due = jobs
+            .iter()
+            .filter_map(|running| running.job.schedule().next_after(now))
+            .min();
 
-        if datetime_middle.is_none() {
-            break;
+        let sleep = next_due
+            .and_then(|next| (next - now).to_std().ok())
+            .map_or(interval, |until| until.min(interval));
+
+        debug!("daemon::run_forever: next due {next_due:?}, sleeping {sleep:?}");
+
+        tokio::select! {
+            _ = clock.sleep(sleep) => (),
+            _ = shutdown_requested(&mut shutdown) => break,
         }
+    }
 
-        if Job::format_datetime(datetime_top.expect("`datetime_top` cannot be None"))
-            == Job::format_datetime(datetime_middle.expect("`datetime_middle` cannot be None"))
-        {
-            clock.sleep(interval / 2).await;
+    debug!(
+        "daemon::run_forever: stopping, waiting for {} task(s)",
+        pending.len() + jobs.len()
+    );
+
+    // Dropping the jobs closes their effect channels once the runs in flight are done, so the
+    // effects handlers exit after handling all effects sent
+    pending.extend(jobs.into_iter().map(|running| running.effects_handler));
+
+    let drain = async {
+        for handle in pending {
+            let _ = handle.await;
         }
+    };
+
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, drain).await.is_err() {
+        warn!("daemon::run_forever: timed out waiting for jobs to finish, shutting down");
     }
 }
 
@@ -312,18 +962,29 @@ pub async fn run_forever(
 mod tests {
     use std::{
         env,
-        sync::atomic::{AtomicU32, Ordering::SeqCst},
+        net::SocketAddr,
+        sync::{
+            Mutex,
+            atomic::{AtomicU32, Ordering::SeqCst},
+        },
     };
 
-    use chrono::TimeDelta;
+    use chrono::TimeZone;
 
     use crate::{
         daemon::cron::CronSpec,
-        effect::{EffectArgs, EffectKwArgs},
+        effect::{EffectArgs, EffectKwArgs, EffectOptions},
+        scraper::{HttpResponse, RequestSpec},
+        testutils::TestHttpDriver,
     };
 
     use super::*;
 
+    /// A shutdown channel on which shutdown is never requested.
+    fn no_shutdown() -> watch::Receiver<bool> {
+        watch::channel(false).1
+    }
+
     fn panicking_script_loader(filename: &str) -> Result<String, Error> {
         let result = fs::read_to_string(filename);
 
@@ -351,39 +1012,38 @@ mod tests {
             self.timestamps.get(self.offset - 1).cloned()
         }
 
-        fn peek(&mut self) -> Option<DateTime<Local>> {
-            self.timestamps.get(self.offset - 1).cloned()
-        }
-
         async fn sleep(&mut self, _time: Duration) {}
     }
 
-    /// A mock clock specifically designed for the implementation detail where [run_forever]
-    /// peeks at the clock once after having tried to sleep for half the interval, and
-    /// then tries to sleep for another half of the interval unless the clock has already
-    /// reached a new minute value.
-    struct HalfIntervalPeekMockClock {
-        /// Timestamps T[n] such that after having slept a total of n times, calling
-        /// [Clock::now] or [Clock::peek] will return T[n].
-        timestamps: Vec<DateTime<Local>>,
-        times_slept: usize,
+    /// A script loader loading a script that prints the name of the script.
+    fn print_name_script_loader(name: &str) -> Result<String, Error> {
+        Ok(format!("effect(\"print\", {{\"{name}\"}})"))
+    }
+
+    /// A mock clock starting at `now` and advancing by exactly the time slept, which runs out
+    /// after `end`, recording the time of each wakeup.
+    struct SteppingMockClock {
+        now: DateTime<Local>,
+        end: DateTime<Local>,
+        wakeups: Arc<Mutex<Vec<DateTime<Local>>>>,
     }
 
-    impl Clock for HalfIntervalPeekMockClock {
+    impl Clock for SteppingMockClock {
         fn interval(&mut self) -> Duration {
-            Duration::ZERO
+            Duration::from_secs(24 * 60 * 60)
         }
 
         fn now(&mut self) -> Option<DateTime<Local>> {
-            self.timestamps.get(self.times_slept).cloned()
-        }
+            if self.now > self.end {
+                return None;
+            }
 
-        fn peek(&mut self) -> Option<DateTime<Local>> {
-            self.timestamps.get(self.times_slept).cloned()
+            self.wakeups.lock().unwrap().push(self.now);
+            Some(self.now)
         }
 
-        async fn sleep(&mut self, _time: Duration) {
-            self.times_slept += 1;
+        async fn sleep(&mut self, time: Duration) {
+            self.now += TimeDelta::from_std(time).unwrap();
         }
     }
 
@@ -405,7 +1065,9 @@ mod tests {
                     "* * * * *".parse::<CronSpec>().unwrap(),
                     false,
                 )
-                .unwrap(),
+                .unwrap()
+                // Ticks pass instantly, so runs would otherwise be skipped as overlapping
+                .with_allow_overlap(true),
             ],
         );
 
@@ -416,8 +1078,7 @@ mod tests {
             None
         }
 
-        let effects: HashMap<String, EffectSignature> =
-            HashMap::from([("print".to_string(), print as EffectSignature)]);
+        let effects = EffectRegistry::new().register("print", print);
 
         let t0 = Local::now();
 
@@ -426,48 +1087,129 @@ mod tests {
             offset: 0,
         };
 
-        let task_handle = tokio::spawn(run_forever(
+        let task_handle = tokio::spawn(run_forever::<TestHttpDriver>(
             vec![suite],
             Arc::new(RwLock::new(panicking_script_loader)),
             effects,
             clock,
+            NoConfigWatcher,
+            no_shutdown(),
         ));
 
         let _ = tokio::join!(task_handle);
         assert_eq!(TEST_PRINT_EACH_MINUTE_COUNT.load(SeqCst), 3);
     }
 
-    static TEST_PRINT_EACH_MINUTE_DEDUP_COUNT: AtomicU32 = AtomicU32::new(0);
+    static TEST_ISOLATED_SUITE_RELEASED: AtomicBool = AtomicBool::new(false);
+    static TEST_ISOLATED_SUITE_UNBLOCKED: AtomicBool = AtomicBool::new(false);
 
     #[tokio::test]
-    async fn test_print_each_minute_dedup() {
-        let suite = Suite::new(
-            "default".to_string(),
-            vec![
-                Job::new(
-                    "default",
-                    format!(
-                        "{}/tests/assets/scripts/print.scrape",
-                        env::var("CARGO_MANIFEST_DIR").unwrap()
-                    ),
-                    None,
-                    None,
-                    "* * * * *".parse::<CronSpec>().unwrap(),
-                    true,
-                )
-                .unwrap(),
-            ],
-        );
-
-        TEST_PRINT_EACH_MINUTE_DEDUP_COUNT.swap(0, SeqCst);
+    async fn test_isolated_suite() {
+        /// A driver blocking its thread for requests for `block` until released by the `print`
+        /// effect, or for at most five seconds.
+        #[derive(Clone)]
+        struct BlockingTestHttpDriver;
+
+        impl HttpDriver for BlockingTestHttpDriver {
+            async fn request(
+                spec: RequestSpec<'_>,
+                _proxy: Option<&str>,
+                _resolve: &im::HashMap<String, SocketAddr>,
+            ) -> Result<HttpResponse, Error> {
+                if spec.url == "block" {
+                    let start = std::time::Instant::now();
+
+                    while start.elapsed() < Duration::from_secs(5) {
+                        if TEST_ISOLATED_SUITE_RELEASED.load(SeqCst) {
+                            TEST_ISOLATED_SUITE_UNBLOCKED.store(true, SeqCst);
+                            break;
+                        }
+
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                }
+
+                Ok(HttpResponse::default())
+            }
+        }
+
+        let job = |name: &str| {
+            Job::new(
+                name,
+                name,
+                None,
+                None,
+                "* * * * *".parse::<CronSpec>().unwrap(),
+                false,
+            )
+            .unwrap()
+        };
+
+        let isolated = Suite::new("isolated", vec![job("block")]).with_isolated(true);
+        let shared = Suite::new("shared", vec![job("print")]);
+
+        TEST_ISOLATED_SUITE_RELEASED.store(false, SeqCst);
+        TEST_ISOLATED_SUITE_UNBLOCKED.store(false, SeqCst);
+
+        fn print(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_ISOLATED_SUITE_RELEASED.store(true, SeqCst);
+            None
+        }
+
+        let clock = PerfectMockClock {
+            timestamps: vec![Local::now()],
+            offset: 0,
+        };
+
+        let task_handle = tokio::spawn(run_forever::<BlockingTestHttpDriver>(
+            vec![isolated, shared],
+            Arc::new(RwLock::new(|name: &str| match name {
+                "block" => Ok(r#"get("block")"#.to_string()),
+                _ => Ok(r#"effect("print", {"shared"})"#.to_string()),
+            })),
+            EffectRegistry::new().register("print", print),
+            clock,
+            NoConfigWatcher,
+            no_shutdown(),
+        ));
+
+        assert!(tokio::join!(task_handle).0.is_ok());
+
+        // The shared suite ran its job and handled its effect while the runtime of the isolated
+        // suite was blocked, which would otherwise have blocked the only thread of the test
+        assert!(TEST_ISOLATED_SUITE_UNBLOCKED.load(SeqCst));
+    }
+
+    static TEST_PRINT_EACH_MINUTE_DEDUP_COUNT: AtomicU32 = AtomicU32::new(0);
+
+    #[tokio::test]
+    async fn test_print_each_minute_dedup() {
+        let suite = Suite::new(
+            "default".to_string(),
+            vec![
+                Job::new(
+                    "default",
+                    format!(
+                        "{}/tests/assets/scripts/print.scrape",
+                        env::var("CARGO_MANIFEST_DIR").unwrap()
+                    ),
+                    None,
+                    None,
+                    "* * * * *".parse::<CronSpec>().unwrap(),
+                    true,
+                )
+                .unwrap(),
+            ],
+        );
+
+        TEST_PRINT_EACH_MINUTE_DEDUP_COUNT.swap(0, SeqCst);
 
         fn print(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
             TEST_PRINT_EACH_MINUTE_DEDUP_COUNT.fetch_add(1, SeqCst);
             None
         }
 
-        let effects: HashMap<String, EffectSignature> =
-            HashMap::from([("print".to_string(), print as EffectSignature)]);
+        let effects = EffectRegistry::new().register("print", print);
 
         let t0 = Local::now();
 
@@ -476,11 +1218,13 @@ mod tests {
             offset: 0,
         };
 
-        let task_handle = tokio::spawn(run_forever(
+        let task_handle = tokio::spawn(run_forever::<TestHttpDriver>(
             vec![suite],
             Arc::new(RwLock::new(panicking_script_loader)),
             effects,
             clock,
+            NoConfigWatcher,
+            no_shutdown(),
         ));
 
         let _ = tokio::join!(task_handle);
@@ -491,6 +1235,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_print_each_minute_oversleep() {
+        use chrono::Timelike;
+
         let suite = Suite::new(
             "default".to_string(),
             vec![
@@ -505,7 +1251,9 @@ mod tests {
                     "* * * * *".parse::<CronSpec>().unwrap(),
                     false,
                 )
-                .unwrap(),
+                .unwrap()
+                // Ticks pass instantly, so runs would otherwise be skipped as overlapping
+                .with_allow_overlap(true),
             ],
         );
 
@@ -516,76 +1264,838 @@ mod tests {
             None
         }
 
-        let effects: HashMap<String, EffectSignature> =
-            HashMap::from([("print".to_string(), print as EffectSignature)]);
+        let effects = EffectRegistry::new().register("print", print);
 
-        let t0 = Local::now();
+        let t0 = Local::now()
+            .with_second(0)
+            .and_then(|t0| t0.with_nanosecond(0))
+            .unwrap();
 
-        let clock = HalfIntervalPeekMockClock {
+        // Waking up late, past the start of the next minute, still runs the job once for
+        // each minute
+        let clock = PerfectMockClock {
             timestamps: vec![
-                // first response to .now()
                 t0,
-                // * half-interval sleep *
+                t0 + TimeDelta::minutes(1) + TimeDelta::seconds(50),
+                t0 + TimeDelta::minutes(2) + TimeDelta::seconds(5),
+            ],
+            offset: 0,
+        };
+
+        let task_handle = tokio::spawn(run_forever::<TestHttpDriver>(
+            vec![suite],
+            Arc::new(RwLock::new(panicking_script_loader)),
+            effects,
+            clock,
+            NoConfigWatcher,
+            no_shutdown(),
+        ));
+
+        let _ = tokio::join!(task_handle);
+        assert_eq!(TEST_PRINT_EACH_MINUTE_OVERSLEEP_COUNT.load(SeqCst), 3);
+    }
+
+    /// A mock watcher reporting a changed config on the nth poll.
+    struct SwapMockConfigWatcher {
+        suites: Option<Vec<Suite>>,
+        swap_at_poll: usize,
+        polls: usize,
+    }
+
+    impl ConfigWatcher for SwapMockConfigWatcher {
+        fn poll(&mut self) -> Option<Vec<Suite>> {
+            self.polls += 1;
+
+            if self.polls == self.swap_at_poll {
+                self.suites.take()
+            } else {
+                None
+            }
+        }
+    }
+
+    static TEST_RELOAD_CONFIG_COUNT: AtomicU32 = AtomicU32::new(0);
+
+    #[tokio::test]
+    async fn test_reload_config() {
+        use chrono::Timelike;
+
+        let t0 = Local::now();
+
+        let suite = |schedule: &str| {
+            Suite::new(
+                "default".to_string(),
+                vec![
+                    Job::new(
+                        "default",
+                        format!(
+                            "{}/tests/assets/scripts/print.scrape",
+                            env::var("CARGO_MANIFEST_DIR").unwrap()
+                        ),
+                        None,
+                        None,
+                        schedule.parse::<CronSpec>().unwrap(),
+                        false,
+                    )
+                    .unwrap()
+                    // Ticks pass instantly, so runs would otherwise be skipped as overlapping
+                    .with_allow_overlap(true),
+                ],
+            )
+        };
+
+        TEST_RELOAD_CONFIG_COUNT.swap(0, SeqCst);
+
+        fn print(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_RELOAD_CONFIG_COUNT.fetch_add(1, SeqCst);
+            None
+        }
+
+        let effects = EffectRegistry::new().register("print", print);
+
+        let clock = PerfectMockClock {
+            timestamps: vec![t0, t0 + TimeDelta::minutes(1), t0 + TimeDelta::minutes(2)],
+            offset: 0,
+        };
+
+        // The initial schedule is not due at any of the clock's timestamps, while the new
+        // schedule picked up before the second timestamp is due every minute.
+        let watcher = SwapMockConfigWatcher {
+            suites: Some(vec![suite("* * * * *")]),
+            swap_at_poll: 2,
+            polls: 0,
+        };
+
+        let task_handle = tokio::spawn(run_forever::<TestHttpDriver>(
+            vec![suite(&format!("{} * * * *", (t0.minute() + 30) % 60))],
+            Arc::new(RwLock::new(panicking_script_loader)),
+            effects,
+            clock,
+            watcher,
+            no_shutdown(),
+        ));
+
+        let _ = tokio::join!(task_handle);
+
+        assert_eq!(TEST_RELOAD_CONFIG_COUNT.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn test_config_file_watcher() {
+        let path = env::temp_dir().join(format!(
+            "scrapeycat-test-config-file-watcher-{}.toml",
+            std::process::id()
+        ));
+        let path = path.to_str().unwrap();
+
+        let config = |schedule: &str| {
+            format!(
+                "config_version = 1\n\
+                script_dirs = [\"/{schedule}\"]\n\
+                script_names = [\"${{NAME}}\"]\n\
+                [suites.default]\n\
+                jobs = [{{ script = \"a\", schedule = \"{schedule}\", dedup = false }}]\n"
+            )
+        };
+
+        fs::write(path, config("0 * * * *")).unwrap();
+
+        let mut watcher = ConfigFileWatcher::new(path).unwrap();
+
+        assert_eq!(watcher.script_paths.read().unwrap().0, vec!["/0 * * * *"]);
+        assert!(watcher.poll().is_none());
+
+        // A broken config is ignored, keeping the previous one
+        fs::write(path, "config_version = 1\nscript_dirs = [").unwrap();
+
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().is_none());
+        assert_eq!(watcher.script_paths.read().unwrap().0, vec!["/0 * * * *"]);
+
+        fs::write(path, config("5 * * * *")).unwrap();
+
+        let suites = watcher.poll().unwrap();
+
+        assert_eq!(suites.len(), 1);
+        assert_eq!(
+            suites[0]
+                .jobs()
+                .map(|job| job.script_name())
+                .collect::<Vec<_>>(),
+            vec!["a"]
+        );
+        assert_eq!(watcher.script_paths.read().unwrap().0, vec!["/5 * * * *"]);
+        assert!(watcher.poll().is_none());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    static SLOW_DRIVER_IN_FLIGHT: AtomicU32 = AtomicU32::new(0);
+    static SLOW_DRIVER_MAX_IN_FLIGHT: AtomicU32 = AtomicU32::new(0);
+
+    /// A driver taking 200ms to respond, keeping track of the maximum number of requests
+    /// in flight at the same time.
+    #[derive(Clone)]
+    struct SlowTestHttpDriver;
+
+    impl HttpDriver for SlowTestHttpDriver {
+        async fn request(
+            _spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &im::HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let in_flight = SLOW_DRIVER_IN_FLIGHT.fetch_add(1, SeqCst) + 1;
+            SLOW_DRIVER_MAX_IN_FLIGHT.fetch_max(in_flight, SeqCst);
+
+            tokio::time::sleep(Duration::from_millis(200)).await;
+
+            SLOW_DRIVER_IN_FLIGHT.fetch_sub(1, SeqCst);
+
+            Ok(HttpResponse {
+                body: "slow".to_string(),
+                ..Default::default()
+            })
+        }
+    }
+
+    static TEST_NO_OVERLAP_COUNT: AtomicU32 = AtomicU32::new(0);
+
+    #[tokio::test]
+    async fn test_no_overlap() {
+        let suite = |allow_overlap: bool| {
+            Suite::new(
+                "default".to_string(),
+                vec![
+                    Job::new(
+                        "default",
+                        format!(
+                            "{}/tests/assets/scripts/slow-print.scrape",
+                            env::var("CARGO_MANIFEST_DIR").unwrap()
+                        ),
+                        None,
+                        None,
+                        "* * * * *".parse::<CronSpec>().unwrap(),
+                        false,
+                    )
+                    .unwrap()
+                    .with_allow_overlap(allow_overlap),
+                ],
+            )
+        };
+
+        fn print(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_NO_OVERLAP_COUNT.fetch_add(1, SeqCst);
+            None
+        }
+
+        for (allow_overlap, expected_runs) in [(false, 1), (true, 3)] {
+            TEST_NO_OVERLAP_COUNT.swap(0, SeqCst);
+            SLOW_DRIVER_MAX_IN_FLIGHT.swap(0, SeqCst);
+
+            let t0 = Local::now();
+
+            // All three ticks pass instantly, while each run takes 200ms
+            let clock = PerfectMockClock {
+                timestamps: vec![t0, t0 + TimeDelta::minutes(1), t0 + TimeDelta::minutes(2)],
+                offset: 0,
+            };
+
+            let task_handle = tokio::spawn(run_forever::<SlowTestHttpDriver>(
+                vec![suite(allow_overlap)],
+                Arc::new(RwLock::new(panicking_script_loader)),
+                EffectRegistry::new().register("print", print),
+                clock,
+                NoConfigWatcher,
+                no_shutdown(),
+            ));
+
+            let _ = tokio::join!(task_handle);
+
+            assert_eq!(TEST_NO_OVERLAP_COUNT.load(SeqCst), expected_runs);
+            assert_eq!(SLOW_DRIVER_MAX_IN_FLIGHT.load(SeqCst), expected_runs);
+        }
+    }
+
+    /// A mock clock that requests shutdown on its first sleep, i.e. after the first tick, and
+    /// then sleeps forever.
+    struct ShutdownMockClock {
+        shutdown_tx: watch::Sender<bool>,
+    }
+
+    impl Clock for ShutdownMockClock {
+        fn interval(&mut self) -> Duration {
+            Duration::from_secs(60)
+        }
+
+        fn now(&mut self) -> Option<DateTime<Local>> {
+            Some(Local::now())
+        }
+
+        async fn sleep(&mut self, _time: Duration) {
+            let _ = self.shutdown_tx.send(true);
+            std::future::pending::<()>().await
+        }
+    }
+
+    static TEST_SHUTDOWN_COUNT: AtomicU32 = AtomicU32::new(0);
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        /// A driver taking 200ms to respond.
+        #[derive(Clone)]
+        struct DelayTestHttpDriver;
+
+        impl HttpDriver for DelayTestHttpDriver {
+            async fn request(
+                _spec: RequestSpec<'_>,
+                _proxy: Option<&str>,
+                _resolve: &im::HashMap<String, SocketAddr>,
+            ) -> Result<HttpResponse, Error> {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+
+                Ok(HttpResponse {
+                    body: "slow".to_string(),
+                    ..Default::default()
+                })
+            }
+        }
+
+        let suite = Suite::new(
+            "default".to_string(),
+            vec![
+                Job::new(
+                    "default",
+                    format!(
+                        "{}/tests/assets/scripts/slow-print.scrape",
+                        env::var("CARGO_MANIFEST_DIR").unwrap()
+                    ),
+                    None,
+                    None,
+                    "* * * * *".parse::<CronSpec>().unwrap(),
+                    false,
+                )
+                .unwrap(),
+            ],
+        );
+
+        fn print(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_SHUTDOWN_COUNT.fetch_add(1, SeqCst);
+            None
+        }
+
+        TEST_SHUTDOWN_COUNT.swap(0, SeqCst);
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let task_handle = tokio::spawn(run_forever::<DelayTestHttpDriver>(
+            vec![suite],
+            Arc::new(RwLock::new(panicking_script_loader)),
+            EffectRegistry::new().register("print", print),
+            ShutdownMockClock { shutdown_tx },
+            NoConfigWatcher,
+            shutdown_rx,
+        ));
 
-                // overslept!
-                // first response to .peek()
-                // second response to .now()
-                t0 + TimeDelta::minutes(1),
-                // * half-interval sleep *
+        // Returns despite the clock never running out
+        assert!(
+            tokio::time::timeout(Duration::from_secs(5), task_handle)
+                .await
+                .is_ok()
+        );
+
+        // The run in flight at shutdown was allowed to finish and its effect was handled
+        assert_eq!(TEST_SHUTDOWN_COUNT.load(SeqCst), 1);
+    }
 
-                // second response to .peek()
-                t0 + TimeDelta::minutes(1),
-                // * half-interval sleep *
+    static TEST_SUMMARIZE_INVOCATIONS: Mutex<Vec<Vec<String>>> = Mutex::new(vec![]);
 
-                // third response to .now()
-                t0 + TimeDelta::minutes(2),
+    #[tokio::test]
+    async fn test_summarize() {
+        let suite = Suite::new(
+            "default".to_string(),
+            vec![
+                Job::new(
+                    "default",
+                    format!(
+                        "{}/tests/assets/scripts/print-many.scrape",
+                        env::var("CARGO_MANIFEST_DIR").unwrap()
+                    ),
+                    None,
+                    None,
+                    "* * * * *".parse::<CronSpec>().unwrap(),
+                    false,
+                )
+                .unwrap()
+                .with_summarize(true),
             ],
-            times_slept: 0,
+        );
+
+        TEST_SUMMARIZE_INVOCATIONS.lock().unwrap().clear();
+
+        fn print(args: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_SUMMARIZE_INVOCATIONS
+                .lock()
+                .unwrap()
+                .push(args.to_vec());
+            None
+        }
+
+        let effects = EffectRegistry::new().register("print", print);
+
+        let clock = PerfectMockClock {
+            timestamps: vec![Local::now()],
+            offset: 0,
         };
 
-        let task_handle = tokio::spawn(run_forever(
+        let task_handle = tokio::spawn(run_forever::<TestHttpDriver>(
             vec![suite],
             Arc::new(RwLock::new(panicking_script_loader)),
             effects,
             clock,
+            NoConfigWatcher,
+            no_shutdown(),
         ));
 
         let _ = tokio::join!(task_handle);
-        assert_eq!(TEST_PRINT_EACH_MINUTE_OVERSLEEP_COUNT.load(SeqCst), 3);
+
+        assert_eq!(
+            *TEST_SUMMARIZE_INVOCATIONS.lock().unwrap(),
+            vec![vec!["3 new items: apple, banana split, cherry".to_string()]]
+        );
     }
 
+    static TEST_ON_ERROR_INVOCATIONS: Mutex<Vec<(String, Vec<String>)>> = Mutex::new(vec![]);
+
     #[tokio::test]
-    async fn test_local_minute_interval_clock() {
-        use chrono::SecondsFormat;
+    async fn test_on_error() {
+        let job = |name: &str, on_error: Option<String>| {
+            Job::new(
+                name,
+                format!(
+                    "{}/tests/assets/scripts/fail.scrape",
+                    env::var("CARGO_MANIFEST_DIR").unwrap()
+                ),
+                None,
+                None,
+                "* * * * *".parse::<CronSpec>().unwrap(),
+                false,
+            )
+            .unwrap()
+            .with_on_error(on_error)
+        };
 
-        let mut clock = LocalMinuteIntervalClock;
+        let suite = Suite::new(
+            "default".to_string(),
+            vec![job("quiet", None), job("loud", Some("error".to_string()))],
+        );
 
-        assert_eq!(clock.interval(), Duration::from_secs(60));
+        TEST_ON_ERROR_INVOCATIONS.lock().unwrap().clear();
 
-        let mut now = clock
-            .now()
-            .unwrap()
-            .to_rfc3339_opts(SecondsFormat::Secs, false);
-        let mut peek = clock
-            .peek()
+        fn print(args: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_ON_ERROR_INVOCATIONS
+                .lock()
+                .unwrap()
+                .push(("print".to_string(), args.to_vec()));
+            None
+        }
+
+        fn error(args: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_ON_ERROR_INVOCATIONS
+                .lock()
+                .unwrap()
+                .push(("error".to_string(), args.to_vec()));
+            None
+        }
+
+        let effects = EffectRegistry::new()
+            .register("print", print)
+            .register("error", error);
+
+        let clock = PerfectMockClock {
+            timestamps: vec![Local::now()],
+            offset: 0,
+        };
+
+        let task_handle = tokio::spawn(run_forever::<TestHttpDriver>(
+            vec![suite],
+            Arc::new(RwLock::new(panicking_script_loader)),
+            effects,
+            clock,
+            NoConfigWatcher,
+            no_shutdown(),
+        ));
+
+        let _ = tokio::join!(task_handle);
+
+        let invocations = TEST_ON_ERROR_INVOCATIONS.lock().unwrap();
+
+        assert_eq!(invocations.len(), 1);
+        assert_eq!(invocations[0].0, "error");
+        assert!(invocations[0].1[0].starts_with("Job `default.loud` ("));
+        assert!(invocations[0].1[0].contains("failed: Lua error: "));
+        assert!(invocations[0].1[0].contains("HTTP driver error: invalid url"));
+    }
+
+    #[test]
+    fn test_format_schedule() {
+        let job = |name: &str, schedule: &str| {
+            Job::new(name, "news", None, None, schedule.parse().unwrap(), false).unwrap()
+        };
+
+        let suites = vec![
+            Suite::new("yearly", vec![job("never", "0 0 31 2 *")]),
+            Suite::new("daily", vec![job("lunch", "0 12 * * *")]),
+        ];
+
+        assert_eq!(
+            format_schedule(
+                &suites,
+                Local.with_ymd_and_hms(2024, 6, 1, 13, 0, 0).unwrap(),
+                2
+            ),
+            "daily.lunch (script `news`, schedule `0 12 * * *`)\n    \
+            2024-06-02 12:00\n    \
+            2024-06-03 12:00\n\
+            yearly.never (script `news`, schedule `0 0 31 2 *`)\n    \
+            never\n"
+        );
+    }
+
+    #[test]
+    fn test_summarize_invocations() {
+        let summarized = summarize_invocations(vec![
+            EffectInvocation::new("print", vec!["a".to_string()], HashMap::new()),
+            EffectInvocation::new(
+                "notify",
+                vec!["b".to_string()],
+                HashMap::from([("title".to_string(), "News".to_string())]),
+            ),
+            EffectInvocation::new(
+                "print",
+                vec!["c".to_string(), "d".to_string()],
+                HashMap::new(),
+            ),
+        ]);
+
+        assert_eq!(summarized.len(), 2);
+
+        assert_eq!(summarized[0].name(), "print");
+        assert_eq!(
+            summarized[0].args(),
+            &vec!["2 new items: a, c d".to_string()]
+        );
+
+        assert_eq!(summarized[1].name(), "notify");
+        assert_eq!(summarized[1].args(), &vec!["b".to_string()]);
+        assert_eq!(
+            summarized[1].kwargs().get("title"),
+            Some(&"News".to_string())
+        );
+    }
+
+    static TEST_SLEEP_UNTIL_DUE_RUNS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    #[tokio::test]
+    async fn test_sleep_until_due() {
+        let job = |name: &str, schedule: &str| {
+            Job::new(name, name, None, None, schedule.parse().unwrap(), false)
+                .unwrap()
+                .with_allow_overlap(true)
+        };
+
+        let suite = Suite::new(
+            "default",
+            vec![job("quarterly", "*/15 * * * *"), job("hourly", "0 * * * *")],
+        );
+
+        fn print(args: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_SLEEP_UNTIL_DUE_RUNS
+                .lock()
+                .unwrap()
+                .extend(args.iter().cloned());
+            None
+        }
+
+        TEST_SLEEP_UNTIL_DUE_RUNS.lock().unwrap().clear();
+
+        let wakeups = Arc::new(Mutex::new(vec![]));
+        let at = |hour, minute| Local.with_ymd_and_hms(2024, 6, 1, hour, minute, 0).unwrap();
+
+        let clock = SteppingMockClock {
+            now: at(9, 50),
+            end: at(10, 40),
+            wakeups: wakeups.clone(),
+        };
+
+        let task_handle = tokio::spawn(run_forever::<TestHttpDriver>(
+            vec![suite],
+            Arc::new(RwLock::new(print_name_script_loader)),
+            EffectRegistry::new().register("print", print),
+            clock,
+            NoConfigWatcher,
+            no_shutdown(),
+        ));
+
+        let _ = tokio::join!(task_handle);
+
+        // Woken up only at the start and when jobs are due
+        assert_eq!(
+            *wakeups.lock().unwrap(),
+            vec![at(9, 50), at(10, 0), at(10, 15), at(10, 30)]
+        );
+
+        let mut runs = TEST_SLEEP_UNTIL_DUE_RUNS.lock().unwrap().clone();
+        runs.sort();
+
+        assert_eq!(runs, vec!["hourly", "quarterly", "quarterly", "quarterly"]);
+    }
+
+    static TEST_CATCHUP_RUNS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+    #[tokio::test]
+    async fn test_catchup() {
+        let job = |name: &str, schedule: &str, catchup: bool| {
+            Job::new(name, name, None, None, schedule.parse().unwrap(), false)
+                .unwrap()
+                .with_catchup(catchup)
+        };
+
+        let suite = Suite::new(
+            "default",
+            vec![
+                job("hourly", "0 * * * *", true),
+                job("monthly", "0 0 1 * *", true),
+                job("plain", "0 * * * *", false),
+            ],
+        );
+
+        fn print(args: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_CATCHUP_RUNS
+                .lock()
+                .unwrap()
+                .extend(args.iter().cloned());
+            None
+        }
+
+        TEST_CATCHUP_RUNS.lock().unwrap().clear();
+
+        let path = env::temp_dir()
+            .join(format!(
+                "scrapeycat-test-catchup-{}.json",
+                std::process::id()
+            ))
+            .to_str()
             .unwrap()
-            .to_rfc3339_opts(SecondsFormat::Secs, false);
+            .to_string();
 
-        // On the off chance that the above two calls happened right on a second-boundary
-        if now != peek {
-            tokio::time::sleep(Duration::from_millis(50)).await;
+        let _ = fs::remove_file(&path);
 
-            now = clock
-                .now()
+        let at = |month, day, hour| {
+            Local
+                .with_ymd_and_hms(2024, month, day, hour, 0, 0)
                 .unwrap()
-                .to_rfc3339_opts(SecondsFormat::Secs, false);
-            peek = clock
-                .peek()
+        };
+
+        // The daemon was down from after the 07:00 runs until 10:05, missing three runs of the
+        // hourly jobs, while the run of the monthly job missed is too long ago to catch up on
+        let mut last_runs = LastRuns::load(&path).unwrap();
+        last_runs.record("default.hourly", at(6, 5, 7));
+        last_runs.record("default.monthly", at(5, 1, 0));
+        last_runs.record("default.plain", at(6, 5, 7));
+        last_runs.save().await.unwrap();
+
+        let clock = SteppingMockClock {
+            now: at(6, 5, 10) + TimeDelta::minutes(5),
+            end: at(6, 5, 10) + TimeDelta::minutes(50),
+            wakeups: Arc::new(Mutex::new(vec![])),
+        };
+
+        let task_handle = tokio::spawn(run_forever_with_last_runs::<TestHttpDriver>(
+            vec![suite],
+            Arc::new(RwLock::new(print_name_script_loader)),
+            EffectRegistry::new().register("print", print),
+            clock,
+            NoConfigWatcher,
+            LastRuns::load(&path).unwrap(),
+            no_shutdown(),
+        ));
+
+        let _ = tokio::join!(task_handle);
+
+        assert_eq!(*TEST_CATCHUP_RUNS.lock().unwrap(), vec!["hourly"]);
+        assert_eq!(
+            LastRuns::load(&path).unwrap().get("default.hourly"),
+            Some(at(6, 5, 10) + TimeDelta::minutes(5))
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    static TEST_CATCHUP_CONCURRENCY_COUNT: AtomicU32 = AtomicU32::new(0);
+    static TEST_CATCHUP_CONCURRENCY_IN_FLIGHT: AtomicU32 = AtomicU32::new(0);
+    static TEST_CATCHUP_CONCURRENCY_MAX_IN_FLIGHT: AtomicU32 = AtomicU32::new(0);
+
+    #[tokio::test]
+    async fn test_catchup_concurrency() {
+        /// A driver taking 50ms to respond, keeping track of the maximum number of requests in
+        /// flight at the same time.
+        #[derive(Clone)]
+        struct CountingTestHttpDriver;
+
+        impl HttpDriver for CountingTestHttpDriver {
+            async fn request(
+                _spec: RequestSpec<'_>,
+                _proxy: Option<&str>,
+                _resolve: &im::HashMap<String, SocketAddr>,
+            ) -> Result<HttpResponse, Error> {
+                let in_flight = TEST_CATCHUP_CONCURRENCY_IN_FLIGHT.fetch_add(1, SeqCst) + 1;
+                TEST_CATCHUP_CONCURRENCY_MAX_IN_FLIGHT.fetch_max(in_flight, SeqCst);
+
+                tokio::time::sleep(Duration::from_millis(50)).await;
+
+                TEST_CATCHUP_CONCURRENCY_IN_FLIGHT.fetch_sub(1, SeqCst);
+
+                Ok(HttpResponse {
+                    body: "slow".to_string(),
+                    ..Default::default()
+                })
+            }
+        }
+
+        let at = |hour, minute| Local.with_ymd_and_hms(2024, 6, 5, hour, minute, 0).unwrap();
+
+        let mut last_runs = LastRuns::new();
+
+        let jobs = (0..5)
+            .map(|n| {
+                last_runs.record(&format!("default.job{n}"), at(7, 0));
+
+                Job::new(
+                    format!("job{n}"),
+                    format!(
+                        "{}/tests/assets/scripts/slow-print.scrape",
+                        env::var("CARGO_MANIFEST_DIR").unwrap()
+                    ),
+                    None,
+                    None,
+                    "0 * * * *".parse::<CronSpec>().unwrap(),
+                    false,
+                )
                 .unwrap()
-                .to_rfc3339_opts(SecondsFormat::Secs, false);
+                .with_catchup(true)
+            })
+            .collect();
+
+        fn print(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_CATCHUP_CONCURRENCY_COUNT.fetch_add(1, SeqCst);
+            None
         }
 
-        assert_eq!(now, peek);
+        TEST_CATCHUP_CONCURRENCY_COUNT.swap(0, SeqCst);
+        TEST_CATCHUP_CONCURRENCY_MAX_IN_FLIGHT.swap(0, SeqCst);
+
+        let clock = SteppingMockClock {
+            now: at(10, 5),
+            end: at(10, 50),
+            wakeups: Arc::new(Mutex::new(vec![])),
+        };
+
+        let task_handle = tokio::spawn(run_forever_with_last_runs::<CountingTestHttpDriver>(
+            vec![Suite::new("default", jobs)],
+            Arc::new(RwLock::new(panicking_script_loader)),
+            EffectRegistry::new().register("print", print),
+            clock,
+            NoConfigWatcher,
+            last_runs,
+            no_shutdown(),
+        ));
+
+        let _ = tokio::join!(task_handle);
+
+        // Every job was caught up on, but no more than the limit at a time
+        assert_eq!(TEST_CATCHUP_CONCURRENCY_COUNT.load(SeqCst), 5);
+        assert_eq!(
+            TEST_CATCHUP_CONCURRENCY_MAX_IN_FLIGHT.load(SeqCst),
+            CATCHUP_CONCURRENCY as u32
+        );
+    }
+
+    static TEST_RUN_CONFIG_JOB_INVOCATIONS: Mutex<Vec<Vec<String>>> = Mutex::new(vec![]);
+
+    #[tokio::test]
+    async fn test_run_config_job() {
+        let config = ConfigFile::config_from_str(&format!(
+            "config_version = 2\n\
+            script_dirs = [\"{}/tests/assets/scripts\"]\n\
+            script_names = [\"${{NAME}}.scrape\"]\n\
+            [[suites.default.jobs]]\n\
+            name = \"hello\"\n\
+            script = \"print\"\n\
+            schedule = \"0 0 1 1 *\"\n\
+            dedup = false\n\
+            [[suites.default.jobs]]\n\
+            name = \"many\"\n\
+            script = \"print-many\"\n\
+            schedule = \"0 0 1 1 *\"\n\
+            dedup = false\n\
+            summarize = true\n",
+            env::var("CARGO_MANIFEST_DIR").unwrap()
+        ))
+        .unwrap();
+
+        fn print(args: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_RUN_CONFIG_JOB_INVOCATIONS
+                .lock()
+                .unwrap()
+                .push(args.to_vec());
+            None
+        }
+
+        TEST_RUN_CONFIG_JOB_INVOCATIONS.lock().unwrap().clear();
+
+        let effects = EffectRegistry::new().register("print", print);
+
+        // Runs right away despite not being due, and returns once its effects are handled
+        assert!(
+            run_config_job(config.clone(), "default.hello", effects.clone())
+                .await
+                .is_ok()
+        );
+
+        assert_eq!(
+            *TEST_RUN_CONFIG_JOB_INVOCATIONS.lock().unwrap(),
+            vec![vec!["hello world".to_string()]]
+        );
+
+        TEST_RUN_CONFIG_JOB_INVOCATIONS.lock().unwrap().clear();
+
+        // Effects are summarized as when run on schedule
+        assert!(
+            run_config_job(config.clone(), "default.many", effects.clone())
+                .await
+                .is_ok()
+        );
+
+        assert_eq!(
+            *TEST_RUN_CONFIG_JOB_INVOCATIONS.lock().unwrap(),
+            vec![vec!["3 new items: apple, banana split, cherry".to_string()]]
+        );
+
+        assert!(matches!(
+            run_config_job(config.clone(), "default.missing", effects.clone()).await,
+            Err(Error::JobNotFoundError)
+        ));
+        assert!(matches!(
+            run_config_job(config, "hello", effects).await,
+            Err(Error::JobNotFoundError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_local_minute_interval_clock() {
+        let mut clock = LocalMinuteIntervalClock;
+
+        assert_eq!(clock.interval(), Duration::from_secs(60));
 
         let millis = clock.now().unwrap().timestamp_millis();
 
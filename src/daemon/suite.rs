@@ -1,14 +1,15 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
 
 use chrono::{DateTime, Local};
-use regex::Regex;
 
-use crate::{Error, daemon::cron::CronSpec};
+use crate::{Error, daemon::cron::CronSpec, rate_limiter::RateLimiter};
 
 #[derive(Debug, Clone)]
 pub struct Suite {
     name: String,
     jobs: Vec<Job>,
+    isolated: bool,
+    rate_limiter: Option<RateLimiter>,
 }
 
 impl Suite {
@@ -16,9 +17,29 @@ impl Suite {
         Suite {
             name: name.into(),
             jobs,
+            isolated: false,
+            rate_limiter: None,
         }
     }
 
+    /// Run the jobs of the suite on a thread of their own, so that jobs blocking or crashing
+    /// the thread do not affect the jobs of other suites.
+    pub fn with_isolated(self, isolated: bool) -> Suite {
+        Suite { isolated, ..self }
+    }
+
+    /// Limit the requests made by all jobs of the suite together to `requests_per_second` on
+    /// average, or not at all if None.
+    pub fn with_requests_per_second(
+        self,
+        requests_per_second: Option<f64>,
+    ) -> Result<Suite, Error> {
+        Ok(Suite {
+            rate_limiter: requests_per_second.map(RateLimiter::new).transpose()?,
+            ..self
+        })
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -26,9 +47,17 @@ impl Suite {
     pub fn jobs(&self) -> impl Iterator<Item = &Job> {
         self.jobs.iter()
     }
+
+    pub fn is_isolated(&self) -> bool {
+        self.isolated
+    }
+
+    /// The rate limiter shared by the jobs of the suite, if any.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
 }
 
-#[expect(unused)]
 #[derive(Debug, Clone)]
 pub struct Job {
     name: String,
@@ -36,8 +65,16 @@ pub struct Job {
     args: Vec<String>,
     kwargs: HashMap<String, String>,
     schedule: CronSpec,
-    schedule_regex: Regex,
     dedup: bool,
+    summarize: bool,
+    allow_overlap: bool,
+    on_error: Option<String>,
+    headers: HashMap<String, String>,
+    resolve: HashMap<String, SocketAddr>,
+    allowed_env: Option<Vec<String>>,
+    timeout: Option<Duration>,
+    catchup: bool,
+    cookie_jar: Option<String>,
 }
 
 impl Job {
@@ -49,19 +86,80 @@ impl Job {
         schedule: CronSpec,
         dedup: bool,
     ) -> Result<Job, Error> {
-        let schedule_regex = Regex::new(&schedule.to_regex_pattern())?;
-
         Ok(Job {
             name: name.into(),
             script_name: script_name.into(),
             args: args.unwrap_or_default(),
             kwargs: kwargs.unwrap_or_default(),
             schedule,
-            schedule_regex,
             dedup,
+            summarize: false,
+            allow_overlap: false,
+            on_error: None,
+            headers: HashMap::new(),
+            resolve: HashMap::new(),
+            allowed_env: None,
+            timeout: None,
+            catchup: false,
+            cookie_jar: None,
         })
     }
 
+    pub fn with_summarize(self, summarize: bool) -> Job {
+        Job { summarize, ..self }
+    }
+
+    pub fn with_allow_overlap(self, allow_overlap: bool) -> Job {
+        Job {
+            allow_overlap,
+            ..self
+        }
+    }
+
+    /// Report failed runs of the job by invoking the effect with the given name, with a
+    /// description of the error as its only argument.
+    pub fn with_on_error(self, on_error: Option<String>) -> Job {
+        Job { on_error, ..self }
+    }
+
+    /// Send the given headers with every request made by the job, unless the script sets a
+    /// header of the same name.
+    pub fn with_headers(self, headers: HashMap<String, String>) -> Job {
+        Job { headers, ..self }
+    }
+
+    /// Connect to the given addresses for the given hosts instead of resolving them, as if using
+    /// `resolve()` at the start of the script.
+    pub fn with_resolve(self, resolve: HashMap<String, SocketAddr>) -> Job {
+        Job { resolve, ..self }
+    }
+
+    /// Allow the job to read the given environment variables using `env()`, as names or prefixes
+    /// followed by `*`, or the default variables if None.
+    pub fn with_allowed_env(self, allowed_env: Option<Vec<String>>) -> Job {
+        Job {
+            allowed_env,
+            ..self
+        }
+    }
+
+    /// Fail runs of the job that have not finished within `timeout`.
+    pub fn with_timeout(self, timeout: Option<Duration>) -> Job {
+        Job { timeout, ..self }
+    }
+
+    /// Run the job once when the daemon starts if its most recent scheduled run was missed
+    /// while the daemon was not running.
+    pub fn with_catchup(self, catchup: bool) -> Job {
+        Job { catchup, ..self }
+    }
+
+    /// Keep the cookies of the job in the jar persisted to the file at `path` across runs, as
+    /// if using `cookieJar(path)` at the start of the script.
+    pub fn with_cookie_jar(self, cookie_jar: Option<String>) -> Job {
+        Job { cookie_jar, ..self }
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -78,13 +176,23 @@ impl Job {
         &self.kwargs
     }
 
+    pub fn schedule(&self) -> &CronSpec {
+        &self.schedule
+    }
+
     pub fn is_due(&self) -> bool {
         self.is_due_at(Local::now())
     }
 
     pub fn is_due_at(&self, when: DateTime<Local>) -> bool {
-        self.schedule_regex
-            .is_match(&Job::format_datetime(when).to_string())
+        self.schedule.matches(&when)
+    }
+
+    /// Whether the job is due at any minute after `since`, up to and including `until`.
+    pub fn is_due_between(&self, since: DateTime<Local>, until: DateTime<Local>) -> bool {
+        self.schedule
+            .next_after(since)
+            .is_some_and(|next| next <= until)
     }
 
     pub fn format_datetime(when: DateTime<Local>) -> String {
@@ -94,29 +202,97 @@ impl Job {
     pub fn is_dedup(&self) -> bool {
         self.dedup
     }
+
+    pub fn is_summarize(&self) -> bool {
+        self.summarize
+    }
+
+    pub fn is_allow_overlap(&self) -> bool {
+        self.allow_overlap
+    }
+
+    pub fn on_error(&self) -> Option<&str> {
+        self.on_error.as_deref()
+    }
+
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+
+    pub fn resolve(&self) -> &HashMap<String, SocketAddr> {
+        &self.resolve
+    }
+
+    pub fn allowed_env(&self) -> Option<&Vec<String>> {
+        self.allowed_env.as_ref()
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn is_catchup(&self) -> bool {
+        self.catchup
+    }
+
+    pub fn cookie_jar(&self) -> Option<&str> {
+        self.cookie_jar.as_deref()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::TimeZone;
+
     use super::*;
 
+    fn job(spec: &str) -> Job {
+        Job::new("", "", None, None, spec.parse::<CronSpec>().unwrap(), true).unwrap()
+    }
+
     #[test]
-    fn test_job_cronspec_to_regex() {
-        let specs = [
-            "* * * * *",
-            "*/10 * * * *",
-            "1 2 3 4 5",
-            "1-2 3-4 5-6 7-8 1",
-        ];
-
-        for spec in specs {
-            assert_eq!(
-                Job::new("", "", None, None, spec.parse::<CronSpec>().unwrap(), true)
-                    .unwrap()
-                    .schedule_regex
-                    .to_string(),
-                spec.parse::<CronSpec>().unwrap().to_regex_pattern()
-            );
-        }
+    fn test_job_is_due_at() {
+        let at = |day: u32, hour: u32, minute: u32| {
+            Local
+                .with_ymd_and_hms(2024, 6, day, hour, minute, 0)
+                .unwrap()
+        };
+
+        assert!(job("* * * * *").is_due_at(at(3, 12, 34)));
+        assert!(job("*/10 * * * *").is_due_at(at(3, 12, 30)));
+        assert!(!job("*/10 * * * *").is_due_at(at(3, 12, 31)));
+        assert!(job("30 12 3 6 1").is_due_at(at(3, 12, 30)));
+        assert!(!job("30 12 3 6 1").is_due_at(at(3, 12, 31)));
+        assert!(!job("30 12 3 7 *").is_due_at(at(3, 12, 30)));
+        assert!(
+            job("1-2 3-4 5-6 7-8 1")
+                .is_due_at(Local.with_ymd_and_hms(2024, 7, 5, 3, 1, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_job_is_due_at_day_of_month_or_day_of_week() {
+        let midnight =
+            |month: u32, day: u32| Local.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap();
+
+        // 2024-09-13 was a Friday the 13th, 2024-09-20 a Friday and 2024-11-13 a Wednesday
+        let friday_13th = midnight(9, 13);
+        let friday = midnight(9, 20);
+        let wednesday_13th = midnight(11, 13);
+        let wednesday = midnight(11, 20);
+
+        // When both days are restricted, either one matching suffices
+        assert!(job("0 0 13 * 5").is_due_at(friday_13th));
+        assert!(job("0 0 13 * 5").is_due_at(friday));
+        assert!(job("0 0 13 * 5").is_due_at(wednesday_13th));
+        assert!(!job("0 0 13 * 5").is_due_at(wednesday));
+
+        // When either day starts with `*`, both must match
+        assert!(job("0 0 13 * *").is_due_at(wednesday_13th));
+        assert!(!job("0 0 13 * *").is_due_at(friday));
+        assert!(job("0 0 * * 5").is_due_at(friday));
+        assert!(!job("0 0 * * 5").is_due_at(wednesday_13th));
+        assert!(job("0 0 */2 * 5").is_due_at(friday_13th));
+        assert!(!job("0 0 */2 * 5").is_due_at(friday));
     }
 }
@@ -1,12 +1,24 @@
 use std::{
     collections::HashMap,
-    hash::{Hash, Hasher},
+    fs::OpenOptions,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    process::Command,
+    sync::{LazyLock, mpsc},
+    time::Duration,
 };
 
 use flagset::{FlagSet, flags};
 use log::{debug, error};
-use notify_rust::Notification;
-use tokio::sync::mpsc::UnboundedReceiver;
+use notify_rust::{Notification, Timeout, Urgency};
+use reqwest::{Client, header::CONTENT_TYPE};
+use rusqlite::{Connection, params_from_iter};
+use serde_json::json;
+use tokio::{
+    runtime::{Builder, Runtime},
+    sync::mpsc::UnboundedReceiver,
+    task,
+};
 
 use crate::Error;
 
@@ -53,6 +65,8 @@ impl EffectOptionsExt for FlagSet<EffectOptions> {
 pub type EffectArgs<'a> = &'a [String];
 pub type EffectKwArgs<'a> = &'a HashMap<String, String>;
 pub type EffectSignature = fn(EffectArgs, EffectKwArgs, FlagSet<EffectOptions>) -> Option<Error>;
+pub type ResultEffectSignature =
+    fn(EffectArgs, EffectKwArgs, FlagSet<EffectOptions>) -> Result<Vec<String>, Error>;
 
 #[derive(Debug, Clone)]
 pub struct EffectInvocation {
@@ -107,36 +121,120 @@ impl EffectInvocation {
     pub fn kwargs(&self) -> &HashMap<String, String> {
         &self.kwargs
     }
+
+    /// The hash by which duplicate invocations are recognized.
+    pub fn dedup_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
-pub async fn default_effects_runner_task(
+/// A registry of named effects, for use with [effects_runner_task].
+///
+/// Besides regular effects, which are invoked asynchronously and whose outcome is not visible
+/// to scripts, the registry also holds *result effects*, which are invoked synchronously
+/// by scripts using `effectResult` and return a list of strings that is appended to the list
+/// of results.
+#[derive(Debug, Clone, Default)]
+pub struct EffectRegistry {
+    effects: HashMap<String, EffectSignature>,
+    result_effects: HashMap<String, ResultEffectSignature>,
+}
+
+impl EffectRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        EffectRegistry {
+            effects: HashMap::new(),
+            result_effects: HashMap::new(),
+        }
+    }
+
+    /// Create a registry containing the builtin effects.
+    pub fn with_defaults() -> Self {
+        EffectRegistry::new()
+            .register("print", print)
+            .register("notify", notify)
+            .register("writefile", writefile)
+            .register("exec", exec)
+            .register("sqlite", sqlite)
+            .register("telegram", telegram)
+    }
+
+    /// Register an effect under the given name, replacing any effect previously registered
+    /// under the same name.
+    pub fn register(mut self, name: impl Into<String>, effect: EffectSignature) -> Self {
+        self.effects.insert(name.into(), effect);
+        self
+    }
+
+    /// Register a result effect under the given name, replacing any result effect previously
+    /// registered under the same name.
+    pub fn register_result(
+        mut self,
+        name: impl Into<String>,
+        effect: ResultEffectSignature,
+    ) -> Self {
+        self.result_effects.insert(name.into(), effect);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<EffectSignature> {
+        self.effects.get(name).copied()
+    }
+
+    pub fn get_result(&self, name: &str) -> Option<ResultEffectSignature> {
+        self.result_effects.get(name).copied()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.effects.keys().map(|name| name.as_str())
+    }
+}
+
+/// Invoke `effect` for `invocation` on a thread where blocking is allowed, so that effects
+/// waiting for programs or servers, such as `exec`, do not block the async runtime.
+pub async fn invoke_effect(
+    effect: EffectSignature,
+    invocation: &EffectInvocation,
+) -> Option<Error> {
+    let invocation = invocation.clone();
+
+    task::spawn_blocking(move || {
+        effect(
+            invocation.args(),
+            invocation.kwargs(),
+            EffectOptions::default().into(),
+        )
+    })
+    .await
+    .unwrap_or_else(|e| Some(Error::EffectError(e.to_string())))
+}
+
+pub async fn default_effects_runner_task(effects_receiver: UnboundedReceiver<EffectInvocation>) {
+    effects_runner_task(effects_receiver, EffectRegistry::with_defaults()).await
+}
+
+pub async fn effects_runner_task(
     mut effects_receiver: UnboundedReceiver<EffectInvocation>,
+    registry: EffectRegistry,
 ) {
     loop {
         match effects_receiver.recv().await {
             Some(invocation) => {
-                let effect_fn = match invocation.name() {
-                    "print" => Some(print as EffectSignature),
-                    "notify" => Some(notify as EffectSignature),
-                    _ => None,
-                };
-
                 debug!(
-                    "effect::default_effects_runner_task: invoking `{}` (args: {:?}, kwargs: {:?})",
+                    "effect::effects_runner_task: invoking `{}` (args: {:?}, kwargs: {:?})",
                     invocation.name(),
                     invocation.args(),
                     invocation.kwargs()
                 );
 
-                match effect_fn {
+                match registry.get(invocation.name()) {
                     Some(f) => {
-                        if let Some(e) = f(
-                            invocation.args(),
-                            invocation.kwargs(),
-                            EffectOptions::default().into(),
-                        ) {
+                        if let Some(e) = invoke_effect(f, &invocation).await {
                             error!(
-                                "effect::default_effects_runner_task: \
+                                "effect::effects_runner_task: \
                                 error invoking effect `{}`: {e} (args: {:?}, kwargs: {:?})",
                                 invocation.name(),
                                 invocation.args(),
@@ -145,7 +243,7 @@ pub async fn default_effects_runner_task(
                         }
                     }
                     None => error!(
-                        "effect::default_effects_runner_task: unknown effect `{}`",
+                        "effect::effects_runner_task: unknown effect `{}`",
                         invocation.name(),
                     ),
                 }
@@ -211,6 +309,38 @@ pub fn notify(
         notification.sound_name(sound);
     }
 
+    if let Some(urgency) = kwargs.get("urgency") {
+        let urgency = match urgency.as_str() {
+            "low" => Urgency::Low,
+            "normal" => Urgency::Normal,
+            "critical" => Urgency::Critical,
+            other => {
+                return Some(Error::EffectError(format!(
+                    "Invalid value for keyword argument `urgency` passed to `notify`: {other:?}, \
+                     valid values are: [\"low\", \"normal\", \"critical\"]"
+                )));
+            }
+        };
+
+        // Urgency is only supported by the XDG notification spec
+        #[cfg(all(unix, not(target_os = "macos")))]
+        notification.urgency(urgency);
+
+        #[cfg(not(all(unix, not(target_os = "macos"))))]
+        let _ = urgency;
+    }
+
+    if let Some(timeout) = kwargs.get("timeout") {
+        let Ok(millis) = timeout.parse::<u32>() else {
+            return Some(Error::EffectError(format!(
+                "Invalid value for keyword argument `timeout` passed to `notify`: {timeout:?}, \
+                 expected a number of milliseconds"
+            )));
+        };
+
+        notification.timeout(Timeout::Milliseconds(millis));
+    }
+
     let send_error = if !opts.is_silent_test() {
         match notification.show() {
             Err(e) => Some(format!("{e}")),
@@ -222,7 +352,9 @@ pub fn notify(
 
     let kw_error = report_unknown_kwargs(
         "notify",
-        &["body", "appname", "title", "icon", "sound"],
+        &[
+            "body", "appname", "title", "icon", "sound", "urgency", "timeout",
+        ],
         kwargs,
     )
     .map(|e| match e {
@@ -238,8 +370,289 @@ pub fn notify(
     }
 }
 
+pub fn writefile(
+    args: EffectArgs,
+    kwargs: EffectKwArgs,
+    opts: FlagSet<EffectOptions>,
+) -> Option<Error> {
+    let Some(path) = kwargs.get("path") else {
+        return Some(Error::EffectError(
+            "Missing keyword argument `path` for `writefile`".to_string(),
+        ));
+    };
+
+    let append = match kwargs.get("append").map(|value| value.as_str()) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(other) => {
+            return Some(Error::EffectError(format!(
+                "Invalid value for keyword argument `append` passed to `writefile`: {other:?}, \
+                valid values are: [\"true\", \"false\"]"
+            )));
+        }
+    };
+
+    let separator = kwargs.get("sep").map(|sep| sep.as_str()).unwrap_or("\n");
+    let content = format!("{}\n", args.join(separator));
+
+    if !opts.is_silent_test() {
+        let result = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .and_then(|mut file| file.write_all(content.as_bytes()));
+
+        if let Err(e) = result {
+            return Some(Error::IOError(e));
+        }
+    }
+
+    report_unknown_kwargs("writefile", &["path", "sep", "append"], kwargs)
+}
+
+/// Run the first argument as a program, with the remaining arguments as its arguments.
+///
+/// Keyword arguments of the form `env_NAME` set the environment variable `NAME` for the program.
+/// The program is not run through a shell, and inherits the environment of scrapeycat itself.
+pub fn exec(args: EffectArgs, kwargs: EffectKwArgs, opts: FlagSet<EffectOptions>) -> Option<Error> {
+    let Some((program, program_args)) = args.split_first() else {
+        return Some(Error::EffectError(
+            "Missing program argument for `exec`".to_string(),
+        ));
+    };
+
+    let (env_kwargs, other_kwargs): (HashMap<_, _>, HashMap<_, _>) = kwargs
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .partition(|(key, _)| key.starts_with("env_"));
+
+    if let Some(e) = report_unknown_kwargs("exec", &["cwd"], &other_kwargs) {
+        return Some(e);
+    }
+
+    if opts.is_silent_test() {
+        return None;
+    }
+
+    let mut command = Command::new(program);
+
+    command.args(program_args);
+
+    for (key, value) in &env_kwargs {
+        command.env(key.strip_prefix("env_").expect("Should have prefix"), value);
+    }
+
+    if let Some(cwd) = kwargs.get("cwd") {
+        command.current_dir(cwd);
+    }
+
+    match command.status() {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(Error::EffectError(format!(
+            "`exec` of {program:?} failed: {status}"
+        ))),
+        Err(e) => Some(Error::IOError(e)),
+    }
+}
+
+/// Append rows to a table of an SQLite database, creating the database and the table if they do
+/// not exist. Keyword arguments other than `db` and `table` give the columns of a single row to
+/// insert, otherwise each argument is inserted as a row of its own, in the column `value`.
+pub fn sqlite(
+    args: EffectArgs,
+    kwargs: EffectKwArgs,
+    opts: FlagSet<EffectOptions>,
+) -> Option<Error> {
+    let (Some(db), Some(table)) = (kwargs.get("db"), kwargs.get("table")) else {
+        return Some(Error::EffectError(
+            "Missing keyword argument `db` or `table` for `sqlite`".to_string(),
+        ));
+    };
+
+    let mut column_kwargs = kwargs
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "db" | "table"))
+        .collect::<Vec<_>>();
+
+    column_kwargs.sort();
+
+    let (columns, rows) = if column_kwargs.is_empty() {
+        (
+            vec!["value".to_string()],
+            args.iter().map(|arg| vec![arg.clone()]).collect::<Vec<_>>(),
+        )
+    } else {
+        let (columns, values): (Vec<_>, Vec<_>) = column_kwargs
+            .into_iter()
+            .map(|(column, value)| (column.clone(), value.clone()))
+            .unzip();
+
+        (columns, vec![values])
+    };
+
+    if opts.is_silent_test() {
+        return None;
+    }
+
+    Connection::open(db)
+        .and_then(|mut connection| sqlite_insert(&mut connection, table, &columns, &rows))
+        .err()
+        .map(|e| Error::EffectError(format!("`sqlite` failed for {db:?}: {e}")))
+}
+
+/// Insert `rows`, each holding a value per column of `columns`, into `table`, creating the table
+/// with the given columns if it does not exist. All rows are inserted in a single transaction.
+fn sqlite_insert(
+    connection: &mut Connection,
+    table: &str,
+    columns: &[String],
+    rows: &[Vec<String>],
+) -> Result<(), rusqlite::Error> {
+    let table = sqlite_identifier(table);
+    let columns = columns
+        .iter()
+        .map(|column| sqlite_identifier(column))
+        .collect::<Vec<_>>();
+
+    let transaction = connection.transaction()?;
+
+    transaction.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {table} ({})",
+            columns
+                .iter()
+                .map(|column| format!("{column} TEXT"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        [],
+    )?;
+
+    {
+        let mut statement = transaction.prepare(&format!(
+            "INSERT INTO {table} ({}) VALUES ({})",
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        ))?;
+
+        for row in rows {
+            statement.execute(params_from_iter(row))?;
+        }
+    }
+
+    transaction.commit()
+}
+
+/// Quote `name` for use as an SQLite identifier, such as a table or column name.
+fn sqlite_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Send a message to a Telegram chat using the Bot API, with the text of the `text` keyword
+/// argument or else all arguments separated by spaces.
+pub fn telegram(
+    args: EffectArgs,
+    kwargs: EffectKwArgs,
+    opts: FlagSet<EffectOptions>,
+) -> Option<Error> {
+    telegram_with_sender(args, kwargs, opts, post_json)
+}
+
+/// Like [telegram], but sending the request using `send`, which is given the URL of the API
+/// method and the JSON payload to post to it.
+fn telegram_with_sender(
+    args: EffectArgs,
+    kwargs: EffectKwArgs,
+    opts: FlagSet<EffectOptions>,
+    send: impl FnOnce(&str, &str) -> Result<(), Error>,
+) -> Option<Error> {
+    if let Some(e) = report_unknown_kwargs("telegram", &["bot_token", "chat_id", "text"], kwargs) {
+        return Some(e);
+    }
+
+    let (Some(bot_token), Some(chat_id)) = (kwargs.get("bot_token"), kwargs.get("chat_id")) else {
+        return Some(Error::EffectError(
+            "Missing keyword argument `bot_token` or `chat_id` for `telegram`".to_string(),
+        ));
+    };
+
+    let text = match kwargs.get("text") {
+        Some(text) => text.clone(),
+        None => args.join(" "),
+    };
+
+    if text.is_empty() {
+        return Some(Error::EffectError(
+            "Missing text for `telegram`".to_string(),
+        ));
+    }
+
+    if opts.is_silent_test() {
+        return None;
+    }
+
+    let url = format!("https://api.telegram.org/bot{bot_token}/sendMessage");
+    let payload = json!({ "chat_id": chat_id, "text": text }).to_string();
+
+    send(&url, &payload).err()
+}
+
+/// The runtime on which effects make HTTP requests, since effects are not async.
+static HTTP_RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
+    Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("scrapeycat-effects")
+        .enable_all()
+        .build()
+        .expect("Should be able to create runtime")
+});
+
+/// The client with which effects make HTTP requests.
+static HTTP_CLIENT: LazyLock<Client> = LazyLock::new(|| {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("Should be able to create client")
+});
+
+/// Post `payload` as JSON to `url`, failing unless the response has a success status. Errors
+/// leave out the URL, which may contain secrets such as a bot token.
+fn post_json(url: &str, payload: &str) -> Result<(), Error> {
+    let request = HTTP_CLIENT
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(payload.to_string());
+
+    let (result_tx, result_rx) = mpsc::channel();
+
+    HTTP_RUNTIME.spawn(async move {
+        let result = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|e| Error::FetchError(e.without_url()));
+
+        let _ = result_tx.send(result);
+    });
+
+    result_rx
+        .recv()
+        .map_err(|_| Error::EffectError("Sending request panicked".to_string()))?
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        env, fs, process,
+        sync::atomic::{AtomicU32, Ordering::SeqCst},
+    };
+
+    use tokio::sync::mpsc::unbounded_channel;
+
     use super::*;
 
     macro_rules! map {
@@ -299,6 +712,179 @@ mod tests {
         );
     }
 
+    fn writefile_test_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("scrapeycat-{}-{name}", process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_writefile_overwrite() {
+        let path = writefile_test_path("test_writefile_overwrite");
+
+        assert!(
+            writefile(
+                &["hello".to_string(), "world".to_string()],
+                &map!["path" => path],
+                EffectOptions::default().into(),
+            )
+            .is_none()
+        );
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello\nworld\n");
+
+        assert!(
+            writefile(
+                &["goodbye".to_string(), "world".to_string()],
+                &map!["path" => path, "sep" => ", "],
+                EffectOptions::default().into(),
+            )
+            .is_none()
+        );
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "goodbye, world\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_writefile_append() {
+        let path = writefile_test_path("test_writefile_append");
+
+        for word in ["cat", "dog"] {
+            assert!(
+                writefile(
+                    &[word.to_string()],
+                    &map!["path" => path, "append" => "true"],
+                    EffectOptions::default().into(),
+                )
+                .is_none()
+            );
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "cat\ndog\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_writefile_errors() {
+        assert!(
+            writefile(
+                &["hello".to_string()],
+                &HashMap::new(),
+                EffectOptions::SilentTest.into(),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        assert!(
+            writefile(
+                &["hello".to_string()],
+                &map!["path" => "/dev/null", "append" => "maybe"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        assert!(
+            writefile(
+                &["hello".to_string()],
+                &map!["path" => "/dev/null", "mode" => "rw"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        assert!(
+            writefile(
+                &["hello".to_string()],
+                &map!["path" => "/nonexistent-dir/scrapeycat/file.txt"],
+                EffectOptions::default().into(),
+            )
+            .is_some_and(|e| matches!(e, Error::IOError(_)))
+        );
+    }
+
+    #[test]
+    fn test_exec() {
+        assert!(
+            exec(
+                &["/usr/bin/mpv".to_string(), "https://url".to_string()],
+                &map!["cwd" => "/tmp", "env_DISPLAY" => ":0"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_none()
+        );
+
+        assert!(
+            exec(&[], &HashMap::new(), EffectOptions::SilentTest.into())
+                .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        assert!(
+            exec(
+                &["/usr/bin/mpv".to_string()],
+                &map!["dir" => "/tmp"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        assert!(
+            exec(
+                &["true".to_string()],
+                &HashMap::new(),
+                EffectOptions::default().into(),
+            )
+            .is_none()
+        );
+
+        assert!(
+            exec(
+                &["false".to_string()],
+                &HashMap::new(),
+                EffectOptions::default().into(),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        assert!(
+            exec(
+                &["/nonexistent-dir/scrapeycat/program".to_string()],
+                &HashMap::new(),
+                EffectOptions::default().into(),
+            )
+            .is_some_and(|e| matches!(e, Error::IOError(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invoke_effect() {
+        let sleep = EffectInvocation::new(
+            "exec",
+            vec!["sleep".to_string(), "1".to_string()],
+            HashMap::new(),
+        );
+
+        let invoking = tokio::spawn(async move { invoke_effect(exec, &sleep).await });
+
+        // The runtime keeps running other tasks while the program runs
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!invoking.is_finished());
+        assert!(invoking.await.unwrap().is_none());
+
+        let fail = EffectInvocation::new("exec", vec!["false".to_string()], HashMap::new());
+
+        assert!(
+            invoke_effect(exec, &fail)
+                .await
+                .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+    }
+
     #[test]
     fn test_notify() {
         assert!(
@@ -315,5 +901,289 @@ mod tests {
             )
             .is_none()
         );
+
+        assert!(
+            notify(
+                &["transient".to_string()],
+                &map!["urgency" => "critical", "timeout" => "5000"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_none()
+        );
+
+        for kwargs in [
+            map!["urgency" => "urgent"],
+            map!["timeout" => "5s"],
+            map!["timeout" => "-1"],
+            map!["urgncy" => "low"],
+            map!["timout" => "5000"],
+        ] {
+            assert!(
+                notify(
+                    &["transient".to_string()],
+                    &kwargs,
+                    EffectOptions::SilentTest.into()
+                )
+                .is_some_and(|e| matches!(e, Error::EffectError(_))),
+                "{kwargs:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sqlite_insert() {
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        let read_back = |connection: &Connection, query: &str| {
+            connection
+                .prepare(query)
+                .unwrap()
+                .query_map([], |row| row.get::<_, String>(0))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        // Creates the table, then appends to it
+        for rows in [vec!["Alice", "Bob"], vec!["Charlie"]] {
+            sqlite_insert(
+                &mut connection,
+                "names",
+                &["value".to_string()],
+                &rows
+                    .iter()
+                    .map(|row| vec![row.to_string()])
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            read_back(&connection, "SELECT value FROM names ORDER BY rowid"),
+            vec!["Alice", "Bob", "Charlie"]
+        );
+
+        // Identifiers are quoted
+        sqlite_insert(
+            &mut connection,
+            "price \"history\"",
+            &["item".to_string(), "price; DROP TABLE names".to_string()],
+            &[vec!["Lamp".to_string(), "12.50".to_string()]],
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_back(
+                &connection,
+                r#"SELECT item || '=' || "price; DROP TABLE names" FROM "price ""history""""#
+            ),
+            vec!["Lamp=12.50"]
+        );
+
+        // Columns missing from an existing table
+        assert!(
+            sqlite_insert(
+                &mut connection,
+                "names",
+                &["name".to_string()],
+                &[vec!["Diego".to_string()]],
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_sqlite() {
+        assert!(
+            sqlite(
+                &["Alice".to_string()],
+                &map!["db" => "/tmp/scrapeycat.db", "table" => "names"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_none()
+        );
+
+        assert!(
+            sqlite(
+                &["Alice".to_string()],
+                &map!["db" => "/tmp/scrapeycat.db"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        assert!(
+            sqlite(
+                &[],
+                &map![
+                    "db" => "/nonexistent-dir/scrapeycat/data.db",
+                    "table" => "prices",
+                    "item" => "Lamp"
+                ],
+                EffectOptions::default().into(),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+    }
+
+    #[test]
+    fn test_telegram() {
+        let mut sent = None;
+
+        assert!(
+            telegram_with_sender(
+                &["ignored".to_string()],
+                &map!["bot_token" => "123:abc", "chat_id" => "-42", "text" => "Daily \"summary\""],
+                EffectOptions::default().into(),
+                |url, payload| {
+                    sent = Some((url.to_string(), payload.to_string()));
+                    Ok(())
+                },
+            )
+            .is_none()
+        );
+
+        let (url, payload) = sent.unwrap();
+        assert_eq!(url, "https://api.telegram.org/bot123:abc/sendMessage");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&payload).unwrap(),
+            json!({ "chat_id": "-42", "text": "Daily \"summary\"" })
+        );
+
+        // Without a `text` kwarg, the arguments are joined
+        let mut sent = None;
+
+        telegram_with_sender(
+            &["hello".to_string(), "world".to_string()],
+            &map!["bot_token" => "123:abc", "chat_id" => "-42"],
+            EffectOptions::default().into(),
+            |_, payload| {
+                sent = Some(payload.to_string());
+                Ok(())
+            },
+        );
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&sent.unwrap()).unwrap()["text"],
+            "hello world"
+        );
+
+        // Errors from sending are reported
+        assert!(
+            telegram_with_sender(
+                &["hello".to_string()],
+                &map!["bot_token" => "123:abc", "chat_id" => "-42"],
+                EffectOptions::default().into(),
+                |_, _| Err(Error::EffectError("unreachable".to_string())),
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+    }
+
+    #[test]
+    fn test_telegram_invalid() {
+        let never_send = |_: &str, _: &str| -> Result<(), Error> { panic!("should not send") };
+
+        for kwargs in [
+            map!["chat_id" => "-42"],
+            map!["bot_token" => "123:abc"],
+            map!["bot_token" => "123:abc", "chat_id" => "-42", "title" => "News"],
+        ] {
+            assert!(
+                telegram_with_sender(
+                    &["hello".to_string()],
+                    &kwargs,
+                    EffectOptions::default().into(),
+                    never_send,
+                )
+                .is_some_and(|e| matches!(e, Error::EffectError(_)))
+            );
+        }
+
+        assert!(
+            telegram_with_sender(
+                &[],
+                &map!["bot_token" => "123:abc", "chat_id" => "-42"],
+                EffectOptions::default().into(),
+                never_send,
+            )
+            .is_some_and(|e| matches!(e, Error::EffectError(_)))
+        );
+
+        // Silent tests do not send
+        assert!(
+            telegram(
+                &["hello".to_string()],
+                &map!["bot_token" => "123:abc", "chat_id" => "-42"],
+                EffectOptions::SilentTest.into(),
+            )
+            .is_none()
+        );
+    }
+
+    static TEST_EFFECTS_RUNNER_TASK_COUNT: AtomicU32 = AtomicU32::new(0);
+
+    #[tokio::test]
+    async fn test_effects_runner_task() {
+        fn count(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            TEST_EFFECTS_RUNNER_TASK_COUNT.fetch_add(1, SeqCst);
+            None
+        }
+
+        TEST_EFFECTS_RUNNER_TASK_COUNT.swap(0, SeqCst);
+
+        let registry = EffectRegistry::new().register("count", count);
+        let (effects_tx, effects_rx) = unbounded_channel::<EffectInvocation>();
+
+        for _ in 0..3 {
+            effects_tx
+                .send(EffectInvocation::new("count", vec![], HashMap::new()))
+                .unwrap();
+        }
+
+        effects_tx
+            .send(EffectInvocation::new("unknown", vec![], HashMap::new()))
+            .unwrap();
+
+        drop(effects_tx);
+
+        effects_runner_task(effects_rx, registry).await;
+
+        assert_eq!(TEST_EFFECTS_RUNNER_TASK_COUNT.load(SeqCst), 3);
+    }
+
+    #[test]
+    fn test_effect_registry() {
+        fn nothing(_: EffectArgs, _: EffectKwArgs, _: FlagSet<EffectOptions>) -> Option<Error> {
+            None
+        }
+
+        let mut names = EffectRegistry::with_defaults()
+            .register("nothing", nothing)
+            .names()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec![
+                "exec",
+                "nothing",
+                "notify",
+                "print",
+                "sqlite",
+                "telegram",
+                "writefile"
+            ]
+        );
+        assert!(EffectRegistry::new().get("print").is_none());
+        assert!(
+            EffectRegistry::new()
+                .register("print", nothing)
+                .get("print")
+                .is_some()
+        );
     }
 }
@@ -1,5 +1,11 @@
+pub mod circuit_breaker;
+pub mod cookies;
 pub mod daemon;
 pub mod effect;
+pub mod polite_delay;
+pub mod rate_limiter;
+pub mod response_cache;
+pub mod robots;
 pub mod scrapelang;
 pub mod scraper;
 pub mod util;
@@ -29,12 +35,18 @@ pub enum Error {
     #[error("Parse error: {0}")]
     ParseError(String),
 
+    #[error("Syntax error on line {0}: {1}")]
+    SyntaxError(usize, String),
+
     #[error("Parse error: {0}")]
     ParseIntError(#[from] ParseIntError),
 
     #[error("No such variable: `{0}`")]
     VariableNotFoundError(String),
 
+    #[error("Index {1} out of range for variable `{0}` with {2} value(s)")]
+    VariableIndexOutOfRangeError(String, usize, usize),
+
     #[error("Stopped: {0}")]
     Stopped(String),
 
@@ -53,15 +65,36 @@ pub enum Error {
     #[error("Invalid range")]
     InvalidRangeError,
 
+    #[error("Capture group count mismatch: pattern has {0} group(s), but {1} were expected")]
+    CaptureGroupCountMismatchError(usize, usize),
+
     #[error("Unsupported config version")]
     UnsupportedConfigVersionError,
 
+    #[error("Timed out after {0} second(s)")]
+    TimeoutError(u64),
+
     #[error("Script loader locking error")]
     ScriptLoaderLockingError,
 
+    #[error("Unable to find the user's home directory")]
+    HomeDirNotFoundError,
+
+    #[error("Circuit open for host `{0}` after repeated failures, try again later")]
+    CircuitOpenError(String),
+
+    #[error("Fetching {0} is disallowed by robots.txt")]
+    RobotsDisallowedError(String),
+
+    #[error("Unexpected content type: `{0}` does not match `{1}`")]
+    ContentTypeMismatchError(String, String),
+
     #[error("HTTP driver error: {0}")]
     HTTPDriverError(String),
 
+    #[error("Access to environment variable `{0}` is not allowed")]
+    EnvVarNotAllowedError(String),
+
     #[error("Lua error: {0}")]
     LuaError(String),
 
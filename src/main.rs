@@ -1,25 +1,55 @@
 use std::{
     collections::HashMap,
-    fs,
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
     sync::{Arc, RwLock},
 };
 
-use clap::Parser;
-use log::{debug, error};
+use chrono::Local;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
+use im::Vector;
+use log::{debug, error, info};
 use regex::Regex;
 use stderrlog::Timestamp;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 
 use libscrapeycat::{
     Error,
     daemon::{self, config_file::ConfigFile},
-    effect::{self, EffectInvocation, EffectSignature},
-    scrapelang::program::run,
+    effect::{self, EffectInvocation, EffectRegistry},
+    scrapelang::program::{RunOptions, run_with_options, validate},
     scraper::ReqwestHttpDriver,
+    util::{home, rng},
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Each result as is on its own line
+    Lines,
+
+    /// A JSON array of strings
+    Json,
+
+    /// Each result as a JSON string on its own line
+    Ndjson,
+
+    /// The list of results as formatted for debugging
+    Debug,
+}
+
 #[derive(Debug, Parser)]
-enum Cli {
+struct Cli {
+    /// Show more log output, may be given several times: `-v` for info, `-vv` for debug and `-vvv`
+    /// for trace output
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
     Run {
         script: String,
 
@@ -28,6 +58,39 @@ enum Cli {
 
         #[arg(short, long, required = false)]
         debug: bool,
+
+        /// Seed for randomized operations, making runs reproducible
+        #[arg(long, required = false)]
+        seed: Option<u64>,
+
+        /// Additional directory to look for scripts in, may be given several times
+        #[arg(long, required = false)]
+        script_dir: Vec<String>,
+
+        /// Format to print the results in
+        #[arg(long, value_enum, default_value_t = OutputFormat::Lines)]
+        format: OutputFormat,
+
+        /// Print each result as is on its own line, the same as `--format lines`
+        #[arg(long, required = false, conflicts_with = "format")]
+        raw: bool,
+
+        /// Separator to print between results in the `lines` format, rather than newline
+        #[arg(long, required = false)]
+        separator: Option<String>,
+
+        /// File to read additional arguments from, one per line
+        #[arg(long, required = false)]
+        args_file: Option<String>,
+    },
+
+    /// Check a script for syntax errors without running it
+    Check {
+        script: String,
+
+        /// Additional directory to look for scripts in, may be given several times
+        #[arg(long, required = false)]
+        script_dir: Vec<String>,
     },
 
     Daemon {
@@ -35,15 +98,141 @@ enum Cli {
 
         #[arg(short, long, required = false)]
         debug: bool,
+
+        /// Seed for randomized operations, making runs reproducible
+        #[arg(long, required = false)]
+        seed: Option<u64>,
+
+        /// List each job with its next few scheduled runs, and exit
+        #[arg(long, required = false)]
+        list: bool,
+
+        /// Run the given job once right away, and exit
+        #[arg(
+            long,
+            required = false,
+            value_name = "SUITE.JOB",
+            conflicts_with = "list"
+        )]
+        run: Option<String>,
     },
 }
 
-fn load_script(name_or_filename: &str) -> Result<String, Error> {
-    fs::read_to_string(name_or_filename)
-        .or_else(|_| fs::read_to_string(format!("{name_or_filename}.scrape")))
-        .or_else(|_| fs::read_to_string(format!("./scripts/{name_or_filename}")))
-        .or_else(|_| fs::read_to_string(format!("./scripts/{name_or_filename}.scrape")))
-        .map_err(|e| e.into())
+/// The log level to show given the number of `--verbose` flags, where `--debug` shows at least
+/// debug output.
+fn log_level(verbose: u8, debug: bool) -> log::Level {
+    match (verbose, debug) {
+        (3.., _) => log::Level::Trace,
+        (2, _) | (_, true) => log::Level::Debug,
+        (1, _) => log::Level::Info,
+        _ => log::Level::Error,
+    }
+}
+
+/// The paths at which to look for the script `name_or_filename`, in order: the name itself and
+/// within `./scripts/`, followed by within each of `script_dirs`, each tried as given and with the
+/// `.scrape` extension added. `${HOME}` in `script_dirs` is replaced with the path of the user's
+/// home directory.
+fn script_paths(name_or_filename: &str, script_dirs: &[String]) -> Vec<String> {
+    let home = home::home_dir().ok();
+
+    [".".to_string(), "./scripts".to_string()]
+        .into_iter()
+        .chain(script_dirs.iter().map(|dir| match &home {
+            Some(home) => dir.replace("${HOME}", home),
+            None => dir.clone(),
+        }))
+        .flat_map(|dir| {
+            let path = if dir == "." {
+                name_or_filename.to_string()
+            } else {
+                format!("{}/{name_or_filename}", dir.trim_end_matches('/'))
+            };
+
+            [path.clone(), format!("{path}.scrape")]
+        })
+        .collect()
+}
+
+fn load_script(name_or_filename: &str, script_dirs: &[String]) -> Result<String, Error> {
+    script_paths(name_or_filename, script_dirs)
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .ok_or_else(|| Error::ScriptNotFoundError(name_or_filename.to_string()))
+}
+
+/// Format the results of running a script for output in the given format, using `separator`
+/// between results in the `lines` format. Ends with a newline unless there is nothing to print.
+fn format_results(results: &Vector<String>, format: OutputFormat, separator: &str) -> String {
+    match format {
+        OutputFormat::Lines if results.is_empty() => String::new(),
+        OutputFormat::Lines => format!(
+            "{}\n",
+            results.iter().cloned().collect::<Vec<_>>().join(separator)
+        ),
+        OutputFormat::Json => format!(
+            "{}\n",
+            serde_json::to_string(&results.iter().collect::<Vec<_>>())
+                .expect("Should be able to serialize strings")
+        ),
+        OutputFormat::Ndjson => results
+            .iter()
+            .map(|result| {
+                format!(
+                    "{}\n",
+                    serde_json::to_string(result).expect("Should be able to serialize strings")
+                )
+            })
+            .collect(),
+        OutputFormat::Debug => format!("{results:#?}\n"),
+    }
+}
+
+/// Check that a `--separator` is only given for the `lines` format, the only one that uses it.
+fn check_separator(format: OutputFormat, separator: Option<&str>) -> Result<(), clap::Error> {
+    if separator.is_some() && format != OutputFormat::Lines {
+        Err(Cli::command().error(
+            ErrorKind::ArgumentConflict,
+            "`--separator` can only be used with `--format lines`",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Read arguments one per line, skipping empty lines.
+fn read_args(reader: impl BufRead) -> Result<Vec<String>, Error> {
+    let mut args = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if !line.trim().is_empty() {
+            args.push(line);
+        }
+    }
+
+    Ok(args)
+}
+
+/// Replace an argument of `-` with the arguments read from stdin, and append the arguments read
+/// from `args_file` if given.
+fn expand_args(args: Vec<String>, args_file: Option<&str>) -> Result<Vec<String>, Error> {
+    let mut expanded = Vec::new();
+
+    for arg in args {
+        if arg == "-" {
+            expanded.extend(read_args(io::stdin().lock())?);
+        } else {
+            expanded.push(arg);
+        }
+    }
+
+    if let Some(path) = args_file {
+        expanded.extend(read_args(BufReader::new(File::open(path)?))?);
+    }
+
+    Ok(expanded)
 }
 
 fn split_posargs_and_kwargs(args: Vec<String>) -> (Vec<String>, HashMap<String, String>) {
@@ -66,28 +255,49 @@ fn split_posargs_and_kwargs(args: Vec<String>) -> (Vec<String>, HashMap<String,
 
 #[tokio::main]
 async fn main() {
-    fn init_logging(debug: bool) {
+    fn init_logging(level: log::Level) {
         stderrlog::new()
             .modules(["scrapeycat", "libscrapeycat"])
             .show_module_names(false)
-            .verbosity(if debug {
-                log::Level::Debug
-            } else {
-                log::Level::Error
-            })
+            .verbosity(level)
             .timestamp(Timestamp::Millisecond)
             .init()
             .expect("Should be able to init logging");
     }
 
-    match Cli::parse() {
-        Cli::Run {
+    let Cli { verbose, command } = Cli::parse();
+
+    match command {
+        Command::Run {
             script,
             args,
             debug,
+            seed,
+            script_dir,
+            format,
+            raw: _,
+            separator,
+            args_file,
         } => {
-            init_logging(debug);
-            debug!("Cli::Run({script}, {args:?})");
+            if let Err(e) = check_separator(format, separator.as_deref()) {
+                e.exit();
+            }
+
+            init_logging(log_level(verbose, debug));
+
+            if let Some(seed) = seed {
+                rng::set_default_seed(seed);
+            }
+
+            debug!("Command::Run({script}, {args:?})");
+
+            let args = match expand_args(args, args_file.as_deref()) {
+                Ok(args) => args,
+                Err(e) => {
+                    error!("{e}");
+                    return;
+                }
+            };
 
             let (effects_sender, effects_receiver) = mpsc::unbounded_channel::<EffectInvocation>();
             let effects_runner_task =
@@ -95,40 +305,112 @@ async fn main() {
 
             let (posargs, kwargs) = split_posargs_and_kwargs(args);
 
-            match run::<ReqwestHttpDriver>(
+            match run_with_options::<ReqwestHttpDriver>(
                 &script,
                 posargs,
                 kwargs,
-                Arc::new(RwLock::new(load_script)),
+                RunOptions {
+                    effects: EffectRegistry::with_defaults(),
+                    ..RunOptions::default()
+                },
+                Arc::new(RwLock::new(move |name: &str| {
+                    load_script(name, &script_dir)
+                })),
                 effects_sender,
             )
             .await
             {
-                Ok(results) => println!("{results:#?}"),
+                Ok(results) => print!(
+                    "{}",
+                    format_results(&results, format, separator.as_deref().unwrap_or("\n"))
+                ),
                 Err(e) => error!("{e}"),
             }
 
             let _ = tokio::join!(effects_runner_task);
         }
 
-        Cli::Daemon { config, debug } => {
-            init_logging(debug);
-            debug!("Cli::Daemon({config})");
-
-            match ConfigFile::config_from_file(&config) {
-                Ok(config) => {
-                    daemon::run_config(
-                        config,
-                        HashMap::from([
-                            ("print".to_string(), effect::print as EffectSignature),
-                            ("notify".to_string(), effect::notify as EffectSignature),
-                        ]),
-                    )
-                    .await;
+        Command::Check { script, script_dir } => {
+            init_logging(log_level(verbose, false));
+
+            match load_script(&script, &script_dir).and_then(|code| validate(&code)) {
+                Ok(()) => println!("{script}: OK"),
+                Err(e) => {
+                    error!("{script}: {e}");
+                    std::process::exit(1);
                 }
-                Err(e) => error!("{e}"),
             }
         }
+
+        Command::Daemon {
+            config,
+            debug,
+            seed,
+            list,
+            run,
+        } => {
+            init_logging(log_level(verbose, debug));
+
+            if let Some(seed) = seed {
+                rng::set_default_seed(seed);
+            }
+
+            if list {
+                match ConfigFile::config_from_file(&config) {
+                    Ok(loaded) => print!(
+                        "{}",
+                        daemon::format_schedule(
+                            &loaded.suites.unwrap_or_default(),
+                            Local::now(),
+                            3
+                        )
+                    ),
+                    Err(e) => {
+                        error!("{config}: {e}");
+                        std::process::exit(1);
+                    }
+                }
+
+                return;
+            }
+
+            if let Some(job_id) = run {
+                let result = match ConfigFile::config_from_file(&config) {
+                    Ok(loaded) => {
+                        daemon::run_config_job(loaded, &job_id, EffectRegistry::with_defaults())
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+
+                match result {
+                    Ok(results) => {
+                        for result in results {
+                            println!("{result}");
+                        }
+                    }
+                    Err(e) => {
+                        error!("{job_id}: {e}");
+                        std::process::exit(1);
+                    }
+                }
+
+                return;
+            }
+
+            debug!("Command::Daemon({config})");
+
+            let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    info!("Interrupted, shutting down");
+                    let _ = shutdown_tx.send(true);
+                }
+            });
+
+            daemon::run_config_file(&config, EffectRegistry::with_defaults(), shutdown_rx).await;
+        }
     }
 }
 
@@ -185,4 +467,197 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_log_level() {
+        fn parse(args: &[&str]) -> log::Level {
+            let cli = Cli::try_parse_from(args).unwrap();
+
+            let debug = match cli.command {
+                Command::Run { debug, .. } | Command::Daemon { debug, .. } => debug,
+                Command::Check { .. } => false,
+            };
+
+            log_level(cli.verbose, debug)
+        }
+
+        assert_eq!(parse(&["scrapeycat", "run", "foo"]), log::Level::Error);
+        assert_eq!(parse(&["scrapeycat", "-v", "run", "foo"]), log::Level::Info);
+        assert_eq!(
+            parse(&["scrapeycat", "-vv", "run", "foo"]),
+            log::Level::Debug
+        );
+        assert_eq!(
+            parse(&["scrapeycat", "-vvv", "check", "foo"]),
+            log::Level::Trace
+        );
+        assert_eq!(
+            parse(&["scrapeycat", "-v", "-v", "-v", "-v", "run", "foo"]),
+            log::Level::Trace
+        );
+        assert_eq!(
+            parse(&["scrapeycat", "daemon", "-v", "foo.conf"]),
+            log::Level::Info
+        );
+        assert_eq!(
+            parse(&["scrapeycat", "daemon", "--debug", "foo.conf"]),
+            log::Level::Debug
+        );
+        assert_eq!(
+            parse(&["scrapeycat", "-v", "run", "--debug", "foo"]),
+            log::Level::Debug
+        );
+    }
+
+    #[test]
+    fn test_daemon_run() {
+        let cli =
+            Cli::try_parse_from(["scrapeycat", "daemon", "foo.conf", "--run", "default.news"])
+                .unwrap();
+
+        assert!(matches!(
+            cli.command,
+            Command::Daemon { run: Some(job_id), list: false, .. } if job_id == "default.news"
+        ));
+
+        assert!(
+            Cli::try_parse_from(["scrapeycat", "daemon", "foo.conf", "--list", "--run", "a.b"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_run_format() {
+        let cli = Cli::try_parse_from(["scrapeycat", "run", "--raw", "foo"]).unwrap();
+
+        assert!(matches!(
+            cli.command,
+            Command::Run {
+                format: OutputFormat::Lines,
+                raw: true,
+                ..
+            }
+        ));
+
+        assert!(
+            Cli::try_parse_from(["scrapeycat", "run", "--raw", "--format", "json", "foo"]).is_err()
+        );
+
+        assert!(check_separator(OutputFormat::Lines, Some(", ")).is_ok());
+        assert!(check_separator(OutputFormat::Json, None).is_ok());
+        assert!(check_separator(OutputFormat::Json, Some(", ")).is_err());
+        assert!(check_separator(OutputFormat::Ndjson, Some(", ")).is_err());
+    }
+
+    #[test]
+    fn test_script_paths() {
+        assert_eq!(
+            script_paths("foo", &[]),
+            vec!["foo", "foo.scrape", "./scripts/foo", "./scripts/foo.scrape"]
+        );
+
+        assert_eq!(
+            script_paths("foo", &["/a".to_string(), "/b/".to_string()]),
+            vec![
+                "foo",
+                "foo.scrape",
+                "./scripts/foo",
+                "./scripts/foo.scrape",
+                "/a/foo",
+                "/a/foo.scrape",
+                "/b/foo",
+                "/b/foo.scrape"
+            ]
+        );
+
+        let home = home::home_dir().unwrap();
+
+        assert_eq!(
+            script_paths("foo", &["${HOME}/scripts".to_string()])[4],
+            format!("{home}/scripts/foo")
+        );
+    }
+
+    #[test]
+    fn test_load_script_from_script_dir() {
+        let dir = std::env::temp_dir()
+            .join(format!("scrapeycat-test-script-dir-{}", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(format!("{dir}/scrapeycat-test-script.scrape"), "print()").unwrap();
+
+        assert!(load_script("scrapeycat-test-script", &[]).is_err());
+        assert_eq!(
+            load_script("scrapeycat-test-script", std::slice::from_ref(&dir)).unwrap(),
+            "print()"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_results() {
+        let results = Vector::from(vec!["a".to_string(), "b \"c\"".to_string()]);
+        let empty = Vector::new();
+
+        assert_eq!(
+            format_results(&results, OutputFormat::Lines, "\n"),
+            "a\nb \"c\"\n"
+        );
+        assert_eq!(
+            format_results(&results, OutputFormat::Lines, ", "),
+            "a, b \"c\"\n"
+        );
+        assert_eq!(format_results(&empty, OutputFormat::Lines, "\n"), "");
+
+        assert_eq!(
+            format_results(&results, OutputFormat::Json, "\n"),
+            "[\"a\",\"b \\\"c\\\"\"]\n"
+        );
+        assert_eq!(format_results(&empty, OutputFormat::Json, "\n"), "[]\n");
+
+        assert_eq!(
+            format_results(&results, OutputFormat::Ndjson, "\n"),
+            "\"a\"\n\"b \\\"c\\\"\"\n"
+        );
+        assert_eq!(format_results(&empty, OutputFormat::Ndjson, "\n"), "");
+
+        assert_eq!(
+            format_results(&results, OutputFormat::Debug, "\n"),
+            "[\n    \"a\",\n    \"b \\\"c\\\"\",\n]\n"
+        );
+        assert_eq!(format_results(&empty, OutputFormat::Debug, "\n"), "[]\n");
+    }
+
+    #[test]
+    fn test_read_args() {
+        let lines = "https://a.test/\n\nhttps://b.test/\r\nlang=en\n  \n";
+        let args = read_args(lines.as_bytes()).unwrap();
+
+        assert_eq!(args, vec!["https://a.test/", "https://b.test/", "lang=en"]);
+
+        let mut inline = vec!["https://c.test/".to_string(), "page=2".to_string()];
+        inline.extend(args);
+
+        assert_eq!(
+            split_posargs_and_kwargs(inline),
+            (
+                vec![
+                    "https://c.test/".to_string(),
+                    "https://a.test/".to_string(),
+                    "https://b.test/".to_string()
+                ],
+                HashMap::from([
+                    ("page".to_string(), "2".to_string()),
+                    ("lang".to_string(), "en".to_string())
+                ])
+            )
+        );
+
+        assert!(read_args("".as_bytes()).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,69 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use tokio::sync::Mutex;
+
+/// Ensures that at least `delay` passes between any two requests, regardless of their hosts.
+/// Clones share the time of the last request, so the delay also holds between requests made
+/// concurrently.
+#[derive(Debug, Clone)]
+pub struct PoliteDelay {
+    delay: Duration,
+    last_request: Arc<Mutex<Option<Instant>>>,
+}
+
+impl PoliteDelay {
+    pub fn new(delay: Duration) -> PoliteDelay {
+        PoliteDelay {
+            delay,
+            last_request: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Wait until a request may be made, and record that one is made now.
+    pub async fn wait(&self) {
+        let mut last_request = self.last_request.lock().await;
+
+        if let Some(last) = *last_request {
+            let remaining = self.delay.saturating_sub(last.elapsed());
+
+            if !remaining.is_zero() {
+                debug!("polite delay: waiting {remaining:?}");
+                tokio::time::sleep(remaining).await;
+            }
+        }
+
+        *last_request = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_polite_delay() {
+        let polite_delay = PoliteDelay::new(Duration::from_millis(50));
+        let start = Instant::now();
+
+        polite_delay.wait().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // Clones share the time of the last request
+        polite_delay.clone().wait().await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        let start = Instant::now();
+        polite_delay.wait().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
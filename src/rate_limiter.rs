@@ -0,0 +1,105 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+use tokio::sync::Mutex;
+
+use crate::Error;
+
+/// A token bucket holding a single token, refilled at `requests_per_second`, which limits
+/// requests to that rate on average. Clones share the bucket, so the limit holds for all
+/// requests made through any of them, including requests made concurrently.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    requests_per_second: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter allowing `requests_per_second` requests per second, which must be
+    /// positive and finite.
+    pub fn new(requests_per_second: f64) -> Result<RateLimiter, Error> {
+        if !requests_per_second.is_finite() || requests_per_second <= 0.0 {
+            return Err(Error::ValueOutOfRangeError);
+        }
+
+        Ok(RateLimiter {
+            requests_per_second,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: 1.0,
+                last_refill: Instant::now(),
+            })),
+        })
+    }
+
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests_per_second
+    }
+
+    /// Wait until a token is available, and take it.
+    pub async fn wait(&self) {
+        let mut bucket = self.bucket.lock().await;
+
+        let now = Instant::now();
+        let refilled = (now - bucket.last_refill).as_secs_f64() * self.requests_per_second;
+
+        bucket.tokens = (bucket.tokens + refilled).min(1.0);
+        bucket.last_refill = now;
+
+        if bucket.tokens < 1.0 {
+            let remaining =
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.requests_per_second);
+
+            debug!("rate limiter: waiting {remaining:?}");
+            tokio::time::sleep(remaining).await;
+
+            bucket.tokens = 1.0;
+            bucket.last_refill = Instant::now();
+        }
+
+        bucket.tokens -= 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_new() {
+        assert!(RateLimiter::new(0.5).is_ok());
+        assert!(RateLimiter::new(0.0).is_err());
+        assert!(RateLimiter::new(-1.0).is_err());
+        assert!(RateLimiter::new(f64::NAN).is_err());
+        assert!(RateLimiter::new(f64::INFINITY).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let rate_limiter = RateLimiter::new(20.0).unwrap();
+        let start = Instant::now();
+
+        rate_limiter.wait().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // Clones share the bucket, each further request waiting for a token
+        for n in 1..=4 {
+            rate_limiter.clone().wait().await;
+            assert!(start.elapsed() >= Duration::from_millis(50 * n));
+        }
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        let start = Instant::now();
+        rate_limiter.wait().await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::Utc;
+use im::HashMap;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, scraper::HttpResponse, util::home::home_dir};
+
+/// A response as stored in a cache file.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    /// Time of fetching as a unix timestamp.
+    fetched: i64,
+    body: String,
+    headers: BTreeMap<String, String>,
+}
+
+/// An on-disk cache of responses, keyed by the URL and headers of the request. Responses are
+/// served from the cache until `ttl` has passed since they were fetched, which avoids making the
+/// request at all, and the cache persists between runs of scripts and of the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseCache {
+    dir: String,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    /// Create a cache storing responses in the directory `dir`, where `${HOME}` is replaced with
+    /// the path of the user's home directory. The directory is created when first storing a
+    /// response.
+    pub fn new(dir: &str, ttl: Duration) -> ResponseCache {
+        let dir = match home_dir() {
+            Ok(home) => dir.replace("${HOME}", &home),
+            Err(_) => dir.to_string(),
+        };
+
+        ResponseCache { dir, ttl }
+    }
+
+    pub fn dir(&self) -> &str {
+        &self.dir
+    }
+
+    /// Get the cached response to a request for `url` with the given headers, unless there is
+    /// none or it has expired.
+    pub fn load(
+        &self,
+        url: &str,
+        headers: &HashMap<String, String>,
+    ) -> Result<Option<HttpResponse>, Error> {
+        self.load_at(url, headers, Utc::now().timestamp())
+    }
+
+    fn load_at(
+        &self,
+        url: &str,
+        headers: &HashMap<String, String>,
+        now: i64,
+    ) -> Result<Option<HttpResponse>, Error> {
+        let path = self.path(url, headers);
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let Ok(cached) = serde_json::from_str::<CachedResponse>(&text) else {
+            debug!("response cache: ignoring unreadable {path:?}");
+            return Ok(None);
+        };
+
+        if now.saturating_sub(cached.fetched) >= self.ttl.as_secs() as i64 {
+            debug!("response cache: expired {url}");
+            return Ok(None);
+        }
+
+        debug!("response cache: hit {url}");
+
+        Ok(Some(HttpResponse {
+            body: cached.body,
+            headers: cached.headers.into_iter().collect(),
+            ..Default::default()
+        }))
+    }
+
+    /// Store the response to a request for `url` with the given headers.
+    pub fn store(
+        &self,
+        url: &str,
+        headers: &HashMap<String, String>,
+        response: &HttpResponse,
+    ) -> Result<(), Error> {
+        self.store_at(url, headers, response, Utc::now().timestamp())
+    }
+
+    fn store_at(
+        &self,
+        url: &str,
+        headers: &HashMap<String, String>,
+        response: &HttpResponse,
+        now: i64,
+    ) -> Result<(), Error> {
+        let cached = CachedResponse {
+            fetched: now,
+            body: response.body.clone(),
+            headers: response
+                .headers
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        fs::write(
+            self.path(url, headers),
+            serde_json::to_string(&cached).map_err(|e| Error::JsonParseError(e.to_string()))?,
+        )?;
+
+        debug!("response cache: stored {url}");
+        Ok(())
+    }
+
+    /// The path of the cache file for a request for `url` with the given headers.
+    fn path(&self, url: &str, headers: &HashMap<String, String>) -> PathBuf {
+        let mut headers = headers.iter().collect::<Vec<_>>();
+        headers.sort();
+
+        let mut key = url.to_string();
+
+        for (name, value) in headers {
+            key.push_str(&format!("\n{}: {value}", name.to_lowercase()));
+        }
+
+        PathBuf::from(&self.dir).join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+}
+
+/// An in-memory cache of responses keyed by URL alone, shared between clones. Responses never
+/// expire, so the cache is meant to live no longer than a single run of a script.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCache {
+    responses: Arc<Mutex<std::collections::HashMap<String, HttpResponse>>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> MemoryCache {
+        MemoryCache::default()
+    }
+
+    pub fn get(&self, url: &str) -> Option<HttpResponse> {
+        let response = self
+            .responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(url)
+            .cloned();
+
+        if response.is_some() {
+            debug!("memory cache: hit {url}");
+        }
+
+        response
+    }
+
+    pub fn insert(&self, url: &str, response: &HttpResponse) {
+        self.responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(url.to_string(), response.clone());
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike [std::hash::DefaultHasher] is stable across Rust
+/// versions, keeping cache files valid after upgrading.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("scrapeycat-test-{name}-{}", process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn response(body: &str) -> HttpResponse {
+        HttpResponse {
+            body: body.to_string(),
+            headers: HashMap::unit("link".to_string(), "<page2>; rel=\"next\"".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = temp_dir("response-cache");
+        let _ = fs::remove_dir_all(&dir);
+
+        let cache = ResponseCache::new(&dir, Duration::from_secs(60));
+        let headers = HashMap::unit("User-Agent".to_string(), "cat".to_string());
+
+        assert!(cache.load("http://a.test/", &headers).unwrap().is_none());
+
+        cache
+            .store_at("http://a.test/", &headers, &response("a"), 1000)
+            .unwrap();
+
+        let loaded = cache
+            .load_at("http://a.test/", &headers, 1059)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(loaded.body, "a");
+        assert_eq!(
+            loaded.headers.get("link"),
+            Some(&"<page2>; rel=\"next\"".to_string())
+        );
+
+        // Expired
+        assert!(
+            cache
+                .load_at("http://a.test/", &headers, 1060)
+                .unwrap()
+                .is_none()
+        );
+
+        // Different URL or headers
+        assert!(
+            cache
+                .load_at("http://b.test/", &headers, 1000)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            cache
+                .load_at("http://a.test/", &HashMap::new(), 1000)
+                .unwrap()
+                .is_none()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = MemoryCache::new();
+        assert!(cache.get("http://a.test/").is_none());
+
+        // Clones share the cache
+        cache.clone().insert("http://a.test/", &response("a"));
+
+        assert_eq!(cache.get("http://a.test/").unwrap().body, "a");
+        assert!(cache.get("http://b.test/").is_none());
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use reqwest::Url;
+use texting_robots::Robot;
+
+/// The `robots.txt` rules of an origin (RFC 9309), or None if everything is allowed.
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    text: Option<Arc<str>>,
+}
+
+impl Robots {
+    /// Rules allowing everything, used when a host has no `robots.txt`.
+    pub fn allow_all() -> Robots {
+        Robots::default()
+    }
+
+    pub fn parse(text: &str) -> Robots {
+        Robots {
+            text: Some(text.into()),
+        }
+    }
+
+    /// Check whether `user_agent` may fetch `path` (including any query string), matching the
+    /// product token of the user-agent, e.g. `scrapeycat` in `Scrapeycat/1.0 (+https://...)`.
+    /// Rules that cannot be parsed allow everything.
+    pub fn is_allowed(&self, user_agent: &str, path: &str) -> bool {
+        let Some(text) = &self.text else {
+            return true;
+        };
+
+        let product = user_agent.split(['/', ' ']).next().unwrap_or_default();
+
+        match Robot::new(product, text.as_bytes()) {
+            Ok(robot) => robot.allowed(path),
+            Err(_) => true,
+        }
+    }
+}
+
+/// Cached `robots.txt` rules per origin, shared between clones.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsCache {
+    origins: Arc<Mutex<HashMap<String, Robots>>>,
+}
+
+impl RobotsCache {
+    pub fn new() -> RobotsCache {
+        RobotsCache::default()
+    }
+
+    pub fn get(&self, origin: &str) -> Option<Robots> {
+        self.origins
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(origin)
+            .cloned()
+    }
+
+    pub fn insert(&self, origin: &str, robots: Robots) {
+        self.origins
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(origin.to_string(), robots);
+    }
+}
+
+/// Split an HTTP(S) `url` into its origin (e.g. `https://example.com:8080`) and its path
+/// including any query string, or None if `url` is not an HTTP(S) URL.
+pub fn origin_and_path(url: &str) -> Option<(String, String)> {
+    let url = Url::parse(url).ok()?;
+
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return None;
+    }
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+
+    Some((url.origin().ascii_serialization(), path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS_TXT: &str = "
+# comment
+User-agent: *
+Disallow: /private
+Allow: /private/public   # still allowed
+Disallow: /*.pdf$
+
+User-agent: BadBot
+User-agent: WorseBot
+Disallow: /
+
+User-agent: GoodBot
+Disallow:
+";
+
+    #[test]
+    fn test_is_allowed() {
+        let robots = Robots::parse(ROBOTS_TXT);
+
+        assert!(robots.is_allowed("scrapeycat", "/"));
+        assert!(robots.is_allowed("scrapeycat", "/news"));
+        assert!(!robots.is_allowed("scrapeycat", "/private"));
+        assert!(!robots.is_allowed("scrapeycat", "/private/x"));
+        assert!(!robots.is_allowed("scrapeycat", "/privateer"));
+        assert!(robots.is_allowed("scrapeycat", "/private/public/x"));
+        assert!(!robots.is_allowed("scrapeycat", "/docs/a.pdf"));
+        assert!(robots.is_allowed("scrapeycat", "/docs/a.pdf?download"));
+
+        assert!(!robots.is_allowed("BadBot", "/news"));
+        assert!(!robots.is_allowed("worsebot/2.1", "/news"));
+        assert!(robots.is_allowed("GoodBot", "/private"));
+    }
+
+    #[test]
+    fn test_allow_all() {
+        assert!(Robots::allow_all().is_allowed("scrapeycat", "/anything"));
+        assert!(Robots::parse("").is_allowed("scrapeycat", "/anything"));
+        assert!(Robots::parse("garbage\n:::").is_allowed("scrapeycat", "/anything"));
+    }
+
+    #[test]
+    fn test_origin_and_path() {
+        assert_eq!(
+            origin_and_path("https://Example.com:8080/a/b?c=d#e"),
+            Some((
+                "https://example.com:8080".to_string(),
+                "/a/b?c=d".to_string()
+            ))
+        );
+        assert_eq!(
+            origin_and_path("http://example.com"),
+            Some(("http://example.com".to_string(), "/".to_string()))
+        );
+        assert_eq!(origin_and_path("string://foo"), None);
+        assert_eq!(origin_and_path("not a url"), None);
+    }
+}
@@ -1,21 +1,59 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    env,
+    net::{IpAddr, SocketAddr},
     ops::Deref,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
+use chrono::{DateTime, Local};
 use im::{Vector, vector};
-use log::error;
+use log::{debug, error};
 use mlua::prelude::*;
 use regex::Regex;
+use serde_json::{Map as JsonMap, Number as JsonNumber, Value as JsonValue};
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     Error,
-    effect::EffectInvocation,
-    scraper::{HttpDriver, Scraper},
+    circuit_breaker::CircuitBreaker,
+    cookies::CookieJar,
+    effect::{EffectInvocation, EffectOptions, EffectRegistry},
+    polite_delay::PoliteDelay,
+    rate_limiter::RateLimiter,
+    response_cache::{MemoryCache, ResponseCache},
+    robots::RobotsCache,
+    scraper::{
+        DEFAULT_MAX_BODY_SIZE, DEFAULT_TRACKING_PARAMS, HttpDriver, HttpMethod, MultipartForm,
+        ParseMode, Scraper,
+    },
+    util::timeofday::{DEFAULT_TIME_FORMAT, format_time, is_time_between_at, parse_time_of_day},
 };
 
+/// The number of meta refresh redirects followed when calling `followMetaRefresh()` without
+/// arguments.
+const DEFAULT_MAX_META_REFRESH_HOPS: usize = 5;
+
+/// The number of consecutive failures to a host after which requests are refused when calling
+/// `circuitBreaker()` without arguments.
+const DEFAULT_CIRCUIT_BREAKER_FAILURES: u32 = 5;
+
+/// The time to wait before again trying a host that has failed repeatedly, when calling
+/// `circuitBreaker()` without a cooldown argument.
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS: f64 = 300.0;
+
+/// The maximum number of requests made at a time when calling `getAll()` without a limit.
+const DEFAULT_GET_ALL_MAX_CONCURRENT: usize = 8;
+
+/// The directory in which responses are cached when calling `cache()` without a directory
+/// argument.
+const DEFAULT_RESPONSE_CACHE_DIR: &str = "${HOME}/.scrapeycat/cache";
+
+/// The maximum nesting depth of tables encoded by `jsonEncode()`, guarding against tables that
+/// contain themselves.
+const MAX_JSON_ENCODE_DEPTH: usize = 128;
+
 /// This function was refactored with the help of generative AI.
 fn substitute_variables(
     text: &str,
@@ -24,6 +62,7 @@ fn substitute_variables(
     let mut result = String::new();
     let mut remaining = text;
     let matcher = Regex::new("(?s)^\\{(.+?)\\}").expect("Should be a valid regex");
+    let indexed = Regex::new(r"(?s)^(.+)\[(\d+)\]$").expect("Should be a valid regex");
 
     while let Some(start) = remaining.find('{') {
         // Append text before '{'
@@ -34,18 +73,45 @@ fn substitute_variables(
             result.push_str("{{");
             remaining = &remaining[2..];
         } else if let Some(matched) = matcher.captures(remaining) {
-            let varname = matched.get(1).expect("Group 1 should exist").as_str();
-
-            result.push_str(
-                variables
-                    .get(varname)
-                    .ok_or_else(|| Error::VariableNotFoundError(varname.to_string()))?
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<_>>()
-                    .join("")
-                    .as_str(),
-            );
+            let reference = matched.get(1).expect("Group 1 should exist").as_str();
+
+            // A reference of the form `{name:default}` falls back to the default when the
+            // variable is not set, or the index given is out of range
+            let (varname, default) = match reference.split_once(':') {
+                Some((varname, default)) => (varname, Some(default)),
+                None => (reference, None),
+            };
+
+            // A reference of the form `{name[n]}` selects the n:th value, counting from 1
+            let (varname, index) = match indexed.captures(varname) {
+                Some(indexed) => (
+                    indexed.get(1).expect("Group 1 should exist").as_str(),
+                    Some(indexed[2].parse::<usize>()?),
+                ),
+                None => (varname, None),
+            };
+
+            let value = match (variables.get(varname), index) {
+                (Some(values), None) => Some(values.iter().cloned().collect::<Vec<_>>().join("")),
+                (Some(values), Some(index)) => match index.checked_sub(1) {
+                    Some(index) if index < values.len() => Some(values[index].clone()),
+                    _ if default.is_some() => None,
+                    _ => {
+                        return Err(Error::VariableIndexOutOfRangeError(
+                            varname.to_string(),
+                            index,
+                            values.len(),
+                        ));
+                    }
+                },
+                (None, _) => None,
+            };
+
+            match (value, default) {
+                (Some(value), _) => result.push_str(&value),
+                (None, Some(default)) => result.push_str(default),
+                (None, None) => return Err(Error::VariableNotFoundError(varname.to_string())),
+            }
 
             remaining = &remaining[matched.get(0).expect("Group 0 always exists").range().end..]
         } else {
@@ -58,6 +124,138 @@ fn substitute_variables(
     Ok(result.replace("{{", "{").replace("}}", "}"))
 }
 
+/// Parse headers given as lines of the form `key=value`, skipping blank lines. Surrounding
+/// whitespace is trimmed from both keys and values.
+fn parse_header_lines(lines: &Vector<String>) -> Result<Vec<(String, String)>, Error> {
+    lines
+        .iter()
+        .flat_map(|str| str.lines())
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(Error::ParseError(format!(
+                "Invalid header line `{line}`, expected `key=value`"
+            ))),
+        })
+        .collect()
+}
+
+/// Whether the environment variable `name` is matched by any of `allowed`, which are names or
+/// prefixes followed by `*`.
+fn is_env_allowed(name: &str, allowed: &[String]) -> bool {
+    allowed
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        })
+}
+
+/// Parse an address given either as an IP address, meaning the port of the URL, or as an IP
+/// address and port.
+pub(crate) fn parse_resolve_addr(addr: &str) -> Result<SocketAddr, Error> {
+    addr.parse::<SocketAddr>()
+        .or_else(|_| addr.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 0)))
+        .map_err(|_| Error::ParseError(format!("Invalid address `{addr}`")))
+}
+
+/// Convert a JSON value to a Lua value. Arrays become sequences and objects become tables keyed
+/// by strings, while `null` becomes `nil`.
+fn json_to_lua(lua: &Lua, value: &JsonValue) -> LuaResult<LuaValue> {
+    Ok(match value {
+        JsonValue::Null => LuaValue::Nil,
+        JsonValue::Bool(value) => LuaValue::Boolean(*value),
+        JsonValue::Number(number) => match number.as_i64() {
+            Some(integer) => LuaValue::Integer(integer),
+            None => LuaValue::Number(number.as_f64().unwrap_or(f64::NAN)),
+        },
+        JsonValue::String(str) => LuaValue::String(lua.create_string(str)?),
+        JsonValue::Array(values) => {
+            let table = lua.create_table()?;
+
+            for (index, value) in values.iter().enumerate() {
+                table.raw_set(index + 1, json_to_lua(lua, value)?)?;
+            }
+
+            LuaValue::Table(table)
+        }
+        JsonValue::Object(map) => {
+            let table = lua.create_table()?;
+
+            for (key, value) in map {
+                table.raw_set(key.as_str(), json_to_lua(lua, value)?)?;
+            }
+
+            LuaValue::Table(table)
+        }
+    })
+}
+
+/// Convert a Lua value to a JSON value. Tables whose keys are exactly `1..=n` for some `n > 0`
+/// become arrays, and other tables become objects with their keys converted to strings, so
+/// that empty tables become empty objects.
+fn lua_to_json(value: &LuaValue, depth: usize) -> Result<JsonValue, Error> {
+    if depth > MAX_JSON_ENCODE_DEPTH {
+        return Err(Error::LuaError(
+            "Cannot encode deeply nested or self-referencing table as JSON".to_string(),
+        ));
+    }
+
+    Ok(match value {
+        LuaValue::Nil => JsonValue::Null,
+        LuaValue::Boolean(value) => JsonValue::Bool(*value),
+        LuaValue::Integer(integer) => JsonValue::Number((*integer).into()),
+        LuaValue::Number(number) => {
+            JsonValue::Number(JsonNumber::from_f64(*number).ok_or_else(|| {
+                Error::LuaError(format!("Cannot encode number `{number}` as JSON"))
+            })?)
+        }
+        LuaValue::String(str) => JsonValue::String(str.to_str()?.to_string()),
+        LuaValue::Table(table) => {
+            let len = table.raw_len();
+            let pairs = table
+                .pairs::<LuaValue, LuaValue>()
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if len > 0 && pairs.len() == len {
+                JsonValue::Array(
+                    (1..=len)
+                        .map(|index| lua_to_json(&table.raw_get::<LuaValue>(index)?, depth + 1))
+                        .collect::<Result<Vec<_>, _>>()?,
+                )
+            } else {
+                let mut map = JsonMap::new();
+
+                for (key, value) in pairs {
+                    let key = match key {
+                        LuaValue::String(str) => str.to_str()?.to_string(),
+                        LuaValue::Integer(integer) => integer.to_string(),
+                        LuaValue::Number(number) => number.to_string(),
+                        other => {
+                            return Err(Error::LuaError(format!(
+                                "Cannot encode table key of type `{}` as JSON",
+                                other.type_name()
+                            )));
+                        }
+                    };
+
+                    map.insert(key, lua_to_json(&value, depth + 1)?);
+                }
+
+                JsonValue::Object(map)
+            }
+        }
+        other => {
+            return Err(Error::LuaError(format!(
+                "Cannot encode value of type `{}` as JSON",
+                other.type_name()
+            )));
+        }
+    })
+}
+
 impl From<mlua::Error> for Error {
     fn from(value: mlua::Error) -> Self {
         Error::LuaError(value.to_string())
@@ -70,9 +268,20 @@ impl From<Error> for mlua::Error {
     }
 }
 
+/// The environment variables readable by scripts using `env()` unless configured otherwise, as
+/// names or as prefixes followed by `*`.
+pub const DEFAULT_ALLOWED_ENV: &[&str] = &["SCRAPEYCAT_*"];
+
 struct LuaScraperState<H: HttpDriver + 'static> {
     scraper: Scraper<H>,
     variables: HashMap<String, Vector<String>>,
+    script_name: Option<String>,
+    /// Hashes of the effect invocations sent so far, when duplicates are to be suppressed.
+    effect_dedup: Option<HashSet<u64>>,
+    /// The environment variables readable using `env()`, as names or prefixes followed by `*`.
+    allowed_env: Vec<String>,
+    /// The time used by `now()` and `timeBetween()` instead of the current time, for testing.
+    fixed_now: Option<DateTime<Local>>,
 }
 
 impl<H: HttpDriver + 'static> LuaScraperState<H> {
@@ -80,7 +289,28 @@ impl<H: HttpDriver + 'static> LuaScraperState<H> {
         LuaScraperState {
             scraper: Scraper::new(),
             variables: HashMap::new(),
+            script_name: None,
+            effect_dedup: None,
+            allowed_env: DEFAULT_ALLOWED_ENV.iter().map(|s| s.to_string()).collect(),
+            fixed_now: None,
+        }
+    }
+
+    /// Send `invocation` on `effect_sender`, unless duplicates are suppressed and an identical
+    /// invocation has already been sent.
+    fn send_effect(
+        &mut self,
+        effect_sender: &UnboundedSender<EffectInvocation>,
+        invocation: EffectInvocation,
+    ) -> LuaResult<()> {
+        if let Some(seen) = &mut self.effect_dedup
+            && !seen.insert(invocation.dedup_hash())
+        {
+            debug!("effect deduplicated: {invocation:?}");
+            return Ok(());
         }
+
+        effect_sender.send(invocation).map_err(|e| e.into_lua_err())
     }
 }
 
@@ -105,11 +335,116 @@ fn get_state<H: HttpDriver + 'static>(
         ))
 }
 
+/// Split an optional Lua arguments table such as `{"a", "b", key="value"}` into positional
+/// and keyword arguments, substituting variables in all values. If no positional arguments
+/// are given, the current list of results is used instead.
+fn args_from_table<H: HttpDriver + 'static>(
+    args_table: Option<LuaTable>,
+    state: &LuaScraperState<H>,
+) -> Result<(Vec<String>, HashMap<String, String>), Error> {
+    let mut args: Vec<String> = vec![];
+    let mut kwargs: HashMap<String, String> = HashMap::new();
+
+    if let Some(args_table) = args_table {
+        for i in 1..100 {
+            if let Ok(value) = args_table.get::<String>(i) {
+                args.push(substitute_variables(&value, &state.variables)?);
+            }
+        }
+
+        for (key, value) in args_table.pairs::<String, String>().flatten() {
+            if !key.chars().all(|ch| ch.is_ascii_digit()) {
+                kwargs.insert(key, substitute_variables(&value, &state.variables)?);
+            }
+        }
+    }
+
+    if args.is_empty() {
+        args.extend(state.scraper.results().iter().cloned());
+    }
+
+    Ok((args, kwargs))
+}
+
+/// Options given to `get` in its optional table argument, such as `{timeout=10}`, which apply to
+/// that request only.
+#[derive(Debug, Default)]
+struct GetOptions {
+    /// Timeout of the request in seconds.
+    timeout: Option<u64>,
+    max_body_size: Option<usize>,
+    decompress: Option<bool>,
+}
+
+impl GetOptions {
+    const NAMES: [&str; 3] = ["decompress", "maxBodySize", "timeout"];
+
+    fn from_table(table: Option<LuaTable>) -> LuaResult<GetOptions> {
+        let Some(table) = table else {
+            return Ok(GetOptions::default());
+        };
+
+        for pair in table.pairs::<LuaValue, LuaValue>() {
+            let (key, _) = pair?;
+            let key = key.to_string()?;
+
+            if !GetOptions::NAMES.contains(&key.as_str()) {
+                return Err(Error::ParseError(format!(
+                    "Unknown option `{key}` for `get`, expected one of {}",
+                    GetOptions::NAMES.join(", ")
+                ))
+                .into_lua_err());
+            }
+        }
+
+        Ok(GetOptions {
+            timeout: table.get("timeout")?,
+            max_body_size: table.get("maxBodySize")?,
+            decompress: table.get("decompress")?,
+        })
+    }
+
+    /// Get `url` using `scraper` with the options applied, keeping the settings of `scraper`
+    /// for later requests.
+    async fn get<H: HttpDriver + Send + Sync + 'static>(
+        &self,
+        scraper: Scraper<H>,
+        url: &str,
+    ) -> Result<Scraper<H>, Error> {
+        let mut request_scraper = scraper.clone();
+
+        if let Some(max_body_size) = self.max_body_size {
+            if max_body_size == 0 {
+                return Err(Error::ValueOutOfRangeError);
+            }
+
+            request_scraper = request_scraper.set_max_body_size(max_body_size);
+        }
+
+        if let Some(decompress) = self.decompress {
+            request_scraper = request_scraper.set_decompress(decompress);
+        }
+
+        let updated_scraper = match self.timeout {
+            Some(0) => return Err(Error::ValueOutOfRangeError),
+            Some(secs) => tokio::time::timeout(Duration::from_secs(secs), request_scraper.get(url))
+                .await
+                .map_err(|_| Error::TimeoutError(secs))??,
+            None => request_scraper.get(url).await?,
+        };
+
+        Ok(updated_scraper
+            .set_max_body_size(scraper.max_body_size())
+            .set_decompress(scraper.decompress()))
+    }
+}
+
 fn create_lua_context<H: HttpDriver + Send + Sync + 'static>(
     args: Vec<String>,
     kwargs: HashMap<String, String>,
     effect_sender: UnboundedSender<EffectInvocation>,
     script_loader: ScriptLoaderPointer,
+    effects: EffectRegistry,
 ) -> Result<Lua, Error> {
     let mut state = LuaScraperState::<H>::new();
 
@@ -141,6 +476,52 @@ fn create_lua_context<H: HttpDriver + Send + Sync + 'static>(
         })?,
     )?;
 
+    lua.globals().set(
+        "abortIfMatch",
+        lua.create_function(|lua: &Lua, pattern: String| {
+            let state = get_state::<H>(lua)?;
+            let pattern = substitute_variables(&pattern, &state.variables)?;
+
+            if state.scraper.any_match(&pattern)? {
+                Err(LuaError::ExternalError(Arc::new(InterruptedError {})))
+            } else {
+                Ok(())
+            }
+        })?,
+    )?;
+
+    lua.globals().set(
+        "abortUnlessMatch",
+        lua.create_function(|lua: &Lua, pattern: String| {
+            let state = get_state::<H>(lua)?;
+            let pattern = substitute_variables(&pattern, &state.variables)?;
+
+            if state.scraper.any_match(&pattern)? {
+                Ok(())
+            } else {
+                Err(LuaError::ExternalError(Arc::new(InterruptedError {})))
+            }
+        })?,
+    )?;
+
+    lua.globals().set(
+        "accept",
+        lua.create_function(|lua: &Lua, (media_type, mode): (String, Option<String>)| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.set_header(
+                "Accept".to_string(),
+                substitute_variables(&media_type, &state.variables)?,
+            );
+
+            if let Some(mode) = mode {
+                state.scraper = state.scraper.set_parse_mode(mode.parse::<ParseMode>()?);
+            }
+
+            Ok(())
+        })?,
+    )?;
+
     lua.globals().set(
         "append",
         lua.create_function(|lua: &Lua, text: String| {
@@ -154,6 +535,17 @@ fn create_lua_context<H: HttpDriver + Send + Sync + 'static>(
         })?,
     )?;
 
+    lua.globals().set(
+        "appendStore",
+        lua.create_function(|lua: &Lua, name: String| {
+            let mut state = get_state::<H>(lua)?;
+            let results = state.scraper.results().clone();
+
+            state.variables.entry(name).or_default().append(results);
+            Ok(())
+        })?,
+    )?;
+
     lua.globals().set(
         "apply",
         lua.create_function(|lua: &Lua, f: LuaFunction| {
@@ -172,199 +564,355 @@ fn create_lua_context<H: HttpDriver + Send + Sync + 'static>(
     )?;
 
     lua.globals().set(
-        "clear",
-        lua.create_function(|lua: &Lua, ()| {
+        "asEnv",
+        lua.create_function(|lua: &Lua, prefix: String| {
             let mut state = get_state::<H>(lua)?;
 
-            state.scraper = state.scraper.clear();
+            state.scraper = state.scraper.as_env(&prefix)?;
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "clearHeaders",
-        lua.create_function(|lua: &Lua, ()| {
+        "asJsonObject",
+        lua.create_function(|lua: &Lua, key: String| {
             let mut state = get_state::<H>(lua)?;
 
-            state.scraper = state.scraper.clear_headers();
+            state.scraper = state.scraper.as_json_object(&key);
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "delete",
-        lua.create_function(|lua: &Lua, pattern: String| {
+        "bottomN",
+        lua.create_function(|lua: &Lua, (pattern, count): (String, usize)| {
             let mut state = get_state::<H>(lua)?;
 
             state.scraper = state
                 .scraper
-                .delete(&substitute_variables(&pattern, &state.variables)?)?;
+                .bottom_n(&substitute_variables(&pattern, &state.variables)?, count)?;
 
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "discard",
-        lua.create_function(|lua: &Lua, pattern: String| {
+        "cache",
+        lua.create_function(|lua: &Lua, (ttl_secs, dir): (f64, Option<String>)| {
             let mut state = get_state::<H>(lua)?;
 
-            state.scraper = state
-                .scraper
-                .discard(&substitute_variables(&pattern, &state.variables)?)?;
+            let ttl =
+                Duration::try_from_secs_f64(ttl_secs).map_err(|_| Error::ValueOutOfRangeError)?;
 
+            let response_cache = if ttl.is_zero() {
+                None
+            } else {
+                let dir = match dir {
+                    Some(dir) => substitute_variables(&dir, &state.variables)?,
+                    None => DEFAULT_RESPONSE_CACHE_DIR.to_string(),
+                };
+
+                Some(ResponseCache::new(&dir, ttl))
+            };
+
+            state.scraper = state.scraper.set_response_cache(response_cache);
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "drop",
-        lua.create_function(|lua: &Lua, n: usize| {
+        "canonicalizeUrls",
+        lua.create_function(
+            |lua: &Lua, (unique, params): (Option<bool>, Option<Vec<String>>)| {
+                let mut state = get_state::<H>(lua)?;
+
+                let params = params.unwrap_or_else(|| {
+                    DEFAULT_TRACKING_PARAMS
+                        .iter()
+                        .map(|param| param.to_string())
+                        .collect()
+                });
+
+                state.scraper = state
+                    .scraper
+                    .canonicalize_urls(&params, unique.unwrap_or(false));
+
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "changedVs",
+        lua.create_function(|lua: &Lua, name: String| {
             let mut state = get_state::<H>(lua)?;
 
-            state.scraper = state.scraper.drop(n);
+            let snapshot = state
+                .variables
+                .get(&name)
+                .ok_or_else(|| Error::VariableNotFoundError(name.clone()))?;
+
+            state.scraper = state.scraper.changed_vs(snapshot);
             Ok(())
         })?,
     )?;
 
-    let effect_sender_for_effect_fn = UnboundedSender::clone(&effect_sender);
-
     lua.globals().set(
-        "effect",
+        "circuitBreaker",
         lua.create_function(
-            move |lua: &Lua, (name, args_table): (String, Option<LuaTable>)| {
-                let state = get_state::<H>(lua)?;
-                let mut args: Vec<String> = vec![];
-                let mut kwargs: HashMap<String, String> = HashMap::new();
-
-                if let Some(args_table) = args_table {
-                    for i in 1..100 {
-                        if let Ok(value) = args_table.get::<String>(i) {
-                            args.push(substitute_variables(&value, &state.variables)?);
-                        }
-                    }
-
-                    for (key, value) in args_table.pairs::<String, String>().flatten() {
-                        if !key.chars().all(|ch| ch.is_ascii_digit()) {
-                            kwargs.insert(key, substitute_variables(&value, &state.variables)?);
-                        }
-                    }
-                }
-
-                if args.is_empty() {
-                    args.extend(state.scraper.results().iter().cloned());
-                }
-
-                match effect_sender_for_effect_fn.send(EffectInvocation::new(name, args, kwargs)) {
-                    Ok(_) => Ok(()),
-                    Err(e) => Err(e.into_lua_err()),
-                }
+            |lua: &Lua, (max_failures, cooldown_secs): (Option<u32>, Option<f64>)| {
+                let mut state = get_state::<H>(lua)?;
+
+                let circuit_breaker = match max_failures.unwrap_or(DEFAULT_CIRCUIT_BREAKER_FAILURES)
+                {
+                    0 => None,
+                    max_failures => Some(CircuitBreaker::shared(
+                        max_failures,
+                        Duration::try_from_secs_f64(
+                            cooldown_secs.unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS),
+                        )
+                        .map_err(|_| Error::ValueOutOfRangeError)?,
+                    )),
+                };
+
+                state.scraper = state.scraper.set_circuit_breaker(circuit_breaker);
+                Ok(())
             },
         )?,
     )?;
 
     lua.globals().set(
-        "extract",
-        lua.create_function(|lua: &Lua, pattern: String| {
+        "clear",
+        lua.create_function(|lua: &Lua, ()| {
             let mut state = get_state::<H>(lua)?;
 
-            state.scraper = state
-                .scraper
-                .extract(&substitute_variables(&pattern, &state.variables)?)?;
+            state.scraper = state.scraper.clear();
+            Ok(())
+        })?,
+    )?;
 
+    lua.globals().set(
+        "clearHeaders",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.clear_headers();
             Ok(())
         })?,
     )?;
 
+    // An alias for `join("\n")`, since joining all results into one means a following
+    // `effect()` without explicit arguments passes them as a single, multi-line argument rather
+    // than as one argument per result.
     lua.globals().set(
-        "first",
+        "collect",
         lua.create_function(|lua: &Lua, ()| {
             let mut state = get_state::<H>(lua)?;
 
-            state.scraper = state.scraper.first();
+            state.scraper = state.scraper.join("\n");
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "get",
-        lua.create_async_function(|lua: Lua, url: String| async move {
-            let (scraper, url_subst) = {
-                let state = get_state::<H>(&lua)?;
-                (
-                    state.scraper.clone(),
-                    &substitute_variables(&url, &state.variables)?,
-                )
+        "cookieJar",
+        lua.create_function(|lua: &Lua, path: Option<String>| {
+            let mut state = get_state::<H>(lua)?;
+
+            let cookie_jar = match path {
+                Some(path) => CookieJar::open(&substitute_variables(&path, &state.variables)?)?,
+                None => CookieJar::new(),
             };
 
-            let updated_scraper = scraper.get(url_subst).await?;
+            state.scraper = state.scraper.set_cookie_jar(Some(cookie_jar));
+            Ok(())
+        })?,
+    )?;
 
-            let mut state = get_state::<H>(&lua)?;
-            state.scraper = updated_scraper;
+    lua.globals().set(
+        "decompress",
+        lua.create_function(|lua: &Lua, enabled: bool| {
+            let mut state = get_state::<H>(lua)?;
 
+            state.scraper = state.scraper.set_decompress(enabled);
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "header",
-        lua.create_function(|lua: &Lua, (key, value): (String, String)| {
+        "delete",
+        lua.create_function(|lua: &Lua, pattern: String| {
             let mut state = get_state::<H>(lua)?;
 
             state.scraper = state
                 .scraper
-                .set_header(key, substitute_variables(&value, &state.variables)?);
+                .delete(&substitute_variables(&pattern, &state.variables)?)?;
 
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "jsonPath",
-        lua.create_function(|lua: &Lua, expr: String| {
+        "discard",
+        lua.create_function(|lua: &Lua, pattern: String| {
             let mut state = get_state::<H>(lua)?;
 
             state.scraper = state
                 .scraper
-                .jsonpath(&substitute_variables(&expr, &state.variables)?)?;
+                .discard(&substitute_variables(&pattern, &state.variables)?)?;
 
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "list",
-        lua.create_function(|lua: &Lua, name: String| {
-            get_state::<H>(lua)?
-                .variables
-                .get(&name)
-                .map(|v| v.iter().cloned().collect::<Vec<_>>())
-                .ok_or_else(|| {
-                    error!("variable `{name}` not found");
-                    Error::LuaError(format!("variable `{name}` not found")).into_lua_err()
-                })
-        })?,
-    )?;
-
-    lua.globals().set(
-        "load",
-        lua.create_function(|lua: &Lua, name: String| {
+        "drop",
+        lua.create_function(|lua: &Lua, n: usize| {
             let mut state = get_state::<H>(lua)?;
-            let mut results = state.scraper.results().clone();
-
-            let stored = state.variables.get(&name).ok_or_else(|| {
-                error!("variable `{name}` not found");
-                Error::LuaError(format!("variable `{name}` not found")).into_lua_err()
-            })?;
 
-            results.extend(stored.iter().cloned());
-            state.scraper = state.scraper.clone().with_results(results);
+            state.scraper = state.scraper.drop(n);
             Ok(())
         })?,
     )?;
 
-    lua.globals().set(
-        "map",
+    let effect_sender_for_effect_fn = UnboundedSender::clone(&effect_sender);
+
+    lua.globals().set(
+        "effect",
+        lua.create_function(
+            move |lua: &Lua, (name, args_table): (String, Option<LuaTable>)| {
+                let mut state = get_state::<H>(lua)?;
+                let (args, kwargs) = args_from_table(args_table, &state)?;
+
+                state.send_effect(
+                    &effect_sender_for_effect_fn,
+                    EffectInvocation::new(name, args, kwargs),
+                )
+            },
+        )?,
+    )?;
+
+    let effect_sender_for_effect_batched_fn = effect_sender.clone();
+
+    lua.globals().set(
+        "effectBatched",
+        lua.create_function(
+            move |lua: &Lua, (name, size, args_table): (String, usize, Option<LuaTable>)| {
+                if size == 0 {
+                    return Err(Error::ValueOutOfRangeError.into_lua_err());
+                }
+
+                let mut state = get_state::<H>(lua)?;
+                let (_, kwargs) = args_from_table(args_table, &state)?;
+                let results = state.scraper.results().iter().cloned().collect::<Vec<_>>();
+
+                for batch in results.chunks(size) {
+                    state.send_effect(
+                        &effect_sender_for_effect_batched_fn,
+                        EffectInvocation::new(name.clone(), batch.to_vec(), kwargs.clone()),
+                    )?;
+                }
+
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "effectDedup",
+        lua.create_function(|lua: &Lua, enabled: Option<bool>| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.effect_dedup = match enabled.unwrap_or(true) {
+                true => Some(state.effect_dedup.take().unwrap_or_default()),
+                false => None,
+            };
+
+            Ok(())
+        })?,
+    )?;
+
+    let effects_for_effect_result_fn = effects.clone();
+
+    lua.globals().set(
+        "effectResult",
+        lua.create_function(
+            move |lua: &Lua, (name, args_table): (String, Option<LuaTable>)| {
+                let (args, kwargs) = {
+                    let state = get_state::<H>(lua)?;
+                    args_from_table(args_table, &state)?
+                };
+
+                let effect_fn = effects_for_effect_result_fn
+                    .get_result(&name)
+                    .ok_or(Error::EffectNotFoundError)?;
+
+                // We don't want to hold a borrow to the state while running the effect
+                let returned = effect_fn(&args, &kwargs, EffectOptions::default().into()).map_err(
+                    |e| match e {
+                        Error::EffectError(text) => Error::EffectError(text),
+                        e => Error::EffectError(format!("`{name}`: {e}")),
+                    },
+                )?;
+
+                let mut state = get_state::<H>(lua)?;
+                let mut results = state.scraper.results().clone();
+
+                results.extend(returned);
+                state.scraper = state.scraper.clone().with_results(results);
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "env",
+        lua.create_function(|lua: &Lua, name: String| {
+            let state = get_state::<H>(lua)?;
+
+            if !is_env_allowed(&name, &state.allowed_env) {
+                return Err(Error::EnvVarNotAllowedError(name).into_lua_err());
+            }
+
+            Ok(env::var(&name).ok())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "expectContentType",
+        lua.create_function(|lua: &Lua, pattern: String| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.expect_content_type(&pattern)?;
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "extract",
+        lua.create_function(|lua: &Lua, pattern: String| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .extract_by_mode(&substitute_variables(&pattern, &state.variables)?)?;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "first",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.first();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "flatMap",
         lua.create_function(|lua: &Lua, f: LuaFunction| {
             // We don't want to hold a borrow to the state while applying the function
             let results = {
@@ -372,12 +920,11 @@ fn create_lua_context<H: HttpDriver + Send + Sync + 'static>(
                 state.scraper.results().clone()
             };
 
-            let mapped = Vector::from(
-                results
-                    .into_iter()
-                    .map(|s| f.call::<String>(s))
-                    .collect::<Result<Vec<_>, mlua::Error>>()?,
-            );
+            let mut mapped = Vector::new();
+
+            for s in results {
+                mapped.extend(f.call::<Vec<String>>(s)?);
+            }
 
             let mut state = get_state::<H>(lua)?;
 
@@ -387,178 +934,1124 @@ fn create_lua_context<H: HttpDriver + Send + Sync + 'static>(
     )?;
 
     lua.globals().set(
-        "prepend",
-        lua.create_function(|lua: &Lua, text: String| {
+        "followMetaRefresh",
+        lua.create_function(|lua: &Lua, max_hops: Option<usize>| {
             let mut state = get_state::<H>(lua)?;
 
             state.scraper = state
                 .scraper
-                .prepend(&substitute_variables(&text, &state.variables)?);
+                .set_max_meta_refresh_hops(max_hops.unwrap_or(DEFAULT_MAX_META_REFRESH_HOPS));
 
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "retain",
-        lua.create_function(|lua: &Lua, pattern: String| {
+        "formatNumber",
+        lua.create_function(|lua: &Lua, precision: usize| {
             let mut state = get_state::<H>(lua)?;
 
-            state.scraper = state
-                .scraper
-                .retain(&substitute_variables(&pattern, &state.variables)?)?;
-
+            state.scraper = state.scraper.format_number(precision);
             Ok(())
         })?,
     )?;
 
-    let effect_sender_for_run_fn = UnboundedSender::clone(&effect_sender);
-    let script_loader_for_run_fn = Arc::clone(&script_loader);
-
     lua.globals().set(
-        "run",
+        "get",
         lua.create_async_function(
-            move |lua: Lua, (name, args_table): (String, Option<LuaTable>)| {
-                let effect_sender_inner = UnboundedSender::clone(&effect_sender_for_run_fn);
-                let script_loader_inner = Arc::clone(&script_loader_for_run_fn);
+            |lua: Lua, (url, options): (String, Option<LuaTable>)| async move {
+                let options = GetOptions::from_table(options)?;
 
-                async move {
-                    let (args, kwargs, mut new_results) = {
-                        let state = get_state::<H>(&lua)?;
-                        let mut args: Vec<String> = vec![];
-                        let mut kwargs: HashMap<String, String> = HashMap::new();
-
-                        if let Some(args_table) = args_table {
-                            for i in 1..100 {
-                                if let Ok(value) = args_table.get::<String>(i) {
-                                    args.push(substitute_variables(&value, &state.variables)?);
-                                }
-                            }
-
-                            for (key, value) in args_table.pairs::<String, String>().flatten() {
-                                if !key.chars().all(|ch| ch.is_ascii_digit()) {
-                                    kwargs.insert(
-                                        key,
-                                        substitute_variables(&value, &state.variables)?,
-                                    );
-                                }
-                            }
-                        }
+                let (scraper, url_subst) = {
+                    let state = get_state::<H>(&lua)?;
+                    (
+                        state.scraper.clone(),
+                        &substitute_variables(&url, &state.variables)?,
+                    )
+                };
 
-                        if args.is_empty() {
-                            args.extend(state.scraper.results().iter().cloned());
-                        }
+                let updated_scraper = options.get(scraper, url_subst).await?;
+
+                let mut state = get_state::<H>(&lua)?;
+                state.scraper = updated_scraper;
+
+                Ok(())
+            },
+        )?,
+    )?;
 
-                        (args, kwargs, state.scraper.results().clone())
+    lua.globals().set(
+        "getAll",
+        lua.create_async_function(
+            |lua: Lua, (urls, max_concurrent): (LuaValue, Option<usize>)| async move {
+                let (scraper, urls) = {
+                    let state = get_state::<H>(&lua)?;
+
+                    let urls = match urls {
+                        LuaValue::String(name) => {
+                            let name = name.to_str()?.to_string();
+
+                            state
+                                .variables
+                                .get(&name)
+                                .ok_or_else(|| Error::VariableNotFoundError(name.clone()))?
+                                .iter()
+                                .cloned()
+                                .collect::<Vec<_>>()
+                        }
+                        LuaValue::Table(table) => table
+                            .sequence_values::<String>()
+                            .map(|url| substitute_variables(&url?, &state.variables))
+                            .collect::<Result<Vec<_>, Error>>()?,
+                        other => {
+                            return Err(Error::LuaError(format!(
+                                "`getAll` expects a variable name or a table of URLs, got `{}`",
+                                other.type_name()
+                            ))
+                            .into_lua_err());
+                        }
                     };
 
-                    let inner_results = Box::pin(run::<H>(
-                        &name,
-                        args,
-                        kwargs,
-                        script_loader_inner,
-                        effect_sender_inner,
-                    ))
-                    .await;
+                    (state.scraper.clone(), urls)
+                };
 
-                    match inner_results {
-                        Ok(results) => {
-                            new_results.append(results);
+                let updated_scraper = scraper
+                    .get_all(
+                        &urls,
+                        max_concurrent.unwrap_or(DEFAULT_GET_ALL_MAX_CONCURRENT),
+                    )
+                    .await?;
 
-                            let mut state = get_state::<H>(&lua)?;
-                            state.scraper = state.scraper.clone().with_results(new_results);
+                let mut state = get_state::<H>(&lua)?;
+                state.scraper = updated_scraper;
 
-                            Ok(())
-                        }
-                        Err(e) => Err(e.into_lua_err()),
-                    }
-                }
+                Ok(())
             },
         )?,
     )?;
 
     lua.globals().set(
-        "store",
-        lua.create_function(|lua: &Lua, name: String| {
-            let mut state = get_state::<H>(lua)?;
-            let results = state.scraper.results().clone();
+        "getPaginated",
+        lua.create_async_function(|lua: Lua, (url, max_pages): (String, usize)| async move {
+            let (scraper, url_subst) = {
+                let state = get_state::<H>(&lua)?;
+                (
+                    state.scraper.clone(),
+                    &substitute_variables(&url, &state.variables)?,
+                )
+            };
+
+            let updated_scraper = scraper.get_paginated(url_subst, max_pages).await?;
+
+            let mut state = get_state::<H>(&lua)?;
+            state.scraper = updated_scraper;
 
-            state.variables.insert(name, results);
             Ok(())
         })?,
     )?;
 
     lua.globals().set(
-        "var",
-        lua.create_function(|lua: &Lua, name: String| {
-            get_state::<H>(lua)?
-                .variables
-                .get(&name)
-                .map(|v| v.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" "))
-                .ok_or_else(|| {
-                    error!("variable `{name}` not found");
-                    Error::LuaError(format!("variable `{name}` not found")).into_lua_err()
-                })
+        "header",
+        lua.create_function(|lua: &Lua, (key, value): (String, String)| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .set_header(key, substitute_variables(&value, &state.variables)?);
+
+            Ok(())
         })?,
     )?;
 
-    Ok(lua)
-}
+    lua.globals().set(
+        "headersFromVar",
+        lua.create_function(|lua: &Lua, name: String| {
+            let mut state = get_state::<H>(lua)?;
 
-fn is_interruption(error: &LuaError) -> bool {
-    if let LuaError::CallbackError { cause, .. } = error
-        && let LuaError::ExternalError(inner_error) = cause.deref()
-    {
-        return inner_error.downcast_ref::<InterruptedError>().is_some();
-    }
+            let stored = state.variables.get(&name).ok_or_else(|| {
+                error!("variable `{name}` not found");
+                Error::LuaError(format!("variable `{name}` not found")).into_lua_err()
+            })?;
 
-    false
-}
+            let headers = parse_header_lines(stored)?;
 
-pub type ScriptLoaderPointer = Arc<RwLock<dyn Fn(&str) -> Result<String, Error> + Send + Sync>>;
+            for (key, value) in headers {
+                state.scraper = state.scraper.set_header(key, value);
+            }
 
-pub async fn run<H: HttpDriver + Send + Sync + 'static>(
-    script_name: &str,
-    args: Vec<String>,
-    kwargs: HashMap<String, String>,
-    script_loader: ScriptLoaderPointer,
-    effect_sender: UnboundedSender<EffectInvocation>,
-) -> Result<Vector<String>, Error> {
-    let lua_code = {
-        let locked_loader_fn = script_loader
-            .read()
-            .map_err(|_| Error::ScriptLoaderLockingError)?;
+            Ok(())
+        })?,
+    )?;
 
-        locked_loader_fn(script_name)?
+    lua.globals().set(
+        "htmlDecode",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
 
-        // Lock dropped here
-    };
+            state.scraper = state.scraper.html_decode();
+            Ok(())
+        })?,
+    )?;
 
-    let lua = create_lua_context::<H>(args, kwargs, effect_sender, script_loader)?;
+    lua.globals().set(
+        "init",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
 
-    if let Err(e) = lua.load(lua_code).exec_async().await
-        && !is_interruption(&e)
-    {
-        return Err(e.into());
-    }
+            state.scraper = state.scraper.init();
+            Ok(())
+        })?,
+    )?;
 
-    Ok({
-        // Workaround for "temporary dropped while borrowed"
+    lua.globals().set(
+        "join",
+        lua.create_function(|lua: &Lua, separator: String| {
+            let mut state = get_state::<H>(lua)?;
 
-        get_state::<H>(&lua)?.scraper.results().clone()
-    })
-}
+            state.scraper = state
+                .scraper
+                .join(&substitute_variables(&separator, &state.variables)?);
+            Ok(())
+        })?,
+    )?;
 
-#[cfg(test)]
-mod tests {
-    use tokio::sync::mpsc::unbounded_channel;
+    lua.globals().set(
+        "jsonCanonical",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
 
-    use crate::{
-        scraper::NullHttpDriver,
-        testutils::{HeaderTestHttpDriver, TestHttpDriver},
-    };
+            state.scraper = state.scraper.json_canonical()?;
+            Ok(())
+        })?,
+    )?;
 
-    use super::*;
+    lua.globals().set(
+        "jsonDecode",
+        lua.create_function(|lua: &Lua, text: String| {
+            let json = text
+                .parse::<JsonValue>()
+                .map_err(|e| Error::JsonParseError(e.to_string()))?;
+
+            json_to_lua(lua, &json)
+        })?,
+    )?;
+
+    lua.globals().set(
+        "jsonEncode",
+        lua.create_function(|_lua: &Lua, value: LuaValue| Ok(lua_to_json(&value, 0)?.to_string()))?,
+    )?;
+
+    lua.globals().set(
+        "jsonFilter",
+        lua.create_function(|lua: &Lua, expr: String| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .json_filter(&substitute_variables(&expr, &state.variables)?)?;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "jsonLd",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.json_ld()?;
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "jsonPath",
+        lua.create_function(|lua: &Lua, expr: String| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .jsonpath(&substitute_variables(&expr, &state.variables)?)?;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "list",
+        lua.create_function(|lua: &Lua, name: String| {
+            get_state::<H>(lua)?
+                .variables
+                .get(&name)
+                .map(|v| v.iter().cloned().collect::<Vec<_>>())
+                .ok_or_else(|| {
+                    error!("variable `{name}` not found");
+                    Error::LuaError(format!("variable `{name}` not found")).into_lua_err()
+                })
+        })?,
+    )?;
+
+    lua.globals().set(
+        "load",
+        lua.create_function(|lua: &Lua, name: String| {
+            let mut state = get_state::<H>(lua)?;
+            let mut results = state.scraper.results().clone();
+
+            let stored = state.variables.get(&name).ok_or_else(|| {
+                error!("variable `{name}` not found");
+                Error::LuaError(format!("variable `{name}` not found")).into_lua_err()
+            })?;
+
+            results.extend(stored.iter().cloned());
+            state.scraper = state.scraper.clone().with_results(results);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "log",
+        lua.create_function(|lua: &Lua, message: String| {
+            let state = get_state::<H>(lua)?;
+            let message = substitute_variables(&message, &state.variables)?;
+
+            match &state.script_name {
+                Some(script_name) => debug!("scrapelang::log: ({script_name}) {message}"),
+                None => debug!("scrapelang::log: {message}"),
+            }
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "lowercase",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.to_lowercase();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "map",
+        lua.create_function(|lua: &Lua, f: LuaFunction| {
+            // We don't want to hold a borrow to the state while applying the function
+            let results = {
+                let state = get_state::<H>(lua)?;
+                state.scraper.results().clone()
+            };
+
+            let mapped = Vector::from(
+                results
+                    .into_iter()
+                    .map(|s| f.call::<String>(s))
+                    .collect::<Result<Vec<_>, mlua::Error>>()?,
+            );
+
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.clone().with_results(mapped);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "markdownTable",
+        lua.create_function(|lua: &Lua, columns: LuaVariadic<String>| {
+            let mut state = get_state::<H>(lua)?;
+
+            let columns = columns
+                .iter()
+                .map(|column| substitute_variables(column, &state.variables))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            state.scraper = state.scraper.markdown_table(&columns)?;
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "maxBodySize",
+        lua.create_function(|lua: &Lua, bytes: Option<usize>| {
+            let mut state = get_state::<H>(lua)?;
+
+            let max_body_size = match bytes {
+                None => DEFAULT_MAX_BODY_SIZE,
+                Some(0) => return Err(Error::ValueOutOfRangeError.into_lua_err()),
+                Some(bytes) => bytes,
+            };
+
+            state.scraper = state.scraper.set_max_body_size(max_body_size);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "memoryCache",
+        lua.create_function(|lua: &Lua, enabled: Option<bool>| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .set_memory_cache(enabled.unwrap_or(true).then(MemoryCache::new));
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "normalizeWhitespace",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.normalize_whitespace();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "now",
+        lua.create_function(|lua: &Lua, format: Option<String>| {
+            let state = get_state::<H>(lua)?;
+            let now = state.fixed_now.unwrap_or_else(Local::now);
+
+            Ok(format_time(
+                &now,
+                format.as_deref().unwrap_or(DEFAULT_TIME_FORMAT),
+            )?)
+        })?,
+    )?;
+
+    lua.globals().set(
+        "nth",
+        lua.create_function(|lua: &Lua, index: usize| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.nth(index);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "parseNumber",
+        lua.create_function(|lua: &Lua, decimal_separator: Option<String>| {
+            let mut state = get_state::<H>(lua)?;
+
+            let decimal_separator = match decimal_separator.as_deref() {
+                None => '.',
+                Some(separator) if separator.chars().count() == 1 => {
+                    separator.chars().next().unwrap()
+                }
+                Some(separator) => {
+                    return Err(Error::ParseError(format!(
+                        "Invalid decimal separator `{separator}`, expected `.` or `,`"
+                    ))
+                    .into_lua_err());
+                }
+            };
+
+            state.scraper = state.scraper.parse_number(decimal_separator)?;
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "partition",
+        lua.create_function(
+            |lua: &Lua, (pattern, matched_name, unmatched_name): (String, String, String)| {
+                let mut state = get_state::<H>(lua)?;
+
+                let (matched, unmatched) = state
+                    .scraper
+                    .partition(&substitute_variables(&pattern, &state.variables)?)?;
+
+                state.variables.insert(matched_name, matched);
+                state.variables.insert(unmatched_name, unmatched);
+
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "politeDelay",
+        lua.create_function(|lua: &Lua, millis: u64| {
+            let mut state = get_state::<H>(lua)?;
+
+            let polite_delay = match millis {
+                0 => None,
+                millis => Some(PoliteDelay::new(Duration::from_millis(millis))),
+            };
+
+            state.scraper = state.scraper.set_polite_delay(polite_delay);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "postMultipart",
+        lua.create_async_function(
+            |lua: Lua,
+             (url, fields, files): (
+                String,
+                HashMap<String, String>,
+                Option<HashMap<String, String>>,
+            )| async move {
+                let (scraper, url_subst, form) = {
+                    let state = get_state::<H>(&lua)?;
+
+                    let substitute_all = |table: HashMap<String, String>| {
+                        let mut pairs = table
+                            .into_iter()
+                            .map(|(name, value)| {
+                                Ok((name, substitute_variables(&value, &state.variables)?))
+                            })
+                            .collect::<Result<Vec<_>, Error>>()?;
+
+                        pairs.sort();
+                        Ok::<_, Error>(pairs)
+                    };
+
+                    (
+                        state.scraper.clone(),
+                        substitute_variables(&url, &state.variables)?,
+                        MultipartForm {
+                            fields: substitute_all(fields)?,
+                            files: substitute_all(files.unwrap_or_default())?,
+                        },
+                    )
+                };
+
+                let updated_scraper = scraper.post_multipart(&url_subst, &form).await?;
+
+                let mut state = get_state::<H>(&lua)?;
+                state.scraper = updated_scraper;
+
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "prepend",
+        lua.create_function(|lua: &Lua, text: String| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .prepend(&substitute_variables(&text, &state.variables)?);
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "proxy",
+        lua.create_function(|lua: &Lua, proxies: LuaVariadic<String>| {
+            let mut state = get_state::<H>(lua)?;
+
+            let proxies = proxies
+                .iter()
+                .map(|proxy| substitute_variables(proxy, &state.variables))
+                .collect::<Result<Vector<_>, _>>()?;
+
+            state.scraper = state.scraper.set_proxies(proxies);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "random",
+        lua.create_function(|lua: &Lua, n: usize| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.random(n);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "request",
+        lua.create_async_function(
+            |lua: Lua, (method, url, body): (String, String, Option<String>)| async move {
+                let (scraper, method, url_subst, body_subst) = {
+                    let state = get_state::<H>(&lua)?;
+
+                    (
+                        state.scraper.clone(),
+                        method.parse::<HttpMethod>()?,
+                        substitute_variables(&url, &state.variables)?,
+                        body.map(|body| substitute_variables(&body, &state.variables))
+                            .transpose()?,
+                    )
+                };
+
+                let updated_scraper = scraper
+                    .request(method, &url_subst, body_subst.as_deref())
+                    .await?;
+
+                let mut state = get_state::<H>(&lua)?;
+                state.scraper = updated_scraper;
+
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "resolve",
+        lua.create_function(|lua: &Lua, (host, addr): (String, Option<String>)| {
+            let mut state = get_state::<H>(lua)?;
+
+            let host = substitute_variables(&host, &state.variables)?;
+            let addr = addr
+                .map(|addr| parse_resolve_addr(&substitute_variables(&addr, &state.variables)?))
+                .transpose()?;
+
+            state.scraper = state.scraper.set_resolve(&host, addr);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "retain",
+        lua.create_function(|lua: &Lua, pattern: String| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .retain(&substitute_variables(&pattern, &state.variables)?)?;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "retryIfEmpty",
+        lua.create_async_function(|lua: Lua, (times, millis): (usize, u64)| async move {
+            let scraper = get_state::<H>(&lua)?.scraper.clone();
+
+            let updated_scraper = scraper
+                .retry_if_empty(times, Duration::from_millis(millis))
+                .await?;
+
+            let mut state = get_state::<H>(&lua)?;
+            state.scraper = updated_scraper;
+
+            Ok(())
+        })?,
+    )?;
+
+    let effect_sender_for_run_fn = UnboundedSender::clone(&effect_sender);
+    let script_loader_for_run_fn = Arc::clone(&script_loader);
+    let effects_for_run_fn = effects.clone();
+
+    lua.globals().set(
+        "robots",
+        lua.create_function(|lua: &Lua, enabled: Option<bool>| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .set_robots(enabled.unwrap_or(true).then(RobotsCache::new));
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "run",
+        lua.create_async_function(
+            move |lua: Lua, (name, args_table): (String, Option<LuaTable>)| {
+                let effect_sender_inner = UnboundedSender::clone(&effect_sender_for_run_fn);
+                let script_loader_inner = Arc::clone(&script_loader_for_run_fn);
+                let effects_inner = effects_for_run_fn.clone();
+
+                async move {
+                    let (args, kwargs, options, mut new_results) = {
+                        let state = get_state::<H>(&lua)?;
+                        let (args, kwargs) = args_from_table(args_table, &state)?;
+
+                        // Runs share the rate limit, memory cache, allowed environment variables
+                        // and effects of the script running them
+                        let options = RunOptions {
+                            rate_limiter: state.scraper.rate_limiter().cloned(),
+                            memory_cache: state.scraper.memory_cache().cloned(),
+                            allowed_env: Some(state.allowed_env.clone()),
+                            effects: effects_inner,
+                            ..RunOptions::default()
+                        };
+
+                        (args, kwargs, options, state.scraper.results().clone())
+                    };
+
+                    let inner_results = Box::pin(run_with_options::<H>(
+                        &name,
+                        args,
+                        kwargs,
+                        options,
+                        script_loader_inner,
+                        effect_sender_inner,
+                    ))
+                    .await;
+
+                    match inner_results {
+                        Ok(results) => {
+                            new_results.append(results);
+
+                            let mut state = get_state::<H>(&lua)?;
+                            state.scraper = state.scraper.clone().with_results(new_results);
+
+                            Ok(())
+                        }
+                        Err(e) => Err(e.into_lua_err()),
+                    }
+                }
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "scan",
+        lua.create_function(
+            |lua: &Lua, (pattern, names): (String, LuaVariadic<String>)| {
+                let mut state = get_state::<H>(lua)?;
+
+                let groups = state.scraper.scan(
+                    &substitute_variables(&pattern, &state.variables)?,
+                    names.len(),
+                )?;
+
+                for (name, group) in names.into_iter().zip(groups) {
+                    state.variables.insert(name, group);
+                }
+
+                Ok(())
+            },
+        )?,
+    )?;
+
+    lua.globals().set(
+        "seed",
+        lua.create_function(|lua: &Lua, seed: u64| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.set_seed(seed);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "sitemapUrls",
+        lua.create_async_function(|lua: Lua, ()| async move {
+            let scraper = get_state::<H>(&lua)?.scraper.clone();
+            let updated_scraper = scraper.sitemap_urls().await?;
+
+            let mut state = get_state::<H>(&lua)?;
+            state.scraper = updated_scraper;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "sleep",
+        lua.create_async_function(|_lua: Lua, millis: u64| async move {
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "sortByNumber",
+        lua.create_function(|lua: &Lua, pattern: String| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .sort_by_number(&substitute_variables(&pattern, &state.variables)?)?;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "split",
+        lua.create_function(|lua: &Lua, (pattern, drop_empty): (String, Option<bool>)| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.split(
+                &substitute_variables(&pattern, &state.variables)?,
+                drop_empty.unwrap_or(false),
+            )?;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "stats",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.stats();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "store",
+        lua.create_function(|lua: &Lua, name: String| {
+            let mut state = get_state::<H>(lua)?;
+            let results = state.scraper.results().clone();
+
+            state.variables.insert(name, results);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "tail",
+        lua.create_function(|lua: &Lua, n: usize| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.tail(n);
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "timeBudget",
+        lua.create_function(|lua: &Lua, seconds: f64| {
+            let mut state = get_state::<H>(lua)?;
+
+            let time_budget =
+                Duration::try_from_secs_f64(seconds).map_err(|_| Error::ValueOutOfRangeError)?;
+
+            state.scraper = state
+                .scraper
+                .set_time_budget((!time_budget.is_zero()).then_some(time_budget));
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "timeBetween",
+        lua.create_function(|lua: &Lua, (start, end): (String, String)| {
+            let state = get_state::<H>(lua)?;
+            let start = parse_time_of_day(&substitute_variables(&start, &state.variables)?)?;
+            let end = parse_time_of_day(&substitute_variables(&end, &state.variables)?)?;
+            let now = state.fixed_now.unwrap_or_else(Local::now);
+
+            if is_time_between_at(start, end, now.time()) {
+                Ok(())
+            } else {
+                Err(LuaError::ExternalError(Arc::new(InterruptedError {})))
+            }
+        })?,
+    )?;
+
+    lua.globals().set(
+        "topN",
+        lua.create_function(|lua: &Lua, (pattern, count): (String, usize)| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state
+                .scraper
+                .top_n(&substitute_variables(&pattern, &state.variables)?, count)?;
+
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "trim",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.trim();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "unique",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.unique();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "uniqueCaseInsensitive",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.unique_case_insensitive();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "uppercase",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.to_uppercase();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "urlDecode",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.url_decode();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "urlEncode",
+        lua.create_function(|lua: &Lua, ()| {
+            let mut state = get_state::<H>(lua)?;
+
+            state.scraper = state.scraper.url_encode();
+            Ok(())
+        })?,
+    )?;
+
+    lua.globals().set(
+        "var",
+        lua.create_function(|lua: &Lua, name: String| {
+            get_state::<H>(lua)?
+                .variables
+                .get(&name)
+                .map(|v| v.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" "))
+                .ok_or_else(|| {
+                    error!("variable `{name}` not found");
+                    Error::LuaError(format!("variable `{name}` not found")).into_lua_err()
+                })
+        })?,
+    )?;
+
+    Ok(lua)
+}
+
+fn is_interruption(error: &LuaError) -> bool {
+    if let LuaError::CallbackError { cause, .. } = error
+        && let LuaError::ExternalError(inner_error) = cause.deref()
+    {
+        return inner_error.downcast_ref::<InterruptedError>().is_some();
+    }
+
+    false
+}
+
+pub type ScriptLoaderPointer = Arc<RwLock<dyn Fn(&str) -> Result<String, Error> + Send + Sync>>;
+
+/// Check `script` for syntax errors without running it, reporting the first error found.
+pub fn validate(script: &str) -> Result<(), Error> {
+    match Lua::new().load(script).set_name("=script").into_function() {
+        Ok(_) => Ok(()),
+        Err(LuaError::SyntaxError { message, .. }) => {
+            let position =
+                Regex::new(r"(?s)^script:(\d+): (.*)$").expect("Should be a valid regex");
+
+            match position.captures(&message) {
+                Some(captures) => {
+                    let line = captures[1].parse()?;
+
+                    Err(Error::SyntaxError(
+                        line,
+                        format!(
+                            "{}\n{}",
+                            &captures[2],
+                            syntax_error_context(script, line, &captures[2])
+                        ),
+                    ))
+                }
+                None => Err(Error::ParseError(message)),
+            }
+        }
+        Err(e) => Err(Error::LuaError(e.to_string())),
+    }
+}
+
+/// The text of line `line` (counting from 1) of `script`, underlined with a caret at the token
+/// named in a Lua syntax error message such as `unexpected symbol near '='`, or at the end of the
+/// line if the message names no token found on the line, boxed like cron spec errors.
+fn syntax_error_context(script: &str, line: usize, message: &str) -> String {
+    let text = script
+        .lines()
+        .nth(line.saturating_sub(1))
+        .unwrap_or_default()
+        .trim_end();
+
+    let near = Regex::new(r"near '(.+)'$").expect("Should be a valid regex");
+
+    let column = near
+        .captures(message)
+        .and_then(|captures| text.find(&captures[1]))
+        .unwrap_or(text.len());
+
+    format!(
+        r#"-------------------------------
+{text}
+{}^
+-------------------------------
+"#,
+        " ".repeat(text[..column].chars().count())
+    )
+}
+
+pub async fn run<H: HttpDriver + Send + Sync + 'static>(
+    script_name: &str,
+    args: Vec<String>,
+    kwargs: HashMap<String, String>,
+    script_loader: ScriptLoaderPointer,
+    effect_sender: UnboundedSender<EffectInvocation>,
+) -> Result<Vector<String>, Error> {
+    run_with_options::<H>(
+        script_name,
+        args,
+        kwargs,
+        RunOptions::default(),
+        script_loader,
+        effect_sender,
+    )
+    .await
+}
+
+/// Like [run], but with the given HTTP headers set before the script starts, as if set using
+/// `header()` at the start of the script.
+pub async fn run_with_headers<H: HttpDriver + Send + Sync + 'static>(
+    script_name: &str,
+    args: Vec<String>,
+    kwargs: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    script_loader: ScriptLoaderPointer,
+    effect_sender: UnboundedSender<EffectInvocation>,
+) -> Result<Vector<String>, Error> {
+    run_with_options::<H>(
+        script_name,
+        args,
+        kwargs,
+        RunOptions {
+            headers,
+            ..RunOptions::default()
+        },
+        script_loader,
+        effect_sender,
+    )
+    .await
+}
+
+/// Settings applied to the scraper before a script starts.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// HTTP headers set as if using `header()` at the start of the script.
+    pub headers: HashMap<String, String>,
+    /// A rate limiter awaited before each request made by the script, possibly shared with
+    /// other runs.
+    pub rate_limiter: Option<RateLimiter>,
+    /// An in-memory cache of responses used by the script, possibly shared with other runs.
+    pub memory_cache: Option<MemoryCache>,
+    /// Addresses to connect to for the given hosts, set as if using `resolve()` at the start of
+    /// the script.
+    pub resolve: HashMap<String, SocketAddr>,
+    /// The environment variables readable using `env()`, as names or prefixes followed by `*`,
+    /// or None for [DEFAULT_ALLOWED_ENV].
+    pub allowed_env: Option<Vec<String>>,
+    /// The file of a cookie jar opened as if using `cookieJar()` at the start of the script.
+    pub cookie_jar: Option<String>,
+    /// The effects available to the script using `effectResult`, none by default.
+    pub effects: EffectRegistry,
+}
+
+/// Like [run], but with the given [RunOptions] applied before the script starts.
+pub async fn run_with_options<H: HttpDriver + Send + Sync + 'static>(
+    script_name: &str,
+    args: Vec<String>,
+    kwargs: HashMap<String, String>,
+    options: RunOptions,
+    script_loader: ScriptLoaderPointer,
+    effect_sender: UnboundedSender<EffectInvocation>,
+) -> Result<Vector<String>, Error> {
+    let lua_code = {
+        let locked_loader_fn = script_loader
+            .read()
+            .map_err(|_| Error::ScriptLoaderLockingError)?;
+
+        locked_loader_fn(script_name)?
+
+        // Lock dropped here
+    };
+
+    let lua = create_lua_context::<H>(args, kwargs, effect_sender, script_loader, options.effects)?;
+
+    {
+        let mut state = get_state::<H>(&lua)?;
+        state.script_name = Some(script_name.to_string());
+
+        for (key, value) in options.headers {
+            state.scraper = state.scraper.set_header(key, value);
+        }
+
+        for (host, addr) in options.resolve {
+            state.scraper = state.scraper.set_resolve(&host, Some(addr));
+        }
+
+        if let Some(allowed_env) = options.allowed_env {
+            state.allowed_env = allowed_env;
+        }
+
+        if let Some(path) = options.cookie_jar {
+            state.scraper = state.scraper.set_cookie_jar(Some(CookieJar::open(&path)?));
+        }
+
+        state.scraper = state
+            .scraper
+            .set_rate_limiter(options.rate_limiter)
+            .set_memory_cache(options.memory_cache);
+    }
+
+    if let Err(e) = lua.load(lua_code).exec_async().await
+        && !is_interruption(&e)
+    {
+        return Err(e.into());
+    }
+
+    Ok({
+        // Workaround for "temporary dropped while borrowed"
+
+        get_state::<H>(&lua)?.scraper.results().clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use flagset::FlagSet;
+
+    use crate::{
+        effect::{EffectArgs, EffectKwArgs},
+        scraper::NullHttpDriver,
+        testutils::{
+            ContentTypeTestHttpDriver, FlakyTestHttpDriver, HeaderTestHttpDriver,
+            MethodTestHttpDriver, MultipartTestHttpDriver, ProxyTestHttpDriver,
+            ResolveTestHttpDriver, RobotsTestHttpDriver, SlowTestHttpDriver, TestHttpDriver,
+            flaky_fetches,
+        },
+    };
+
+    use super::*;
 
     macro_rules! results {
         ($($str:expr),*) => {
@@ -566,402 +2059,2934 @@ mod tests {
         };
     }
 
-    macro_rules! lua_call {
-        ($lua:ident, $fname:expr, $args:expr => $ret:ty) => {
-            $lua.globals()
-                .get::<LuaFunction>($fname)
-                .unwrap()
-                .call::<$ret>($args)
-                .unwrap()
-        };
+    macro_rules! lua_call {
+        ($lua:ident, $fname:expr, $args:expr => $ret:ty) => {
+            $lua.globals()
+                .get::<LuaFunction>($fname)
+                .unwrap()
+                .call::<$ret>($args)
+                .unwrap()
+        };
+    }
+
+    macro_rules! lua_run_async {
+        ($lua:ident, $script:expr) => {
+            $lua.load($script).exec_async().await
+        };
+    }
+
+    fn null_script_loader_inner(_name: &str) -> Result<String, Error> {
+        Err(Error::JobNotFoundError)
+    }
+
+    fn null_script_loader() -> ScriptLoaderPointer {
+        Arc::new(RwLock::new(null_script_loader_inner))
+    }
+
+    #[test]
+    fn test_substitute_variables_no_vars() {
+        assert_eq!(substitute_variables("", &HashMap::new()).unwrap(), "");
+        assert_eq!(
+            substitute_variables("hello world", &HashMap::new()).unwrap(),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_missing_var() {
+        assert!(
+            substitute_variables("{x}", &HashMap::new())
+                .is_err_and(|e| matches!(e, Error::VariableNotFoundError(_)))
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_default() {
+        let variables = HashMap::from([
+            ("limit".to_string(), results!["25"]),
+            ("empty".to_string(), results![""]),
+        ]);
+
+        // Present variables ignore the default
+        assert_eq!(
+            substitute_variables("{limit:10}", &variables).unwrap(),
+            "25"
+        );
+        assert_eq!(substitute_variables("{empty:10}", &variables).unwrap(), "");
+
+        // Absent variables use the default
+        assert_eq!(
+            substitute_variables("?page={page:1}&limit={limit:10}", &variables).unwrap(),
+            "?page=1&limit=25"
+        );
+        assert_eq!(
+            substitute_variables("{sort:name:asc}", &variables).unwrap(),
+            "name:asc"
+        );
+
+        // Empty default
+        assert_eq!(substitute_variables("[{page:}]", &variables).unwrap(), "[]");
+
+        assert!(
+            substitute_variables("{page}", &variables)
+                .is_err_and(|e| matches!(e, Error::VariableNotFoundError(name) if name == "page"))
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_index() {
+        let variables = HashMap::from([
+            ("names".to_string(), results!["Alice", "Bob", "Charlie"]),
+            ("empty".to_string(), results![]),
+        ]);
+
+        assert_eq!(
+            substitute_variables("{names[1]} and {names[3]}", &variables).unwrap(),
+            "Alice and Charlie"
+        );
+
+        // The whole variable is still joined
+        assert_eq!(
+            substitute_variables("{names}", &variables).unwrap(),
+            "AliceBobCharlie"
+        );
+
+        // Out of range
+        for text in ["{names[0]}", "{names[4]}", "{empty[1]}"] {
+            assert!(
+                substitute_variables(text, &variables)
+                    .is_err_and(|e| matches!(e, Error::VariableIndexOutOfRangeError(..)))
+            );
+        }
+
+        // Defaults apply to missing variables and indices out of range
+        assert_eq!(
+            substitute_variables("{names[4]:Dave} {names[2]:Dave} {x[1]:Eve}", &variables).unwrap(),
+            "Dave Bob Eve"
+        );
+
+        assert!(
+            substitute_variables("{x[1]}", &variables)
+                .is_err_and(|e| matches!(e, Error::VariableNotFoundError(name) if name == "x"))
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_multiple() {
+        let variables = HashMap::from([
+            ("x1".to_string(), results!["1"]),      // Result gets shorter
+            ("x2".to_string(), results!["2345"]),   // Result stays same length
+            ("x3".to_string(), results!["678912"]), // Result gets longer
+            ("$bar".to_string(), results![""]),
+        ]);
+
+        assert!(
+            substitute_variables("{x1}{x2}{x3}", &variables).is_ok_and(|result| {
+                assert_eq!(result, "12345678912");
+                true
+            })
+        );
+
+        assert!(
+            substitute_variables("{x1} {x2} {x3}", &variables).is_ok_and(|result| {
+                assert_eq!(result, "1 2345 678912");
+                true
+            })
+        );
+
+        assert!(
+            substitute_variables("{x1} {x3} {x2}", &variables).is_ok_and(|result| {
+                assert_eq!(result, "1 678912 2345");
+                true
+            })
+        );
+
+        assert!(
+            substitute_variables("{x2} {x1} {x3}", &variables).is_ok_and(|result| {
+                assert_eq!(result, "2345 1 678912");
+                true
+            })
+        );
+
+        assert!(
+            substitute_variables("{x2} {x3} {x1}", &variables).is_ok_and(|result| {
+                assert_eq!(result, "2345 678912 1");
+                true
+            })
+        );
+
+        assert!(
+            substitute_variables("{x3} {x1} {x2}", &variables).is_ok_and(|result| {
+                assert_eq!(result, "678912 1 2345");
+                true
+            })
+        );
+
+        assert!(
+            substitute_variables("{x3} {x2} {x1}", &variables).is_ok_and(|result| {
+                assert_eq!(result, "678912 2345 1");
+                true
+            })
+        );
+
+        assert!(
+            substitute_variables("x1 {x1} foo {x2} bar {$bar} {x3} baz {x1}", &variables)
+                .is_ok_and(|result| {
+                    assert_eq!(result, "x1 1 foo 2345 bar  678912 baz 1");
+                    true
+                })
+        );
+    }
+
+    #[test]
+    fn test_substitute_variables_escaped_braces() {
+        let variables = HashMap::from([
+            ("x1".to_string(), results!["1"]),      // Result gets shorter
+            ("x2".to_string(), results!["2345"]),   // Result stays same length
+            ("x3".to_string(), results!["678912"]), // Result gets longer
+        ]);
+
+        assert!(
+            substitute_variables("x1 {x1} {{x1}} {{ x1 {{ foo bar }} }}", &variables).is_ok_and(
+                |result| {
+                    assert_eq!(result, "x1 1 {x1} { x1 { foo bar } }");
+                    true
+                }
+            )
+        );
+
+        assert!(
+            substitute_variables("x2 {x2} {{x2}} {{ x2 {{ foo bar }} }}", &variables).is_ok_and(
+                |result| {
+                    assert_eq!(result, "x2 2345 {x2} { x2 { foo bar } }");
+                    true
+                }
+            )
+        );
+
+        assert!(
+            substitute_variables("x3 {x3} {{x3}} {{ x3 {{ foo bar }} }}", &variables).is_ok_and(
+                |result| {
+                    assert_eq!(result, "x3 678912 {x3} { x3 { foo bar } }");
+                    true
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_create_lua_context_get_and_set_state() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<NullHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        {
+            let mut state = get_state::<NullHttpDriver>(&lua).unwrap();
+
+            state.scraper = state.scraper.clone().with_results(results!["hello"]);
+
+            state
+                .variables
+                .insert("test".to_string(), results!["world"]);
+        }
+
+        let state = get_state::<NullHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello"]);
+        assert_eq!(state.variables.get("test"), Some(&results!["world"]));
+    }
+
+    #[tokio::test]
+    async fn test_lua_abort_if_empty() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                abortIfEmpty()
+                effect("print", { "hello" })
+                get("string://test")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results![]);
+
+        effect_rx.close();
+
+        assert!(effect_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lua_abort_unless_match() {
+        for (pattern, expected_results, expected_effects) in [
+            ("OUT OF STOCK", results!["Catnip: IN STOCK"], 0),
+            ("IN STOCK", results!["Catnip: IN STOCK", "notified"], 1),
+            ("{status}", results!["Catnip: IN STOCK", "notified"], 1),
+        ] {
+            let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+
+            let lua = create_lua_context::<TestHttpDriver>(
+                vec![],
+                HashMap::new(),
+                effect_tx,
+                null_script_loader(),
+                EffectRegistry::new(),
+            )
+            .unwrap();
+
+            lua.globals().set("pattern", pattern).unwrap();
+
+            let result = lua_run_async!(
+                lua,
+                r#"
+                    get("string://IN STOCK")
+                    store("status")
+                    clear()
+                    get("string://Catnip: IN STOCK")
+                    abortUnlessMatch(pattern)
+                    effect("print", { "in stock" })
+                    get("string://notified")
+                "#
+            );
+
+            assert!(result.is_ok() || is_interruption(&result.unwrap_err()));
+
+            assert_eq!(
+                get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+                &expected_results
+            );
+
+            effect_rx.close();
+
+            let mut effects = 0;
+
+            while effect_rx.recv().await.is_some() {
+                effects += 1;
+            }
+
+            assert_eq!(effects, expected_effects);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lua_abort_if_match() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            null_script_loader(),
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let result = lua_run_async!(
+            lua,
+            r#"
+                get("string://Catnip: OUT OF STOCK")
+                abortUnlessMatch("IN STOCK")
+                effect("print", { "in stock" })
+                get("string://notified")
+            "#
+        );
+
+        assert!(is_interruption(&result.unwrap_err()));
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["Catnip: OUT OF STOCK"]
+        );
+
+        lua_run_async!(
+            lua,
+            r#"
+                abortIfMatch("SOLD OUT")
+                get("string://continued")
+            "#
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["Catnip: OUT OF STOCK", "continued"]
+        );
+
+        effect_rx.close();
+
+        assert!(effect_rx.recv().await.is_none());
+
+        let result = lua_run_async!(lua, r#"abortIfMatch("OUT OF STOCK")"#);
+
+        assert!(is_interruption(&result.unwrap_err()));
+        assert!(lua_run_async!(lua, r#"abortIfMatch("(")"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_time_between() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        get_state::<TestHttpDriver>(&lua).unwrap().fixed_now =
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 12, 30, 0).unwrap());
+
+        lua_run_async!(
+            lua,
+            r#"
+                timeBetween("11:30", "13:00")
+                get("string://inside")
+            "#
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["inside"]
+        );
+
+        let result = lua_run_async!(
+            lua,
+            r#"
+                get("string://14:30")
+                store("start")
+                timeBetween("{start[2]}", "15:00")
+                get("string://outside")
+            "#
+        );
+
+        assert!(is_interruption(&result.unwrap_err()));
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["inside", "14:30"]
+        );
+
+        assert!(lua_run_async!(lua, r#"timeBetween("9am", "5pm")"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_as_env() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get([[string://don't panic]])
+                get("string://42")
+                asEnv("ANSWER")
+            "#
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results![
+                r#"export ANSWER_1='don'\''t panic'"#,
+                "export ANSWER_2='42'"
+            ]
+        );
+
+        assert!(lua_run_async!(lua, r#"asEnv("NOT VALID")"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_as_json_object() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get([[string://say "hi"]])
+                asJsonObject("greeting")
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results![r#"{"greeting":"say \"hi\""}"#]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_append() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                append(" world")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello world"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_append_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://world!!")
+                store("varname")
+                clear()
+                get("string://hello")
+                append(" {varname}")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello world!!"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_apply() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                function process(results)
+                    table.insert(results, "a")
+                    table.insert(results, "b")
+                    return results
+                end
+
+                get("string://hello")
+                apply(process)
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_apply_using_variables_in_applied_fn() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                function process(results)
+                    table.insert(results, var("varname"))
+                    return results
+                end
+
+                get("string://hello")
+                store("varname")
+                clear()
+                get("string://world")
+                apply(process)
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["world", "hello"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_canonicalize_urls() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://https://a.test/post/?utm_source=feed")
+                get("string://https://a.test/post?fbclid=abc&ref=home")
+                get("string://https://a.test/post")
+                canonicalizeUrls()
+                store("default")
+                canonicalizeUrls(true, {"ref"})
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.variables.get("default").unwrap(),
+            &results![
+                "https://a.test/post",
+                "https://a.test/post?ref=home",
+                "https://a.test/post"
+            ]
+        );
+        assert_eq!(state.scraper.results(), &results!["https://a.test/post"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_changed_vs() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://alice")
+                get("string://bob")
+                store("before")
+                clear()
+                get("string://bob")
+                get("string://charlie")
+                changedVs("before")
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(state.scraper.results(), &results!["- alice", "+ charlie"]);
+        }
+
+        assert!(lua_run_async!(lua, r#"changedVs("missing")"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_clear() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                clear()
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results![]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_clearheaders() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<HeaderTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                header("User-Agent", "Mozilla/Firefox")
+                clearHeaders()
+                get("")
+            "#
+        );
+
+        let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["Headers({})"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_delete() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                delete("-")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results!["123456", "84985185844", "7868584"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_delete_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://-")
+                store("varname")
+                clear()
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                delete("{varname}4")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results!["12356", "84-9851-8584", "786---858"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_discard() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                discard("858")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["123-456"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_discard_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://-")
+                store("varname")
+                clear()
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                discard("{varname}{varname}858")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results!["123-456", "84-9851-858-44"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_drop() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                drop(2)
+            "#
+        );
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(state.scraper.results(), &results!["786---858-4"]);
+        }
+
+        lua_call!(lua, "drop", 200 => ());
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        assert_eq!(state.scraper.results(), &results![]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_effect() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"effect("notify", {"hello", "world", mode="default"})"#
+        );
+
+        assert!(effect_rx.recv().await.is_some_and(|invocation| {
+            assert_eq!(invocation.name(), "notify");
+            assert_eq!(
+                invocation.args(),
+                &vec!["hello".to_string(), "world".to_string()]
+            );
+            assert_eq!(
+                invocation.kwargs().get("mode"),
+                Some(&"default".to_string())
+            );
+            true
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_lua_effect_trailing_comma() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(lua, r#"effect("notify", {"hello", "world",})"#).unwrap();
+
+        assert!(effect_rx.recv().await.is_some_and(|invocation| {
+            assert_eq!(
+                invocation.args(),
+                &vec!["hello".to_string(), "world".to_string()]
+            );
+            true
+        }));
+
+        assert!(lua_run_async!(lua, r#"effect("notify", {"hello",, "world"})"#).is_err());
+        assert!(lua_run_async!(lua, r#"effect("notify", {, "hello"})"#).is_err());
+        assert!(lua_run_async!(lua, r#"effect("notify", {"hello", "world"},)"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_effect_batched() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        {
+            let mut state = get_state::<TestHttpDriver>(&lua).unwrap();
+            state.scraper = state
+                .scraper
+                .clone()
+                .with_results(results!["a", "b", "c", "d", "e"]);
+        }
+
+        lua_run_async!(lua, r#"effectBatched("notify", 2, {mode="default"})"#).unwrap();
+
+        for expected in [vec!["a", "b"], vec!["c", "d"], vec!["e"]] {
+            let invocation = effect_rx.try_recv().unwrap();
+
+            assert_eq!(invocation.name(), "notify");
+            assert_eq!(invocation.args(), &expected);
+            assert_eq!(
+                invocation.kwargs().get("mode"),
+                Some(&"default".to_string())
+            );
+        }
+
+        assert!(effect_rx.try_recv().is_err());
+
+        lua_run_async!(lua, r#"effectBatched("notify", 10)"#).unwrap();
+
+        assert_eq!(
+            effect_rx.try_recv().unwrap().args(),
+            &vec!["a", "b", "c", "d", "e"]
+        );
+        assert!(effect_rx.try_recv().is_err());
+
+        assert!(lua_run_async!(lua, r#"effectBatched("notify", 0)"#).is_err());
+        assert!(effect_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_effect_dedup() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        {
+            let mut state = get_state::<TestHttpDriver>(&lua).unwrap();
+            state.scraper = state
+                .scraper
+                .clone()
+                .with_results(results!["a", "b", "a", "c", "b"]);
+        }
+
+        lua_run_async!(
+            lua,
+            r#"
+                effectDedup()
+                effectBatched("notify", 1)
+                effect("notify", {"c"})
+                effect("notify", {"c", mode="default"})
+                effect("notify", {"c", mode="default"})
+            "#
+        )
+        .unwrap();
+
+        for (expected, mode) in [
+            ("a", None),
+            ("b", None),
+            ("c", None),
+            ("c", Some("default")),
+        ] {
+            let invocation = effect_rx.try_recv().unwrap();
+
+            assert_eq!(invocation.args(), &vec![expected]);
+            assert_eq!(invocation.kwargs().get("mode").map(String::as_str), mode);
+        }
+
+        assert!(effect_rx.try_recv().is_err());
+
+        // Duplicates are sent again once disabled
+        lua_run_async!(
+            lua,
+            r#"
+                effectDedup(false)
+                effect("notify", {"c"})
+                effect("notify", {"c"})
+            "#
+        )
+        .unwrap();
+
+        assert_eq!(effect_rx.try_recv().unwrap().args(), &vec!["c"]);
+        assert_eq!(effect_rx.try_recv().unwrap().args(), &vec!["c"]);
+        assert!(effect_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_effect_using_variables() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://variabilitious")
+                store("varname")
+                effect("notify", {"hello", "{varname}", "world", mode="{varname}"})
+            "#
+        );
+
+        assert!(effect_rx.recv().await.is_some_and(|invocation| {
+            assert_eq!(invocation.name(), "notify");
+            assert_eq!(
+                invocation.args(),
+                &vec![
+                    "hello".to_string(),
+                    "variabilitious".to_string(),
+                    "world".to_string()
+                ]
+            );
+            assert_eq!(
+                invocation.kwargs().get("mode"),
+                Some(&"variabilitious".to_string())
+            );
+            true
+        }));
+    }
+
+    fn fixed_result_effect(
+        args: EffectArgs,
+        kwargs: EffectKwArgs,
+        _: FlagSet<EffectOptions>,
+    ) -> Result<Vec<String>, Error> {
+        match kwargs.get("fail") {
+            Some(_) => Err(Error::ValueOutOfRangeError),
+            None => Ok(vec!["fixed".to_string(), format!("{} arg(s)", args.len())]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lua_effect_result() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new().register_result("fixed", fixed_result_effect),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                effectResult("fixed")
+                effectResult("fixed", {"a", "b", "c"})
+            "#
+        );
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.scraper.results(),
+                &results!["hello", "fixed", "1 arg(s)", "fixed", "3 arg(s)"]
+            );
+        }
+
+        effect_rx.close();
+        assert!(effect_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lua_effect_result_errors() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new().register_result("fixed", fixed_result_effect),
+        )
+        .unwrap();
+
+        assert!(
+            lua_run_async!(lua, r#"effectResult("fixed", {fail="yes"})"#).is_err_and(|e| {
+                assert!(
+                    e.to_string()
+                        .contains("Effect error: `fixed`: Value out of range")
+                );
+                true
+            })
+        );
+
+        assert!(lua_run_async!(lua, r#"effectResult("unknown")"#).is_err());
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results![]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_extract() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                extract("-(4.?)")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["45", "44", "4"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_extract_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://-(4.?)")
+                store("varname")
+                clear()
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                extract("{varname}")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["45", "44", "4"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_extract_single_quoted() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get('string://he said "hi", she said \'bye\'')
+                extract('said ["\'](\\w+)')
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(state.scraper.results(), &results!["hi", "bye"]);
+        }
+
+        assert!(lua_run_async!(lua, r#"extract('unterminated)"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_extract_long_string() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://1.5 a]]b 2.25")
+                store("text")
+                extract([[\d+\.\d+]])
+                store("raw")
+                clear()
+                get("string://{text}")
+                extract("\\d+\\.\\d+")
+                store("escaped")
+                clear()
+                get("string://{text}")
+                extract([=[\w]]\w]=])
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.variables.get("raw").unwrap(),
+            &results!["1.5", "2.25"]
+        );
+        assert_eq!(state.variables.get("raw"), state.variables.get("escaped"));
+        assert_eq!(state.scraper.results(), &results!["a]]b"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_first() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                first()
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["123-456"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_get() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(lua, r#"get("string://hello")"#);
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_get_with_options() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<SlowTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("slow://10/a")
+                get("slow://10/b", {timeout=5, maxBodySize=1024, decompress=false})
+                get("slow://10/c", {})
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<SlowTestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(state.scraper.results(), &results!["a", "b", "c"]);
+
+            // Options apply to a single request
+            assert_eq!(state.scraper.max_body_size(), DEFAULT_MAX_BODY_SIZE);
+            assert!(state.scraper.decompress());
+        }
+
+        assert!(
+            lua_run_async!(lua, r#"get("slow://2000/d", {timeout=1})"#)
+                .is_err_and(|e| e.to_string().contains(&Error::TimeoutError(1).to_string()))
+        );
+
+        assert!(lua_run_async!(lua, r#"get("slow://10/e", {timeout=0})"#).is_err());
+        assert!(lua_run_async!(lua, r#"get("slow://10/e", {maxBodySize=0})"#).is_err());
+        assert!(lua_run_async!(lua, r#"get("slow://10/e", {timeout="soon"})"#).is_err());
+        assert!(lua_run_async!(lua, r#"get("slow://10/e", {retries=3})"#).is_err());
+        assert!(lua_run_async!(lua, r#"get("slow://10/e", {"positional"})"#).is_err());
+
+        let state = get_state::<SlowTestHttpDriver>(&lua).unwrap();
+        assert_eq!(state.scraper.results(), &results!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_get_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://foobar")
+                store("myvar")
+                clear()
+                get("string://{myvar}")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["foobar"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_get_all() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://string://a")
+                get("string://string://b")
+                store("urls")
+                clear()
+                getAll("urls")
+                getAll({"string://c", "string://{urls}"}, 1)
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.scraper.results(),
+                &results!["a", "b", "c", "string://astring://b"]
+            );
+        }
+
+        assert!(lua_run_async!(lua, r#"getAll("missing")"#).is_err());
+        assert!(lua_run_async!(lua, r#"getAll(42)"#).is_err());
+        assert!(lua_run_async!(lua, r#"getAll("urls", 0)"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_expect_content_type() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<ContentTypeTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("content-type://application/json")
+                expectContentType("^application/json")
+                append("!")
+            "#
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_state::<ContentTypeTestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .results(),
+            &results!["{}!"]
+        );
+
+        let error = lua_run_async!(
+            lua,
+            r#"
+                clear()
+                get("content-type://text/html")
+                expectContentType("^application/json")
+                append("unreachable")
+            "#
+        )
+        .unwrap_err();
+
+        assert!(
+            error.to_string().contains(
+                "Unexpected content type: `text/html` does not match `^application/json`"
+            )
+        );
+        assert_eq!(
+            get_state::<ContentTypeTestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .results(),
+            &results!["{}"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_post_multipart() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<MultipartTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("Jerry")
+                store("name")
+                clear()
+                postMultipart("https://a.test/submit", { name = "{name}", age = "3" })
+                postMultipart("https://a.test/upload", {}, { photo = "/tmp/{name}.png" })
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<MultipartTestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results![
+                "https://a.test/submit\nage=3\nname=Jerry",
+                "https://a.test/upload\nphoto@/tmp/Jerry.png"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_request() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<MethodTestHttpDriver>(
+            vec![],
+            HashMap::from([("id".to_string(), "42".to_string())]),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                request("GET", "https://a.test/items/{id}")
+                request("post", "https://a.test/items", [[{{"id": {id}}}]])
+                request("PUT", "https://a.test/items/{id}", "Tom")
+                request("delete", "https://a.test/items/{id}")
+                request("HEAD", "https://a.test/items")
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<MethodTestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results![
+                "GET https://a.test/items/42",
+                "POST https://a.test/items\n{\"id\": 42}",
+                "PUT https://a.test/items/42\nTom",
+                "DELETE https://a.test/items/42",
+                "HEAD https://a.test/items"
+            ]
+        );
+
+        drop(state);
+
+        assert!(lua_run_async!(lua, r#"request("PATCH", "https://a.test/items")"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_sitemap_urls() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("file://tests/assets/sitemap/sitemapindex.xml")
+                sitemapUrls()
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.scraper.results(),
+                &results![
+                    "https://example.com/",
+                    "https://example.com/cats?breed=siamese&age=2",
+                    "https://example.com/dogs",
+                    "https://example.com/birds"
+                ]
+            );
+        }
+
+        assert!(lua_run_async!(lua, r#"get("string://nope") sitemapUrls()"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_get_paginated() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                store("greeting")
+                getPaginated("string://{greeting} world", 5)
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello", "hello world"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_stats() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://3")
+                get("string://n/a")
+                get("string://6")
+                stats()
+            "#
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["min=3", "max=6", "sum=9", "mean=4.5", "count=2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_time_budget() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(lua, "timeBudget(2.5)").unwrap();
+
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .time_budget(),
+            Some(Duration::from_millis(2500))
+        );
+
+        lua_run_async!(lua, "timeBudget(0)").unwrap();
+
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .time_budget(),
+            None
+        );
+
+        assert!(lua_run_async!(lua, "timeBudget(-1)").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_accept() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<HeaderTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                accept("application/json", "json")
+                get("")
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.scraper.results(),
+                &results!["Headers({\"Accept\": \"application/json\"})"]
+            );
+            assert_eq!(state.scraper.parse_mode(), ParseMode::Json);
+        }
+
+        // Without a mode, the mode is kept
+        lua_run_async!(
+            lua,
+            r#"
+                clear()
+                accept("text/html")
+                get("")
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.scraper.results(),
+                &results!["Headers({\"Accept\": \"text/html\"})"]
+            );
+            assert_eq!(state.scraper.parse_mode(), ParseMode::Json);
+        }
+
+        lua_run_async!(lua, r#"accept("text/html", "html")"#).unwrap();
+
+        assert_eq!(
+            get_state::<HeaderTestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .parse_mode(),
+            ParseMode::Html
+        );
+
+        assert!(lua_run_async!(lua, r#"accept("text/xml", "xml")"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_extract_by_mode() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                accept("application/json", "json")
+                get([[string://{{"cats": ["Tom", "Garfield"]}}]])
+                extract("$.cats[*]")
+            "#
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["Tom", "Garfield"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_header() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<HeaderTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                header("User-Agent", "Mozilla/Firefox")
+                get("")
+            "#
+        );
+
+        {
+            let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.scraper.results(),
+                &results!["Headers({\"User-Agent\": \"Mozilla/Firefox\"})"]
+            );
+        }
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                clear()
+                header("Accept-Encoding", "gzip")
+                get("")
+            "#
+        );
+
+        let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results![r#"Headers({"Accept-Encoding": "gzip", "User-Agent": "Mozilla/Firefox"})"#]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_header_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<HeaderTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                header("Test", "123")
+                get("")
+                store("$MyVariable")
+                clear()
+                clearHeaders()
+                header("pre{$MyVariable}post", "aff{$MyVariable}suff")
+                get("")
+            "#
+        );
+
+        let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            // Variable substitution only occurs for the value
+            &results![r#"Headers({"pre{$MyVariable}post": "affHeaders({"Test": "123"})suff"})"#]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_headers_from_var() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<HeaderTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        {
+            let mut state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+            state.variables.insert(
+                "headers".to_string(),
+                results![
+                    "User-Agent=Mozilla/Firefox",
+                    "Authorization = Bearer a=b\n\n"
+                ],
+            );
+            state.variables.insert(
+                "broken".to_string(),
+                results!["User-Agent: Mozilla/Firefox"],
+            );
+        }
+
+        lua_run_async!(
+            lua,
+            r#"
+                headersFromVar("headers")
+                get("")
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.scraper.results(),
+                &results![
+                    r#"Headers({"Authorization": "Bearer a=b", "User-Agent": "Mozilla/Firefox"})"#
+                ]
+            );
+        }
+
+        assert!(
+            lua_run_async!(lua, r#"headersFromVar("broken")"#)
+                .is_err_and(|e| e.to_string().contains("Invalid header line"))
+        );
+        assert!(lua_run_async!(lua, r#"headersFromVar("missing")"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lua_html_decode() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://Fish &amp; Chips &#8211; &pound;5")
+                htmlDecode()
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["Fish & Chips – £5"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_init() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                init()
+            "#
+        );
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(
+                state.scraper.results(),
+                &results!["123-456", "84-9851-858-44"]
+            );
+        }
+
+        lua_call!(lua, "init", () => ());
+        lua_call!(lua, "init", () => ());
+        lua_call!(lua, "init", () => ());
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        assert_eq!(state.scraper.results(), &results![]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_join() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://Alice")
+                get("string://Bob")
+                join(", ")
+            "#
+        );
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(state.scraper.results(), &results!["Alice, Bob"]);
+        }
+
+        // `collect()` is `join("\n")`
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://Charlie")
+                collect()
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        assert_eq!(state.scraper.results(), &results!["Alice, Bob\nCharlie"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_json_decode_encode() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let text = r#"{"empty":{},"name":"a","nested":{"flag":true,"list":[1,2.5,"three"]}}"#;
+
+        {
+            let mut state = get_state::<TestHttpDriver>(&lua).unwrap();
+            state.scraper = state.scraper.clone().with_results(results![text]);
+        }
+
+        lua_run_async!(
+            lua,
+            r#"
+                map(function(text)
+                    local value = jsonDecode(text)
+
+                    assert(value.name == "a")
+                    assert(value.nested.flag == true)
+                    assert(#value.nested.list == 3)
+                    assert(value.nested.list[3] == "three")
+
+                    return jsonEncode(value)
+                end)
+
+                map(function(text)
+                    local value = jsonDecode(text)
+                    value.nested.list[4] = "four"
+                    return jsonEncode(value)
+                end)
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results![
+                r#"{"empty":{},"name":"a","nested":{"flag":true,"list":[1,2.5,"three","four"]}}"#
+            ]
+        );
     }
 
-    macro_rules! lua_run_async {
-        ($lua:ident, $script:expr) => {
-            $lua.load($script).exec_async().await
-        };
+    #[tokio::test]
+    async fn test_lua_json_decode_encode_errors() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        assert!(
+            lua_run_async!(lua, r#"jsonDecode("{not json")"#)
+                .is_err_and(|e| e.to_string().contains("JSON parse error"))
+        );
+        assert!(lua_run_async!(lua, r#"jsonEncode(print)"#).is_err());
+        assert!(lua_run_async!(lua, r#"local t = {}; t.t = t; jsonEncode(t)"#).is_err());
     }
 
-    fn null_script_loader_inner(_name: &str) -> Result<String, Error> {
-        Err(Error::JobNotFoundError)
+    #[tokio::test]
+    async fn test_lua_json_filter() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        {
+            let mut state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+            state.scraper = state.scraper.clone().with_results(results![
+                r#"[{"name": "a", "price": 50}, {"name": "b", "price": 150}]"#
+            ]);
+        }
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                jsonPath("$[*]")
+                jsonFilter("$.price < 100")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results![r#"{"name":"a","price":50}"#]
+        );
     }
 
-    fn null_script_loader() -> ScriptLoaderPointer {
-        Arc::new(RwLock::new(null_script_loader_inner))
+    #[tokio::test]
+    async fn test_lua_json_ld() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("file://tests/assets/jsonld/product.html")
+                jsonLd()
+                jsonPath("$.name")
+            "#
+        )
+        .unwrap();
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["Catnip Deluxe"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_jsonpath() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get([[string://{{
+                  "authors": {{
+                    "horror": [
+                      "Garth Marenghi",
+                      "Steven King"
+                    ],
+                    "scifi": [
+                      "Carl Sagan",
+                      "Isaac Asimov"
+                    ]
+                  }}
+                }}]])
+                
+                jsonPath("$.authors.horror[0]")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["Garth Marenghi"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_list() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                get("string://world")
+                store("myVariable")
+            "#
+        );
+
+        let my_variable = lua_call!(lua, "list", "myVariable" => Vec<String>);
+
+        assert_eq!(my_variable, vec!["hello", "world"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_list_missing() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        assert!(
+            lua_run_async!(
+                lua,
+                r#"
+                local x = list("foo")
+            "#
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_load() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                store("myVariable")
+                clear()
+                load("myVariable")
+            "#
+        );
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello"]);
+    }
+
+    #[tokio::test]
+    #[should_panic]
+    async fn test_lua_load_does_not_do_variable_substitution() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                store("myVariable")
+                clear()
+                load("{myVariable}") -- variable `{myVariable}` not found!
+            "#
+        )
+        .unwrap();
     }
 
-    #[test]
-    fn test_substitute_variables_no_vars() {
-        assert_eq!(substitute_variables("", &HashMap::new()).unwrap(), "");
-        assert_eq!(
-            substitute_variables("hello world", &HashMap::new()).unwrap(),
-            "hello world"
-        );
-    }
+    #[tokio::test]
+    async fn test_lua_log() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
 
-    #[test]
-    fn test_substitute_variables_missing_var() {
-        assert!(
-            substitute_variables("{x}", &HashMap::new())
-                .is_err_and(|e| matches!(e, Error::VariableNotFoundError(_)))
-        );
-    }
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-    #[test]
-    fn test_substitute_variables_multiple() {
-        let variables = HashMap::from([
-            ("x1".to_string(), results!["1"]),      // Result gets shorter
-            ("x2".to_string(), results!["2345"]),   // Result stays same length
-            ("x3".to_string(), results!["678912"]), // Result gets longer
-            ("$bar".to_string(), results![""]),
-        ]);
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://hello")
+                store("greeting")
+                log("greeting is {greeting}")
+            "#
+        )
+        .unwrap();
 
-        assert!(
-            substitute_variables("{x1}{x2}{x3}", &variables).is_ok_and(|result| {
-                assert_eq!(result, "12345678912");
-                true
-            })
-        );
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert!(
-            substitute_variables("{x1} {x2} {x3}", &variables).is_ok_and(|result| {
-                assert_eq!(result, "1 2345 678912");
-                true
-            })
-        );
+            assert_eq!(state.scraper.results(), &results!["hello"]);
+            assert_eq!(state.variables.len(), 1);
+        }
 
-        assert!(
-            substitute_variables("{x1} {x3} {x2}", &variables).is_ok_and(|result| {
-                assert_eq!(result, "1 678912 2345");
-                true
-            })
-        );
+        assert!(lua_run_async!(lua, r#"log("{missing}")"#).is_err());
+    }
 
-        assert!(
-            substitute_variables("{x2} {x1} {x3}", &variables).is_ok_and(|result| {
-                assert_eq!(result, "2345 1 678912");
-                true
-            })
-        );
+    #[tokio::test]
+    async fn test_lua_lowercase_and_uppercase() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
 
-        assert!(
-            substitute_variables("{x2} {x3} {x1}", &variables).is_ok_and(|result| {
-                assert_eq!(result, "2345 678912 1");
-                true
-            })
-        );
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        assert!(
-            substitute_variables("{x3} {x1} {x2}", &variables).is_ok_and(|result| {
-                assert_eq!(result, "678912 1 2345");
-                true
-            })
-        );
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://Hello World")
+                get("string://Straße")
+                lowercase()
+                store("lower")
+                uppercase()
+            "#
+        )
+        .unwrap();
 
-        assert!(
-            substitute_variables("{x3} {x2} {x1}", &variables).is_ok_and(|result| {
-                assert_eq!(result, "678912 2345 1");
-                true
-            })
-        );
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert!(
-            substitute_variables("x1 {x1} foo {x2} bar {$bar} {x3} baz {x1}", &variables)
-                .is_ok_and(|result| {
-                    assert_eq!(result, "x1 1 foo 2345 bar  678912 baz 1");
-                    true
-                })
+        assert_eq!(
+            state.variables.get("lower").unwrap(),
+            &results!["hello world", "straße"]
         );
+        assert_eq!(state.scraper.results(), &results!["HELLO WORLD", "STRASSE"]);
     }
 
-    #[test]
-    fn test_substitute_variables_escaped_braces() {
-        let variables = HashMap::from([
-            ("x1".to_string(), results!["1"]),      // Result gets shorter
-            ("x2".to_string(), results!["2345"]),   // Result stays same length
-            ("x3".to_string(), results!["678912"]), // Result gets longer
-        ]);
+    #[tokio::test]
+    async fn test_lua_map() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
 
-        assert!(
-            substitute_variables("x1 {x1} {{x1}} {{ x1 {{ foo bar }} }}", &variables).is_ok_and(
-                |result| {
-                    assert_eq!(result, "x1 1 {x1} { x1 { foo bar } }");
-                    true
-                }
-            )
-        );
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        assert!(
-            substitute_variables("x2 {x2} {{x2}} {{ x2 {{ foo bar }} }}", &variables).is_ok_and(
-                |result| {
-                    assert_eq!(result, "x2 2345 {x2} { x2 { foo bar } }");
-                    true
-                }
-            )
-        );
+        lua.load(
+            r#"
+                get("string://mapme")
+                get("string://mapmetoo")
+                map(function(x)
+                    return "(" .. x .. ")!"
+                end)
+            "#,
+        )
+        .exec()
+        .unwrap();
 
-        assert!(
-            substitute_variables("x3 {x3} {{x3}} {{ x3 {{ foo bar }} }}", &variables).is_ok_and(
-                |result| {
-                    assert_eq!(result, "x3 678912 {x3} { x3 { foo bar } }");
-                    true
-                }
-            )
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(
+            state.scraper.results(),
+            &results!["(mapme)!", "(mapmetoo)!"]
         );
     }
 
-    #[test]
-    fn test_create_lua_context_get_and_set_state() {
+    #[tokio::test]
+    async fn test_lua_flat_map() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<NullHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
-
-        {
-            let mut state = get_state::<NullHttpDriver>(&lua).unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-            state.scraper = state.scraper.clone().with_results(results!["hello"]);
+        lua.load(
+            r#"
+                get("string://a")
+                get("string://")
+                get("string://b,c")
+                get("string://d")
+                flatMap(function(x)
+                    local parts = {}
+
+                    for part in x:gmatch("[^,]+") do
+                        table.insert(parts, part)
+                    end
+
+                    return parts
+                end)
+            "#,
+        )
+        .exec()
+        .unwrap();
 
-            state
-                .variables
-                .insert("test".to_string(), results!["world"]);
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(state.scraper.results(), &results!["a", "b", "c", "d"]);
         }
 
-        let state = get_state::<NullHttpDriver>(&lua).unwrap();
-
-        assert_eq!(state.scraper.results(), &results!["hello"]);
-        assert_eq!(state.variables.get("test"), Some(&results!["world"]));
+        assert!(
+            lua.load(r#"flatMap(function(x) return x end)"#)
+                .exec()
+                .is_err()
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_abort_if_empty() {
-        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+    async fn test_lua_map_using_variables_in_applied_fn() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                abortIfEmpty()
-                effect("print", { "hello" })
-                get("string://test")
+                get("string://foo")
+                store("myvar")
+                clear()
+                get("string://mapme")
+                get("string://mapmetoo")
+                map(function(x)
+                    return var("myvar") .. x .. "!"
+                end)
             "#
         );
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results![]);
-
-        effect_rx.close();
-
-        assert!(effect_rx.recv().await.is_none());
+        assert_eq!(
+            state.scraper.results(),
+            &results!["foomapme!", "foomapmetoo!"]
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_append() {
+    async fn test_lua_parse_and_format_number() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://hello")
-                append(" world")
+                get("string://$1,234.567")
+                get("string://sold out")
+                parseNumber()
+                store("parsed")
+                formatNumber(2)
+                store("formatted")
+                clear()
+                get("string://1.234,5 kr")
+                parseNumber(",")
             "#
-        );
+        )
+        .unwrap();
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+            assert_eq!(
+                state.variables.get("parsed").unwrap(),
+                &results!["1234.567"]
+            );
+            assert_eq!(
+                state.variables.get("formatted").unwrap(),
+                &results!["1234.57"]
+            );
+            assert_eq!(state.scraper.results(), &results!["1234.5"]);
+        }
 
-        assert_eq!(state.scraper.results(), &results!["hello world"]);
+        assert!(lua_run_async!(lua, r#"parseNumber("x")"#).is_err());
+        assert!(lua_run_async!(lua, r#"parseNumber(",.")"#).is_err());
     }
 
     #[tokio::test]
-    async fn test_lua_append_using_variables() {
+    async fn test_lua_retry_if_empty() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<FlakyTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let start = std::time::Instant::now();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://world!!")
-                store("varname")
-                clear()
-                get("string://hello")
-                append(" {varname}")
+                get("flaky://2/cats")
+                retryIfEmpty(3, 50)
             "#
-        );
-
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        )
+        .unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["hello world!!"]);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(
+            get_state::<FlakyTestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .results(),
+            &results!["cats"]
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_apply() {
+    async fn test_lua_memory_cache() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
-        let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let script_loader = Arc::new(RwLock::new(|name: &str| match name {
+            "page" => Ok(r#"get("flaky://0/lua-memory-index")"#.to_string()),
+            _ => Err(Error::ScriptNotFoundError(name.to_string())),
+        }));
 
-        let _ = lua_run_async!(
+        let lua = create_lua_context::<FlakyTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        // Scripts started using run share the cache
+        lua_run_async!(
             lua,
             r#"
-                function process(results)
-                    table.insert(results, "a")
-                    table.insert(results, "b")
-                    return results
-                end
-
-                get("string://hello")
-                apply(process)
+                memoryCache()
+                get("flaky://0/lua-memory-index")
+                run("page")
+                get("flaky://0/lua-memory-index")
             "#
+        )
+        .unwrap();
+
+        assert_eq!(flaky_fetches("flaky://0/lua-memory-index"), 1);
+        assert_eq!(
+            get_state::<FlakyTestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .results()
+                .len(),
+            3
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        lua_run_async!(
+            lua,
+            r#"
+                memoryCache(false)
+                get("flaky://0/lua-memory-index")
+            "#
+        )
+        .unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["hello", "a", "b"]);
+        assert_eq!(flaky_fetches("flaky://0/lua-memory-index"), 2);
     }
 
     #[tokio::test]
-    async fn test_lua_apply_using_variables_in_applied_fn() {
+    async fn test_lua_polite_delay() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let start = std::time::Instant::now();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                function process(results)
-                    table.insert(results, var("varname"))
-                    return results
-                end
+                politeDelay(100)
+                get("string://a")
+                get("string://b")
+                get("string://c")
+            "#
+        )
+        .unwrap();
 
-                get("string://hello")
-                store("varname")
-                clear()
-                get("string://world")
-                apply(process)
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        let start = std::time::Instant::now();
+
+        lua_run_async!(
+            lua,
+            r#"
+                politeDelay(0)
+                get("string://d")
+                get("string://e")
             "#
-        );
+        )
+        .unwrap();
+
+        assert!(start.elapsed() < Duration::from_millis(100));
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["world", "hello"]);
+        assert_eq!(state.scraper.results(), &results!["a", "b", "c", "d", "e"]);
     }
 
     #[tokio::test]
-    async fn test_lua_clear() {
+    async fn test_lua_prepend() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                get("string://hello")
-                clear()
+                get("string://world")
+                prepend("hello ")
             "#
         );
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results![]);
+        assert_eq!(state.scraper.results(), &results!["hello world"]);
     }
 
     #[tokio::test]
-    async fn test_lua_clearheaders() {
+    async fn test_lua_prepend_using_variables() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua = create_lua_context::<HeaderTestHttpDriver>(
+        let lua = create_lua_context::<TestHttpDriver>(
             vec![],
             HashMap::new(),
             effect_tx,
             script_loader,
+            EffectRegistry::new(),
         )
         .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                header("User-Agent", "Mozilla/Firefox")
-                clearHeaders()
-                get("")
+                get("string://hello")
+                store("myvar")
+                clear()
+                get("string://world")
+                prepend("{myvar} ")
             "#
         );
 
-        let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["Headers({})"]);
+        assert_eq!(state.scraper.results(), &results!["hello world"]);
     }
 
     #[tokio::test]
-    async fn test_lua_delete() {
+    async fn test_lua_cookie_jar() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let path = std::env::temp_dir()
+            .join(format!(
+                "scrapeycat-test-lua-cookies-{}.txt",
+                std::process::id()
+            ))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        std::fs::write(&path, "example.com\tFALSE\t/\tFALSE\t0\tsession\tabc\n").unwrap();
+
+        let lua = create_lua_context::<HeaderTestHttpDriver>(
+            vec![],
+            HashMap::from([("jar".to_string(), path.clone())]),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                delete("-")
+                get("http://example.com/")
+                cookieJar("{jar}")
+                get("http://example.com/")
+                get("http://example.org/")
+                cookieJar()
+                get("http://example.com/")
             "#
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
 
         assert_eq!(
             state.scraper.results(),
-            &results!["123456", "84985185844", "7868584"]
+            &results![
+                "Headers({})",
+                r#"Headers({"cookie": "session=abc"})"#,
+                "Headers({})",
+                "Headers({})"
+            ]
         );
+
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[tokio::test]
-    async fn test_lua_delete_using_variables() {
+    async fn test_lua_follow_meta_refresh() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                get("string://-")
-                store("varname")
-                clear()
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                delete("{varname}4")
+                get('string://<meta http-equiv="refresh" content="0; url=string://done">')
+                followMetaRefresh()
+                get('string://<meta http-equiv="refresh" content="0; url=string://done">')
+                followMetaRefresh(0)
+                get('string://<meta http-equiv="refresh" content="0; url=string://done">')
             "#
         );
 
@@ -969,426 +4994,614 @@ mod tests {
 
         assert_eq!(
             state.scraper.results(),
-            &results!["12356", "84-9851-8584", "786---858"]
+            &results![
+                r#"<meta http-equiv="refresh" content="0; url=string://done">"#,
+                "done",
+                r#"<meta http-equiv="refresh" content="0; url=string://done">"#
+            ]
         );
     }
 
     #[tokio::test]
-    async fn test_lua_discard() {
+    async fn test_lua_cache() {
+        let dir = std::env::temp_dir()
+            .join(format!(
+                "scrapeycat-test-lua-response-cache-{}",
+                std::process::id()
+            ))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let _ = std::fs::remove_dir_all(&dir);
+
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::from([("dir".to_string(), dir.clone())]),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                discard("858")
+                cache(3600, "{dir}")
+                get("string://hello")
             "#
-        );
-
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
-
-        assert_eq!(state.scraper.results(), &results!["123-456"]);
-    }
-
-    #[tokio::test]
-    async fn test_lua_discard_using_variables() {
-        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
-        let script_loader = null_script_loader();
+        )
+        .unwrap();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://-")
-                store("varname")
-                clear()
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                discard("{varname}{varname}858")
+                cache(0)
+                get("string://world")
             "#
-        );
+        )
+        .unwrap();
+
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(lua_run_async!(lua, r#"cache(-1)"#).is_err());
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(
-            state.scraper.results(),
-            &results!["123-456", "84-9851-858-44"]
-        );
+        assert_eq!(state.scraper.results(), &results!["hello", "world"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[tokio::test]
-    async fn test_lua_drop() {
+    async fn test_lua_circuit_breaker() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        // TestHttpDriver fails for unsupported URL schemes
+        let result = lua_run_async!(
             lua,
             r#"
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                drop(2)
+                circuitBreaker(2, 60)
+                pcall(get, "http://circuit-breaker-lua.test/")
+                pcall(get, "http://circuit-breaker-lua.test/")
+                get("http://circuit-breaker-lua.test/")
             "#
         );
 
-        {
-            let state = get_state::<TestHttpDriver>(&lua).unwrap();
-            assert_eq!(state.scraper.results(), &results!["786---858-4"]);
-        }
-
-        lua_call!(lua, "drop", 200 => ());
-
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
-        assert_eq!(state.scraper.results(), &results![]);
-    }
-
-    #[tokio::test]
-    async fn test_lua_effect() {
-        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
-        let script_loader = null_script_loader();
-
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Circuit open for host `circuit-breaker-lua.test`")
+        );
 
-        let _ = lua_run_async!(
+        let result = lua_run_async!(
             lua,
-            r#"effect("notify", {"hello", "world", mode="default"})"#
+            r#"
+                circuitBreaker(0)
+                pcall(get, "http://circuit-breaker-lua.test/")
+                get("string://ok")
+            "#
         );
 
-        assert!(effect_rx.recv().await.is_some_and(|invocation| {
-            assert_eq!(invocation.name(), "notify");
-            assert_eq!(
-                invocation.args(),
-                &vec!["hello".to_string(), "world".to_string()]
-            );
-            assert_eq!(
-                invocation.kwargs().get("mode"),
-                Some(&"default".to_string())
-            );
-            true
-        }));
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
-    async fn test_lua_effect_using_variables() {
-        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+    async fn test_lua_decompress() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        assert!(
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .decompress()
+        );
 
-        let _ = lua_run_async!(
-            lua,
-            r#"
-                get("string://variabilitious")
-                store("varname")
-                effect("notify", {"hello", "{varname}", "world", mode="{varname}"})
-            "#
+        lua_run_async!(lua, "decompress(false)").unwrap();
+        assert!(
+            !get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .decompress()
         );
 
-        assert!(effect_rx.recv().await.is_some_and(|invocation| {
-            assert_eq!(invocation.name(), "notify");
-            assert_eq!(
-                invocation.args(),
-                &vec![
-                    "hello".to_string(),
-                    "variabilitious".to_string(),
-                    "world".to_string()
-                ]
-            );
-            assert_eq!(
-                invocation.kwargs().get("mode"),
-                Some(&"variabilitious".to_string())
-            );
-            true
-        }));
+        lua_run_async!(lua, "decompress(true)").unwrap();
+        assert!(
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .decompress()
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_extract() {
+    async fn test_lua_max_body_size() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
-            lua,
-            r#"
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                extract("-(4.?)")
-            "#
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .max_body_size(),
+            DEFAULT_MAX_BODY_SIZE
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        lua_run_async!(lua, "maxBodySize(1048576)").unwrap();
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .max_body_size(),
+            1048576
+        );
 
-        assert_eq!(state.scraper.results(), &results!["45", "44", "4"]);
+        assert!(lua_run_async!(lua, "maxBodySize(0)").is_err());
+
+        lua_run_async!(lua, "maxBodySize()").unwrap();
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .max_body_size(),
+            DEFAULT_MAX_BODY_SIZE
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_extract_using_variables() {
+    async fn test_lua_robots() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<RobotsTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        // Off by default
+        assert!(lua_run_async!(lua, r#"get("http://robots-lua.test/private")"#).is_ok());
 
-        let _ = lua_run_async!(
+        let result = lua_run_async!(
             lua,
             r#"
-                get("string://-(4.?)")
-                store("varname")
-                clear()
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                extract("{varname}")
+                robots()
+                get("http://robots-lua.test/public")
+                get("http://robots-lua.test/private")
             "#
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Fetching http://robots-lua.test/private is disallowed by robots.txt")
+        );
 
-        assert_eq!(state.scraper.results(), &results!["45", "44", "4"]);
+        let result = lua_run_async!(
+            lua,
+            r#"
+                robots(false)
+                get("http://robots-lua.test/private")
+            "#
+        );
+
+        assert!(result.is_ok());
     }
 
     #[tokio::test]
-    async fn test_lua_first() {
+    async fn test_lua_proxy() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<ProxyTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                first()
+                get("")
+                proxy("http://a:8080", "http://b:8080")
+                get("")
+                get("")
+                get("")
+                proxy()
+                get("")
             "#
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        let state = get_state::<ProxyTestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["123-456"]);
+        assert_eq!(
+            state.scraper.results(),
+            &results![
+                "direct",
+                "http://a:8080",
+                "http://b:8080",
+                "http://a:8080",
+                "direct"
+            ]
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_get() {
-        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
-        let script_loader = null_script_loader();
+    async fn test_lua_random_seeded() {
+        let sample = || async {
+            let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+            let script_loader = null_script_loader();
+
+            let lua = create_lua_context::<TestHttpDriver>(
+                vec![],
+                HashMap::new(),
+                effect_tx,
+                script_loader,
+                EffectRegistry::new(),
+            )
+            .unwrap();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+            lua_run_async!(
+                lua,
+                r#"
+                    seed(1234)
 
-        let _ = lua_run_async!(lua, r#"get("string://hello")"#);
+                    for i = 1, 20 do
+                        get("string://" .. i)
+                    end
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+                    random(5)
+                "#
+            )
+            .unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["hello"]);
+            get_state::<TestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .results()
+                .clone()
+        };
+
+        let first = sample().await;
+
+        assert_eq!(first.len(), 5);
+        assert_eq!(sample().await, first);
     }
 
     #[tokio::test]
-    async fn test_lua_get_using_variables() {
+    async fn test_lua_resolve() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<ResolveTestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://foobar")
-                store("myvar")
-                clear()
-                get("string://{myvar}")
+                resolve("example.com", "203.0.113.7")
+                resolve("example.net", "203.0.113.8:8080")
+                resolve("example.org", "[2001:db8::1]:8443")
+                get("https://example.com/")
+                resolve("example.org")
+                get("https://example.com/")
             "#
+        )
+        .unwrap();
+
+        // The overrides set at the time of each request were passed to the driver
+        assert_eq!(
+            get_state::<ResolveTestHttpDriver>(&lua)
+                .unwrap()
+                .scraper
+                .results(),
+            &results![
+                "example.com=203.0.113.7:0, example.net=203.0.113.8:8080, \
+                 example.org=[2001:db8::1]:8443",
+                "example.com=203.0.113.7:0, example.net=203.0.113.8:8080"
+            ]
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        assert!(lua_run_async!(lua, r#"resolve("example.com", "not an address")"#).is_err());
+        assert!(lua_run_async!(lua, r#"resolve("example.com", "203.0.113.7:99999")"#).is_err());
+    }
 
-        assert_eq!(state.scraper.results(), &results!["foobar"]);
+    #[test]
+    fn test_parse_resolve_addr() {
+        assert_eq!(
+            parse_resolve_addr("203.0.113.7").unwrap(),
+            "203.0.113.7:0".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            parse_resolve_addr("203.0.113.7:8080").unwrap(),
+            "203.0.113.7:8080".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            parse_resolve_addr("2001:db8::1").unwrap(),
+            "[2001:db8::1]:0".parse::<SocketAddr>().unwrap()
+        );
+        assert!(parse_resolve_addr("example.com").is_err());
     }
 
     #[tokio::test]
-    async fn test_lua_header() {
+    async fn test_lua_retain() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua = create_lua_context::<HeaderTestHttpDriver>(
+        let lua = create_lua_context::<TestHttpDriver>(
             vec![],
             HashMap::new(),
             effect_tx,
             script_loader,
+            EffectRegistry::new(),
         )
         .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                header("User-Agent", "Mozilla/Firefox")
-                get("")
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                retain("858")
             "#
         );
 
-        {
-            let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-            assert_eq!(
-                state.scraper.results(),
-                &results!["Headers({\"User-Agent\": \"Mozilla/Firefox\"})"]
-            );
-        }
+        assert_eq!(
+            state.scraper.results(),
+            &results!["84-9851-858-44", "786---858-4"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lua_retain_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
+                get("string://5")
+                store("myvar")
                 clear()
-                header("Accept-Encoding", "gzip")
-                get("")
+                get("string://123-456")
+                get("string://84-9851-858-44")
+                get("string://786---858-4")
+                retain("8{myvar}8")
             "#
         );
 
-        let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
         assert_eq!(
             state.scraper.results(),
-            &results![r#"Headers({"Accept-Encoding": "gzip", "User-Agent": "Mozilla/Firefox"})"#]
+            &results!["84-9851-858-44", "786---858-4"]
         );
     }
 
     #[tokio::test]
-    async fn test_lua_header_using_variables() {
+    async fn test_lua_run() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
-        let script_loader = null_script_loader();
 
-        let lua = create_lua_context::<HeaderTestHttpDriver>(
+        let script_loader = Arc::new(RwLock::new(|name: &str| {
+            if name == "test123" {
+                Ok(r#"get("string://bazinga")"#.to_string())
+            } else {
+                Err(Error::JobNotFoundError)
+            }
+        }));
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        let _ = lua_run_async!(lua, r#"run("test123")"#);
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        assert_eq!(state.scraper.results(), &results!["bazinga"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_run_using_variables() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+
+        let script_loader = Arc::new(RwLock::new(|name: &str| {
+            if name == "{myvar}" {
+                Ok(r#"get("string://bazinga {1} {2} {limit}")"#.to_string())
+            } else {
+                Err(Error::JobNotFoundError)
+            }
+        }));
+
+        let lua = create_lua_context::<TestHttpDriver>(
             vec![],
             HashMap::new(),
             effect_tx,
             script_loader,
+            EffectRegistry::new(),
         )
         .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                header("Test", "123")
-                get("")
-                store("$MyVariable")
+                get("string://foobar")
+                store("myvar")
                 clear()
-                clearHeaders()
-                header("pre{$MyVariable}post", "aff{$MyVariable}suff")
-                get("")
+                run("{myvar}", {"hello", "{myvar}", limit="_{myvar}_"})
             "#
         );
 
-        let state = get_state::<HeaderTestHttpDriver>(&lua).unwrap();
-
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
         assert_eq!(
             state.scraper.results(),
-            // Variable substitution only occurs for the value
-            &results![r#"Headers({"pre{$MyVariable}post": "affHeaders({"Test": "123"})suff"})"#]
+            &results!["bazinga hello foobar _foobar_"]
         );
     }
 
     #[tokio::test]
-    async fn test_lua_jsonpath() {
+    async fn test_lua_scan() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                get([[string://{{
-                  "authors": {{
-                    "horror": [
-                      "Garth Marenghi",
-                      "Steven King"
-                    ],
-                    "scifi": [
-                      "Carl Sagan",
-                      "Isaac Asimov"
-                    ]
-                  }}
-                }}]])
-                
-                jsonPath("$.authors.horror[0]")
+                get("string://Neuromancer - 9.99")
+                get("string://Snow Crash - 14.99")
+                scan("(.+) - (.+)", "title", "price")
             "#
         );
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["Garth Marenghi"]);
+        assert_eq!(
+            state.scraper.results(),
+            &results!["Neuromancer - 9.99", "Snow Crash - 14.99"]
+        );
+        assert_eq!(
+            state.variables.get("title"),
+            Some(&results!["Neuromancer", "Snow Crash"])
+        );
+        assert_eq!(
+            state.variables.get("price"),
+            Some(&results!["9.99", "14.99"])
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_list() {
+    async fn test_lua_partition() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://hello")
-                get("string://world")
-                store("myVariable")
+                get("string://Lamp (in stock)")
+                get("string://Chair (sold out)")
+                get("string://Table (in stock)")
+                partition("in stock", "inStock", "soldOut")
             "#
-        );
+        )
+        .unwrap();
 
-        let my_variable = lua_call!(lua, "list", "myVariable" => Vec<String>);
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(my_variable, vec!["hello", "world"]);
+        assert_eq!(
+            state.scraper.results(),
+            &results!["Lamp (in stock)", "Chair (sold out)", "Table (in stock)"]
+        );
+        assert_eq!(
+            state.variables.get("inStock"),
+            Some(&results!["Lamp (in stock)", "Table (in stock)"])
+        );
+        assert_eq!(
+            state.variables.get("soldOut"),
+            Some(&results!["Chair (sold out)"])
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_list_missing() {
+    async fn test_lua_scan_group_count_mismatch() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         assert!(
             lua_run_async!(
                 lua,
                 r#"
-                local x = list("foo")
+                get("string://Neuromancer - 9.99")
+                scan("(.+) - (.+)", "title")
             "#
             )
             .is_err()
@@ -1396,23 +5609,29 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_lua_load() {
+    async fn test_lua_sleep() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        assert!(lua.globals().get::<LuaFunction>("sleep").is_ok());
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
                 get("string://hello")
-                store("myVariable")
-                clear()
-                load("myVariable")
+                sleep(0)
             "#
-        );
+        )
+        .unwrap();
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
@@ -1420,142 +5639,168 @@ mod tests {
     }
 
     #[tokio::test]
-    #[should_panic]
-    async fn test_lua_load_does_not_do_variable_substitution() {
+    async fn test_lua_sleep_negative() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
-
-        lua_run_async!(
-            lua,
-            r#"
-                get("string://hello")
-                store("myVariable")
-                clear()
-                load("{myVariable}") -- variable `{myVariable}` not found!
-            "#
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
         )
         .unwrap();
+
+        assert!(lua_run_async!(lua, r#"sleep(-1)"#).is_err());
     }
 
     #[tokio::test]
-    async fn test_lua_map() {
+    async fn test_lua_split() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        lua.load(
+        lua_run_async!(
+            lua,
             r#"
-                get("string://mapme")
-                get("string://mapmetoo")
-                map(function(x)
-                    return "(" .. x .. ")!"
-                end)
-            "#,
+                get("string://;")
+                store("sep")
+                clear()
+                get("string://a;b;;c")
+                split("{sep}")
+                store("pieces")
+                clear()
+                get("string://a;b;;c")
+                split("{sep}", true)
+            "#
         )
-        .exec()
         .unwrap();
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
         assert_eq!(
-            state.scraper.results(),
-            &results!["(mapme)!", "(mapmetoo)!"]
+            state.variables.get("pieces").unwrap(),
+            &results!["a", "b", "", "c"]
         );
+        assert_eq!(state.scraper.results(), &results!["a", "b", "c"]);
     }
 
     #[tokio::test]
-    async fn test_lua_map_using_variables_in_applied_fn() {
+    async fn test_lua_store() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
-                get("string://foo")
-                store("myvar")
-                clear()
-                get("string://mapme")
-                get("string://mapmetoo")
-                map(function(x)
-                    return var("myvar") .. x .. "!"
-                end)
+                get("string://hello")
+                store("myVariable")
             "#
         );
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(
-            state.scraper.results(),
-            &results!["foomapme!", "foomapmetoo!"]
-        );
+        assert_eq!(state.variables.get("myVariable"), Some(&results!["hello"]));
     }
 
     #[tokio::test]
-    async fn test_lua_prepend() {
+    async fn test_lua_append_store() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://world")
-                prepend("hello ")
+                for _, name in ipairs({"a", "b"}) do
+                    clear()
+                    get("string://" .. name .. "1")
+                    get("string://" .. name .. "2")
+                    appendStore("appended")
+                    store("stored")
+                end
             "#
-        );
+        )
+        .unwrap();
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["hello world"]);
+        assert_eq!(
+            state.variables.get("appended"),
+            Some(&results!["a1", "a2", "b1", "b2"])
+        );
+        assert_eq!(state.variables.get("stored"), Some(&results!["b1", "b2"]));
     }
 
     #[tokio::test]
-    async fn test_lua_prepend_using_variables() {
+    async fn test_lua_store_does_not_do_variable_substitution() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
                 get("string://hello")
-                store("myvar")
-                clear()
-                get("string://world")
-                prepend("{myvar} ")
+                store("{myVariable}")
             "#
         );
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
-        assert_eq!(state.scraper.results(), &results!["hello world"]);
+        assert_eq!(
+            state.variables.get("{myVariable}"),
+            Some(&results!["hello"])
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_retain() {
+    async fn test_lua_tail() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
@@ -1563,12 +5808,21 @@ mod tests {
                 get("string://123-456")
                 get("string://84-9851-858-44")
                 get("string://786---858-4")
-                retain("858")
+                tail(2)
             "#
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(
+                state.scraper.results(),
+                &results!["84-9851-858-44", "786---858-4"]
+            );
+        }
+
+        lua_call!(lua, "tail", 200 => ());
 
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
         assert_eq!(
             state.scraper.results(),
             &results!["84-9851-858-44", "786---858-4"]
@@ -1576,158 +5830,268 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_lua_retain_using_variables() {
+    async fn test_lua_nth() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://id=12 id=345 id=6")
+                extract("id=(\\d+)")
+                nth(2)
+            "#
+        )
+        .unwrap();
+
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(state.scraper.results(), &results!["345"]);
+        }
+
+        lua_call!(lua, "nth", 2 => ());
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        assert_eq!(state.scraper.results(), &results![]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_trim() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://5")
-                store("myvar")
-                clear()
-                get("string://123-456")
-                get("string://84-9851-858-44")
-                get("string://786---858-4")
-                retain("8{myvar}8")
+                get("string://  hello\t")
+                get("string://\n world \n")
+                trim()
             "#
-        );
+        )
+        .unwrap();
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results!["hello", "world"]);
+    }
+
+    #[tokio::test]
+    async fn test_lua_normalize_whitespace() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://  hello\t\tthere ")
+                get("string://\n big \n\n world \n")
+                normalizeWhitespace()
+            "#
+        )
+        .unwrap();
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
 
         assert_eq!(
             state.scraper.results(),
-            &results!["84-9851-858-44", "786---858-4"]
+            &results!["hello there", "big world"]
         );
     }
 
     #[tokio::test]
-    async fn test_lua_run() {
+    async fn test_lua_unique() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
 
-        let script_loader = Arc::new(RwLock::new(|name: &str| {
-            if name == "test123" {
-                Ok(r#"get("string://bazinga")"#.to_string())
-            } else {
-                Err(Error::JobNotFoundError)
-            }
-        }));
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://Apple,apple,APPLE,banana,banana")
+                split(",")
+                unique()
+            "#
+        )
+        .unwrap();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["Apple", "apple", "APPLE", "banana"]
+        );
 
-        let _ = lua_run_async!(lua, r#"run("test123")"#);
+        lua_run_async!(lua, "uniqueCaseInsensitive()").unwrap();
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
-        assert_eq!(state.scraper.results(), &results!["bazinga"]);
+        assert_eq!(
+            get_state::<TestHttpDriver>(&lua).unwrap().scraper.results(),
+            &results!["Apple", "banana"]
+        );
     }
 
     #[tokio::test]
-    async fn test_lua_run_using_variables() {
+    async fn test_lua_url_encode_and_decode() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
 
-        let script_loader = Arc::new(RwLock::new(|name: &str| {
-            if name == "{myvar}" {
-                Ok(r#"get("string://bazinga {1} {2} {limit}")"#.to_string())
-            } else {
-                Err(Error::JobNotFoundError)
-            }
-        }));
-
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        lua_run_async!(
             lua,
             r#"
-                get("string://foobar")
-                store("myvar")
-                clear()
-                run("{myvar}", {"hello", "{myvar}", limit="_{myvar}_"})
+                get("string://fish & chips")
+                urlEncode()
+                store("encoded")
+                urlDecode()
             "#
-        );
+        )
+        .unwrap();
 
         let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
         assert_eq!(
-            state.scraper.results(),
-            &results!["bazinga hello foobar _foobar_"]
+            state.variables.get("encoded").unwrap(),
+            &results!["fish%20%26%20chips"]
         );
+        assert_eq!(state.scraper.results(), &results!["fish & chips"]);
     }
 
     #[tokio::test]
-    async fn test_lua_store() {
+    async fn test_lua_var() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
             r#"
                 get("string://hello")
+                get("string://world")
                 store("myVariable")
             "#
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        let my_variable = lua_call!(lua, "var", "myVariable" => String);
 
-        assert_eq!(state.variables.get("myVariable"), Some(&results!["hello"]));
+        assert_eq!(my_variable, "hello world");
     }
 
     #[tokio::test]
-    async fn test_lua_store_does_not_do_variable_substitution() {
+    async fn test_lua_env() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
-            lua,
-            r#"
-                get("string://hello")
-                store("{myVariable}")
-            "#
+        // Set by cargo when running tests, but not allowed by default
+        assert!(lua_run_async!(lua, r#"env("CARGO_MANIFEST_DIR")"#).is_err());
+
+        assert_eq!(
+            lua_call!(lua, "env", "SCRAPEYCAT_UNSET_FOR_TESTING" => Option<String>),
+            None
         );
 
-        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+        get_state::<TestHttpDriver>(&lua).unwrap().allowed_env =
+            vec!["CARGO_*".to_string(), "HOME".to_string()];
 
         assert_eq!(
-            state.variables.get("{myVariable}"),
-            Some(&results!["hello"])
+            lua_call!(lua, "env", "CARGO_MANIFEST_DIR" => Option<String>),
+            Some(env!("CARGO_MANIFEST_DIR").to_string())
         );
+        assert!(lua_run_async!(lua, r#"env("HOMEPAGE")"#).is_err());
+        assert!(lua_run_async!(lua, r#"env("SCRAPEYCAT_UNSET_FOR_TESTING")"#).is_err());
     }
 
     #[tokio::test]
-    async fn test_lua_var() {
+    async fn test_lua_now() {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
-        let _ = lua_run_async!(
+        get_state::<TestHttpDriver>(&lua).unwrap().fixed_now =
+            Some(Local.with_ymd_and_hms(2024, 6, 1, 9, 5, 7).unwrap());
+
+        lua_run_async!(
             lua,
             r#"
-                get("string://hello")
-                get("string://world")
-                store("myVariable")
+                get("string://" .. now())
+                get("string:///reports/" .. now("%Y%m%d-%H%M") .. ".json")
             "#
-        );
+        )
+        .unwrap();
 
-        let my_variable = lua_call!(lua, "var", "myVariable" => String);
+        {
+            let state = get_state::<TestHttpDriver>(&lua).unwrap();
+            assert_eq!(
+                state.scraper.results(),
+                &results!["2024-06-01", "/reports/20240601-0905.json"]
+            );
+        }
 
-        assert_eq!(my_variable, "hello world");
+        assert!(lua_run_async!(lua, r#"now("%Q")"#).is_err());
     }
 
     #[tokio::test]
@@ -1735,9 +6099,14 @@ mod tests {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         assert!(
             lua_run_async!(
@@ -1755,9 +6124,14 @@ mod tests {
         let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
@@ -1777,9 +6151,14 @@ mod tests {
         let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
@@ -1804,14 +6183,57 @@ mod tests {
         }));
     }
 
+    #[tokio::test]
+    async fn test_collect_as_implicit_arg_for_effect() {
+        let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
+        let script_loader = null_script_loader();
+
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
+
+        lua_run_async!(
+            lua,
+            r#"
+                get("string://hello world")
+                extract("\\S+")
+                collect()
+                effect("notify", {title="News"})
+                clear()
+                collect()
+            "#
+        )
+        .unwrap();
+
+        let invocation = effect_rx.try_recv().unwrap();
+
+        assert_eq!(invocation.name(), "notify");
+        assert_eq!(invocation.args(), &vec!["hello\nworld".to_string()]);
+        assert_eq!(invocation.kwargs().get("title"), Some(&"News".to_string()));
+
+        let state = get_state::<TestHttpDriver>(&lua).unwrap();
+
+        assert_eq!(state.scraper.results(), &results![]);
+    }
+
     #[tokio::test]
     async fn test_results_as_implicit_args_for_effect_with_explicit_args() {
         let (effect_tx, mut effect_rx) = unbounded_channel::<EffectInvocation>();
         let script_loader = null_script_loader();
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
@@ -1848,9 +6270,14 @@ mod tests {
             }
         }));
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
@@ -1880,9 +6307,14 @@ mod tests {
             }
         }));
 
-        let lua =
-            create_lua_context::<TestHttpDriver>(vec![], HashMap::new(), effect_tx, script_loader)
-                .unwrap();
+        let lua = create_lua_context::<TestHttpDriver>(
+            vec![],
+            HashMap::new(),
+            effect_tx,
+            script_loader,
+            EffectRegistry::new(),
+        )
+        .unwrap();
 
         let _ = lua_run_async!(
             lua,
@@ -1943,4 +6375,166 @@ mod tests {
             true
         }));
     }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("").is_ok());
+        assert!(
+            validate(
+                r#"
+                    get("https://example.com")
+                    extract("<b>(.+?)</b>")
+                    effect("notify", { title = "Bold" })
+                "#
+            )
+            .is_ok()
+        );
+
+        // Unknown commands are not syntax errors
+        assert!(validate("noSuchCommand()").is_ok());
+
+        assert!(matches!(
+            validate("get(\"https://example.com\")\nextract(\"a\" = \"b\")\nfirst()\n"),
+            Err(Error::SyntaxError(2, message)) if message.contains("near '='")
+        ));
+
+        assert!(matches!(
+            validate("for i = 1, 2 do\n  get(\"x\")\n"),
+            Err(Error::SyntaxError(3, message)) if message.contains("'end' expected")
+        ));
+    }
+
+    #[test]
+    fn test_validate_context() {
+        let Err(e) = validate("get(\"https://example.com\")\n  extract(\"a\" = \"b\")\nfirst()\n")
+        else {
+            panic!("Should be a syntax error");
+        };
+
+        assert!(
+            e.to_string()
+                .contains("\n  extract(\"a\" = \"b\")\n              ^\n")
+        );
+
+        // Missing tokens are pointed out at the end of the line
+        let Err(e) = validate("for i = 1, 2 do\n  get(\"x\")") else {
+            panic!("Should be a syntax error");
+        };
+
+        assert!(e.to_string().contains("\n  get(\"x\")\n          ^\n"));
+    }
+
+    #[tokio::test]
+    async fn test_run_with_headers() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+
+        let script_loader = Arc::new(RwLock::new(|_name: &str| {
+            Ok(r#"
+                    get("")
+                    header("Accept", "text/html")
+                    get("")
+                    header("User-Agent", "dog")
+                    get("")
+                    clearHeaders()
+                    get("")
+                "#
+            .to_string())
+        }));
+
+        let results = run_with_headers::<HeaderTestHttpDriver>(
+            "script",
+            vec![],
+            HashMap::new(),
+            HashMap::from([("User-Agent".to_string(), "cat".to_string())]),
+            script_loader,
+            effect_tx,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            results,
+            results![
+                r#"Headers({"User-Agent": "cat"})"#,
+                r#"Headers({"Accept": "text/html", "User-Agent": "cat"})"#,
+                r#"Headers({"Accept": "text/html", "User-Agent": "dog"})"#,
+                r#"Headers({})"#
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_with_options_rate_limiter() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+
+        let script_loader = Arc::new(RwLock::new(|_name: &str| {
+            Ok(r#"get("string://a") get("string://b")"#.to_string())
+        }));
+
+        let options = RunOptions {
+            rate_limiter: Some(RateLimiter::new(10.0).unwrap()),
+            ..RunOptions::default()
+        };
+
+        let start = std::time::Instant::now();
+
+        let results = run_with_options::<TestHttpDriver>(
+            "script",
+            vec![],
+            HashMap::new(),
+            options.clone(),
+            script_loader.clone(),
+            effect_tx.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(results, results!["a", "b"]);
+
+        // Runs sharing a rate limiter share the limit
+        run_with_options::<TestHttpDriver>(
+            "script",
+            vec![],
+            HashMap::new(),
+            options,
+            script_loader,
+            effect_tx,
+        )
+        .await
+        .unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[tokio::test]
+    async fn test_run_shares_rate_limiter() {
+        let (effect_tx, _effect_rx) = unbounded_channel::<EffectInvocation>();
+
+        let script_loader = Arc::new(RwLock::new(|name: &str| match name {
+            "inner" => Ok(r#"get("string://b")"#.to_string()),
+            _ => Ok(r#"get("string://a") run("inner") get("string://c")"#.to_string()),
+        }));
+
+        let options = RunOptions {
+            rate_limiter: Some(RateLimiter::new(10.0).unwrap()),
+            ..RunOptions::default()
+        };
+
+        let start = std::time::Instant::now();
+
+        let results = run_with_options::<TestHttpDriver>(
+            "outer",
+            vec![],
+            HashMap::new(),
+            options,
+            script_loader,
+            effect_tx,
+        )
+        .await
+        .unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(results, results!["a", "b", "c"]);
+    }
 }
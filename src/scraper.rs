@@ -1,16 +1,88 @@
-use std::{cmp::min, future::Future, marker::PhantomData};
+use std::{
+    cmp::{Ordering, min},
+    collections::HashSet,
+    future::Future,
+    marker::PhantomData,
+    net::SocketAddr,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
 
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
 use im::{HashMap, Vector, vector};
 use jsonpath_rust::JsonPath;
 use log::debug;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
 use regex::Regex;
 use reqwest::{
-    ClientBuilder,
-    header::{HeaderMap, HeaderName, InvalidHeaderValue},
+    Client, ClientBuilder, Method, Proxy, Response, Url,
+    header::{CONTENT_TYPE, HeaderMap, HeaderName, InvalidHeaderValue, SET_COOKIE},
+    multipart::{Form, Part},
+};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use tokio::task::JoinSet;
+
+use crate::{
+    Error,
+    circuit_breaker::CircuitBreaker,
+    cookies::CookieJar,
+    polite_delay::PoliteDelay,
+    rate_limiter::RateLimiter,
+    response_cache::{MemoryCache, ResponseCache},
+    robots::{Robots, RobotsCache, origin_and_path},
+    util::{
+        rng::Rng,
+        sitemap::{Sitemap, parse_sitemap},
+    },
 };
-use serde_json::Value as JsonValue;
 
-use crate::Error;
+/// The query parameters removed by [Scraper::canonicalize_urls] unless others are given. A
+/// trailing `*` matches any parameter name starting with what precedes it.
+pub const DEFAULT_TRACKING_PARAMS: &[&str] = &["utm_*", "fbclid", "gclid"];
+
+/// The characters percent-encoded by [Scraper::url_encode]: all but the unreserved characters
+/// of RFC 3986, making encoded results safe to use as any component of a URL.
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Matches runs of whitespace, including newlines, for [Scraper::normalize_whitespace].
+static WHITESPACE_RUN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s+").expect("Should be a valid regex"));
+
+/// Matches `<script>` elements, capturing their attributes and contents, for [Scraper::json_ld].
+static SCRIPT_ELEMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<script\b([^>]*)>(.*?)</script\s*>").expect("Should be a valid regex")
+});
+
+/// Matches the `type` attribute of a JSON-LD `<script>` element, for [Scraper::json_ld].
+static JSON_LD_TYPE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\btype\s*=\s*["']?\s*application/ld\+json\b"#)
+        .expect("Should be a valid regex")
+});
+
+/// Matches the `charset` parameter of a `Content-Type` header value, for [decode_body].
+static CHARSET_PARAM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i);\s*charset\s*=\s*["']?([^"';\s]+)"#).expect("Should be a valid regex")
+});
+
+/// The size in bytes of the largest response body read by drivers, unless set otherwise using
+/// [Scraper::set_max_body_size].
+pub const DEFAULT_MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+
+/// A function applied to each response body before it becomes a result, see
+/// [Scraper::add_response_transformer].
+pub type ResponseTransformer = Arc<dyn Fn(&str) -> Result<String, Error> + Send + Sync>;
+
+/// The fetches of [Scraper::get_all] in progress, each tagged with the index of its URL.
+type GetAllTasks<H> = JoinSet<(usize, Result<(Scraper<H>, HttpResponse), Error>)>;
+
+/// The user-agent whose `robots.txt` rules apply when no `User-Agent` header has been set.
+const DEFAULT_ROBOTS_USER_AGENT: &str = "scrapeycat";
 
 #[derive(Debug)]
 pub enum HttpHeaders<'a> {
@@ -18,24 +90,253 @@ pub enum HttpHeaders<'a> {
     Headers(&'a HashMap<String, String>),
 }
 
+/// A response body along with the response headers and the status code, if known to the
+/// driver. Header names are lowercase, and the values of repeated headers are joined by `", "`,
+/// except for `set-cookie` whose values are joined by newlines since cookie attributes may
+/// themselves contain commas.
+#[derive(Debug, Clone, Default)]
+pub struct HttpResponse {
+    pub body: String,
+    pub headers: HashMap<String, String>,
+    pub status: Option<u16>,
+}
+
+/// The fields of a `multipart/form-data` request: text fields, and file fields given by the path
+/// of the file to upload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultipartForm {
+    pub fields: Vec<(String, String)>,
+    pub files: Vec<(String, String)>,
+}
+
+/// The method of an HTTP request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HttpMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Delete,
+    Head,
+}
+
+impl HttpMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Head => "HEAD",
+        }
+    }
+}
+
+impl std::fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HttpMethod {
+    type Err = Error;
+
+    /// Parse a method name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            HttpMethod::Get,
+            HttpMethod::Post,
+            HttpMethod::Put,
+            HttpMethod::Delete,
+            HttpMethod::Head,
+        ]
+        .into_iter()
+        .find(|method| method.as_str().eq_ignore_ascii_case(s.trim()))
+        .ok_or_else(|| Error::ParseError(format!("Unsupported HTTP method `{s}`")))
+    }
+}
+
+impl From<HttpMethod> for Method {
+    fn from(method: HttpMethod) -> Method {
+        match method {
+            HttpMethod::Get => Method::GET,
+            HttpMethod::Post => Method::POST,
+            HttpMethod::Put => Method::PUT,
+            HttpMethod::Delete => Method::DELETE,
+            HttpMethod::Head => Method::HEAD,
+        }
+    }
+}
+
+/// How [Scraper::extract_by_mode] treats results: as plain text matched using regular
+/// expressions, as JSON queried using JSONPath, or as HTML matched using regular expressions
+/// with character references decoded in the matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    #[default]
+    Text,
+    Json,
+    Html,
+}
+
+impl FromStr for ParseMode {
+    type Err = Error;
+
+    /// Parse a mode name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(ParseMode::Text),
+            "json" => Ok(ParseMode::Json),
+            "html" => Ok(ParseMode::Html),
+            _ => Err(Error::ParseError(format!(
+                "Invalid parse mode `{s}`, expected `text`, `json` or `html`"
+            ))),
+        }
+    }
+}
+
+/// The body of a request made using [HttpDriver::request].
+#[derive(Debug, Clone, Copy, Default)]
+pub enum RequestBody<'a> {
+    #[default]
+    Empty,
+    Text(&'a str),
+    Multipart(&'a MultipartForm),
+}
+
+/// A request to make using [HttpDriver::request]. Unless `decompress` is false, compressed
+/// responses are decompressed by drivers able to do so. Drivers reading response bodies
+/// incrementally fail rather than read a body larger than `max_body_size` bytes.
+#[derive(Debug)]
+pub struct RequestSpec<'a> {
+    pub method: HttpMethod,
+    pub url: &'a str,
+    pub body: RequestBody<'a>,
+    pub headers: HttpHeaders<'a>,
+    pub decompress: bool,
+    pub max_body_size: usize,
+}
+
+impl<'a> RequestSpec<'a> {
+    /// A request for `url` using the given method, without a body.
+    pub fn new(method: HttpMethod, url: &'a str, headers: HttpHeaders<'a>) -> RequestSpec<'a> {
+        RequestSpec {
+            method,
+            url,
+            body: RequestBody::Empty,
+            headers,
+            decompress: true,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// A GET request for `url` without a body.
+    pub fn get(url: &'a str, headers: HttpHeaders<'a>) -> RequestSpec<'a> {
+        RequestSpec::new(HttpMethod::Get, url, headers)
+    }
+
+    /// A `multipart/form-data` POST request for `url` with the given form.
+    pub fn post_multipart(
+        url: &'a str,
+        headers: HttpHeaders<'a>,
+        form: &'a MultipartForm,
+    ) -> RequestSpec<'a> {
+        RequestSpec::new(HttpMethod::Post, url, headers).with_body(RequestBody::Multipart(form))
+    }
+
+    pub fn with_body(self, body: RequestBody<'a>) -> RequestSpec<'a> {
+        RequestSpec { body, ..self }
+    }
+
+    pub fn with_decompress(self, decompress: bool) -> RequestSpec<'a> {
+        RequestSpec { decompress, ..self }
+    }
+
+    pub fn with_max_body_size(self, max_body_size: usize) -> RequestSpec<'a> {
+        RequestSpec {
+            max_body_size,
+            ..self
+        }
+    }
+}
+
 // #[allow(async_fn_in_trait)]
 pub trait HttpDriver: Clone {
+    /// Make the given request, routing it through the given proxy, if any, and connecting to
+    /// the given addresses for the given hosts instead of resolving them.
+    fn request(
+        spec: RequestSpec<'_>,
+        proxy: Option<&str>,
+        resolve: &HashMap<String, SocketAddr>,
+    ) -> impl Future<Output = Result<HttpResponse, Error>> + Send;
+
+    /// Make a GET request for `url` using [HttpDriver::request], returning the response text.
     fn get(
         url: &str,
         headers: HttpHeaders<'_>,
-    ) -> impl Future<Output = Result<String, Error>> + Send;
+    ) -> impl Future<Output = Result<String, Error>> + Send {
+        Self::get_with_proxy(url, headers, None)
+    }
+
+    /// Like [HttpDriver::get], but routing the request through the given proxy, if any.
+    fn get_with_proxy(
+        url: &str,
+        headers: HttpHeaders<'_>,
+        proxy: Option<&str>,
+    ) -> impl Future<Output = Result<String, Error>> + Send {
+        async move { Ok(Self::get_response(url, headers, proxy).await?.body) }
+    }
+
+    /// Like [HttpDriver::get_with_proxy], but also capturing the response headers.
+    fn get_response(
+        url: &str,
+        headers: HttpHeaders<'_>,
+        proxy: Option<&str>,
+    ) -> impl Future<Output = Result<HttpResponse, Error>> + Send {
+        async move { Self::get_response_resolved(url, headers, proxy, &HashMap::new()).await }
+    }
 
-    // TODO: post(url, content)
+    /// Like [HttpDriver::get_response], but connecting to the given addresses for the given hosts
+    /// instead of resolving them.
+    fn get_response_resolved(
+        url: &str,
+        headers: HttpHeaders<'_>,
+        proxy: Option<&str>,
+        resolve: &HashMap<String, SocketAddr>,
+    ) -> impl Future<Output = Result<HttpResponse, Error>> + Send {
+        async move { Self::request(RequestSpec::get(url, headers), proxy, resolve).await }
+    }
 
-    // TODO(?): other request methods?
+    /// Make a `multipart/form-data` POST request for `url` with the given form, otherwise like
+    /// [HttpDriver::get_response_resolved].
+    fn post_multipart(
+        url: &str,
+        headers: HttpHeaders<'_>,
+        proxy: Option<&str>,
+        resolve: &HashMap<String, SocketAddr>,
+        form: &MultipartForm,
+    ) -> impl Future<Output = Result<HttpResponse, Error>> + Send {
+        async move {
+            Self::request(
+                RequestSpec::post_multipart(url, headers, form),
+                proxy,
+                resolve,
+            )
+            .await
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct NullHttpDriver;
 
 impl HttpDriver for NullHttpDriver {
-    async fn get(_url: &str, _headers: HttpHeaders<'_>) -> Result<String, Error> {
-        Ok("".to_string())
+    async fn request(
+        _spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        Ok(HttpResponse::default())
     }
 }
 
@@ -43,11 +344,69 @@ impl HttpDriver for NullHttpDriver {
 pub struct ReqwestHttpDriver;
 
 impl HttpDriver for ReqwestHttpDriver {
-    async fn get(url: &str, headers: HttpHeaders<'_>) -> Result<String, Error> {
+    async fn request(
+        spec: RequestSpec<'_>,
+        proxy: Option<&str>,
+        resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let client = Self::client(&spec.headers, proxy, resolve, spec.decompress)?;
+
+        debug!(
+            "reqwest http driver: {} request to {} (headers={:?}, proxy={proxy:?}, \
+             resolve={resolve:?}, body={:?})",
+            spec.method, spec.url, spec.headers, spec.body
+        );
+
+        let request = client.request(spec.method.into(), spec.url);
+
+        let request = match spec.body {
+            RequestBody::Empty => request,
+            RequestBody::Text(body) => request.body(body.to_string()),
+            RequestBody::Multipart(form) => request.multipart(Self::multipart(form).await?),
+        };
+
+        Self::http_response(spec.url, request.send().await?, spec.max_body_size).await
+    }
+}
+
+impl ReqwestHttpDriver {
+    /// Build the body of a `multipart/form-data` request, reading the files to upload.
+    async fn multipart(form: &MultipartForm) -> Result<Form, Error> {
+        let mut multipart = Form::new();
+
+        for (name, value) in &form.fields {
+            multipart = multipart.text(name.clone(), value.clone());
+        }
+
+        for (name, path) in &form.files {
+            let file_name = Path::new(path)
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            multipart = multipart.part(
+                name.clone(),
+                Part::bytes(tokio::fs::read(path).await?).file_name(file_name),
+            );
+        }
+
+        Ok(multipart)
+    }
+
+    /// Build a client sending the given headers, routing requests through the given proxy, if
+    /// any, and connecting to the given addresses for the given hosts. With `decompress`, the
+    /// client accepts and decompresses the response encodings enabled by the `gzip`, `brotli` and
+    /// `deflate` features.
+    fn client(
+        headers: &HttpHeaders<'_>,
+        proxy: Option<&str>,
+        resolve: &HashMap<String, SocketAddr>,
+        decompress: bool,
+    ) -> Result<Client, Error> {
         let mut reqwest_headers = HeaderMap::new();
 
         if let HttpHeaders::Headers(map) = headers {
-            for (key, value) in map {
+            for (key, value) in map.iter() {
                 reqwest_headers.insert(
                     HeaderName::from_bytes(key.as_bytes())
                         .map_err(|e| Error::HTTPDriverError(e.to_string()))?,
@@ -58,16 +417,97 @@ impl HttpDriver for ReqwestHttpDriver {
             }
         }
 
-        let client = ClientBuilder::new()
-            .default_headers(reqwest_headers)
-            .build()?;
+        let mut client_builder = ClientBuilder::new().default_headers(reqwest_headers);
 
-        debug!("reqwest http driver: request to {url} (headers={headers:?})");
+        #[cfg(feature = "gzip")]
+        {
+            client_builder = client_builder.gzip(decompress);
+        }
+
+        #[cfg(feature = "brotli")]
+        {
+            client_builder = client_builder.brotli(decompress);
+        }
+
+        #[cfg(feature = "deflate")]
+        {
+            client_builder = client_builder.deflate(decompress);
+        }
+
+        #[cfg(not(any(feature = "gzip", feature = "brotli", feature = "deflate")))]
+        let _ = decompress;
+
+        if let Some(proxy) = proxy {
+            client_builder = client_builder.proxy(Proxy::all(proxy)?);
+        }
+
+        for (host, addr) in resolve {
+            client_builder = client_builder.resolve(host, *addr);
+        }
+
+        Ok(client_builder.build()?)
+    }
+
+    /// Read the body and headers of the response to a request for `url`, failing with
+    /// [Error::HTTPDriverError] as soon as the body is known to be larger than `max_body_size`
+    /// bytes, without reading the rest of it.
+    async fn http_response(
+        url: &str,
+        mut response: Response,
+        max_body_size: usize,
+    ) -> Result<HttpResponse, Error> {
+        let mut response_headers: HashMap<String, String> = HashMap::new();
+
+        for (name, value) in response.headers() {
+            let value = String::from_utf8_lossy(value.as_bytes()).to_string();
+
+            let separator = if name == SET_COOKIE { "\n" } else { ", " };
 
-        let result = client.get(url).send().await?.text().await?;
+            response_headers =
+                response_headers.update_with(name.as_str().to_string(), value, |old, new| {
+                    format!("{old}{separator}{new}")
+                });
+        }
+
+        let status = response.status().as_u16();
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string());
+
+        let too_large = || {
+            Error::HTTPDriverError(format!(
+                "response from {url} exceeds the maximum body size of {max_body_size} bytes"
+            ))
+        };
+
+        if response
+            .content_length()
+            .is_some_and(|length| length > max_body_size as u64)
+        {
+            return Err(too_large());
+        }
+
+        let mut bytes = Vec::new();
+
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > max_body_size {
+                return Err(too_large());
+            }
+
+            bytes.extend_from_slice(&chunk);
+        }
+
+        let body = decode_body(&bytes, content_type.as_deref());
 
         debug!("reqwest http driver: response from {url}");
-        Ok(result)
+
+        Ok(HttpResponse {
+            body,
+            headers: response_headers,
+            status: Some(status),
+        })
     }
 }
 
@@ -75,6 +515,25 @@ impl HttpDriver for ReqwestHttpDriver {
 pub struct Scraper<H: HttpDriver> {
     results: Vector<String>,
     headers: HashMap<String, String>,
+    proxies: Vector<String>,
+    next_proxy: usize,
+    resolve: HashMap<String, SocketAddr>,
+    cookie_jar: Option<CookieJar>,
+    max_meta_refresh_hops: usize,
+    circuit_breaker: Option<CircuitBreaker>,
+    polite_delay: Option<PoliteDelay>,
+    rate_limiter: Option<RateLimiter>,
+    robots: Option<RobotsCache>,
+    response_cache: Option<ResponseCache>,
+    memory_cache: Option<MemoryCache>,
+    last_response_headers: HashMap<String, String>,
+    last_get_url: Option<String>,
+    time_budget: Option<Duration>,
+    decompress: bool,
+    max_body_size: usize,
+    parse_mode: ParseMode,
+    response_transformers: Vector<ResponseTransformer>,
+    rng: Rng,
     _marker: PhantomData<H>,
 }
 
@@ -101,7 +560,6 @@ where
 }
 
 // result.append(" Rules:")
-// result.append("   $random N        Take N results randomly")
 // result.append("   $str.replace S1 S2   Replace all occurrences of string S1 with S2")
 // result.append("   $re.replace P S       Replace all occurrences of regex pattern P with S")
 // result.append("   $post URL        POST to URL, keyvals from query string ?k1=v1&k2=v2...")
@@ -119,6 +577,25 @@ where
         Scraper {
             results: Vector::new(),
             headers: HashMap::new(),
+            proxies: Vector::new(),
+            next_proxy: 0,
+            resolve: HashMap::new(),
+            cookie_jar: None,
+            max_meta_refresh_hops: 0,
+            circuit_breaker: None,
+            polite_delay: None,
+            rate_limiter: None,
+            robots: None,
+            response_cache: None,
+            memory_cache: None,
+            last_response_headers: HashMap::new(),
+            last_get_url: None,
+            time_budget: None,
+            decompress: true,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            parse_mode: ParseMode::Text,
+            response_transformers: Vector::new(),
+            rng: Rng::from_default_seed(),
             _marker: PhantomData,
         }
     }
@@ -131,476 +608,4148 @@ where
         Scraper { results, ..self }
     }
 
+    /// The headers of the most recent response fetched by [Scraper::get],
+    /// [Scraper::get_paginated] or [Scraper::sitemap_urls], with lowercase names.
+    pub fn last_response_headers(&self) -> &HashMap<String, String> {
+        &self.last_response_headers
+    }
+
+    /// Fetch `url` and append the response text as a new result. If a pool of proxies has been
+    /// configured using [Scraper::set_proxies], each request is routed through the next proxy
+    /// in the pool, round-robin. If a cookie jar has been configured using
+    /// [Scraper::set_cookie_jar], matching cookies are sent with the request and any cookies set
+    /// by the response are stored in the jar. If meta refresh following has been enabled using
+    /// [Scraper::set_max_meta_refresh_hops], pages redirecting by means of a
+    /// `<meta http-equiv="refresh">` tag are followed, and the text of the final page is
+    /// appended instead.
     pub async fn get(&self, url: &str) -> Result<Scraper<H>, Error> {
-        let mut new_results = self.results.clone();
+        let requested_url = url.to_string();
+        let mut url = url.to_string();
+        let (mut scraper, mut response) = self.fetch(&url).await?;
+        let mut hops = 0;
 
-        new_results.push_back(H::get(url, HttpHeaders::Headers(&self.headers)).await?);
+        while hops < self.max_meta_refresh_hops
+            && let Some(next) = meta_refresh_url(&response.body)
+        {
+            url = resolve_url(&url, next);
+            debug!("scraper: get: following meta refresh to {url}");
 
-        Ok(Scraper::<H> {
-            results: new_results,
-            ..self.clone()
-        })
+            (scraper, response) = scraper.fetch(&url).await?;
+            hops += 1;
+        }
+
+        scraper
+            .results
+            .push_back(self.transform_body(response.body)?);
+        scraper.last_get_url = Some(requested_url);
+        Ok(scraper)
     }
 
-    pub fn extract(&self, pattern: &str) -> Result<Scraper<H>, Error> {
-        let regex = Regex::new(pattern)?;
+    /// If there are no results other than empty ones, fetch the URL last requested using
+    /// [Scraper::get] again, up to `times` times with `delay` before each attempt, until a
+    /// non-empty result is returned. Each attempt replaces the results with the response text.
+    /// Does nothing if there are non-empty results, or if no URL has been requested using
+    /// [Scraper::get].
+    pub async fn retry_if_empty(&self, times: usize, delay: Duration) -> Result<Scraper<H>, Error> {
+        let Some(url) = &self.last_get_url else {
+            return Ok(self.clone());
+        };
 
-        Ok(Scraper {
-            results: self
-                .results
-                .iter()
-                .flat_map(|str| {
-                    regex
-                        .captures_iter(str)
-                        .filter_map(|matched| {
-                            let group = if matched.len() > 1 { 1 } else { 0 };
+        let mut scraper = self.clone();
+        let mut attempts = 0;
 
-                            matched.get(group).map(|x| x.as_str().to_owned())
-                        })
-                        .collect::<Vector<_>>()
-                })
-                .collect(),
-            ..self.clone()
-        })
+        while attempts < times && scraper.results.iter().all(|result| result.is_empty()) {
+            attempts += 1;
+            debug!("scraper: retry_if_empty: attempt {attempts} of {times} for {url}");
+
+            tokio::time::sleep(delay).await;
+
+            scraper = Scraper {
+                results: Vector::new(),
+                ..scraper
+            }
+            .get(url)
+            .await?;
+        }
+
+        Ok(scraper)
     }
 
-    pub fn delete(&self, pattern: &str) -> Result<Scraper<H>, Error> {
-        let regex = Regex::new(pattern)?;
+    /// Fetch `url`, then keep following the `rel="next"` URL given in the `Link` response header
+    /// (RFC 8288) until there is no next page or `max_pages` pages have been fetched in total,
+    /// appending the response text of each page as a new result. Following stops early once the
+    /// time budget, if any, has run out.
+    pub async fn get_paginated(&self, url: &str, max_pages: usize) -> Result<Scraper<H>, Error> {
+        let mut scraper = self.clone();
+        let mut next_url = Some(url.to_string());
+        let mut num_pages = 0;
+        let start = Instant::now();
 
-        Ok(Scraper {
-            results: self
+        while let Some(url) = next_url
+            && num_pages < max_pages
+        {
+            if num_pages > 0 && self.is_time_budget_spent(start) {
+                debug!("scraper: get_paginated: time budget spent, not following {url}");
+                break;
+            }
+
+            let response;
+            (scraper, response) = scraper.fetch(&url).await?;
+
+            scraper
                 .results
-                .iter()
-                .map(|str| regex.replace_all(str, "").into_owned())
-                .collect(),
-            ..self.clone()
-        })
-    }
+                .push_back(self.transform_body(response.body)?);
+            num_pages += 1;
 
-    pub fn retain(&self, pattern: &str) -> Result<Scraper<H>, Error> {
-        let regex = Regex::new(pattern)?;
+            next_url = response
+                .headers
+                .get("link")
+                .and_then(|link| next_link_url(link))
+                .map(|next| resolve_url(&url, next));
 
-        let mut results = self.results.clone();
-        results.retain(|str| regex.is_match(str));
+            debug!("scraper: get_paginated: page {num_pages} at {url}, next: {next_url:?}");
+        }
 
-        Ok(Scraper {
-            results,
-            ..self.clone()
-        })
+        Ok(scraper)
     }
 
-    pub fn discard(&self, pattern: &str) -> Result<Scraper<H>, Error> {
-        let regex = Regex::new(pattern)?;
+    /// Fetch all of `urls` concurrently, making at most `max_concurrent` requests at a time, and
+    /// append the response texts as new results in the same order as `urls`. Each request is
+    /// made as by [Scraper::get], except that meta refresh redirects are not followed and that
+    /// cookies set by the responses are only stored once all of the responses have arrived.
+    pub async fn get_all(&self, urls: &[String], max_concurrent: usize) -> Result<Scraper<H>, Error>
+    where
+        H: Send + Sync + 'static,
+    {
+        if max_concurrent == 0 {
+            return Err(Error::ValueOutOfRangeError);
+        }
 
-        let mut results = self.results.clone();
-        results.retain(|str| !regex.is_match(str));
+        let mut responses: Vec<Option<HttpResponse>> = vec![None; urls.len()];
+        let mut tasks = JoinSet::new();
 
-        Ok(Scraper {
-            results,
-            ..self.clone()
-        })
-    }
+        for (index, url) in urls.iter().enumerate() {
+            while tasks.len() >= max_concurrent {
+                Scraper::<H>::join_get_all_task(&mut tasks, &mut responses).await?;
+            }
 
-    pub fn first(&self) -> Scraper<H> {
-        Scraper {
-            results: if self.results.is_empty() {
-                vector![]
-            } else {
-                self.results.take(1)
-            },
-            ..self.clone()
+            let scraper = Scraper {
+                next_proxy: (self.next_proxy + index) % self.proxies.len().max(1),
+                cookie_jar: self.cookie_jar.as_ref().map(|jar| jar.unpersisted()),
+                ..self.clone()
+            };
+            let url = url.clone();
+
+            tasks.spawn(async move { (index, scraper.fetch(&url).await) });
         }
-    }
 
-    pub fn last(&self) -> Scraper<H> {
-        Scraper {
-            results: if self.results.is_empty() {
-                vector![]
-            } else {
-                vector![self.results.back().unwrap().clone()]
-            },
-            ..self.clone()
+        while !tasks.is_empty() {
+            Scraper::<H>::join_get_all_task(&mut tasks, &mut responses).await?;
         }
-    }
 
-    pub fn take(&self, n: usize) -> Scraper<H> {
-        Scraper {
-            results: if self.results.is_empty() {
-                vector![]
-            } else {
-                self.results.take(min(n, self.results.len()))
-            },
+        let mut scraper = Scraper {
+            next_proxy: (self.next_proxy + urls.len()) % self.proxies.len().max(1),
             ..self.clone()
+        };
+
+        // Cookies set by the responses are stored in the order of the URLs, once all of the
+        // responses have arrived
+        let mut cookies_stored = false;
+
+        for (url, response) in urls.iter().zip(responses) {
+            let response = response.unwrap_or_default();
+
+            if let Some(jar) = &scraper.cookie_jar
+                && let Some(set_cookie) = response.headers.get(SET_COOKIE.as_str())
+            {
+                scraper.cookie_jar = Some(jar.store(url, set_cookie));
+                cookies_stored = true;
+            }
+
+            scraper
+                .results
+                .push_back(self.transform_body(response.body)?);
         }
-    }
 
-    pub fn drop(&self, n: usize) -> Scraper<H> {
-        Scraper {
-            results: if self.results.is_empty() {
-                vector![]
-            } else {
-                self.results.skip(min(n, self.results.len()))
-            },
-            ..self.clone()
+        if cookies_stored && let Some(jar) = &scraper.cookie_jar {
+            jar.save()?;
         }
+
+        Ok(scraper)
     }
 
-    pub fn prepend(&self, prefix: &str) -> Scraper<H> {
-        Scraper {
-            results: self
-                .results
-                .iter()
-                .map(|str| format!("{prefix}{str}").to_string())
-                .collect(),
-            ..self.clone()
+    /// Wait for one of the tasks spawned by [Scraper::get_all] to finish, storing its response
+    /// at its index in `responses`.
+    async fn join_get_all_task(
+        tasks: &mut GetAllTasks<H>,
+        responses: &mut [Option<HttpResponse>],
+    ) -> Result<(), Error>
+    where
+        H: Send + Sync + 'static,
+    {
+        if let Some(joined) = tasks.join_next().await {
+            let (index, fetched) = joined.map_err(|e| Error::HTTPDriverError(e.to_string()))?;
+            responses[index] = Some(fetched?.1);
         }
+
+        Ok(())
     }
 
-    pub fn append(&self, suffix: &str) -> Scraper<H> {
-        Scraper {
-            results: self
-                .results
-                .iter()
-                .map(|str| format!("{str}{suffix}").to_string())
-                .collect(),
-            ..self.clone()
-        }
+    /// Upload `form` to `url` as a `multipart/form-data` POST request and append the response
+    /// text as a new result. The request is made as by [Scraper::get], except that responses
+    /// are never cached and meta refresh redirects are not followed.
+    pub async fn post_multipart(
+        &self,
+        url: &str,
+        form: &MultipartForm,
+    ) -> Result<Scraper<H>, Error> {
+        let (mut scraper, response) = self
+            .send(HttpMethod::Post, url, RequestBody::Multipart(form))
+            .await?;
+
+        scraper
+            .results
+            .push_back(self.transform_body(response.body)?);
+        Ok(scraper)
     }
 
-    pub fn join(&self, separator: &str) -> Scraper<H> {
-        Scraper {
-            results: if self.results.is_empty() {
-                vector![]
-            } else {
-                vector![
-                    self.results
-                        .iter()
-                        .cloned()
-                        .collect::<Vec<_>>()
-                        .join(separator)
-                ]
-            },
-            ..self.clone()
+    /// Make a request for `url` using the given method, sending `body` if given, and append the
+    /// response text as a new result. The request is made as by [Scraper::get], except that
+    /// responses are never cached and meta refresh redirects are not followed. The response to a
+    /// HEAD request has an empty body.
+    pub async fn request(
+        &self,
+        method: HttpMethod,
+        url: &str,
+        body: Option<&str>,
+    ) -> Result<Scraper<H>, Error> {
+        let (mut scraper, response) = self
+            .send(method, url, body.map(RequestBody::Text).unwrap_or_default())
+            .await?;
+
+        scraper
+            .results
+            .push_back(self.transform_body(response.body)?);
+        Ok(scraper)
+    }
+
+    /// Parse each result as an XML sitemap and replace the results with the page URLs listed.
+    /// Results that are sitemap indexes have each of the sitemaps they list fetched and parsed
+    /// in turn, but sitemap indexes listed within those are not followed. Sitemaps are no longer
+    /// fetched once the time budget, if any, has run out.
+    pub async fn sitemap_urls(&self) -> Result<Scraper<H>, Error> {
+        let mut scraper = self.clone();
+        let mut urls = Vector::new();
+        let start = Instant::now();
+
+        for result in self.results.iter() {
+            match parse_sitemap(result)? {
+                Sitemap::UrlSet(locs) => urls.extend(locs),
+                Sitemap::Index(sitemap_urls) => {
+                    for sitemap_url in sitemap_urls {
+                        if self.is_time_budget_spent(start) {
+                            debug!(
+                                "scraper: sitemap_urls: time budget spent, skipping {sitemap_url}"
+                            );
+                            continue;
+                        }
+
+                        let response;
+                        (scraper, response) = scraper.fetch(&sitemap_url).await?;
+
+                        match parse_sitemap(&response.body)? {
+                            Sitemap::UrlSet(locs) => urls.extend(locs),
+                            Sitemap::Index(_) => {
+                                debug!("scraper: sitemap_urls: not following index {sitemap_url}")
+                            }
+                        }
+                    }
+                }
+            }
         }
+
+        Ok(Scraper {
+            results: urls,
+            ..scraper
+        })
     }
 
-    pub fn clear(&self) -> Scraper<H> {
-        Scraper {
-            results: vector![],
-            ..self.clone()
+    /// Check that the `Content-Type` header of the most recent response matches the regex
+    /// `pattern`, failing with [Error::ContentTypeMismatchError] if it does not or if the
+    /// response had no such header.
+    pub fn expect_content_type(&self, pattern: &str) -> Result<Scraper<H>, Error> {
+        let content_type = self
+            .last_response_headers
+            .get("content-type")
+            .map(|value| value.as_str())
+            .unwrap_or_default();
+
+        if Regex::new(pattern)?.is_match(content_type) {
+            Ok(self.clone())
+        } else {
+            Err(Error::ContentTypeMismatchError(
+                content_type.to_string(),
+                pattern.to_string(),
+            ))
         }
     }
 
-    pub fn set_header(&self, key: String, value: String) -> Scraper<H> {
-        Scraper {
-            headers: self.headers.update(key, value),
-            ..self.clone()
+    /// Make a single request for `url` unless refused by the circuit breaker, returning the
+    /// response along with the scraper updated to use the following proxy and to hold any cookies
+    /// set by the response. If a response cache has been configured using
+    /// [Scraper::set_response_cache] and holds a fresh response, that response is returned
+    /// without making a request.
+    async fn fetch(&self, url: &str) -> Result<(Scraper<H>, HttpResponse), Error> {
+        if let Some(memory_cache) = &self.memory_cache
+            && let Some(response) = memory_cache.get(url)
+        {
+            let scraper = Scraper {
+                last_response_headers: response.headers.clone(),
+                ..self.clone()
+            };
+
+            return Ok((scraper, response));
+        }
+
+        let request_headers = self.request_headers(url);
+
+        if let Some(cache) = &self.response_cache
+            && let Some(response) = cache.load(url, &request_headers)?
+        {
+            let scraper = Scraper {
+                last_response_headers: response.headers.clone(),
+                ..self.clone()
+            };
+
+            return Ok((scraper, response));
         }
+
+        let (scraper, response) = self.send(HttpMethod::Get, url, RequestBody::Empty).await?;
+
+        if let Some(cache) = &self.response_cache {
+            cache.store(url, &request_headers, &response)?;
+        }
+
+        if let Some(memory_cache) = &self.memory_cache {
+            memory_cache.insert(url, &response);
+        }
+
+        Ok((scraper, response))
     }
 
-    pub fn clear_headers(&self) -> Scraper<H> {
-        Scraper {
-            headers: HashMap::new(),
+    /// Apply the response transformers, in the order added, to a response body.
+    fn transform_body(&self, body: String) -> Result<String, Error> {
+        self.response_transformers
+            .iter()
+            .try_fold(body, |body, transformer| transformer(&body))
+    }
+
+    /// Make a request for `url` using the given method and body, unless refused by the circuit
+    /// breaker or `robots.txt`, after waiting for the polite delay and rate limiter, if any.
+    /// Returns the response along with the scraper updated to use the following proxy and to
+    /// hold the response headers and any cookies set by the response.
+    async fn send(
+        &self,
+        method: HttpMethod,
+        url: &str,
+        body: RequestBody<'_>,
+    ) -> Result<(Scraper<H>, HttpResponse), Error> {
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.check(url)?;
+        }
+
+        if let Some(robots) = &self.robots {
+            self.check_robots(robots, url).await?;
+        }
+
+        if let Some(polite_delay) = &self.polite_delay {
+            polite_delay.wait().await;
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.wait().await;
+        }
+
+        let request_headers = self.request_headers(url);
+
+        let response = H::request(
+            RequestSpec::new(method, url, HttpHeaders::Headers(&request_headers))
+                .with_body(body)
+                .with_decompress(self.decompress)
+                .with_max_body_size(self.max_body_size),
+            self.proxy(),
+            &self.resolve,
+        )
+        .await;
+
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.record(url, response.is_ok());
+        }
+
+        let response = response?;
+
+        let mut scraper = Scraper {
+            next_proxy: self.following_proxy(),
+            last_response_headers: response.headers.clone(),
             ..self.clone()
+        };
+
+        if let Some(jar) = &self.cookie_jar
+            && let Some(set_cookie) = response.headers.get(SET_COOKIE.as_str())
+        {
+            let jar = jar.store(url, set_cookie);
+            jar.save()?;
+            scraper.cookie_jar = Some(jar);
         }
+
+        Ok((scraper, response))
     }
 
-    pub fn jsonpath(&self, expr: &str) -> Result<Scraper<H>, Error> {
+    /// Check that `url` may be fetched according to the `robots.txt` of its origin, fetching and
+    /// caching the rules for the origin if needed. As in RFC 9309, origins responding to the
+    /// request for `robots.txt` with a 4xx status allow everything, while origins responding
+    /// with a 5xx status or not at all disallow everything, without caching so that the request
+    /// for `robots.txt` is retried before the next request to the origin.
+    async fn check_robots(&self, robots: &RobotsCache, url: &str) -> Result<(), Error> {
+        let Some((origin, path)) = origin_and_path(url) else {
+            return Ok(());
+        };
+
+        let rules = match robots.get(&origin) {
+            Some(rules) => rules,
+            None => {
+                let robots_url = format!("{origin}/robots.txt");
+
+                if let Some(polite_delay) = &self.polite_delay {
+                    polite_delay.wait().await;
+                }
+
+                if let Some(rate_limiter) = &self.rate_limiter {
+                    rate_limiter.wait().await;
+                }
+
+                let rules = match H::get_response_resolved(
+                    &robots_url,
+                    HttpHeaders::Headers(&self.headers),
+                    self.proxy(),
+                    &self.resolve,
+                )
+                .await
+                {
+                    Ok(HttpResponse {
+                        status: Some(400..=499),
+                        ..
+                    }) => Robots::allow_all(),
+                    Ok(HttpResponse {
+                        status: Some(status @ 500..=599),
+                        ..
+                    }) => {
+                        debug!("scraper: {robots_url} unavailable ({status}), disallowing all");
+                        return Err(Error::RobotsDisallowedError(url.to_string()));
+                    }
+                    Ok(response) => Robots::parse(&response.body),
+                    Err(e) => {
+                        debug!("scraper: unable to fetch {robots_url}, disallowing all: {e}");
+                        return Err(Error::RobotsDisallowedError(url.to_string()));
+                    }
+                };
+
+                robots.insert(&origin, rules.clone());
+                rules
+            }
+        };
+
+        let user_agent = self
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("user-agent"))
+            .map(|(_, value)| value.as_str())
+            .unwrap_or(DEFAULT_ROBOTS_USER_AGENT);
+
+        if rules.is_allowed(user_agent, &path) {
+            Ok(())
+        } else {
+            Err(Error::RobotsDisallowedError(url.to_string()))
+        }
+    }
+
+    /// The headers to send with a request for `url`, including any cookies from the jar.
+    fn request_headers(&self, url: &str) -> HashMap<String, String> {
+        match self.cookie_jar.as_ref().and_then(|jar| jar.header(url)) {
+            Some(cookie) => self.headers.update("cookie".to_string(), cookie),
+            None => self.headers.clone(),
+        }
+    }
+
+    fn proxy(&self) -> Option<&str> {
+        self.proxies
+            .get(self.next_proxy % self.proxies.len().max(1))
+            .map(|proxy| proxy.as_str())
+    }
+
+    fn following_proxy(&self) -> usize {
+        if self.proxies.is_empty() {
+            0
+        } else {
+            (self.next_proxy + 1) % self.proxies.len()
+        }
+    }
+
+    /// Extract from each result according to the parse mode: using `pattern` as a regular
+    /// expression as by [Scraper::extract] in text mode, as a JSONPath expression as by
+    /// [Scraper::jsonpath] in JSON mode, and as by [Scraper::extract] followed by
+    /// [Scraper::html_decode] in HTML mode.
+    pub fn extract_by_mode(&self, pattern: &str) -> Result<Scraper<H>, Error> {
+        match self.parse_mode {
+            ParseMode::Text => self.extract(pattern),
+            ParseMode::Json => self.jsonpath(pattern),
+            ParseMode::Html => Ok(self.extract(pattern)?.html_decode()),
+        }
+    }
+
+    pub fn extract(&self, pattern: &str) -> Result<Scraper<H>, Error> {
+        let regex = Regex::new(pattern)?;
+
         Ok(Scraper {
             results: self
                 .results
                 .iter()
-                .map(|str| match str.parse::<JsonValue>() {
-                    Ok(json) => json
-                        .query(expr)
-                        .map(|matches| matches.into_iter().cloned().collect::<Vec<_>>())
-                        .map_err(Error::JsonPathError),
-                    Err(e) => Err(Error::JsonParseError(e.to_string())),
+                .flat_map(|str| {
+                    regex
+                        .captures_iter(str)
+                        .filter_map(|matched| {
+                            let group = if matched.len() > 1 { 1 } else { 0 };
+
+                            matched.get(group).map(|x| x.as_str().to_owned())
+                        })
+                        .collect::<Vector<_>>()
                 })
-                .collect::<Result<Vec<_>, _>>()?
-                .into_iter()
-                .flatten()
-                .map(|value| jsonval_to_string(&value))
-                .collect::<Vector<_>>(),
+                .collect(),
             ..self.clone()
         })
     }
-}
 
-fn jsonval_to_string(value: &JsonValue) -> String {
-    match value {
-        JsonValue::Null => String::from("null"),
-        JsonValue::Bool(value) => value.to_string(),
-        JsonValue::Number(number) => number.to_string(),
-        JsonValue::String(str) => str.clone(),
-        JsonValue::Array(_) => todo!(),
-        JsonValue::Object(_) => todo!(),
+    /// Apply a pattern with exactly `num_groups` explicit capture groups to each result, and
+    /// collect the captures of group `i` across all matches into the `i`th returned list. Groups
+    /// that did not participate in a match are collected as empty strings, so that all returned
+    /// lists have the same length.
+    pub fn scan(&self, pattern: &str, num_groups: usize) -> Result<Vec<Vector<String>>, Error> {
+        let regex = Regex::new(pattern)?;
+
+        if regex.captures_len() - 1 != num_groups {
+            return Err(Error::CaptureGroupCountMismatchError(
+                regex.captures_len() - 1,
+                num_groups,
+            ));
+        }
+
+        let mut groups = vec![Vector::new(); num_groups];
+
+        for str in self.results.iter() {
+            for matched in regex.captures_iter(str) {
+                for (index, group) in groups.iter_mut().enumerate() {
+                    group.push_back(
+                        matched
+                            .get(index + 1)
+                            .map(|x| x.as_str().to_owned())
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+        }
+
+        Ok(groups)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn delete(&self, pattern: &str) -> Result<Scraper<H>, Error> {
+        let regex = Regex::new(pattern)?;
 
-    fn nullscraper() -> Scraper<NullHttpDriver> {
-        Scraper::<NullHttpDriver>::new()
+        Ok(Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| regex.replace_all(str, "").into_owned())
+                .collect(),
+            ..self.clone()
+        })
     }
 
-    fn no_results() -> Vector<String> {
-        Vector::<String>::new()
+    /// Whether any result matches the regex `pattern`.
+    pub fn any_match(&self, pattern: &str) -> Result<bool, Error> {
+        let regex = Regex::new(pattern)?;
+
+        Ok(self.results.iter().any(|str| regex.is_match(str)))
     }
 
-    macro_rules! results {
-        ($($result:expr),+$(,)?) => {
-            vector![$($result.to_string()),+]
-        };
+    pub fn retain(&self, pattern: &str) -> Result<Scraper<H>, Error> {
+        let regex = Regex::new(pattern)?;
+
+        let mut results = self.results.clone();
+        results.retain(|str| regex.is_match(str));
+
+        Ok(Scraper {
+            results,
+            ..self.clone()
+        })
     }
 
-    #[derive(Clone)]
-    pub struct HeaderTestingHttpDriver;
+    pub fn discard(&self, pattern: &str) -> Result<Scraper<H>, Error> {
+        let regex = Regex::new(pattern)?;
 
-    impl HttpDriver for HeaderTestingHttpDriver {
-        async fn get(_url: &str, headers: HttpHeaders<'_>) -> Result<String, Error> {
-            Ok(match headers {
-                HttpHeaders::NoHeaders => "".to_string(),
-                HttpHeaders::Headers(map) => map
-                    .iter()
-                    .map(|(key, value)| format!("[{key}]:[{value}]"))
-                    .collect::<Vec<_>>()
-                    .join("\n"),
-            })
+        let mut results = self.results.clone();
+        results.retain(|str| !regex.is_match(str));
+
+        Ok(Scraper {
+            results,
+            ..self.clone()
+        })
+    }
+
+    /// Partition the results into those containing a match for `pattern` and those that do not,
+    /// keeping their order. The results themselves are left unchanged.
+    pub fn partition(&self, pattern: &str) -> Result<(Vector<String>, Vector<String>), Error> {
+        let regex = Regex::new(pattern)?;
+
+        Ok(self
+            .results
+            .iter()
+            .cloned()
+            .partition(|str| regex.is_match(str)))
+    }
+
+    /// Split each result on every match of `pattern`, replacing it with the pieces in order.
+    /// Results without a match are kept unchanged. If `drop_empty` is true, empty pieces are
+    /// discarded, such as those between adjacent matches.
+    pub fn split(&self, pattern: &str, drop_empty: bool) -> Result<Scraper<H>, Error> {
+        let regex = Regex::new(pattern)?;
+
+        Ok(Scraper {
+            results: self
+                .results
+                .iter()
+                .flat_map(|str| regex.split(str).map(|piece| piece.to_string()))
+                .filter(|piece| !drop_empty || !piece.is_empty())
+                .collect(),
+            ..self.clone()
+        })
+    }
+
+    /// Sort results in ascending order by a number extracted from each result using the first
+    /// capture group of `pattern` (or the entire match if the pattern has no groups). Results
+    /// without a match, or where the match is not a number, are placed last in their original
+    /// order.
+    pub fn sort_by_number(&self, pattern: &str) -> Result<Scraper<H>, Error> {
+        let regex = Regex::new(pattern)?;
+
+        let mut keyed = self
+            .results
+            .iter()
+            .map(|str| (number_key(&regex, str), str.clone()))
+            .collect::<Vec<_>>();
+
+        keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) => a.total_cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+
+        Ok(Scraper {
+            results: keyed.into_iter().map(|(_, str)| str).collect(),
+            ..self.clone()
+        })
+    }
+
+    /// Keep the `count` results with the largest numbers extracted as by [Scraper::sort_by_number],
+    /// in descending order. Results without a number are discarded, and results with equal
+    /// numbers keep their original order.
+    pub fn top_n(&self, pattern: &str, count: usize) -> Result<Scraper<H>, Error> {
+        self.keep_by_number(pattern, count, true)
+    }
+
+    /// Keep the `count` results with the smallest numbers extracted as by
+    /// [Scraper::sort_by_number], in ascending order. Results without a number are discarded, and
+    /// results with equal numbers keep their original order.
+    pub fn bottom_n(&self, pattern: &str, count: usize) -> Result<Scraper<H>, Error> {
+        self.keep_by_number(pattern, count, false)
+    }
+
+    fn keep_by_number(
+        &self,
+        pattern: &str,
+        count: usize,
+        descending: bool,
+    ) -> Result<Scraper<H>, Error> {
+        let regex = Regex::new(pattern)?;
+
+        let mut keyed = self
+            .results
+            .iter()
+            .filter_map(|str| Some((number_key(&regex, str)?, str.clone())))
+            .collect::<Vec<_>>();
+
+        keyed.sort_by(|(a, _), (b, _)| {
+            if descending {
+                b.total_cmp(a)
+            } else {
+                a.total_cmp(b)
+            }
+        });
+
+        Ok(Scraper {
+            results: keyed.into_iter().take(count).map(|(_, str)| str).collect(),
+            ..self.clone()
+        })
+    }
+
+    /// Replace each result with the first number it contains, written with `.` as the decimal
+    /// separator and without thousands separators, such that `"$1,234.50"` becomes `"1234.50"`.
+    /// In the results, `decimal_separator` separates the decimals while the other one of `.` and
+    /// `,`, or a space, separates groups of three digits. Results that do not contain a number
+    /// are discarded, while results with an ambiguous number such as `"1,23"` are an error.
+    pub fn parse_number(&self, decimal_separator: char) -> Result<Scraper<H>, Error> {
+        let thousands_separator = match decimal_separator {
+            '.' => ',',
+            ',' => '.',
+            _ => {
+                return Err(Error::ParseError(format!(
+                    "Invalid decimal separator `{decimal_separator}`, expected `.` or `,`"
+                )));
+            }
+        };
+
+        let mut results = Vector::new();
+
+        for str in self.results.iter() {
+            match find_number(str, decimal_separator, thousands_separator)? {
+                Some(number) => results.push_back(number),
+                None => debug!("scraper: parse_number: no number in {str:?}"),
+            }
         }
+
+        Ok(Scraper {
+            results,
+            ..self.clone()
+        })
     }
 
-    #[test]
-    fn test_extract() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["its raining cats and dogs"]);
-        let s3 = nullscraper().with_results(results![
-            "its raining cats and dogs",
-            "dogs will sometimes chase cats",
-        ]);
+    /// Round each result, being a number as produced by [Scraper::parse_number], to `precision`
+    /// decimals. Results that are not numbers are discarded.
+    pub fn format_number(&self, precision: usize) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .filter_map(|str| str.trim().parse::<f64>().ok())
+                .filter(|number| number.is_finite())
+                .map(|number| format!("{number:.precision$}"))
+                .collect(),
+            ..self.clone()
+        }
+    }
 
-        assert_eq!(s1.extract("rain").unwrap().results, no_results());
-        assert_eq!(s2.extract("rain").unwrap().results, results!["rain"]);
-        assert_eq!(s3.extract("rain").unwrap().results, results!["rain"]);
+    /// Replace the results, being numbers as produced by [Scraper::parse_number], with statistics
+    /// over them as `name=value` results: `min`, `max`, `sum`, `mean` and `count`, in that order.
+    /// Results that are not numbers are skipped. Without any numbers, only `sum=0` and `count=0`
+    /// are produced.
+    pub fn stats(&self) -> Scraper<H> {
+        let (count, min, max, sum) = self
+            .results
+            .iter()
+            .filter_map(|str| str.trim().parse::<f64>().ok())
+            .filter(|number| number.is_finite())
+            .fold(
+                (0usize, f64::INFINITY, f64::NEG_INFINITY, 0.0),
+                |(count, min, max, sum), number| {
+                    (count + 1, min.min(number), max.max(number), sum + number)
+                },
+            );
 
-        assert_eq!(s1.extract("cat|dog").unwrap().results, no_results());
-        assert_eq!(
+        let results = if count == 0 {
+            vector!["sum=0".to_string(), "count=0".to_string()]
+        } else {
+            vector![
+                format!("min={min}"),
+                format!("max={max}"),
+                format!("sum={sum}"),
+                format!("mean={}", sum / count as f64),
+                format!("count={count}"),
+            ]
+        };
+
+        Scraper {
+            results,
+            ..self.clone()
+        }
+    }
+
+    pub fn first(&self) -> Scraper<H> {
+        Scraper {
+            results: if self.results.is_empty() {
+                vector![]
+            } else {
+                self.results.take(1)
+            },
+            ..self.clone()
+        }
+    }
+
+    pub fn last(&self) -> Scraper<H> {
+        Scraper {
+            results: if self.results.is_empty() {
+                vector![]
+            } else {
+                vector![self.results.back().unwrap().clone()]
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Keep only the result at `index`, counting from 1 as when indexing variables, or no results
+    /// if there is no such result.
+    pub fn nth(&self, index: usize) -> Scraper<H> {
+        Scraper {
+            results: index
+                .checked_sub(1)
+                .and_then(|index| self.results.get(index))
+                .map(|result| vector![result.clone()])
+                .unwrap_or_default(),
+            ..self.clone()
+        }
+    }
+
+    pub fn take(&self, n: usize) -> Scraper<H> {
+        Scraper {
+            results: if self.results.is_empty() {
+                vector![]
+            } else {
+                self.results.take(min(n, self.results.len()))
+            },
+            ..self.clone()
+        }
+    }
+
+    pub fn drop(&self, n: usize) -> Scraper<H> {
+        Scraper {
+            results: if self.results.is_empty() {
+                vector![]
+            } else {
+                self.results.skip(min(n, self.results.len()))
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Keep the last `n` results.
+    pub fn tail(&self, n: usize) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .skip(self.results.len() - min(n, self.results.len())),
+            ..self.clone()
+        }
+    }
+
+    /// Keep all results except for the last one.
+    pub fn init(&self) -> Scraper<H> {
+        Scraper {
+            results: self.results.take(self.results.len().saturating_sub(1)),
+            ..self.clone()
+        }
+    }
+
+    /// Keep `n` results chosen at random, in their original order. If there are no more than
+    /// `n` results, all results are kept.
+    pub fn random(&self, n: usize) -> Scraper<H> {
+        let mut rng = self.rng.clone();
+        let mut results = Vector::new();
+        let mut remaining = self.results.len();
+
+        for str in self.results.iter() {
+            if rng.below(remaining) < n - results.len() {
+                results.push_back(str.clone());
+            }
+
+            remaining -= 1;
+        }
+
+        Scraper {
+            results,
+            rng,
+            ..self.clone()
+        }
+    }
+
+    pub fn prepend(&self, prefix: &str) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| format!("{prefix}{str}").to_string())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn append(&self, suffix: &str) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| format!("{str}{suffix}").to_string())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Decode HTML character references such as `&amp;` and `&#39;` in each result.
+    pub fn html_decode(&self) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| html_escape::decode_html_entities(str).into_owned())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Percent-encode each result for use as a component of a URL, such as a query parameter
+    /// value.
+    pub fn url_encode(&self) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| utf8_percent_encode(str, URL_COMPONENT).to_string())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Decode percent-encoded sequences in each result. Sequences that do not decode to valid
+    /// UTF-8 are replaced with U+FFFD.
+    pub fn url_decode(&self) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| percent_decode_str(str).decode_utf8_lossy().into_owned())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Remove leading and trailing whitespace from each result.
+    pub fn trim(&self) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| str.trim().to_string())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Remove leading and trailing whitespace from each result, and replace each run of
+    /// whitespace within results, including newlines, with a single space.
+    pub fn normalize_whitespace(&self) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| WHITESPACE_RUN.replace_all(str.trim(), " ").into_owned())
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn to_lowercase(&self) -> Scraper<H> {
+        Scraper {
+            results: self.results.iter().map(|str| str.to_lowercase()).collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn to_uppercase(&self) -> Scraper<H> {
+        Scraper {
+            results: self.results.iter().map(|str| str.to_uppercase()).collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn join(&self, separator: &str) -> Scraper<H> {
+        Scraper {
+            results: if self.results.is_empty() {
+                vector![]
+            } else {
+                vector![
+                    self.results
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(separator)
+                ]
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Replace all results with a single result containing a Markdown table with the given
+    /// column headers, where each result forms one row and the cells of a row are separated by
+    /// tabs. Rows with fewer cells than columns are padded with empty cells, and cells beyond
+    /// the last column are ignored. Pipe characters are escaped and newlines within cells are
+    /// replaced with spaces.
+    pub fn markdown_table(&self, columns: &[String]) -> Result<Scraper<H>, Error> {
+        if columns.is_empty() {
+            return Err(Error::ParseError(
+                "A Markdown table needs at least one column".to_string(),
+            ));
+        }
+
+        fn row<'a>(cells: impl Iterator<Item = &'a str>) -> String {
+            format!(
+                "| {} |",
+                cells
+                    .map(|cell| cell.trim().replace('|', "\\|").replace(['\r', '\n'], " "))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            )
+        }
+
+        let mut lines = vec![
+            row(columns.iter().map(|column| column.as_str())),
+            row(columns.iter().map(|_| "---")),
+        ];
+
+        for str in self.results.iter() {
+            lines.push(row(str
+                .split('\t')
+                .chain(std::iter::repeat(""))
+                .take(columns.len())));
+        }
+
+        Ok(Scraper {
+            results: vector![lines.join("\n")],
+            ..self.clone()
+        })
+    }
+
+    /// Remove repeated results, keeping the first occurrence of each.
+    pub fn unique(&self) -> Scraper<H> {
+        let mut seen = HashSet::new();
+
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .filter(|str| seen.insert(*str))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Remove results that are repeated when ignoring case, keeping the first occurrence of each
+    /// with its casing.
+    pub fn unique_case_insensitive(&self) -> Scraper<H> {
+        let mut seen = HashSet::new();
+
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .filter(|str| seen.insert(str.to_lowercase()))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Canonicalize each result that parses as an absolute URL by removing query parameters
+    /// matching any of `tracking_params` and any trailing slashes from the path, leaving other
+    /// results unchanged. A trailing `*` in a parameter name matches any parameter name starting
+    /// with what precedes it. If `unique` is true, only the first of any identical results is
+    /// kept afterwards.
+    pub fn canonicalize_urls(&self, tracking_params: &[String], unique: bool) -> Scraper<H> {
+        let is_tracking = |name: &str| {
+            tracking_params
+                .iter()
+                .any(|param| match param.strip_suffix('*') {
+                    Some(prefix) => name.starts_with(prefix),
+                    None => name == param,
+                })
+        };
+
+        let canonicalize = |str: &String| {
+            let Ok(mut url) = Url::parse(str) else {
+                return str.clone();
+            };
+
+            let query = url
+                .query_pairs()
+                .filter(|(name, _)| !is_tracking(name))
+                .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                .collect::<Vec<_>>();
+
+            if query.is_empty() {
+                url.set_query(None);
+            } else if url.query_pairs().count() != query.len() {
+                url.query_pairs_mut().clear().extend_pairs(query);
+            }
+
+            let path = url.path().trim_end_matches('/').to_string();
+            url.set_path(&path);
+
+            url.to_string()
+        };
+
+        let mut seen = HashSet::new();
+
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(canonicalize)
+                .filter(|str| !unique || seen.insert(str.clone()))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Replace the results with the changes relative to `snapshot`, an earlier list of results:
+    /// first each result of `snapshot` no longer present, prefixed by `"- "`, then each result not
+    /// present in `snapshot`, prefixed by `"+ "`, both in their original order. Results present in
+    /// both are discarded, counting repeated results separately.
+    pub fn changed_vs(&self, snapshot: &Vector<String>) -> Scraper<H> {
+        fn unmatched<'a>(
+            results: &'a Vector<String>,
+            others: &Vector<String>,
+        ) -> impl Iterator<Item = &'a String> {
+            let mut counts = std::collections::HashMap::<&str, usize>::new();
+
+            for str in others {
+                *counts.entry(str.as_str()).or_default() += 1;
+            }
+
+            results
+                .iter()
+                .filter(move |str| match counts.get_mut(str.as_str()) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                })
+        }
+
+        Scraper {
+            results: unmatched(snapshot, &self.results)
+                .map(|str| format!("- {str}"))
+                .chain(unmatched(&self.results, snapshot).map(|str| format!("+ {str}")))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn clear(&self) -> Scraper<H> {
+        Scraper {
+            results: vector![],
+            ..self.clone()
+        }
+    }
+
+    pub fn set_header(&self, key: String, value: String) -> Scraper<H> {
+        Scraper {
+            headers: self.headers.update(key, value),
+            ..self.clone()
+        }
+    }
+
+    pub fn clear_headers(&self) -> Scraper<H> {
+        Scraper {
+            headers: HashMap::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Set the pool of proxies to rotate through for subsequent requests. An empty pool
+    /// disables the use of proxies.
+    pub fn set_proxies(&self, proxies: Vector<String>) -> Scraper<H> {
+        Scraper {
+            proxies,
+            next_proxy: 0,
+            ..self.clone()
+        }
+    }
+
+    /// Make subsequent requests to `host` connect to `addr` instead of an address obtained by
+    /// resolving `host`, or resolve `host` normally again if `addr` is None. The URL, and thereby
+    /// the `Host` header and TLS server name, is unaffected, which allows testing a particular
+    /// server behind a load balancer or CDN. A port of 0 in `addr` means the port of the URL.
+    pub fn set_resolve(&self, host: &str, addr: Option<SocketAddr>) -> Scraper<H> {
+        let host = host.to_lowercase();
+
+        Scraper {
+            resolve: match addr {
+                Some(addr) => self.resolve.update(host, addr),
+                None => self.resolve.without(&host),
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Restart the random number generator used by [Scraper::random] from `seed`.
+    pub fn set_seed(&self, seed: u64) -> Scraper<H> {
+        Scraper {
+            rng: Rng::new(seed),
+            ..self.clone()
+        }
+    }
+
+    /// Set the maximum number of `<meta http-equiv="refresh">` redirects followed by
+    /// [Scraper::get] for a single request. Zero disables following.
+    pub fn set_max_meta_refresh_hops(&self, max_meta_refresh_hops: usize) -> Scraper<H> {
+        Scraper {
+            max_meta_refresh_hops,
+            ..self.clone()
+        }
+    }
+
+    /// Set the circuit breaker guarding requests made by [Scraper::get], or None to disable it.
+    pub fn set_circuit_breaker(&self, circuit_breaker: Option<CircuitBreaker>) -> Scraper<H> {
+        Scraper {
+            circuit_breaker,
+            ..self.clone()
+        }
+    }
+
+    /// Set the minimum delay between requests made by [Scraper::get] and related methods, to any
+    /// host, or None to make requests as soon as possible.
+    pub fn set_polite_delay(&self, polite_delay: Option<PoliteDelay>) -> Scraper<H> {
+        Scraper {
+            polite_delay,
+            ..self.clone()
+        }
+    }
+
+    /// Set the rate limiter awaited before each request made by [Scraper::get] and related
+    /// methods, or None to not limit the rate of requests. Clones of a rate limiter share their
+    /// limit, which lets separate scrapers share it.
+    pub fn set_rate_limiter(&self, rate_limiter: Option<RateLimiter>) -> Scraper<H> {
+        Scraper {
+            rate_limiter,
+            ..self.clone()
+        }
+    }
+
+    /// Set the wall-clock time that [Scraper::get_paginated] and [Scraper::sitemap_urls] may
+    /// spend fetching pages, or None to let them run until done. Once spent, they stop fetching
+    /// further pages, keeping the results gathered so far.
+    pub fn set_time_budget(&self, time_budget: Option<Duration>) -> Scraper<H> {
+        Scraper {
+            time_budget,
+            ..self.clone()
+        }
+    }
+
+    /// Set the parse mode used by [Scraper::extract_by_mode].
+    pub fn set_parse_mode(&self, parse_mode: ParseMode) -> Scraper<H> {
+        Scraper {
+            parse_mode,
+            ..self.clone()
+        }
+    }
+
+    pub fn parse_mode(&self) -> ParseMode {
+        self.parse_mode
+    }
+
+    /// Set whether to accept and decompress compressed responses, which is the default. Turning
+    /// this off helps with sites that mislabel their content as compressed.
+    pub fn set_decompress(&self, decompress: bool) -> Scraper<H> {
+        Scraper {
+            decompress,
+            ..self.clone()
+        }
+    }
+
+    pub fn decompress(&self) -> bool {
+        self.decompress
+    }
+
+    /// Set the size in bytes of the largest response body to read, beyond which requests fail
+    /// instead of reading the body into memory.
+    pub fn set_max_body_size(&self, max_body_size: usize) -> Scraper<H> {
+        Scraper {
+            max_body_size,
+            ..self.clone()
+        }
+    }
+
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    pub fn memory_cache(&self) -> Option<&MemoryCache> {
+        self.memory_cache.as_ref()
+    }
+
+    pub fn time_budget(&self) -> Option<Duration> {
+        self.time_budget
+    }
+
+    /// Whether the time budget, if any, has been spent by an operation started at `start`.
+    fn is_time_budget_spent(&self, start: Instant) -> bool {
+        self.time_budget
+            .is_some_and(|time_budget| start.elapsed() >= time_budget)
+    }
+
+    /// Set the cache of `robots.txt` rules to respect in [Scraper::get], or None to ignore
+    /// `robots.txt`. Requests are matched against the rules for the `User-Agent` header, if
+    /// set, or for `scrapeycat` otherwise.
+    pub fn set_robots(&self, robots: Option<RobotsCache>) -> Scraper<H> {
+        Scraper {
+            robots,
+            ..self.clone()
+        }
+    }
+
+    /// Add a function applied to each response body fetched by [Scraper::get] and related
+    /// methods before it becomes a result, after any previously added functions. This allows
+    /// e.g. custom decoding, logging or sanitization of responses. Errors returned by the
+    /// function fail the request. Responses are cached as received, before being transformed.
+    pub fn add_response_transformer(
+        &self,
+        transformer: impl Fn(&str) -> Result<String, Error> + Send + Sync + 'static,
+    ) -> Scraper<H> {
+        let mut response_transformers = self.response_transformers.clone();
+        response_transformers.push_back(Arc::new(transformer));
+
+        Scraper {
+            response_transformers,
+            ..self.clone()
+        }
+    }
+
+    /// Remove all functions added using [Scraper::add_response_transformer].
+    pub fn clear_response_transformers(&self) -> Scraper<H> {
+        Scraper {
+            response_transformers: Vector::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Set the on-disk cache of responses used by [Scraper::get], or None to always make requests.
+    pub fn set_response_cache(&self, response_cache: Option<ResponseCache>) -> Scraper<H> {
+        Scraper {
+            response_cache,
+            ..self.clone()
+        }
+    }
+
+    /// Set the in-memory cache of responses used by [Scraper::get], or None to always make
+    /// requests. Unlike the on-disk cache, responses are looked up by URL alone and never expire.
+    pub fn set_memory_cache(&self, memory_cache: Option<MemoryCache>) -> Scraper<H> {
+        Scraper {
+            memory_cache,
+            ..self.clone()
+        }
+    }
+
+    /// Set the cookie jar used by [Scraper::get], or None to stop sending and storing cookies.
+    pub fn set_cookie_jar(&self, cookie_jar: Option<CookieJar>) -> Scraper<H> {
+        Scraper {
+            cookie_jar,
+            ..self.clone()
+        }
+    }
+
+    /// Replace each result with a JSON object having the result as the string value of `key`,
+    /// e.g. `{"key":"result"}`.
+    pub fn as_json_object(&self, key: &str) -> Scraper<H> {
+        Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| {
+                    JsonValue::Object(JsonMap::from_iter([(
+                        key.to_string(),
+                        JsonValue::String(str.clone()),
+                    )]))
+                    .to_string()
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Replace each result with a shell line exporting it as an environment variable named by
+    /// `prefix` followed by `_` and the 1-based index of the result, e.g. `export PREFIX_1='a'`.
+    /// Values are single-quoted, making them safe to `eval` in POSIX shells whatever they contain.
+    pub fn as_env(&self, prefix: &str) -> Result<Scraper<H>, Error> {
+        let is_valid_prefix = prefix
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        if !is_valid_prefix {
+            return Err(Error::ParseError(format!(
+                "Invalid environment variable name prefix `{prefix}`"
+            )));
+        }
+
+        Ok(Scraper {
+            results: self
+                .results
+                .iter()
+                .enumerate()
+                .map(|(i, str)| {
+                    format!("export {prefix}_{}='{}'", i + 1, str.replace('\'', "'\\''"))
+                })
+                .collect(),
+            ..self.clone()
+        })
+    }
+
+    pub fn jsonpath(&self, expr: &str) -> Result<Scraper<H>, Error> {
+        Ok(Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| match str.parse::<JsonValue>() {
+                    Ok(json) => json
+                        .query(expr)
+                        .map(|matches| matches.into_iter().cloned().collect::<Vec<_>>())
+                        .map_err(Error::JsonPathError),
+                    Err(e) => Err(Error::JsonParseError(e.to_string())),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .flatten()
+                .map(|value| jsonval_to_string(&value))
+                .collect::<Vector<_>>(),
+            ..self.clone()
+        })
+    }
+
+    /// Replace each result, taken to be an HTML document, with the contents of its
+    /// `<script type="application/ld+json">` elements, one result per element, as compact JSON.
+    /// Elements whose contents are not valid JSON result in an error.
+    pub fn json_ld(&self) -> Result<Scraper<H>, Error> {
+        let mut results = Vector::new();
+
+        for str in self.results.iter() {
+            for element in SCRIPT_ELEMENT.captures_iter(str) {
+                if !JSON_LD_TYPE.is_match(&element[1]) {
+                    continue;
+                }
+
+                let json = element[2]
+                    .trim()
+                    .parse::<JsonValue>()
+                    .map_err(|e| Error::JsonParseError(e.to_string()))?;
+
+                results.push_back(json.to_string());
+            }
+        }
+
+        Ok(Scraper {
+            results,
+            ..self.clone()
+        })
+    }
+
+    /// Retain only the results whose JSON satisfies a simple comparison of the form
+    /// `<jsonpath> <operator> <json literal>`, e.g. `$.price < 100` or `$.name == "Alice"`.
+    /// Supported operators are `==`, `!=`, `<`, `<=`, `>` and `>=`. The comparison is made
+    /// against the first value matched by the JSONPath expression, and results for which the
+    /// expression matches nothing are discarded.
+    pub fn json_filter(&self, expr: &str) -> Result<Scraper<H>, Error> {
+        let (path, operator, literal) = parse_json_condition(expr)?;
+
+        let mut results = Vector::new();
+
+        for str in self.results.iter() {
+            let json = str
+                .parse::<JsonValue>()
+                .map_err(|e| Error::JsonParseError(e.to_string()))?;
+
+            let keep = json
+                .query(path)?
+                .first()
+                .is_some_and(|value| json_compare(value, operator, &literal));
+
+            if keep {
+                results.push_back(str.clone());
+            }
+        }
+
+        Ok(Scraper {
+            results,
+            ..self.clone()
+        })
+    }
+
+    /// Replace each result, parsed as JSON, with compact JSON in which the keys of every object
+    /// are sorted, so that results differing only in the order of keys become identical.
+    pub fn json_canonical(&self) -> Result<Scraper<H>, Error> {
+        Ok(Scraper {
+            results: self
+                .results
+                .iter()
+                .map(|str| {
+                    str.parse::<JsonValue>()
+                        .map(|json| sort_json_keys(json).to_string())
+                        .map_err(|e| Error::JsonParseError(e.to_string()))
+                })
+                .collect::<Result<Vector<_>, _>>()?,
+            ..self.clone()
+        })
+    }
+}
+
+/// Sort the keys of every object within `value`, which matters when `serde_json` preserves the
+/// order of keys as parsed.
+fn sort_json_keys(value: JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Object(map) => {
+            let mut entries = map.into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            JsonValue::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_json_keys(value)))
+                    .collect::<JsonMap<_, _>>(),
+            )
+        }
+        JsonValue::Array(values) => {
+            JsonValue::Array(values.into_iter().map(sort_json_keys).collect())
+        }
+        value => value,
+    }
+}
+
+fn parse_json_condition(expr: &str) -> Result<(&str, &str, JsonValue), Error> {
+    let condition = Regex::new(r"^\s*(\$\S*)\s*(==|!=|<=|>=|<|>)\s*(.+?)\s*$")
+        .expect("Should be a valid regex");
+
+    let matched = condition.captures(expr).ok_or_else(|| {
+        Error::ParseError(format!(
+            "Invalid JSON condition `{expr}`, expected `<jsonpath> <operator> <value>`"
+        ))
+    })?;
+
+    let literal = matched
+        .get(3)
+        .expect("Group 3 should exist")
+        .as_str()
+        .parse::<JsonValue>()
+        .map_err(|e| Error::JsonParseError(e.to_string()))?;
+
+    Ok((
+        matched.get(1).expect("Group 1 should exist").as_str(),
+        matched.get(2).expect("Group 2 should exist").as_str(),
+        literal,
+    ))
+}
+
+fn json_compare(value: &JsonValue, operator: &str, literal: &JsonValue) -> bool {
+    let ordering = match (value, literal) {
+        (JsonValue::Number(a), JsonValue::Number(b)) => a
+            .as_f64()
+            .and_then(|a| b.as_f64().and_then(|b| a.partial_cmp(&b))),
+        (JsonValue::String(a), JsonValue::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+
+    match (operator, ordering) {
+        ("==", None) => value == literal,
+        ("!=", None) => value != literal,
+        ("==", Some(ordering)) => ordering == Ordering::Equal,
+        ("!=", Some(ordering)) => ordering != Ordering::Equal,
+        ("<", Some(ordering)) => ordering == Ordering::Less,
+        ("<=", Some(ordering)) => ordering != Ordering::Greater,
+        (">", Some(ordering)) => ordering == Ordering::Greater,
+        (">=", Some(ordering)) => ordering != Ordering::Less,
+        _ => false,
+    }
+}
+
+/// Resolve a possibly relative URL `next` against the URL `base`, falling back to `next` as-is
+/// if either cannot be parsed.
+fn resolve_url(base: &str, next: String) -> String {
+    match Url::parse(base).and_then(|base| base.join(&next)) {
+        Ok(resolved) => resolved.to_string(),
+        Err(_) => next,
+    }
+}
+
+/// The number in the first capture group of `regex` in `str`, or in the entire match if `regex`
+/// has no groups, or None if there is no match or it is not a number.
+fn number_key(regex: &Regex, str: &str) -> Option<f64> {
+    let matched = regex.captures(str)?;
+    let group = if matched.len() > 1 { 1 } else { 0 };
+
+    matched.get(group)?.as_str().trim().parse::<f64>().ok()
+}
+
+/// The first number in `str`, written with `.` as the decimal separator and without grouping,
+/// where `decimal_separator` separates the decimals and `thousands_separator` or a space separates
+/// groups of three digits. Fails if the number is followed by another separator and more digits,
+/// as in `"1.2.3"`, or `"1,23"` when `.` separates the decimals, since its meaning is unclear.
+fn find_number(
+    str: &str,
+    decimal_separator: char,
+    thousands_separator: char,
+) -> Result<Option<String>, Error> {
+    let chars = str.chars().collect::<Vec<_>>();
+
+    let digit_at = |i: usize| chars.get(i).is_some_and(char::is_ascii_digit);
+    let digits_from = |i: usize| chars[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let is_separator_at =
+        |i: usize, separators: &[char]| chars.get(i).is_some_and(|c| separators.contains(c));
+
+    let Some(start) = (0..chars.len())
+        .find(|&i| digit_at(i) || (chars[i] == decimal_separator && digit_at(i + 1)))
+    else {
+        return Ok(None);
+    };
+
+    let mut number = if start > 0 && chars[start - 1] == '-' {
+        "-".to_string()
+    } else {
+        String::new()
+    };
+
+    let leading = digits_from(start);
+    let mut i = start + leading;
+
+    number.extend(&chars[start..i]);
+
+    if (1..=3).contains(&leading) {
+        while is_separator_at(i, &[thousands_separator, ' ', '\u{a0}', '\u{202f}'])
+            && digits_from(i + 1) == 3
+        {
+            number.extend(&chars[i + 1..i + 4]);
+            i += 4;
+        }
+    }
+
+    if is_separator_at(i, &[decimal_separator]) && digit_at(i + 1) {
+        let decimals = digits_from(i + 1);
+
+        number.push('.');
+        number.extend(&chars[i + 1..i + 1 + decimals]);
+        i += 1 + decimals;
+    }
+
+    if is_separator_at(i, &[decimal_separator, thousands_separator]) && digit_at(i + 1) {
+        return Err(Error::ParseError(format!("Ambiguous number in {str:?}")));
+    }
+
+    Ok(Some(number))
+}
+
+/// Decode a response body to text according to the given `Content-Type` header value. A byte
+/// order mark takes precedence, followed by the `charset` parameter of the content type. JSON
+/// without either is taken to be UTF-16 if its first two bytes reveal so as described in RFC 4627,
+/// and anything else is taken to be UTF-8, replacing invalid sequences.
+fn decode_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|content_type| CHARSET_PARAM.captures(content_type))
+        .and_then(|matched| Encoding::for_label(matched[1].as_bytes()))
+        .unwrap_or_else(|| {
+            let is_json = content_type.is_some_and(|content_type| {
+                content_type.split(';').next().is_some_and(|mime| {
+                    let mime = mime.trim().to_lowercase();
+                    mime.ends_with("/json") || mime.ends_with("+json")
+                })
+            });
+
+            match bytes {
+                [0, b, ..] if is_json && *b != 0 => UTF_16BE,
+                [b, 0, ..] if is_json && *b != 0 => UTF_16LE,
+                _ => UTF_8,
+            }
+        });
+
+    // Sniffs for a byte order mark, which overrides `encoding`
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Find the target URL of a `<meta http-equiv="refresh" content="0; url=...">` tag in an HTML
+/// document, if any.
+fn meta_refresh_url(body: &str) -> Option<String> {
+    let meta = Regex::new(r"(?is)<meta\b[^>]*>").expect("Should be a valid regex");
+    let http_equiv = Regex::new(r#"(?is)\bhttp-equiv\s*=\s*["']?\s*refresh\b"#)
+        .expect("Should be a valid regex");
+    let content = Regex::new(r#"(?is)\bcontent\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+        .expect("Should be a valid regex");
+    let url = Regex::new(r#"(?is)^\s*[\d.]*\s*[;,]\s*(?:url\s*=\s*)?["']?([^"']*)["']?\s*$"#)
+        .expect("Should be a valid regex");
+
+    meta.find_iter(body)
+        .filter(|tag| http_equiv.is_match(tag.as_str()))
+        .filter_map(|tag| content.captures(tag.as_str()))
+        .filter_map(|matched| {
+            let content = matched.get(1).or(matched.get(2))?.as_str();
+
+            url.captures(content)
+                .map(|matched| matched[1].trim().replace("&amp;", "&"))
+        })
+        .find(|url| !url.is_empty())
+}
+
+/// Find the URL of the `rel="next"` link in the value of a `Link` header, e.g.
+/// `<https://api/items?page=2>; rel="next", <https://api/items?page=9>; rel="last"`.
+fn next_link_url(link_header: &str) -> Option<String> {
+    let link = Regex::new(r"<([^>]*)>([^<]*)").expect("Should be a valid regex");
+    let rel =
+        Regex::new(r#"(?i)(?:^|;)\s*rel\s*=\s*"?([^";]*)"?"#).expect("Should be a valid regex");
+
+    link.captures_iter(link_header)
+        .find(|matched| {
+            rel.captures(&matched[2]).is_some_and(|rels| {
+                rels[1]
+                    .split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("next"))
+            })
+        })
+        .map(|matched| matched[1].to_string())
+}
+
+fn jsonval_to_string(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => String::from("null"),
+        JsonValue::Bool(value) => value.to_string(),
+        JsonValue::Number(number) => number.to_string(),
+        JsonValue::String(str) => str.clone(),
+        JsonValue::Array(_) | JsonValue::Object(_) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicU32, Ordering::SeqCst},
+        time::Duration,
+    };
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::testutils::{
+        ContentTypeTestHttpDriver, FlakyTestHttpDriver, MethodTestHttpDriver,
+        MultipartTestHttpDriver, ROBOTS_TXT_FETCHES, RobotsTestHttpDriver, TestHttpDriver,
+        UNAVAILABLE_ROBOTS_TXT_FETCHES, flaky_fetches,
+    };
+
+    fn nullscraper() -> Scraper<NullHttpDriver> {
+        Scraper::<NullHttpDriver>::new()
+    }
+
+    fn no_results() -> Vector<String> {
+        Vector::<String>::new()
+    }
+
+    macro_rules! results {
+        ($($result:expr),+$(,)?) => {
+            vector![$($result.to_string()),+]
+        };
+    }
+
+    #[derive(Clone)]
+    pub struct HeaderTestingHttpDriver;
+
+    impl HttpDriver for HeaderTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            Ok(HttpResponse {
+                body: match spec.headers {
+                    HttpHeaders::NoHeaders => "".to_string(),
+                    HttpHeaders::Headers(map) => map
+                        .iter()
+                        .map(|(key, value)| format!("[{key}]:[{value}]"))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                },
+                ..Default::default()
+            })
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct ProxyTestingHttpDriver;
+
+    impl HttpDriver for ProxyTestingHttpDriver {
+        async fn request(
+            _spec: RequestSpec<'_>,
+            proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            Ok(HttpResponse {
+                body: proxy.unwrap_or("direct").to_string(),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Serves pages `page1` through `page4` with body `bodyN`, each linking to the next.
+    #[derive(Clone)]
+    pub struct LinkTestingHttpDriver;
+
+    impl HttpDriver for LinkTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let n = spec
+                .url
+                .strip_prefix("page")
+                .unwrap()
+                .parse::<usize>()
+                .unwrap();
+
+            Ok(HttpResponse {
+                body: format!("body{n}"),
+                headers: if n < 4 {
+                    HashMap::unit(
+                        "link".to_string(),
+                        format!(r#"<page1>; rel="first", <page{}>; rel="next""#, n + 1),
+                    )
+                } else {
+                    HashMap::new()
+                },
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Responds to `/login` by setting a session cookie, and to any other path with the cookies
+    /// received in the request, or "no cookies".
+    #[derive(Clone)]
+    pub struct CookieTestingHttpDriver;
+
+    impl HttpDriver for CookieTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let received = match spec.headers {
+                HttpHeaders::Headers(map) => map.get("cookie").cloned(),
+                HttpHeaders::NoHeaders => None,
+            };
+
+            Ok(HttpResponse {
+                body: received.unwrap_or("no cookies".to_string()),
+                headers: if spec.url.ends_with("/login") {
+                    HashMap::unit(
+                        "set-cookie".to_string(),
+                        "session=abc; Max-Age=3600\nexpired=x; Max-Age=0".to_string(),
+                    )
+                } else {
+                    HashMap::new()
+                },
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Serves a chain of pages redirecting by meta refresh: `/start` -> `/middle` -> `/final`,
+    /// as well as `/loop` which redirects to itself.
+    #[derive(Clone)]
+    pub struct MetaRefreshTestingHttpDriver;
+
+    impl HttpDriver for MetaRefreshTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let body = match spec.url {
+                "http://a.test/dir/start" => {
+                    r#"<html><head><meta http-equiv="refresh" content="0; url=../middle">"#
+                }
+                "http://a.test/middle" => {
+                    r#"<META CONTENT='1;URL="/final?x=1&amp;y=2"' HTTP-EQUIV='Refresh' />"#
+                }
+                "http://a.test/final?x=1&y=2" => "final",
+                "http://a.test/loop" => r#"<meta http-equiv="refresh" content="0;loop">"#,
+                url => panic!("unexpected url {url}"),
+            };
+
+            Ok(HttpResponse {
+                body: body.to_string(),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Fails every request to the host `down.test`, succeeding for other hosts.
+    #[derive(Clone)]
+    pub struct FailingHostTestingHttpDriver;
+
+    impl HttpDriver for FailingHostTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            if spec.url.starts_with("http://down.test") {
+                Err(Error::HTTPDriverError("connection refused".to_string()))
+            } else {
+                Ok(HttpResponse {
+                    body: "up".to_string(),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
+    /// Responds with the `Host` header sent, if any, and the address overrides in effect.
+    #[derive(Clone)]
+    pub struct ResolveTestingHttpDriver;
+
+    impl HttpDriver for ResolveTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let host = match spec.headers {
+                HttpHeaders::Headers(map) => map.get("Host").cloned(),
+                HttpHeaders::NoHeaders => None,
+            };
+
+            let mut resolve = resolve
+                .iter()
+                .map(|(host, addr)| format!("{host}={addr}"))
+                .collect::<Vec<_>>();
+            resolve.sort();
+
+            Ok(HttpResponse {
+                body: format!("{} [{}]", host.unwrap_or_default(), resolve.join(", ")),
+                ..Default::default()
+            })
+        }
+    }
+
+    static COUNTING_DRIVER_REQUESTS: AtomicU32 = AtomicU32::new(0);
+
+    /// Responds with the requested URL and the number of requests made so far.
+    #[derive(Clone)]
+    pub struct CountingTestingHttpDriver;
+
+    impl HttpDriver for CountingTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let n = COUNTING_DRIVER_REQUESTS.fetch_add(1, SeqCst) + 1;
+
+            Ok(HttpResponse {
+                body: format!("{} #{n}", spec.url),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Sleeps for the number of milliseconds given as the URL, then responds with the URL.
+    #[derive(Clone)]
+    pub struct SleepingTestingHttpDriver;
+
+    impl HttpDriver for SleepingTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let millis = spec
+                .url
+                .parse::<u64>()
+                .map_err(|_| Error::HTTPDriverError("invalid url".to_string()))?;
+
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+
+            Ok(HttpResponse {
+                body: spec.url.to_string(),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Responds with the method of the request, followed by whether decompression was asked for
+    /// and the maximum body size.
+    #[derive(Clone)]
+    pub struct SpecTestingHttpDriver;
+
+    impl HttpDriver for SpecTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            Ok(HttpResponse {
+                body: format!(
+                    "{} decompress={} max_body_size={}",
+                    spec.method, spec.decompress, spec.max_body_size
+                ),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Sleeps for 20 milliseconds, then responds to `page<n>` with `body<n>`, linking to the next
+    /// page without end.
+    #[derive(Clone)]
+    pub struct SlowLinkTestingHttpDriver;
+
+    impl HttpDriver for SlowLinkTestingHttpDriver {
+        async fn request(
+            spec: RequestSpec<'_>,
+            _proxy: Option<&str>,
+            _resolve: &HashMap<String, SocketAddr>,
+        ) -> Result<HttpResponse, Error> {
+            let n = spec
+                .url
+                .strip_prefix("page")
+                .unwrap()
+                .parse::<usize>()
+                .unwrap();
+
+            tokio::time::sleep(Duration::from_millis(20)).await;
+
+            Ok(HttpResponse {
+                body: format!("body{n}"),
+                headers: HashMap::unit(
+                    "link".to_string(),
+                    format!(r#"<page{}>; rel="next""#, n + 1),
+                ),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn test_extract() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["its raining cats and dogs"]);
+        let s3 = nullscraper().with_results(results![
+            "its raining cats and dogs",
+            "dogs will sometimes chase cats",
+        ]);
+
+        assert_eq!(s1.extract("rain").unwrap().results, no_results());
+        assert_eq!(s2.extract("rain").unwrap().results, results!["rain"]);
+        assert_eq!(s3.extract("rain").unwrap().results, results!["rain"]);
+
+        assert_eq!(s1.extract("cat|dog").unwrap().results, no_results());
+        assert_eq!(
             s2.extract("cat|dog").unwrap().results,
             results!["cat", "dog"]
         );
         assert_eq!(
-            s3.extract("cat|dog").unwrap().results,
-            results!["cat", "dog", "dog", "cat"]
+            s3.extract("cat|dog").unwrap().results,
+            results!["cat", "dog", "dog", "cat"]
+        );
+        assert_eq!(s3.extract("some(...)").unwrap().results, results!["tim"]);
+        assert_eq!(
+            s3.extract("cat|dog")
+                .unwrap()
+                .extract("[cad]")
+                .unwrap()
+                .results,
+            results!["c", "a", "d", "d", "c", "a"]
+        );
+
+        assert_eq!(s1.extract("rust").unwrap().results, no_results());
+        assert_eq!(s2.extract("rust").unwrap().results, no_results());
+        assert_eq!(s3.extract("rust").unwrap().results, no_results());
+    }
+
+    #[test]
+    fn test_scan() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results![
+            "Neuromancer - 9.99\nSnow Crash - 14.99",
+            "Hyperion - 8.50",
+        ]);
+
+        assert_eq!(
+            s1.scan("(.+) - (.+)", 2).unwrap(),
+            vec![no_results(), no_results()]
+        );
+
+        assert_eq!(
+            s2.scan("(.+) - (.+)", 2).unwrap(),
+            vec![
+                results!["Neuromancer", "Snow Crash", "Hyperion"],
+                results!["9.99", "14.99", "8.50"]
+            ]
+        );
+
+        assert_eq!(
+            s2.scan("(Snow )?(Crash|Hyperion)", 2).unwrap(),
+            vec![results!["Snow ", ""], results!["Crash", "Hyperion"]]
+        );
+
+        assert!(matches!(
+            s2.scan("(.+) - (.+)", 3),
+            Err(Error::CaptureGroupCountMismatchError(2, 3))
+        ));
+
+        assert!(matches!(
+            s2.scan(".+ - .+", 1),
+            Err(Error::CaptureGroupCountMismatchError(0, 1))
+        ));
+    }
+
+    #[test]
+    fn test_retain() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results![
+            "its raining cats and dogs",
+            "dogs will sometimes chase cats",
+        ]);
+
+        assert_eq!(s1.retain("test").unwrap().results, no_results());
+        assert_eq!(s2.retain("cats").unwrap().results, s2.results);
+        assert_eq!(s2.retain("dogs").unwrap().results, s2.results);
+        assert_eq!(
+            s2.retain("rain").unwrap().results,
+            results!["its raining cats and dogs"]
+        )
+    }
+
+    #[test]
+    fn test_parse_number() {
+        let scraper = nullscraper().with_results(results![
+            "$1,234.50",
+            "-42",
+            "Price: 1 000 000",
+            "free",
+            "-",
+            ".5",
+            "2 for $5",
+            "1, 2, 3",
+            "12,345,678.9 kr",
+        ]);
+
+        assert_eq!(
+            scraper.parse_number('.').unwrap().results,
+            results!["1234.50", "-42", "1000000", ".5", "2", "1", "12345678.9"]
+        );
+
+        let scraper = nullscraper().with_results(results![
+            "1.234,50 €",
+            "-42",
+            "Price: 1 000 000",
+            "2 for 5,50 kr",
+            "1,5",
+        ]);
+
+        assert_eq!(
+            scraper.parse_number(',').unwrap().results,
+            results!["1234.50", "-42", "1000000", "2", "1.5"]
+        );
+
+        // Numbers that could be meant either way are an error
+        for (str, decimal_separator) in [
+            ("1.234,50 €", '.'),
+            ("1.2.3", '.'),
+            ("1,23", '.'),
+            ("1234,567", '.'),
+            ("$1,234.50", ','),
+            ("1.2.3", ','),
+        ] {
+            assert!(
+                nullscraper()
+                    .with_results(results![str])
+                    .parse_number(decimal_separator)
+                    .is_err_and(|e| matches!(e, Error::ParseError(_))),
+                "{str}"
+            );
+        }
+
+        assert!(scraper.parse_number('x').is_err());
+        assert_eq!(
+            nullscraper().parse_number('.').unwrap().results,
+            no_results()
+        );
+    }
+
+    #[test]
+    fn test_format_number() {
+        let scraper =
+            nullscraper().with_results(results!["1234.56", "-1.126", "7", "2.675", "n/a", "inf"]);
+
+        assert_eq!(
+            scraper.format_number(2).results,
+            results!["1234.56", "-1.13", "7.00", "2.67"]
+        );
+        assert_eq!(
+            scraper.format_number(0).results,
+            results!["1235", "-1", "7", "3"]
+        );
+    }
+
+    #[test]
+    fn test_extract_by_mode() {
+        let json = nullscraper().with_results(results![r#"{"name": "Tom &amp; Jerry"}"#]);
+
+        assert_eq!(
+            json.extract_by_mode(r#""name": "([^"]+)""#)
+                .unwrap()
+                .results,
+            results!["Tom &amp; Jerry"]
+        );
+        assert_eq!(
+            json.set_parse_mode(ParseMode::Json)
+                .extract_by_mode("$.name")
+                .unwrap()
+                .results,
+            results!["Tom &amp; Jerry"]
+        );
+
+        let html = nullscraper()
+            .with_results(results!["<b>Tom &amp; Jerry</b><b>it&#39;s</b>"])
+            .set_parse_mode(ParseMode::Html);
+
+        assert_eq!(
+            html.extract_by_mode("<b>(.+?)</b>").unwrap().results,
+            results!["Tom & Jerry", "it's"]
+        );
+
+        // JSON mode requires JSON
+        assert!(
+            html.set_parse_mode(ParseMode::Json)
+                .extract_by_mode("$.name")
+                .is_err()
+        );
+
+        assert_eq!("JSON".parse::<ParseMode>().unwrap(), ParseMode::Json);
+        assert_eq!("html".parse::<ParseMode>().unwrap(), ParseMode::Html);
+        assert_eq!(" text ".parse::<ParseMode>().unwrap(), ParseMode::Text);
+        assert!("xml".parse::<ParseMode>().is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let scraper =
+            nullscraper().with_results(results!["4", " -1.5 ", "n/a", "10", "", "inf", "2.5"]);
+
+        assert_eq!(
+            scraper.stats().results,
+            results!["min=-1.5", "max=10", "sum=15", "mean=3.75", "count=4"]
+        );
+
+        assert_eq!(
+            nullscraper().with_results(results!["7"]).stats().results,
+            results!["min=7", "max=7", "sum=7", "mean=7", "count=1"]
+        );
+
+        // No numbers at all
+        assert_eq!(
+            nullscraper()
+                .with_results(results!["n/a", "NaN", "seven"])
+                .stats()
+                .results,
+            results!["sum=0", "count=0"]
+        );
+        assert_eq!(nullscraper().stats().results, results!["sum=0", "count=0"]);
+    }
+
+    #[test]
+    fn test_split() {
+        let scraper = nullscraper().with_results(results!["alice\nbob\n\ncharlie", "dave"]);
+
+        assert_eq!(
+            scraper.split("\n", false).unwrap().results,
+            results!["alice", "bob", "", "charlie", "dave"]
+        );
+        assert_eq!(
+            scraper.split("\n", true).unwrap().results,
+            results!["alice", "bob", "charlie", "dave"]
+        );
+
+        let scraper = nullscraper().with_results(results!["a :: b::c", "no delimiter"]);
+
+        assert_eq!(
+            scraper.split(r"\s*::\s*", false).unwrap().results,
+            results!["a", "b", "c", "no delimiter"]
+        );
+
+        assert_eq!(
+            nullscraper().split(",", true).unwrap().results,
+            no_results()
+        );
+        assert!(scraper.split("(", false).is_err());
+    }
+
+    #[test]
+    fn test_sort_by_number() {
+        let scraper = nullscraper().with_results(results![
+            "article 120: Cats",
+            "no id here",
+            "#7 Dogs (article 3)",
+            "article 45.5: Birds",
+            "article -2: Fish",
+            "article x: Frogs",
+            "Mice, article 1000",
+        ]);
+
+        assert_eq!(
+            scraper
+                .sort_by_number("article (-?[\\d.]+)")
+                .unwrap()
+                .results(),
+            &results![
+                "article -2: Fish",
+                "#7 Dogs (article 3)",
+                "article 45.5: Birds",
+                "article 120: Cats",
+                "Mice, article 1000",
+                "no id here",
+                "article x: Frogs",
+            ]
+        );
+
+        // Without groups the entire match is used
+        assert_eq!(
+            scraper.sort_by_number("\\d+").unwrap().results(),
+            &results![
+                "article -2: Fish",
+                "#7 Dogs (article 3)",
+                "article 45.5: Birds",
+                "article 120: Cats",
+                "Mice, article 1000",
+                "no id here",
+                "article x: Frogs",
+            ]
+        );
+
+        assert_eq!(
+            nullscraper().sort_by_number("(\\d+)").unwrap().results(),
+            &no_results()
+        );
+        assert!(scraper.sort_by_number("(").is_err());
+    }
+
+    #[test]
+    fn test_top_n_and_bottom_n() {
+        let scraper = nullscraper().with_results(results![
+            "Cats: 12 votes",
+            "Dogs: 30 votes",
+            "no votes",
+            "Birds: 7 votes",
+            "Fish: 30 votes",
+            "Frogs: x votes",
+            "Mice: 12 votes",
+        ]);
+
+        // Ties keep their original order
+        assert_eq!(
+            scraper.top_n("(\\d+) votes", 3).unwrap().results(),
+            &results!["Dogs: 30 votes", "Fish: 30 votes", "Cats: 12 votes"]
+        );
+        assert_eq!(
+            scraper.bottom_n("(\\d+) votes", 3).unwrap().results(),
+            &results!["Birds: 7 votes", "Cats: 12 votes", "Mice: 12 votes"]
+        );
+
+        // Results without a number are excluded, even when fewer than count remain
+        assert_eq!(
+            scraper.bottom_n("\\d+", 10).unwrap().results(),
+            &results![
+                "Birds: 7 votes",
+                "Cats: 12 votes",
+                "Mice: 12 votes",
+                "Dogs: 30 votes",
+                "Fish: 30 votes",
+            ]
+        );
+
+        assert_eq!(scraper.top_n("(\\d+)", 0).unwrap().results(), &no_results());
+        assert!(scraper.top_n("(", 1).is_err());
+        assert!(scraper.bottom_n("(", 1).is_err());
+    }
+
+    #[test]
+    fn test_first() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.first().results, no_results());
+        assert_eq!(s2.first().results, results!["a"]);
+        assert_eq!(s3.first().results, results!["a"]);
+    }
+
+    #[test]
+    fn test_last() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.last().results, no_results());
+        assert_eq!(s2.last().results, results!["a"]);
+        assert_eq!(s3.last().results, results!["c"]);
+    }
+
+    #[test]
+    fn test_nth() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.nth(1).results, no_results());
+        assert_eq!(s2.nth(1).results, results!["a"]);
+        assert_eq!(s2.nth(2).results, results!["b"]);
+        assert_eq!(s2.nth(3).results, results!["c"]);
+        assert_eq!(s2.nth(0).results, no_results());
+        assert_eq!(s2.nth(4).results, no_results());
+    }
+
+    #[test]
+    fn test_take() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.take(0).results, no_results());
+        assert_eq!(s1.take(1).results, no_results());
+        assert_eq!(s2.take(0).results, no_results());
+        assert_eq!(s2.take(1).results, results!["a"]);
+        assert_eq!(s2.take(7).results, results!["a"]);
+        assert_eq!(s3.take(2).results, results!["a", "b"]);
+        assert_eq!(s3.take(3).results, results!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_drop() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.drop(0).results, no_results());
+        assert_eq!(s1.drop(1).results, no_results());
+        assert_eq!(s1.drop(5).results, no_results());
+        assert_eq!(s2.drop(0).results, results!["a"]);
+        assert_eq!(s2.drop(1).results, no_results());
+        assert_eq!(s2.drop(5).results, no_results());
+        assert_eq!(s3.drop(0).results, results!["a", "b", "c"]);
+        assert_eq!(s3.drop(1).results, results!["b", "c"]);
+        assert_eq!(s3.drop(2).results, results!["c"]);
+        assert_eq!(s3.drop(3).results, no_results());
+        assert_eq!(s3.drop(5).results, no_results());
+    }
+
+    #[test]
+    fn test_tail() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.tail(0).results, no_results());
+        assert_eq!(s1.tail(5).results, no_results());
+        assert_eq!(s2.tail(0).results, no_results());
+        assert_eq!(s2.tail(1).results, results!["a"]);
+        assert_eq!(s2.tail(5).results, results!["a"]);
+        assert_eq!(s3.tail(1).results, results!["c"]);
+        assert_eq!(s3.tail(2).results, results!["b", "c"]);
+        assert_eq!(s3.tail(3).results, results!["a", "b", "c"]);
+        assert_eq!(s3.tail(5).results, results!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_init() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.init().results, no_results());
+        assert_eq!(s2.init().results, no_results());
+        assert_eq!(s3.init().results, results!["a", "b"]);
+        assert_eq!(s3.init().init().results, results!["a"]);
+    }
+
+    #[test]
+    fn test_random() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a", "b", "c", "d", "e", "f"]);
+
+        assert_eq!(s1.random(0).results, no_results());
+        assert_eq!(s1.random(3).results, no_results());
+        assert_eq!(s2.random(0).results, no_results());
+        assert_eq!(s2.random(6).results, s2.results);
+        assert_eq!(s2.random(10).results, s2.results);
+
+        let sampled = s2.random(3);
+
+        assert_eq!(sampled.results.len(), 3);
+        assert!(sampled.results.iter().all(|str| s2.results.contains(str)));
+
+        // Results keep their original order
+        assert!(
+            sampled
+                .results
+                .iter()
+                .zip(sampled.results.iter().skip(1))
+                .all(|(a, b)| a < b)
+        );
+    }
+
+    #[test]
+    fn test_random_seeded() {
+        let scraper = nullscraper()
+            .with_results(results!["a", "b", "c", "d", "e", "f", "g", "h"])
+            .set_seed(42);
+
+        let sampled = scraper.random(3);
+
+        assert_eq!(scraper.set_seed(42).random(3).results, sampled.results);
+        assert_eq!(sampled.set_seed(42).random(3).results, sampled.results);
+    }
+
+    #[test]
+    fn test_prepend() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.prepend("_").results, no_results());
+        assert_eq!(s2.prepend("_").results, results!["_a"]);
+        assert_eq!(s3.prepend("_").results, results!["_a", "_b", "_c"]);
+    }
+
+    #[test]
+    fn test_append() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.append("_").results, no_results());
+        assert_eq!(s2.append("_").results, results!["a_"]);
+        assert_eq!(s3.append("_").results, results!["a_", "b_", "c_"]);
+    }
+
+    #[test]
+    fn test_join() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a"]);
+        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.join(",").results, no_results());
+        assert_eq!(s2.join("--").results, results!["a"]);
+        assert_eq!(s3.join("~~~").results, results!["a~~~b~~~c"]);
+    }
+
+    #[test]
+    fn test_markdown_table() {
+        let columns = ["Title".to_string(), "Price".to_string()];
+
+        assert_eq!(
+            nullscraper()
+                .with_results(results!["Neuromancer\t9.99", "Snow Crash\t14.99"])
+                .markdown_table(&columns)
+                .unwrap()
+                .results,
+            results![
+                "| Title | Price |\n\
+                | --- | --- |\n\
+                | Neuromancer | 9.99 |\n\
+                | Snow Crash | 14.99 |"
+            ]
+        );
+
+        assert_eq!(
+            nullscraper()
+                .with_results(results!["a|b", "c\td\textra", "multi\nline\t"])
+                .markdown_table(&columns)
+                .unwrap()
+                .results,
+            results![
+                "| Title | Price |\n\
+                | --- | --- |\n\
+                | a\\|b |  |\n\
+                | c | d |\n\
+                | multi line |  |"
+            ]
+        );
+
+        assert_eq!(
+            nullscraper().markdown_table(&columns).unwrap().results,
+            results!["| Title | Price |\n| --- | --- |"]
+        );
+
+        assert!(nullscraper().markdown_table(&[]).is_err());
+    }
+
+    #[test]
+    fn test_clear() {
+        let s1 = nullscraper();
+        let s2 = nullscraper().with_results(results!["a", "b", "c"]);
+
+        assert_eq!(s1.clear().results, no_results());
+        assert_eq!(s2.clear().results, no_results());
+    }
+
+    #[tokio::test]
+    async fn test_set_header() {
+        let scraper = Scraper::<HeaderTestingHttpDriver>::new()
+            .get("foo")
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results.len(), 1);
+
+        assert!(
+            !scraper
+                .results
+                .get(0)
+                .unwrap()
+                .contains("[User-Agent]:[Scrapeycat 1.2.3]")
+        );
+
+        assert!(
+            !scraper
+                .results
+                .get(0)
+                .unwrap()
+                .contains("[Accept-Charset]:[utf-8]")
+        );
+
+        let scraper = Scraper::<HeaderTestingHttpDriver>::new()
+            .set_header("User-Agent".to_string(), "Scrapeycat 1.2.3".to_string())
+            .set_header("Accept-Charset".to_string(), "utf-8".to_string())
+            .get("foo")
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results.len(), 1);
+
+        assert!(
+            scraper
+                .results
+                .get(0)
+                .unwrap()
+                .contains("[User-Agent]:[Scrapeycat 1.2.3]")
+        );
+
+        assert!(
+            scraper
+                .results
+                .get(0)
+                .unwrap()
+                .contains("[Accept-Charset]:[utf-8]")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clear_headers() {
+        let scraper = Scraper::<HeaderTestingHttpDriver>::new()
+            .set_header("User-Agent".to_string(), "Scrapeycat 1.2.3".to_string())
+            .clear_headers()
+            .get("foo")
+            .await
+            .unwrap();
+
+        assert!(
+            !scraper
+                .results
+                .get(0)
+                .unwrap()
+                .contains("[User-Agent]:[Scrapeycat 1.2.3]")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_proxies() {
+        let mut scraper = Scraper::<ProxyTestingHttpDriver>::new()
+            .get("foo")
+            .await
+            .unwrap()
+            .set_proxies(results![
+                "http://a:8080",
+                "http://b:8080",
+                "socks5://c:1080"
+            ]);
+
+        for _ in 0..4 {
+            scraper = scraper.get("foo").await.unwrap();
+        }
+
+        scraper = scraper.set_proxies(vector![]).get("foo").await.unwrap();
+
+        assert_eq!(
+            scraper.results(),
+            &results![
+                "direct",
+                "http://a:8080",
+                "http://b:8080",
+                "socks5://c:1080",
+                "http://a:8080",
+                "direct"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_paginated() {
+        let scraper = Scraper::<LinkTestingHttpDriver>::new();
+
+        assert_eq!(
+            scraper.get_paginated("page1", 10).await.unwrap().results(),
+            &results!["body1", "body2", "body3", "body4"]
+        );
+
+        assert_eq!(
+            scraper.get_paginated("page2", 2).await.unwrap().results(),
+            &results!["body2", "body3"]
+        );
+
+        assert_eq!(
+            scraper.get_paginated("page1", 0).await.unwrap().results(),
+            &no_results()
+        );
+    }
+
+    /// Accept a single connection on `listener`, respond with `ok`, and return the request head
+    /// in lowercase.
+    /// Accept a single connection on `listener` and respond with `body`, sent without a
+    /// `Content-Length` header unless `content_length` is true. Returns the request headers in
+    /// lowercase.
+    async fn serve_once(listener: TcpListener, body: Vec<u8>, content_length: bool) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            match stream.read(&mut buffer).await.unwrap() {
+                0 => break,
+                n => request.extend_from_slice(&buffer[..n]),
+            }
+        }
+
+        let mut response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n".to_vec();
+
+        if content_length {
+            response.extend_from_slice(format!("Content-Length: {}\r\n", body.len()).as_bytes());
+        }
+
+        response.extend_from_slice(b"\r\n");
+        response.extend_from_slice(&body);
+
+        // The client may hang up early when the body is too large
+        let _ = stream.write_all(&response).await;
+
+        String::from_utf8_lossy(&request).to_lowercase()
+    }
+
+    #[tokio::test]
+    async fn test_reqwest_decompress() {
+        for decompress in [true, false] {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/", listener.local_addr().unwrap());
+            let server = tokio::spawn(serve_once(listener, b"ok".to_vec(), true));
+
+            let scraper = Scraper::<ReqwestHttpDriver>::new()
+                .set_decompress(decompress)
+                .get(&url)
+                .await
+                .unwrap();
+
+            assert_eq!(scraper.results(), &results!["ok"]);
+
+            let request = server.await.unwrap();
+            let accept_encoding = request
+                .lines()
+                .find_map(|line| line.strip_prefix("accept-encoding:"))
+                .map(|value| value.trim().to_string());
+
+            // Encodings are only accepted when decompressing, and as enabled by features
+            for (enabled, encoding) in [
+                (cfg!(feature = "gzip"), "gzip"),
+                (cfg!(feature = "brotli"), "br"),
+                (cfg!(feature = "deflate"), "deflate"),
+            ] {
+                assert_eq!(
+                    accept_encoding
+                        .as_deref()
+                        .is_some_and(|value| value.contains(encoding)),
+                    decompress && enabled,
+                    "{encoding}: {accept_encoding:?}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reqwest_max_body_size() {
+        for content_length in [true, false] {
+            for (body_size, ok) in [(1024, true), (1025, false)] {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let url = format!("http://{}/", listener.local_addr().unwrap());
+                let server =
+                    tokio::spawn(serve_once(listener, vec![b'a'; body_size], content_length));
+
+                let result = Scraper::<ReqwestHttpDriver>::new()
+                    .set_max_body_size(1024)
+                    .get(&url)
+                    .await;
+
+                match (result, ok) {
+                    (Ok(scraper), true) => assert_eq!(scraper.results()[0].len(), body_size),
+                    (Err(Error::HTTPDriverError(message)), false) => {
+                        assert!(message.contains("maximum body size of 1024 bytes"))
+                    }
+                    (result, _) => panic!("unexpected result for {body_size} bytes: {result:?}"),
+                }
+
+                server.await.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reqwest_resolve() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_once(listener, b"staging".to_vec(), true));
+
+        // The unresolvable host is connected to at the given address, and sent as the Host
+        let scraper = Scraper::<ReqwestHttpDriver>::new()
+            .set_resolve("scrapeycat.invalid", Some(addr))
+            .get(&format!("http://scrapeycat.invalid:{}/", addr.port()))
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results!["staging"]);
+
+        let request = server.await.unwrap();
+        assert!(request.contains(&format!("host: scrapeycat.invalid:{}", addr.port())));
+    }
+
+    #[tokio::test]
+    async fn test_get_paginated_time_budget() {
+        let scraper = Scraper::<SlowLinkTestingHttpDriver>::new()
+            .set_time_budget(Some(Duration::from_millis(100)));
+
+        let start = Instant::now();
+        let results = scraper
+            .get_paginated("page1", 1000)
+            .await
+            .unwrap()
+            .results()
+            .clone();
+
+        // Stops with the pages fetched within the budget, plus the one in flight when it ran out
+        assert!(results.len() >= 2 && results.len() <= 7, "{results:?}");
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(results.front(), Some(&"body1".to_string()));
+        assert_eq!(results.back(), Some(&format!("body{}", results.len())));
+
+        // The first page is always fetched
+        assert_eq!(
+            scraper
+                .set_time_budget(Some(Duration::ZERO))
+                .get_paginated("page1", 1000)
+                .await
+                .unwrap()
+                .results(),
+            &results!["body1"]
+        );
+
+        // Without a budget, only the page cap applies
+        assert_eq!(
+            scraper
+                .set_time_budget(None)
+                .get_paginated("page1", 8)
+                .await
+                .unwrap()
+                .results()
+                .len(),
+            8
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_cookies() {
+        let scraper = Scraper::<CookieTestingHttpDriver>::new();
+
+        // Without a cookie jar, no cookies are kept
+        let scraper_nojar = scraper
+            .get("http://example.com/login")
+            .await
+            .unwrap()
+            .get("http://example.com/")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            scraper_nojar.results(),
+            &results!["no cookies", "no cookies"]
+        );
+
+        let scraper_jar = scraper
+            .set_cookie_jar(Some(CookieJar::new()))
+            .get("http://example.com/")
+            .await
+            .unwrap()
+            .get("http://example.com/login")
+            .await
+            .unwrap()
+            .get("http://example.com/")
+            .await
+            .unwrap()
+            .get("http://example.org/")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            scraper_jar.results(),
+            &results!["no cookies", "no cookies", "session=abc", "no cookies"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_cookies_persisted() {
+        let path = std::env::temp_dir()
+            .join(format!(
+                "scrapeycat-test-scraper-cookies-{}.txt",
+                std::process::id()
+            ))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let _ = std::fs::remove_file(&path);
+
+        // First run logs in, saving the session cookie
+        Scraper::<CookieTestingHttpDriver>::new()
+            .set_cookie_jar(Some(CookieJar::open(&path).unwrap()))
+            .get("http://example.com/login")
+            .await
+            .unwrap();
+
+        // Subsequent run reconstructed from the saved jar sends the session cookie
+        let scraper = Scraper::<CookieTestingHttpDriver>::new()
+            .set_cookie_jar(Some(CookieJar::open(&path).unwrap()))
+            .get("http://example.com/")
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results!["session=abc"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_meta_refresh() {
+        let scraper = Scraper::<MetaRefreshTestingHttpDriver>::new();
+
+        // Not followed by default
+        assert!(
+            scraper
+                .get("http://a.test/dir/start")
+                .await
+                .unwrap()
+                .results()[0]
+                .contains("../middle")
+        );
+
+        assert_eq!(
+            scraper
+                .set_max_meta_refresh_hops(5)
+                .get("http://a.test/dir/start")
+                .await
+                .unwrap()
+                .results(),
+            &results!["final"]
+        );
+
+        assert!(
+            scraper
+                .set_max_meta_refresh_hops(1)
+                .get("http://a.test/dir/start")
+                .await
+                .unwrap()
+                .results()[0]
+                .contains("/final")
+        );
+
+        assert_eq!(
+            scraper
+                .set_max_meta_refresh_hops(3)
+                .get("http://a.test/loop")
+                .await
+                .unwrap()
+                .results()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_decode_body() {
+        let latin1 = b"{\"name\": \"Caf\xe9 \xabChat\xbb\"}";
+        let utf16le = "{\"name\": \"Caf\u{e9}\"}"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<_>>();
+        let utf16be = "[\"\u{732b}\"]"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            decode_body(latin1, Some("application/json; charset=ISO-8859-1")),
+            "{\"name\": \"Café «Chat»\"}"
+        );
+        assert_eq!(
+            decode_body(latin1, Some("application/json;charset=\"windows-1252\"")),
+            "{\"name\": \"Café «Chat»\"}"
+        );
+
+        // JSON in UTF-16 without a charset, detected from its first two bytes
+        assert_eq!(
+            decode_body(&utf16le, Some("application/json")),
+            "{\"name\": \"Café\"}"
+        );
+        assert_eq!(
+            decode_body(&utf16be, Some("application/ld+json")),
+            "[\"\u{732b}\"]"
+        );
+
+        // A byte order mark takes precedence over the charset, and is removed
+        assert_eq!(
+            decode_body(
+                &[b"\xef\xbb\xbf".as_slice(), "Café".as_bytes()].concat(),
+                Some("text/plain; charset=iso-8859-1")
+            ),
+            "Café"
+        );
+
+        // UTF-8 by default, replacing invalid sequences
+        assert_eq!(decode_body("Café".as_bytes(), None), "Café");
+        assert_eq!(decode_body("Café".as_bytes(), Some("text/html")), "Café");
+        assert_eq!(decode_body(b"Caf\xe9", Some("text/html")), "Caf\u{fffd}");
+        assert_eq!(
+            decode_body(b"a\x00", Some("text/plain; charset=bogus")),
+            "a\u{0}"
+        );
+        assert_eq!(decode_body(b"", Some("application/json")), "");
+    }
+
+    #[test]
+    fn test_jsonpath_on_decoded_body() {
+        let body = decode_body(
+            b"{\"city\": \"K\xf8benhavn\", \"price\": \"\x80 12\"}",
+            Some("application/json; charset=windows-1252"),
+        );
+
+        let scraper = nullscraper().with_results(results![body]);
+
+        assert_eq!(
+            scraper.jsonpath("$.city").unwrap().results(),
+            &results!["København"]
+        );
+        assert_eq!(
+            scraper.jsonpath("$.price").unwrap().results(),
+            &results!["€ 12"]
+        );
+    }
+
+    #[test]
+    fn test_meta_refresh_url() {
+        assert_eq!(
+            meta_refresh_url(r#"<meta http-equiv="refresh" content="0; url=https://a/b">"#),
+            Some("https://a/b".to_string())
+        );
+
+        assert_eq!(
+            meta_refresh_url(r#"<meta content="5;URL='/x?a=1&amp;b=2'" http-equiv=refresh>"#),
+            Some("/x?a=1&b=2".to_string())
+        );
+
+        assert_eq!(
+            meta_refresh_url(
+                r#"<meta charset="utf-8"><meta http-equiv="Refresh" content="0, next.html">"#
+            ),
+            Some("next.html".to_string())
+        );
+
+        // Plain reloads without a URL are not redirects
+        assert_eq!(
+            meta_refresh_url(r#"<meta http-equiv="refresh" content="30">"#),
+            None
+        );
+        assert_eq!(
+            meta_refresh_url(r#"<meta name="refresh" content="0; url=/x">"#),
+            None
+        );
+        assert_eq!(meta_refresh_url("no meta here"), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_circuit_breaker() {
+        let scraper = Scraper::<FailingHostTestingHttpDriver>::new().set_circuit_breaker(Some(
+            CircuitBreaker::new(3, std::time::Duration::from_millis(100)),
+        ));
+
+        for _ in 0..3 {
+            assert!(matches!(
+                scraper.get("http://down.test/").await,
+                Err(Error::HTTPDriverError(_))
+            ));
+        }
+
+        // The breaker is now open for down.test, but not for other hosts
+        assert!(matches!(
+            scraper.get("http://down.test/").await,
+            Err(Error::CircuitOpenError(host)) if host == "down.test"
+        ));
+        assert_eq!(
+            scraper.get("http://up.test/").await.unwrap().results(),
+            &results!["up"]
+        );
+
+        // After the cooldown, requests are let through again
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+
+        assert!(matches!(
+            scraper.get("http://down.test/").await,
+            Err(Error::HTTPDriverError(_))
+        ));
+        assert!(matches!(
+            scraper.get("http://down.test/").await,
+            Err(Error::CircuitOpenError(_))
+        ));
+    }
+
+    #[test]
+    fn test_html_decode() {
+        let scraper = nullscraper().with_results(results![
+            "Tom &amp; Jerry",
+            "&quot;Don&#39;t&quot;&nbsp;&#x2014; &lt;b&gt;",
+            "no entities here",
+        ]);
+
+        assert_eq!(
+            scraper.html_decode().results,
+            results!["Tom & Jerry", "\"Don't\"\u{a0}— <b>", "no entities here",]
+        );
+
+        let scraper = nullscraper().with_results(results![
+            "&euro;5 &mdash; &hellip; &Aring;",
+            "&amp;amp;",
+            "&bogus; & &amp &#xzz; &#0; &#1114112;",
+            "fish & chips; &",
+        ]);
+
+        // Double-encoded text is decoded once, unknown and malformed references pass through
+        assert_eq!(
+            scraper.html_decode().results,
+            results![
+                "€5 — … Å",
+                "&amp;",
+                "&bogus; & &amp &#xzz; &#0; &#1114112;",
+                "fish & chips; &",
+            ]
+        );
+
+        assert_eq!(nullscraper().html_decode().results, no_results());
+    }
+
+    #[test]
+    fn test_url_encode_and_decode() {
+        let scraper = nullscraper().with_results(results![
+            "cats & dogs",
+            "a=1&b=2",
+            "100% ~fine_-.",
+            "smörgåsbord/?#",
+        ]);
+
+        let encoded = scraper.url_encode();
+
+        assert_eq!(
+            encoded.results,
+            results![
+                "cats%20%26%20dogs",
+                "a%3D1%26b%3D2",
+                "100%25%20~fine_-.",
+                "sm%C3%B6rg%C3%A5sbord%2F%3F%23",
+            ]
+        );
+        assert_eq!(encoded.url_decode().results, scraper.results);
+
+        // Text without percent-encoded sequences passes through
+        assert_eq!(
+            nullscraper()
+                .with_results(results!["cats & dogs", "50%", "%zz", "%E2%82"])
+                .url_decode()
+                .results,
+            results!["cats & dogs", "50%", "%zz", "\u{fffd}"]
+        );
+
+        assert_eq!(nullscraper().url_encode().results, no_results());
+        assert_eq!(nullscraper().url_decode().results, no_results());
+    }
+
+    #[test]
+    fn test_trim() {
+        let scraper = nullscraper().with_results(results![
+            "  spaces  ",
+            "\t\ttabs\t",
+            "\n  newlines\r\n",
+            "already trimmed",
+            "inner  \t whitespace",
+            " \t\n ",
+        ]);
+
+        assert_eq!(
+            scraper.trim().results,
+            results![
+                "spaces",
+                "tabs",
+                "newlines",
+                "already trimmed",
+                "inner  \t whitespace",
+                "",
+            ]
+        );
+
+        assert_eq!(nullscraper().trim().results, no_results());
+    }
+
+    #[test]
+    fn test_any_match() {
+        let scraper =
+            nullscraper().with_results(results!["Cat food: OUT OF STOCK", "Catnip: IN STOCK"]);
+
+        assert!(scraper.any_match("IN STOCK").unwrap());
+        assert!(scraper.any_match("^Cat").unwrap());
+        assert!(!scraper.any_match("^IN STOCK").unwrap());
+        assert!(!nullscraper().any_match("").unwrap());
+        assert!(scraper.any_match("(").is_err());
+    }
+
+    #[test]
+    fn test_unique() {
+        let scraper =
+            nullscraper().with_results(results!["b", "a", "b", "Apple", "apple", "a", "b"]);
+
+        assert_eq!(
+            scraper.unique().results,
+            results!["b", "a", "Apple", "apple"]
+        );
+        assert_eq!(nullscraper().unique().results, no_results());
+    }
+
+    #[test]
+    fn test_unique_case_insensitive() {
+        let scraper = nullscraper().with_results(results!["Apple", "apple", "APPLE", "banana"]);
+
+        assert_eq!(
+            scraper.unique_case_insensitive().results,
+            results!["Apple", "banana"]
+        );
+
+        let scraper = nullscraper().with_results(results!["tag", "Ärlig", "TAG", "ärlig", "Tag"]);
+
+        assert_eq!(
+            scraper.unique_case_insensitive().results,
+            results!["tag", "Ärlig"]
+        );
+
+        assert_eq!(
+            nullscraper().unique_case_insensitive().results,
+            no_results()
+        );
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        let scraper = nullscraper().with_results(results![
+            "  spaces  and   more ",
+            "\t\ttabs\tand\t\ttabs\t",
+            "\n  multi\r\n   line\n\n text\r\n",
+            "<td>\n\t\tCell\n\t</td>",
+            "already normal",
+            "non\u{a0}breaking",
+            " \t\n ",
+        ]);
+
+        assert_eq!(
+            scraper.normalize_whitespace().results,
+            results![
+                "spaces and more",
+                "tabs and tabs",
+                "multi line text",
+                "<td> Cell </td>",
+                "already normal",
+                "non breaking",
+                "",
+            ]
+        );
+
+        assert_eq!(nullscraper().normalize_whitespace().results, no_results());
+    }
+
+    #[test]
+    fn test_to_lowercase_and_uppercase() {
+        let scraper =
+            nullscraper().with_results(results!["Hello, World", "ΣΟΦΟΣ", "Straße", "İ", ""]);
+
+        assert_eq!(
+            scraper.to_lowercase().results,
+            results!["hello, world", "σοφος", "straße", "i\u{307}", ""]
+        );
+        assert_eq!(
+            scraper.to_uppercase().results,
+            results!["HELLO, WORLD", "ΣΟΦΟΣ", "STRASSE", "İ", ""]
+        );
+
+        assert_eq!(nullscraper().to_lowercase().results, no_results());
+        assert_eq!(nullscraper().to_uppercase().results, no_results());
+    }
+
+    #[test]
+    fn test_canonicalize_urls() {
+        let params = DEFAULT_TRACKING_PARAMS
+            .iter()
+            .map(|param| param.to_string())
+            .collect::<Vec<_>>();
+
+        let scraper = nullscraper().with_results(results![
+            "https://a.test/post/?utm_source=feed&utm_medium=rss",
+            "https://a.test/post?fbclid=abc",
+            "https://a.test/post/",
+            "https://a.test/post?id=1&gclid=xyz",
+            "https://a.test/",
+            "not a url",
+        ]);
+
+        assert_eq!(
+            scraper.canonicalize_urls(&params, false).results,
+            results![
+                "https://a.test/post",
+                "https://a.test/post",
+                "https://a.test/post",
+                "https://a.test/post?id=1",
+                "https://a.test/",
+                "not a url",
+            ]
+        );
+
+        assert_eq!(
+            scraper.canonicalize_urls(&params, true).results,
+            results![
+                "https://a.test/post",
+                "https://a.test/post?id=1",
+                "https://a.test/",
+                "not a url",
+            ]
         );
-        assert_eq!(s3.extract("some(...)").unwrap().results, results!["tim"]);
+
         assert_eq!(
-            s3.extract("cat|dog")
-                .unwrap()
-                .extract("[cad]")
+            scraper
+                .canonicalize_urls(&["id".to_string(), "utm_*".to_string()], true)
+                .results,
+            results![
+                "https://a.test/post",
+                "https://a.test/post?fbclid=abc",
+                "https://a.test/post?gclid=xyz",
+                "https://a.test/",
+                "not a url",
+            ]
+        );
+
+        assert_eq!(
+            nullscraper().canonicalize_urls(&params, true).results,
+            no_results()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_polite_delay() {
+        let scraper = Scraper::<TestHttpDriver>::new()
+            .set_polite_delay(Some(PoliteDelay::new(Duration::from_millis(100))));
+
+        let start = std::time::Instant::now();
+        let scraper = scraper.get("string://a").await.unwrap();
+
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        let scraper = scraper.get("string://b").await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(scraper.results(), &results!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_rate_limiter() {
+        let rate_limiter = RateLimiter::new(10.0).unwrap();
+        let first = Scraper::<TestHttpDriver>::new().set_rate_limiter(Some(rate_limiter.clone()));
+        let second = Scraper::<TestHttpDriver>::new().set_rate_limiter(Some(rate_limiter));
+
+        let start = std::time::Instant::now();
+
+        // Scrapers sharing a rate limiter share the limit
+        let first = first.get("string://a").await.unwrap();
+        let second = second.get("string://b").await.unwrap();
+        let first = first.get("string://c").await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(first.results(), &results!["a", "c"]);
+        assert_eq!(second.results(), &results!["b"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_memory_cache() {
+        let scraper = Scraper::<FlakyTestHttpDriver>::new()
+            .set_memory_cache(Some(MemoryCache::new()))
+            .get("flaky://0/memory-index")
+            .await
+            .unwrap()
+            .get("flaky://0/memory-index")
+            .await
+            .unwrap();
+
+        assert_eq!(flaky_fetches("flaky://0/memory-index"), 1);
+        assert_eq!(scraper.results(), &results!["memory-index", "memory-index"]);
+
+        // Without the cache, every get makes a request
+        Scraper::<FlakyTestHttpDriver>::new()
+            .get("flaky://0/memory-uncached")
+            .await
+            .unwrap()
+            .get("flaky://0/memory-uncached")
+            .await
+            .unwrap();
+
+        assert_eq!(flaky_fetches("flaky://0/memory-uncached"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_response_transformer() {
+        let scraper = Scraper::<TestHttpDriver>::new()
+            .add_response_transformer(|body| Ok(body.to_uppercase()))
+            .add_response_transformer(|body| Ok(format!("<{body}>")))
+            .get("string://a")
+            .await
+            .unwrap()
+            .get_all(&["string://b".to_string(), "string://c".to_string()], 2)
+            .await
+            .unwrap()
+            .get_paginated("string://d", 1)
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results!["<A>", "<B>", "<C>", "<D>"]);
+
+        let scraper = scraper
+            .clear_response_transformers()
+            .get("string://e")
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results().back(), Some(&"e".to_string()));
+
+        let scraper = Scraper::<MethodTestHttpDriver>::new()
+            .add_response_transformer(|body| Ok(body.to_lowercase()))
+            .request(HttpMethod::Post, "string://F", Some("G"))
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results!["post string://f\ng"]);
+
+        // Errors fail the request
+        assert!(
+            Scraper::<TestHttpDriver>::new()
+                .add_response_transformer(|_| Err(Error::ParseError("nope".to_string())))
+                .get("string://a")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_if_empty() {
+        let scraper = Scraper::<FlakyTestHttpDriver>::new()
+            .get("flaky://2/retried")
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results![""]);
+
+        let scraper = scraper.retry_if_empty(1, Duration::ZERO).await.unwrap();
+
+        assert_eq!(scraper.results(), &results![""]);
+
+        let scraper = scraper.retry_if_empty(3, Duration::ZERO).await.unwrap();
+
+        assert_eq!(scraper.results(), &results!["retried"]);
+
+        // Gives up after the given number of attempts
+        let scraper = Scraper::<FlakyTestHttpDriver>::new()
+            .get("flaky://5/never")
+            .await
+            .unwrap()
+            .retry_if_empty(2, Duration::ZERO)
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results![""]);
+
+        // Does nothing unless empty, or without a previous get
+        let scraper = Scraper::<FlakyTestHttpDriver>::new()
+            .get("flaky://0/first")
+            .await
+            .unwrap()
+            .retry_if_empty(3, Duration::ZERO)
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results!["first"]);
+        assert_eq!(
+            nullscraper()
+                .retry_if_empty(3, Duration::ZERO)
+                .await
                 .unwrap()
+                .results(),
+            &no_results()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_resolve_and_host_header() {
+        let scraper = Scraper::<ResolveTestingHttpDriver>::new()
+            .set_resolve("Example.com", Some("203.0.113.7:0".parse().unwrap()))
+            .set_resolve("other.test", Some("[2001:db8::1]:8443".parse().unwrap()))
+            .get("https://example.com/")
+            .await
+            .unwrap()
+            .set_resolve("other.test", None)
+            .set_header("Host".to_string(), "cdn.example.com".to_string())
+            .get("http://203.0.113.7/")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            scraper.results(),
+            &results![
+                " [example.com=203.0.113.7:0, other.test=[2001:db8::1]:8443]",
+                "cdn.example.com [example.com=203.0.113.7:0]"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_vs() {
+        let snapshot = results!["alice", "bob", "charlie", "bob"];
+        let scraper = nullscraper().with_results(results!["dave", "bob", "alice", "erin"]);
+
+        assert_eq!(
+            scraper.changed_vs(&snapshot).results,
+            results!["- charlie", "- bob", "+ dave", "+ erin"]
+        );
+
+        // Only additions, only removals and no changes
+        assert_eq!(
+            scraper.changed_vs(&results!["bob"]).results,
+            results!["+ dave", "+ alice", "+ erin"]
+        );
+        assert_eq!(
+            nullscraper().changed_vs(&snapshot).results,
+            results!["- alice", "- bob", "- charlie", "- bob"]
+        );
+        assert_eq!(
+            scraper
+                .changed_vs(&results!["erin", "alice", "bob", "dave"])
                 .results,
-            results!["c", "a", "d", "d", "c", "a"]
+            no_results()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_all() {
+        let urls = ["300", "100", "200"].map(String::from);
+        let scraper = Scraper::<SleepingTestingHttpDriver>::new().with_results(results!["x"]);
+
+        let start = std::time::Instant::now();
+        let concurrent = scraper.get_all(&urls, 3).await.unwrap();
+
+        assert!(start.elapsed() < Duration::from_millis(600));
+        assert_eq!(concurrent.results(), &results!["x", "300", "100", "200"]);
+
+        let start = std::time::Instant::now();
+        let sequential = scraper.get_all(&urls, 1).await.unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(600));
+        assert_eq!(sequential.results(), &results!["x", "300", "100", "200"]);
+
+        assert!(scraper.get_all(&urls, 0).await.is_err());
+        assert!(
+            scraper
+                .get_all(&["100".to_string(), "invalid".to_string()], 2)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_all_cookies() {
+        let urls = ["http://example.com/login", "http://example.com/"].map(String::from);
+
+        let scraper = Scraper::<CookieTestingHttpDriver>::new()
+            .set_cookie_jar(Some(CookieJar::new()))
+            .get_all(&urls, 2)
+            .await
+            .unwrap()
+            .get("http://example.com/")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            scraper.results(),
+            &results!["no cookies", "no cookies", "session=abc"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_test_http_driver() {
+        TestHttpDriver::set_response("https://map.test/a", "apple");
+        TestHttpDriver::set_response("https://map.test/b", "banana");
+
+        let scraper = Scraper::<TestHttpDriver>::new()
+            .get("string://hello")
+            .await
+            .unwrap()
+            .get("string://")
+            .await
+            .unwrap()
+            .get("https://map.test/b")
+            .await
+            .unwrap()
+            .get("https://map.test/a")
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results!["hello", "", "banana", "apple"]);
+        assert!(scraper.get("https://map.test/c").await.is_err());
+
+        TestHttpDriver::remove_response("https://map.test/a");
+        assert!(scraper.get("https://map.test/a").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_post_multipart() {
+        let form = MultipartForm {
+            fields: vec![
+                ("name".to_string(), "Tom".to_string()),
+                ("message".to_string(), "Hello, Jerry".to_string()),
+            ],
+            files: vec![("upload".to_string(), "/tmp/cat.png".to_string())],
+        };
+
+        let scraper = Scraper::<MultipartTestHttpDriver>::new()
+            .with_results(results!["x"])
+            .post_multipart("https://a.test/submit", &form)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            scraper.results(),
+            &results![
+                "x",
+                "https://a.test/submit\nname=Tom\nmessage=Hello, Jerry\nupload@/tmp/cat.png"
+            ]
+        );
+
+        assert_eq!(
+            nullscraper()
+                .post_multipart("https://a.test/submit", &form)
+                .await
+                .unwrap()
+                .results(),
+            &results![""]
+        );
+
+        // Decompression and the maximum body size apply as for GET requests
+        assert_eq!(
+            Scraper::<SpecTestingHttpDriver>::new()
+                .set_decompress(false)
+                .set_max_body_size(1024)
+                .post_multipart("https://a.test/submit", &form)
+                .await
+                .unwrap()
+                .results(),
+            &results!["POST decompress=false max_body_size=1024"]
+        );
+    }
+
+    #[test]
+    fn test_http_method_from_str() {
+        assert_eq!("GET".parse::<HttpMethod>().unwrap(), HttpMethod::Get);
+        assert_eq!("post".parse::<HttpMethod>().unwrap(), HttpMethod::Post);
+        assert_eq!(" Put ".parse::<HttpMethod>().unwrap(), HttpMethod::Put);
+        assert_eq!("delete".parse::<HttpMethod>().unwrap(), HttpMethod::Delete);
+        assert_eq!("HEAD".parse::<HttpMethod>().unwrap(), HttpMethod::Head);
+
+        assert!("PATCH".parse::<HttpMethod>().is_err());
+        assert!("".parse::<HttpMethod>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request() {
+        let scraper = Scraper::<MethodTestHttpDriver>::new();
+
+        for (method, body, expected) in [
+            (HttpMethod::Get, None, "GET https://a.test/items"),
+            (
+                HttpMethod::Post,
+                Some(r#"{"name":"Tom"}"#),
+                "POST https://a.test/items\n{\"name\":\"Tom\"}",
+            ),
+            (
+                HttpMethod::Put,
+                Some("Jerry"),
+                "PUT https://a.test/items\nJerry",
+            ),
+            (HttpMethod::Delete, None, "DELETE https://a.test/items"),
+            (HttpMethod::Head, None, "HEAD https://a.test/items"),
+        ] {
+            assert_eq!(
+                scraper
+                    .request(method, "https://a.test/items", body)
+                    .await
+                    .unwrap()
+                    .results(),
+                &results![expected]
+            );
+        }
+
+        // Plain GET requests go through the same path
+        assert_eq!(
+            scraper.get("https://a.test/items").await.unwrap().results(),
+            &results!["GET https://a.test/items"]
+        );
+
+        assert_eq!(
+            nullscraper()
+                .request(HttpMethod::Delete, "https://a.test/items", None)
+                .await
+                .unwrap()
+                .results(),
+            &results![""]
+        );
+
+        // Every request is made by the driver's `request`, whatever the method
+        let scraper = Scraper::<TestHttpDriver>::new();
+
+        for method in [HttpMethod::Get, HttpMethod::Post, HttpMethod::Put] {
+            assert_eq!(
+                scraper
+                    .request(method, "string://hello", Some("body"))
+                    .await
+                    .unwrap()
+                    .results(),
+                &results!["hello"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sitemap_urls() {
+        let scraper = Scraper::<TestHttpDriver>::new();
+
+        let urlset = scraper
+            .get("file://tests/assets/sitemap/urlset.xml")
+            .await
+            .unwrap()
+            .sitemap_urls()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            urlset.results(),
+            &results![
+                "https://example.com/",
+                "https://example.com/cats?breed=siamese&age=2",
+                "https://example.com/dogs"
+            ]
+        );
+
+        let index = scraper
+            .get("file://tests/assets/sitemap/sitemapindex.xml")
+            .await
+            .unwrap()
+            .sitemap_urls()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            index.results(),
+            &results![
+                "https://example.com/",
+                "https://example.com/cats?breed=siamese&age=2",
+                "https://example.com/dogs",
+                "https://example.com/birds"
+            ]
+        );
+
+        assert_eq!(
+            scraper.sitemap_urls().await.unwrap().results(),
+            &no_results()
+        );
+        assert!(
+            scraper
+                .with_results(results!["<html></html>"])
+                .sitemap_urls()
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expect_content_type() {
+        let scraper = Scraper::<ContentTypeTestHttpDriver>::new();
+
+        let json = scraper
+            .get("content-type://application/json; charset=utf-8")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            json.last_response_headers().get("content-type"),
+            Some(&"application/json; charset=utf-8".to_string())
+        );
+        assert_eq!(
+            json.expect_content_type("^application/json")
+                .unwrap()
+                .results(),
+            &results!["{}"]
+        );
+
+        let html = scraper.get("content-type://text/html").await.unwrap();
+
+        assert!(matches!(
+            html.expect_content_type("^application/json"),
+            Err(Error::ContentTypeMismatchError(content_type, _)) if content_type == "text/html"
+        ));
+
+        // No response, or no Content-Type header
+        assert!(scraper.expect_content_type("json").is_err());
+        assert!(
+            scraper
+                .get("http://a.test/")
+                .await
+                .unwrap()
+                .expect_content_type("json")
+                .is_err()
+        );
+        assert!(html.expect_content_type("(").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_response_cache() {
+        let dir = std::env::temp_dir()
+            .join(format!(
+                "scrapeycat-test-scraper-response-cache-{}",
+                std::process::id()
+            ))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let _ = std::fs::remove_dir_all(&dir);
+
+        COUNTING_DRIVER_REQUESTS.swap(0, SeqCst);
+
+        // Also for a new scraper, as in a later run of the same script
+        let scraper = Scraper::<CountingTestingHttpDriver>::new()
+            .set_response_cache(Some(ResponseCache::new(&dir, Duration::from_secs(3600))));
+
+        // Second fetch within the TTL is served from disk
+        let scraper = scraper.get("http://a.test/").await.unwrap();
+        let scraper = scraper.get("http://a.test/").await.unwrap();
+
+        assert_eq!(
+            scraper.results(),
+            &results!["http://a.test/ #1", "http://a.test/ #1"]
+        );
+
+        // Also for a new scraper, as in a later run of the same script
+        let scraper = Scraper::<CountingTestingHttpDriver>::new()
+            .set_response_cache(Some(ResponseCache::new(&dir, Duration::from_secs(3600))))
+            .get("http://a.test/")
+            .await
+            .unwrap();
+
+        assert_eq!(scraper.results(), &results!["http://a.test/ #1"]);
+        assert_eq!(COUNTING_DRIVER_REQUESTS.load(SeqCst), 1);
+
+        // Fetch after the TTL has passed makes a request
+        let scraper = scraper
+            .set_response_cache(Some(ResponseCache::new(&dir, Duration::ZERO)))
+            .get("http://a.test/")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            scraper.results(),
+            &results!["http://a.test/ #1", "http://a.test/ #2"]
+        );
+        assert_eq!(COUNTING_DRIVER_REQUESTS.load(SeqCst), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_robots() {
+        ROBOTS_TXT_FETCHES.swap(0, SeqCst);
+
+        // Off by default
+        assert!(
+            Scraper::<RobotsTestHttpDriver>::new()
+                .get("http://robots.test/private")
+                .await
+                .is_ok()
+        );
+
+        let scraper = Scraper::<RobotsTestHttpDriver>::new().set_robots(Some(RobotsCache::new()));
+
+        assert_eq!(
+            scraper
+                .get("http://robots.test/news")
+                .await
+                .unwrap()
+                .results(),
+            &results!["http://robots.test/news"]
         );
+        assert!(matches!(
+            scraper.get("http://robots.test/private/page").await,
+            Err(Error::RobotsDisallowedError(url)) if url == "http://robots.test/private/page"
+        ));
+        assert!(matches!(
+            scraper
+                .set_header("User-Agent".to_string(), "BadBot/1.0".to_string())
+                .get("http://robots.test/news")
+                .await,
+            Err(Error::RobotsDisallowedError(_))
+        ));
 
-        assert_eq!(s1.extract("rust").unwrap().results, no_results());
-        assert_eq!(s2.extract("rust").unwrap().results, no_results());
-        assert_eq!(s3.extract("rust").unwrap().results, no_results());
+        // Hosts responding with 4xx allow everything
+        assert!(scraper.get("http://norobots.test/private").await.is_ok());
+
+        // The robots.txt is only fetched once
+        assert_eq!(ROBOTS_TXT_FETCHES.load(SeqCst), 1);
+
+        // Hosts responding with 5xx or not at all disallow everything, until the robots.txt can
+        // be fetched, which is retried before each request
+        UNAVAILABLE_ROBOTS_TXT_FETCHES.swap(0, SeqCst);
+
+        for _ in 0..2 {
+            assert!(matches!(
+                scraper.get("http://unavailable.test/news").await,
+                Err(Error::RobotsDisallowedError(url)) if url == "http://unavailable.test/news"
+            ));
+        }
+
+        assert_eq!(UNAVAILABLE_ROBOTS_TXT_FETCHES.load(SeqCst), 2);
+
+        assert!(matches!(
+            scraper.get("http://unreachable.test/news").await,
+            Err(Error::RobotsDisallowedError(_))
+        ));
     }
 
     #[test]
-    fn test_retain() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results![
-            "its raining cats and dogs",
-            "dogs will sometimes chase cats",
-        ]);
+    fn test_next_link_url() {
+        assert_eq!(
+            next_link_url(r#"<https://a/?page=2>; rel="next""#),
+            Some("https://a/?page=2".to_string())
+        );
 
-        assert_eq!(s1.retain("test").unwrap().results, no_results());
-        assert_eq!(s2.retain("cats").unwrap().results, s2.results);
-        assert_eq!(s2.retain("dogs").unwrap().results, s2.results);
         assert_eq!(
-            s2.retain("rain").unwrap().results,
-            results!["its raining cats and dogs"]
-        )
-    }
+            next_link_url(r#"<https://a/?page=1>; rel="prev", <https://a/?page=3>; rel=next"#),
+            Some("https://a/?page=3".to_string())
+        );
 
-    #[test]
-    fn test_first() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a"]);
-        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+        assert_eq!(
+            next_link_url(r#"<https://a/?page=9>; title="x"; rel="last next""#),
+            Some("https://a/?page=9".to_string())
+        );
 
-        assert_eq!(s1.first().results, no_results());
-        assert_eq!(s2.first().results, results!["a"]);
-        assert_eq!(s3.first().results, results!["a"]);
+        assert_eq!(next_link_url(r#"<https://a/?page=1>; rel="prev""#), None);
+        assert_eq!(next_link_url(""), None);
     }
 
     #[test]
-    fn test_last() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a"]);
-        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+    fn test_discard() {
+        let scraper = nullscraper().with_results(results!["cat", "dog", "puma", "snake", "sheep"]);
 
-        assert_eq!(s1.last().results, no_results());
-        assert_eq!(s2.last().results, results!["a"]);
-        assert_eq!(s3.last().results, results!["c"]);
+        assert_eq!(
+            scraper.discard("a").unwrap().results(),
+            &results!["dog", "sheep"]
+        );
     }
 
     #[test]
-    fn test_take() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a"]);
-        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+    fn test_partition() {
+        let scraper = nullscraper().with_results(results!["cat", "dog", "puma", "snake", "sheep"]);
 
-        assert_eq!(s1.take(0).results, no_results());
-        assert_eq!(s1.take(1).results, no_results());
-        assert_eq!(s2.take(0).results, no_results());
-        assert_eq!(s2.take(1).results, results!["a"]);
-        assert_eq!(s2.take(7).results, results!["a"]);
-        assert_eq!(s3.take(2).results, results!["a", "b"]);
-        assert_eq!(s3.take(3).results, results!["a", "b", "c"]);
+        assert_eq!(
+            scraper.partition("a").unwrap(),
+            (results!["cat", "puma", "snake"], results!["dog", "sheep"])
+        );
+        assert_eq!(
+            scraper.partition("^x").unwrap(),
+            (no_results(), scraper.results().clone())
+        );
+        assert_eq!(
+            nullscraper().partition("a").unwrap(),
+            (no_results(), no_results())
+        );
+        assert!(scraper.partition("(").is_err());
     }
 
     #[test]
-    fn test_drop() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a"]);
-        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+    fn test_as_json_object() {
+        let scraper = nullscraper().with_results(results![
+            "plain",
+            "with \"quotes\" and \\backslash",
+            "line\nbreak\ttab",
+            "",
+        ]);
 
-        assert_eq!(s1.drop(0).results, no_results());
-        assert_eq!(s1.drop(1).results, no_results());
-        assert_eq!(s1.drop(5).results, no_results());
-        assert_eq!(s2.drop(0).results, results!["a"]);
-        assert_eq!(s2.drop(1).results, no_results());
-        assert_eq!(s2.drop(5).results, no_results());
-        assert_eq!(s3.drop(0).results, results!["a", "b", "c"]);
-        assert_eq!(s3.drop(1).results, results!["b", "c"]);
-        assert_eq!(s3.drop(2).results, results!["c"]);
-        assert_eq!(s3.drop(3).results, no_results());
-        assert_eq!(s3.drop(5).results, no_results());
-    }
+        assert_eq!(
+            scraper.as_json_object("title").results,
+            results![
+                r#"{"title":"plain"}"#,
+                r#"{"title":"with \"quotes\" and \\backslash"}"#,
+                r#"{"title":"line\nbreak\ttab"}"#,
+                r#"{"title":""}"#,
+            ]
+        );
 
-    #[test]
-    fn test_prepend() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a"]);
-        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+        // Keys are escaped as well, and the objects round-trip
+        let objects = scraper.as_json_object("a \"key\"");
 
-        assert_eq!(s1.prepend("_").results, no_results());
-        assert_eq!(s2.prepend("_").results, results!["_a"]);
-        assert_eq!(s3.prepend("_").results, results!["_a", "_b", "_c"]);
-    }
+        for (object, result) in objects.results.iter().zip(scraper.results.iter()) {
+            let json = object.parse::<JsonValue>().unwrap();
 
-    #[test]
-    fn test_append() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a"]);
-        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+            assert_eq!(json.as_object().unwrap().len(), 1);
+            assert_eq!(json["a \"key\""], JsonValue::String(result.clone()));
+        }
 
-        assert_eq!(s1.append("_").results, no_results());
-        assert_eq!(s2.append("_").results, results!["a_"]);
-        assert_eq!(s3.append("_").results, results!["a_", "b_", "c_"]);
+        assert_eq!(nullscraper().as_json_object("x").results, no_results());
     }
 
     #[test]
-    fn test_join() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a"]);
-        let s3 = nullscraper().with_results(results!["a", "b", "c"]);
+    fn test_as_env() {
+        let scraper = nullscraper().with_results(results![
+            "plain",
+            "with spaces",
+            r#"it's "quoted""#,
+            "$HOME `date` \\n",
+            "",
+        ]);
 
-        assert_eq!(s1.join(",").results, no_results());
-        assert_eq!(s2.join("--").results, results!["a"]);
-        assert_eq!(s3.join("~~~").results, results!["a~~~b~~~c"]);
-    }
+        assert_eq!(
+            scraper.as_env("ITEM").unwrap().results,
+            results![
+                "export ITEM_1='plain'",
+                "export ITEM_2='with spaces'",
+                r#"export ITEM_3='it'\''s "quoted"'"#,
+                "export ITEM_4='$HOME `date` \\n'",
+                "export ITEM_5=''",
+            ]
+        );
 
-    #[test]
-    fn test_clear() {
-        let s1 = nullscraper();
-        let s2 = nullscraper().with_results(results!["a", "b", "c"]);
+        assert!(scraper.as_env("_x9").is_ok());
+        assert_eq!(nullscraper().as_env("X").unwrap().results, no_results());
 
-        assert_eq!(s1.clear().results, no_results());
-        assert_eq!(s2.clear().results, no_results());
+        for prefix in ["", "9X", "A-B", "A B", "A$"] {
+            assert!(
+                matches!(scraper.as_env(prefix), Err(Error::ParseError(_))),
+                "{prefix:?}"
+            );
+        }
     }
 
     #[tokio::test]
-    async fn test_set_header() {
-        let scraper = Scraper::<HeaderTestingHttpDriver>::new()
-            .get("foo")
+    async fn test_json_ld() {
+        let scraper = Scraper::<TestHttpDriver>::new()
+            .get("file://tests/assets/jsonld/product.html")
             .await
+            .unwrap()
+            .json_ld()
             .unwrap();
 
-        assert_eq!(scraper.results.len(), 1);
-
-        assert!(
-            !scraper
-                .results
-                .get(0)
-                .unwrap()
-                .contains("[User-Agent]:[Scrapeycat 1.2.3]")
-        );
-
-        assert!(
-            !scraper
-                .results
-                .get(0)
-                .unwrap()
-                .contains("[Accept-Charset]:[utf-8]")
-        );
+        assert_eq!(scraper.results().len(), 2);
 
-        let scraper = Scraper::<HeaderTestingHttpDriver>::new()
-            .set_header("User-Agent".to_string(), "Scrapeycat 1.2.3".to_string())
-            .set_header("Accept-Charset".to_string(), "utf-8".to_string())
-            .get("foo")
-            .await
-            .unwrap();
+        let types = scraper
+            .results()
+            .iter()
+            .map(|str| str.parse::<JsonValue>().unwrap()["@type"].clone())
+            .collect::<Vec<_>>();
 
-        assert_eq!(scraper.results.len(), 1);
+        assert_eq!(types, vec!["Product", "BreadcrumbList"]);
 
-        assert!(
-            scraper
-                .results
-                .get(0)
-                .unwrap()
-                .contains("[User-Agent]:[Scrapeycat 1.2.3]")
+        assert_eq!(
+            scraper.jsonpath("$.offers.price").unwrap().results(),
+            &results!["4.99"]
         );
-
-        assert!(
+        assert_eq!(
             scraper
-                .results
-                .get(0)
+                .jsonpath("$.itemListElement[*].name")
                 .unwrap()
-                .contains("[Accept-Charset]:[utf-8]")
+                .results(),
+            &results!["Toys", "Catnip"]
         );
-    }
 
-    #[tokio::test]
-    async fn test_clear_headers() {
-        let scraper = Scraper::<HeaderTestingHttpDriver>::new()
-            .set_header("User-Agent".to_string(), "Scrapeycat 1.2.3".to_string())
-            .clear_headers()
-            .get("foo")
-            .await
-            .unwrap();
+        // Each result is searched separately, and results without JSON-LD yield nothing
+        let scraper = nullscraper().with_results(results![
+            r#"<script type="application/ld+json">[1, 2]</script>"#,
+            "<p>No structured data</p>",
+            r#"<script type=application/ld+json>{"a": null}</script>"#,
+        ]);
 
-        assert!(
-            !scraper
-                .results
-                .get(0)
-                .unwrap()
-                .contains("[User-Agent]:[Scrapeycat 1.2.3]")
+        assert_eq!(
+            scraper.json_ld().unwrap().results(),
+            &results!["[1,2]", r#"{"a":null}"#]
         );
-    }
-
-    #[test]
-    fn test_discard() {
-        let scraper = nullscraper().with_results(results!["cat", "dog", "puma", "snake", "sheep"]);
 
-        assert_eq!(
-            scraper.discard("a").unwrap().results(),
-            &results!["dog", "sheep"]
+        assert!(
+            nullscraper()
+                .with_results(results![
+                    r#"<script type="application/ld+json">{ oops }</script>"#
+                ])
+                .json_ld()
+                .is_err()
         );
+
+        assert_eq!(nullscraper().json_ld().unwrap().results(), &no_results());
     }
 
     #[test]
@@ -668,6 +4817,121 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_jsonpath_objects() {
+        let scraper = nullscraper().with_results(results![
+            r#"{"items": [{"id": 1, "tags": ["a"]}, {"id": 2, "tags": []}]}"#
+        ]);
+
+        assert_eq!(
+            scraper.jsonpath("$.items[*]").unwrap().results(),
+            &results![r#"{"id":1,"tags":["a"]}"#, r#"{"id":2,"tags":[]}"#]
+        );
+    }
+
+    #[test]
+    fn test_json_filter() {
+        let scraper = nullscraper().with_results(results![
+            r#"{"title": "Neuromancer", "price": 9.99, "used": true}"#,
+            r#"{"title": "Snow Crash", "price": 140, "used": false}"#,
+            r#"{"title": "Hyperion", "price": 100, "used": null}"#,
+            r#"{"title": "Dune"}"#,
+        ]);
+
+        let titles = |expr: &str| -> Vector<String> {
+            scraper
+                .json_filter(expr)
+                .unwrap()
+                .jsonpath("$.title")
+                .unwrap()
+                .results()
+                .clone()
+        };
+
+        assert_eq!(titles("$.price < 100"), results!["Neuromancer"]);
+        assert_eq!(
+            titles("$.price <= 100"),
+            results!["Neuromancer", "Hyperion"]
+        );
+        assert_eq!(titles("$.price > 100"), results!["Snow Crash"]);
+        assert_eq!(
+            titles("$.price >= 100.0"),
+            results!["Snow Crash", "Hyperion"]
+        );
+        assert_eq!(titles("$.price == 140"), results!["Snow Crash"]);
+        assert_eq!(
+            titles("$.price != 140"),
+            results!["Neuromancer", "Hyperion"]
+        );
+        assert_eq!(titles(r#"$.title == "Dune""#), results!["Dune"]);
+        assert_eq!(
+            titles(r#"$.title > "M""#),
+            results!["Neuromancer", "Snow Crash"]
+        );
+        assert_eq!(titles("$.used == true"), results!["Neuromancer"]);
+        assert_eq!(titles("$.used == null"), results!["Hyperion"]);
+        assert_eq!(titles("$.used < true"), no_results());
+    }
+
+    #[test]
+    fn test_json_filter_errors() {
+        let scraper = nullscraper().with_results(results![r#"{"price": 1}"#]);
+
+        assert!(matches!(
+            scraper.json_filter("price < 100"),
+            Err(Error::ParseError(_))
+        ));
+        assert!(matches!(
+            scraper.json_filter("$.price ~ 100"),
+            Err(Error::ParseError(_))
+        ));
+        assert!(matches!(
+            scraper.json_filter("$.price < abc"),
+            Err(Error::JsonParseError(_))
+        ));
+        assert!(matches!(
+            nullscraper()
+                .with_results(results!["{"])
+                .json_filter("$.price < 100"),
+            Err(Error::JsonParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_json_canonical() {
+        let scraper = nullscraper().with_results(results![
+            r#"{"b": 1, "a": {"d": [{"f": null, "e": true}], "c": "x"}}"#,
+            r#"{ "a": { "c": "x", "d": [ { "e": true, "f": null } ] }, "b": 1 }"#,
+            r#"[3, {"z": 1, "y": 2}, "s"]"#,
+        ]);
+
+        let canonical = scraper.json_canonical().unwrap();
+
+        assert_eq!(
+            canonical.results(),
+            &results![
+                r#"{"a":{"c":"x","d":[{"e":true,"f":null}]},"b":1}"#,
+                r#"{"a":{"c":"x","d":[{"e":true,"f":null}]},"b":1}"#,
+                r#"[3,{"y":2,"z":1},"s"]"#,
+            ]
+        );
+
+        // Array order is significant
+        assert_eq!(
+            nullscraper()
+                .with_results(results!["[1, 2]", "[2, 1]"])
+                .json_canonical()
+                .unwrap()
+                .results(),
+            &results!["[1,2]", "[2,1]"]
+        );
+
+        assert!(matches!(
+            nullscraper().with_results(results!["{"]).json_canonical(),
+            Err(Error::JsonParseError(_))
+        ));
+    }
+
     #[test]
     fn test_jsonpath_parse_error() {
         let scraper = nullscraper().with_results(results![
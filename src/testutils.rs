@@ -1,10 +1,20 @@
 #![cfg(any(test, feature = "testutils"))]
 
-use std::{env, fs};
+use std::{
+    env, fs,
+    net::SocketAddr,
+    sync::{
+        LazyLock, Mutex, RwLock,
+        atomic::{AtomicU32, Ordering::SeqCst},
+    },
+    time::Duration,
+};
+
+use im::HashMap;
 
 use crate::{
     Error,
-    scraper::{HttpDriver, HttpHeaders},
+    scraper::{HttpDriver, HttpHeaders, HttpResponse, RequestBody, RequestSpec},
 };
 
 /// `path_in_project_root!("foo")` -> `"/<projectroot>/foo"`, where `<projectroot>` is the path
@@ -18,24 +28,58 @@ macro_rules! path_in_project_root {
 
 pub use path_in_project_root;
 
+/// Responses served by [TestHttpDriver], keyed by URL.
+static TEST_DRIVER_RESPONSES: LazyLock<RwLock<HashMap<String, String>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
 /// The TestHttpDriver supports the following forms of URLs:
 ///
 /// * `file://<path>`: returns contents of local filesystem at `<path>`.
 /// * `string://<content>`: returns the string `<content>`.
+/// * any URL given a canned response using [TestHttpDriver::set_response]: returns the response.
+///
+/// Since drivers have no instances, canned responses are shared by all uses of the driver within
+/// the process, so tests running in parallel should use distinct URLs.
 #[derive(Debug, Clone)]
 pub struct TestHttpDriver;
 
+impl TestHttpDriver {
+    /// Respond to requests for `url` with `body`.
+    pub fn set_response(url: impl Into<String>, body: impl Into<String>) {
+        TEST_DRIVER_RESPONSES
+            .write()
+            .unwrap()
+            .insert(url.into(), body.into());
+    }
+
+    /// Stop responding to requests for `url`.
+    pub fn remove_response(url: &str) {
+        TEST_DRIVER_RESPONSES.write().unwrap().remove(url);
+    }
+}
+
 impl HttpDriver for TestHttpDriver {
-    async fn get(url: &str, _headers: HttpHeaders<'_>) -> Result<String, Error> {
-        if url.starts_with("file://") {
-            Ok(fs::read_to_string(path_in_project_root!(
-                url.strip_prefix("file://").unwrap()
-            ))?)
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let url = spec.url;
+
+        let body = if let Some(body) = TEST_DRIVER_RESPONSES.read().unwrap().get(url) {
+            body.clone()
+        } else if url.starts_with("file://") {
+            fs::read_to_string(path_in_project_root!(url.strip_prefix("file://").unwrap()))?
         } else if url.starts_with("string://") {
-            Ok(url.strip_prefix("string://").unwrap().to_string())
+            url.strip_prefix("string://").unwrap().to_string()
         } else {
-            Err(Error::HTTPDriverError("invalid url".to_string()))
-        }
+            return Err(Error::HTTPDriverError("invalid url".to_string()));
+        };
+
+        Ok(HttpResponse {
+            body,
+            ..Default::default()
+        })
     }
 }
 
@@ -43,9 +87,13 @@ impl HttpDriver for TestHttpDriver {
 pub struct HeaderTestHttpDriver;
 
 impl HttpDriver for HeaderTestHttpDriver {
-    async fn get(_url: &str, headers: HttpHeaders<'_>) -> Result<String, Error> {
-        match headers {
-            HttpHeaders::NoHeaders => Ok("NoHeaders".to_string()),
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let body = match spec.headers {
+            HttpHeaders::NoHeaders => "NoHeaders".to_string(),
             HttpHeaders::Headers(hash_map) => {
                 let mut keyvals = hash_map
                     .iter()
@@ -62,8 +110,276 @@ impl HttpDriver for HeaderTestHttpDriver {
                 result.push(&keyvals);
                 result.push("})");
 
-                Ok(result.join(""))
+                result.join("")
             }
+        };
+
+        Ok(HttpResponse {
+            body,
+            ..Default::default()
+        })
+    }
+}
+
+/// The ProxyTestHttpDriver returns the proxy used for each request, or `"direct"` if none.
+#[derive(Debug, Clone)]
+pub struct ProxyTestHttpDriver;
+
+impl HttpDriver for ProxyTestHttpDriver {
+    async fn request(
+        _spec: RequestSpec<'_>,
+        proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        Ok(HttpResponse {
+            body: proxy.unwrap_or("direct").to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+/// The ResolveTestHttpDriver returns the addresses the driver was asked to connect to instead of
+/// resolving hosts, as `host=addr` sorted by host and separated by `", "`.
+#[derive(Debug, Clone)]
+pub struct ResolveTestHttpDriver;
+
+impl HttpDriver for ResolveTestHttpDriver {
+    async fn request(
+        _spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let mut resolve = resolve
+            .iter()
+            .map(|(host, addr)| format!("{host}={addr}"))
+            .collect::<Vec<_>>();
+
+        resolve.sort();
+
+        Ok(HttpResponse {
+            body: resolve.join(", "),
+            ..Default::default()
+        })
+    }
+}
+
+/// The ContentTypeTestHttpDriver responds to `content-type://<type>` with an empty JSON object
+/// and a `Content-Type` header of `<type>`, and to other URLs without a `Content-Type` header.
+#[derive(Debug, Clone)]
+pub struct ContentTypeTestHttpDriver;
+
+impl HttpDriver for ContentTypeTestHttpDriver {
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        Ok(HttpResponse {
+            body: "{}".to_string(),
+            headers: match spec.url.strip_prefix("content-type://") {
+                Some(content_type) => {
+                    HashMap::unit("content-type".to_string(), content_type.to_string())
+                }
+                None => HashMap::new(),
+            },
+            ..Default::default()
+        })
+    }
+}
+
+/// The MultipartTestHttpDriver responds to multipart POST requests with the URL followed by the
+/// fields of the form, one per line, as `name=value` for text fields and `name@path` for files,
+/// and to other requests with the URL.
+#[derive(Debug, Clone)]
+pub struct MultipartTestHttpDriver;
+
+impl HttpDriver for MultipartTestHttpDriver {
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let RequestBody::Multipart(form) = spec.body else {
+            return Ok(HttpResponse {
+                body: spec.url.to_string(),
+                ..Default::default()
+            });
+        };
+
+        let mut lines = vec![spec.url.to_string()];
+
+        lines.extend(
+            form.fields
+                .iter()
+                .map(|(name, value)| format!("{name}={value}")),
+        );
+        lines.extend(
+            form.files
+                .iter()
+                .map(|(name, path)| format!("{name}@{path}")),
+        );
+
+        Ok(HttpResponse {
+            body: lines.join("\n"),
+            ..Default::default()
+        })
+    }
+}
+
+/// The MethodTestHttpDriver records requests by responding with the method and URL of the
+/// request, followed by the body of the request on a line of its own, if any.
+#[derive(Debug, Clone)]
+pub struct MethodTestHttpDriver;
+
+impl HttpDriver for MethodTestHttpDriver {
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let mut body = format!("{} {}", spec.method, spec.url);
+
+        if let RequestBody::Text(request_body) = spec.body {
+            body.push('\n');
+            body.push_str(request_body);
         }
+
+        Ok(HttpResponse {
+            body,
+            ..Default::default()
+        })
+    }
+}
+
+/// The number of times [RobotsTestHttpDriver] has served `http://robots.test/robots.txt`.
+pub static ROBOTS_TXT_FETCHES: AtomicU32 = AtomicU32::new(0);
+
+/// The number of times [RobotsTestHttpDriver] has been asked for
+/// `http://unavailable.test/robots.txt`.
+pub static UNAVAILABLE_ROBOTS_TXT_FETCHES: AtomicU32 = AtomicU32::new(0);
+
+/// The RobotsTestHttpDriver serves a `robots.txt` disallowing `/private` for everyone and
+/// everything for `BadBot`, except for hosts starting with `norobots`, which respond with 404,
+/// hosts starting with `unavailable`, which respond with 503, and hosts starting with
+/// `unreachable`, which fail. Other requests return the requested URL.
+#[derive(Debug, Clone)]
+pub struct RobotsTestHttpDriver;
+
+impl HttpDriver for RobotsTestHttpDriver {
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let url = spec.url;
+
+        let status = if url.starts_with("http://norobots") && url.ends_with("/robots.txt") {
+            Some(404)
+        } else if url.starts_with("http://unavailable") && url.ends_with("/robots.txt") {
+            if url == "http://unavailable.test/robots.txt" {
+                UNAVAILABLE_ROBOTS_TXT_FETCHES.fetch_add(1, SeqCst);
+            }
+
+            Some(503)
+        } else if url.starts_with("http://unreachable") {
+            return Err(Error::HTTPDriverError("connection refused".to_string()));
+        } else {
+            Some(200)
+        };
+
+        let body = if status != Some(200) {
+            String::new()
+        } else if url.ends_with("/robots.txt") {
+            if url == "http://robots.test/robots.txt" {
+                ROBOTS_TXT_FETCHES.fetch_add(1, SeqCst);
+            }
+
+            "User-agent: *\nDisallow: /private\n\nUser-agent: BadBot\nDisallow: /\n".to_string()
+        } else {
+            url.to_string()
+        };
+
+        Ok(HttpResponse {
+            body,
+            headers: HashMap::new(),
+            status,
+        })
+    }
+}
+
+/// The number of times [FlakyTestHttpDriver] has been asked for each URL.
+static FLAKY_FETCHES: LazyLock<Mutex<std::collections::HashMap<String, u32>>> =
+    LazyLock::new(|| Mutex::new(std::collections::HashMap::new()));
+
+/// The number of times [FlakyTestHttpDriver] has been asked for `url`.
+pub fn flaky_fetches(url: &str) -> u32 {
+    FLAKY_FETCHES.lock().unwrap().get(url).copied().unwrap_or(0)
+}
+
+/// The FlakyTestHttpDriver supports URLs of the form `flaky://<n>/<content>`, returning an empty
+/// string the first `<n>` times a URL is requested and the string `<content>` after that.
+#[derive(Debug, Clone)]
+pub struct FlakyTestHttpDriver;
+
+impl HttpDriver for FlakyTestHttpDriver {
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let url = spec.url;
+
+        let Some((failures, content)) = url
+            .strip_prefix("flaky://")
+            .and_then(|rest| rest.split_once('/'))
+        else {
+            return Err(Error::HTTPDriverError("invalid url".to_string()));
+        };
+
+        let failures = failures.parse::<u32>()?;
+
+        let fetches = {
+            let mut fetches = FLAKY_FETCHES.lock().unwrap();
+            let count = fetches.entry(url.to_string()).or_insert(0);
+            *count += 1;
+            *count
+        };
+
+        Ok(HttpResponse {
+            body: if fetches > failures {
+                content.to_string()
+            } else {
+                String::new()
+            },
+            ..Default::default()
+        })
+    }
+}
+
+/// The SlowTestHttpDriver supports URLs of the form `slow://<millis>/<content>`, returning the
+/// string `<content>` after sleeping for `<millis>` milliseconds.
+#[derive(Debug, Clone)]
+pub struct SlowTestHttpDriver;
+
+impl HttpDriver for SlowTestHttpDriver {
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let Some((millis, content)) = spec
+            .url
+            .strip_prefix("slow://")
+            .and_then(|rest| rest.split_once('/'))
+        else {
+            return Err(Error::HTTPDriverError("invalid url".to_string()));
+        };
+
+        tokio::time::sleep(Duration::from_millis(millis.parse::<u64>()?)).await;
+
+        Ok(HttpResponse {
+            body: content.to_string(),
+            ..Default::default()
+        })
     }
 }
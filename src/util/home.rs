@@ -0,0 +1,95 @@
+use std::{env, path::PathBuf};
+
+use crate::Error;
+
+/// The path of the user's home directory, falling back to the `HOME` and `USERPROFILE`
+/// environment variables when the platform is unable to tell, e.g. in some containers.
+pub fn home_dir() -> Result<String, Error> {
+    home_dir_from(dirs::home_dir(), |name| env::var(name).ok())
+}
+
+fn home_dir_from(
+    home: Option<PathBuf>,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> Result<String, Error> {
+    home.and_then(|home| home.to_str().map(|home| home.to_string()))
+        .or_else(|| {
+            ["HOME", "USERPROFILE"]
+                .into_iter()
+                .find_map(|name| env_var(name).filter(|value| !value.is_empty()))
+        })
+        .ok_or(Error::HomeDirNotFoundError)
+}
+
+/// Replace `${HOME}` in `text` with the path of the user's home directory. Fails only if `text`
+/// contains `${HOME}` and the home directory cannot be found.
+pub fn substitute_home(text: &str) -> Result<String, Error> {
+    substitute_home_with(text, home_dir)
+}
+
+fn substitute_home_with(
+    text: &str,
+    home_dir: impl FnOnce() -> Result<String, Error>,
+) -> Result<String, Error> {
+    if text.contains("${HOME}") {
+        Ok(text.replace("${HOME}", &home_dir()?))
+    } else {
+        Ok(text.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_home_dir_from() {
+        let env_var = |name: &str| match name {
+            "HOME" => Some("/home/env".to_string()),
+            "USERPROFILE" => Some("C:\\Users\\env".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            home_dir_from(Some(PathBuf::from("/home/cat")), env_var).unwrap(),
+            "/home/cat"
+        );
+        assert_eq!(home_dir_from(None, env_var).unwrap(), "/home/env");
+
+        let windows_env_var = |name: &str| env_var(name).filter(|_| name == "USERPROFILE");
+
+        assert_eq!(
+            home_dir_from(None, windows_env_var).unwrap(),
+            "C:\\Users\\env"
+        );
+
+        // Empty variables are ignored
+        assert!(matches!(
+            home_dir_from(None, |_| Some(String::new())),
+            Err(Error::HomeDirNotFoundError)
+        ));
+        assert!(matches!(
+            home_dir_from(None, |_| None),
+            Err(Error::HomeDirNotFoundError)
+        ));
+    }
+
+    #[test]
+    fn test_substitute_home() {
+        let home = || Ok("/home/cat".to_string());
+        let missing = || Err(Error::HomeDirNotFoundError);
+
+        assert_eq!(
+            substitute_home_with("${HOME}/scripts/${NAME}", home).unwrap(),
+            "/home/cat/scripts/${NAME}"
+        );
+        assert_eq!(
+            substitute_home_with("/var/scripts", missing).unwrap(),
+            "/var/scripts"
+        );
+        assert!(matches!(
+            substitute_home_with("${HOME}/scripts", missing),
+            Err(Error::HomeDirNotFoundError)
+        ));
+    }
+}
@@ -1 +1,5 @@
 pub mod boundedu8;
+pub mod home;
+pub mod rng;
+pub mod sitemap;
+pub mod timeofday;
@@ -0,0 +1,95 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The seed given to [set_default_seed], if any.
+static DEFAULT_SEED: OnceLock<u64> = OnceLock::new();
+
+/// Make every [Rng] subsequently created using [Rng::from_default_seed] start from `seed`,
+/// making all randomized operations reproducible. The default seed can only be set once per
+/// process, and later calls are ignored.
+pub fn set_default_seed(seed: u64) {
+    let _ = DEFAULT_SEED.set(seed);
+}
+
+/// A small, fast pseudo-random number generator (SplitMix64). Not suitable for cryptographic
+/// purposes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    /// Create a generator starting from the seed given to [set_default_seed], or from a
+    /// randomly chosen seed if no default seed has been set.
+    pub fn from_default_seed() -> Rng {
+        Rng::new(DEFAULT_SEED.get().copied().unwrap_or_else(entropy))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in the range `0..n`, or 0 if `n` is 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            0
+        } else {
+            (self.next_u64() % n as u64) as usize
+        }
+    }
+}
+
+/// A seed that differs between calls and processes.
+fn entropy() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default(),
+    );
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_reproducible() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+
+        let a_values = (0..10).map(|_| a.next_u64()).collect::<Vec<_>>();
+        let b_values = (0..10).map(|_| b.next_u64()).collect::<Vec<_>>();
+        let c_values = (0..10).map(|_| c.next_u64()).collect::<Vec<_>>();
+
+        assert_eq!(a_values, b_values);
+        assert_ne!(a_values, c_values);
+    }
+
+    #[test]
+    fn test_rng_below() {
+        let mut rng = Rng::new(0);
+
+        assert_eq!(rng.below(0), 0);
+        assert_eq!(rng.below(1), 0);
+        assert!((0..1000).all(|_| rng.below(7) < 7));
+    }
+}
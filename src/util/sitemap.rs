@@ -0,0 +1,118 @@
+use quick_xml::{Reader, events::Event};
+
+use crate::Error;
+
+/// The contents of an XML sitemap, see <https://www.sitemaps.org/protocol.html>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sitemap {
+    /// A `<urlset>`, with the URLs of the pages it lists.
+    UrlSet(Vec<String>),
+
+    /// A `<sitemapindex>`, with the URLs of the sitemaps it lists.
+    Index(Vec<String>),
+}
+
+/// Parse an XML sitemap or sitemap index, collecting the `<loc>` of each `<url>` or `<sitemap>`
+/// entry. Other elements, including `<loc>` elements of extensions such as `<image:loc>`, are
+/// ignored.
+pub fn parse_sitemap(text: &str) -> Result<Sitemap, Error> {
+    let mut reader = Reader::from_str(text);
+    reader.config_mut().trim_text(true);
+
+    let mut root: Option<Vec<u8>> = None;
+    let mut depth = 0;
+    let mut loc: Option<String> = None;
+    let mut locs = vec![];
+
+    loop {
+        match reader
+            .read_event()
+            .map_err(|e| Error::ParseError(e.to_string()))?
+        {
+            Event::Start(element) => {
+                depth += 1;
+
+                if depth == 1 {
+                    root = Some(element.name().as_ref().to_vec());
+                } else if depth == 3 && element.name().as_ref() == b"loc" {
+                    loc = Some(String::new());
+                }
+            }
+            Event::Empty(element) if depth == 0 => {
+                root = Some(element.name().as_ref().to_vec());
+            }
+            Event::End(_) => {
+                if depth == 3
+                    && let Some(loc) = loc.take()
+                {
+                    locs.push(loc.trim().to_string());
+                }
+
+                depth -= 1;
+            }
+            Event::Text(text) => {
+                if let Some(loc) = loc.as_mut() {
+                    loc.push_str(
+                        &text
+                            .unescape()
+                            .map_err(|e| Error::ParseError(e.to_string()))?,
+                    );
+                }
+            }
+            Event::CData(data) => {
+                if let Some(loc) = loc.as_mut() {
+                    loc.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    match root.as_deref() {
+        Some(b"urlset") => Ok(Sitemap::UrlSet(locs)),
+        Some(b"sitemapindex") => Ok(Sitemap::Index(locs)),
+        _ => Err(Error::ParseError("not a sitemap".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sitemap() {
+        assert_eq!(
+            parse_sitemap(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+                <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+                        xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">
+                    <url><loc> https://a.test/?x=1&amp;y=2 </loc><lastmod>2024-01-01</lastmod></url>
+                    <url>
+                        <loc><![CDATA[https://a.test/b]]></loc>
+                        <image:image><image:loc>https://a.test/b.jpg</image:loc></image:image>
+                    </url>
+                </urlset>"#
+            )
+            .unwrap(),
+            Sitemap::UrlSet(vec![
+                "https://a.test/?x=1&y=2".to_string(),
+                "https://a.test/b".to_string()
+            ])
+        );
+
+        assert_eq!(
+            parse_sitemap(
+                "<sitemapindex><sitemap><loc>https://a.test/1.xml</loc></sitemap></sitemapindex>"
+            )
+            .unwrap(),
+            Sitemap::Index(vec!["https://a.test/1.xml".to_string()])
+        );
+
+        assert_eq!(parse_sitemap("<urlset/>").unwrap(), Sitemap::UrlSet(vec![]));
+
+        assert!(parse_sitemap("").is_err());
+        assert!(parse_sitemap("<html><body>hi</body></html>").is_err());
+        assert!(parse_sitemap("<urlset><url><loc>x</url></urlset>").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Local, NaiveTime, Timelike};
+
+use crate::Error;
+
+/// The format used for dates and times when no format is given, e.g. `2024-06-01`.
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d";
+
+/// Parse a time of day given as `HH:MM`.
+pub fn parse_time_of_day(text: &str) -> Result<NaiveTime, Error> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M")
+        .map_err(|_| Error::ParseError(format!("invalid time of day `{text}`, expected HH:MM")))
+}
+
+/// Whether `when` is within the window from `start` up to but not including `end`, to the minute.
+/// A window whose end is before its start crosses midnight, and a window whose start and end are
+/// equal covers the whole day.
+pub fn is_time_between_at(start: NaiveTime, end: NaiveTime, when: NaiveTime) -> bool {
+    let when = when
+        .with_second(0)
+        .and_then(|when| when.with_nanosecond(0))
+        .expect("Should be able to truncate time to the minute");
+
+    if start < end {
+        start <= when && when < end
+    } else if start > end {
+        start <= when || when < end
+    } else {
+        true
+    }
+}
+
+/// Format `when` according to the `strftime`-style `format`, such as `%Y-%m-%d %H:%M`.
+pub fn format_time(when: &DateTime<Local>, format: &str) -> Result<String, Error> {
+    let mut text = String::new();
+
+    write!(text, "{}", when.format(format))
+        .map_err(|_| Error::ParseError(format!("invalid time format `{format}`")))?;
+
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(text: &str) -> NaiveTime {
+        parse_time_of_day(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!(time("09:30"), NaiveTime::from_hms_opt(9, 30, 0).unwrap());
+        assert_eq!(time("9:30"), NaiveTime::from_hms_opt(9, 30, 0).unwrap());
+        assert_eq!(time("23:59"), NaiveTime::from_hms_opt(23, 59, 0).unwrap());
+
+        assert!(parse_time_of_day("24:00").is_err());
+        assert!(parse_time_of_day("12:60").is_err());
+        assert!(parse_time_of_day("noon").is_err());
+        assert!(parse_time_of_day("12").is_err());
+        assert!(parse_time_of_day("").is_err());
+    }
+
+    #[test]
+    fn test_is_time_between_at() {
+        let between = |start, end, when| is_time_between_at(time(start), time(end), time(when));
+
+        assert!(between("09:00", "17:00", "09:00"));
+        assert!(between("09:00", "17:00", "12:34"));
+        assert!(between("09:00", "17:00", "16:59"));
+        assert!(!between("09:00", "17:00", "17:00"));
+        assert!(!between("09:00", "17:00", "08:59"));
+        assert!(!between("09:00", "17:00", "00:00"));
+
+        // Crossing midnight
+        assert!(between("22:00", "06:00", "22:00"));
+        assert!(between("22:00", "06:00", "23:59"));
+        assert!(between("22:00", "06:00", "00:00"));
+        assert!(between("22:00", "06:00", "05:59"));
+        assert!(!between("22:00", "06:00", "06:00"));
+        assert!(!between("22:00", "06:00", "12:00"));
+        assert!(!between("22:00", "06:00", "21:59"));
+
+        // Whole day
+        assert!(between("00:00", "00:00", "00:00"));
+        assert!(between("12:00", "12:00", "11:59"));
+
+        // Seconds within the last minute of the window
+        assert!(is_time_between_at(
+            time("09:00"),
+            time("17:00"),
+            NaiveTime::from_hms_opt(16, 59, 59).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_format_time() {
+        let when = Local.with_ymd_and_hms(2024, 6, 1, 9, 5, 7).unwrap();
+
+        assert_eq!(
+            format_time(&when, DEFAULT_TIME_FORMAT).unwrap(),
+            "2024-06-01"
+        );
+        assert_eq!(
+            format_time(&when, "/reports/%Y%m%d-%H%M%S.json").unwrap(),
+            "/reports/20240601-090507.json"
+        );
+        assert_eq!(format_time(&when, "no time").unwrap(), "no time");
+
+        assert!(format_time(&when, "%Q").is_err());
+        assert!(format_time(&when, "%Y-%").is_err());
+    }
+}
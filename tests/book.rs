@@ -59,6 +59,7 @@ use std::{
     collections::HashMap,
     env,
     fs::{read_dir, read_to_string},
+    net::SocketAddr,
     sync::{Arc, RwLock},
 };
 
@@ -70,7 +71,7 @@ use libscrapeycat::{
     Error,
     effect::EffectInvocation,
     scrapelang::program::run,
-    scraper::{HttpDriver, HttpHeaders},
+    scraper::{HttpDriver, HttpHeaders, HttpResponse, RequestSpec},
     testutils::path_in_project_root,
 };
 
@@ -163,7 +164,7 @@ impl TestState {
 }
 
 thread_local! {
-    static TEST_STATE: RefCell<Option<TestState>> = RefCell::new(None);
+    static TEST_STATE: RefCell<Option<TestState>> = const { RefCell::new(None) };
 }
 
 fn script_loader(_name: &str) -> Result<String, Error> {
@@ -174,17 +175,24 @@ fn script_loader(_name: &str) -> Result<String, Error> {
 struct BookTestHttpDriver;
 
 impl HttpDriver for BookTestHttpDriver {
-    async fn get(_url: &str, headers: HttpHeaders<'_>) -> Result<String, Error> {
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &im::HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
         TEST_STATE.with(|state| {
             state
                 .borrow_mut()
                 .as_mut()
                 .unwrap()
                 .headers_seen
-                .push(StringifiedHeaders::new(&headers))
+                .push(StringifiedHeaders::new(&spec.headers))
         });
 
-        Ok(TEST_STATE.with(|state| state.borrow().as_ref().unwrap().input.clone()))
+        Ok(HttpResponse {
+            body: TEST_STATE.with(|state| state.borrow().as_ref().unwrap().input.clone()),
+            ..Default::default()
+        })
     }
 }
 
@@ -201,7 +209,7 @@ async fn run_test(script: String, spec: TestSpec) {
     let result = run::<BookTestHttpDriver>(
         "",
         spec.args.unwrap_or(vec![]),
-        spec.kwargs.unwrap_or(HashMap::new()),
+        spec.kwargs.unwrap_or_default(),
         Arc::new(RwLock::new(script_loader)),
         effect_sender,
     )
@@ -10,6 +10,7 @@ use libscrapeycat::{
     Error,
     effect::EffectInvocation,
     scrapelang::program::run,
+    scraper::Scraper,
     testutils::{TestHttpDriver, path_in_project_root},
 };
 
@@ -59,6 +60,65 @@ async fn test_bbc_science() {
     test!("bbc-science");
 }
 
+/// Each command of a script applies the scraper operation of the same name, so running a script
+/// gives the same results as applying the operations directly, without Lua.
+#[tokio::test]
+async fn test_bbc_science_matches_scraper_operations() {
+    let (effect_sender, _effect_receiver) =
+        tokio::sync::mpsc::unbounded_channel::<EffectInvocation>();
+
+    let script_results = run::<TestHttpDriver>(
+        "bbc-science",
+        vec![],
+        HashMap::new(),
+        Arc::new(RwLock::new(tests_script_loader)),
+        effect_sender,
+    )
+    .await
+    .unwrap();
+
+    let scraper = Scraper::<TestHttpDriver>::new()
+        .get("file://tests/assets/scripts/bbc-science.input")
+        .await
+        .unwrap()
+        .extract("(?s)<item>(.+?)</item>")
+        .unwrap()
+        .retain("news/articles")
+        .unwrap()
+        .extract("(?s)<title>(.+?)</title>")
+        .unwrap()
+        .extract("(?s)CDATA\\[(.+?)\\]\\]")
+        .unwrap();
+
+    assert!(!script_results.is_empty());
+    assert_eq!(&script_results, scraper.results());
+}
+
+#[tokio::test]
+async fn test_run_with_canned_responses() {
+    let (effect_sender, _effect_receiver) =
+        tokio::sync::mpsc::unbounded_channel::<EffectInvocation>();
+
+    TestHttpDriver::set_response("https://shop.test/", "<b>Catnip</b> <b>Yarn</b>");
+
+    let results = run::<TestHttpDriver>(
+        "shop",
+        vec![],
+        HashMap::new(),
+        Arc::new(RwLock::new(|_name: &str| {
+            Ok(r#"get("https://shop.test/") extract("<b>(.+?)</b>")"#.to_string())
+        })),
+        effect_sender,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        results,
+        vec!["Catnip".to_string(), "Yarn".to_string()].into()
+    );
+}
+
 #[tokio::test]
 async fn test_results_as_implicit_args_for_run() {
     test!("results-as-implicit-args-for-run");
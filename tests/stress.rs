@@ -1,5 +1,6 @@
 use std::{
     collections::{HashMap, VecDeque},
+    net::SocketAddr,
     sync::{
         Arc, RwLock,
         atomic::{AtomicUsize, Ordering},
@@ -13,7 +14,7 @@ use libscrapeycat::{
     Error,
     effect::EffectInvocation,
     scrapelang::program::run,
-    scraper::{HttpDriver, HttpHeaders},
+    scraper::{HttpDriver, HttpResponse, RequestSpec},
 };
 use regex::Regex;
 use tokio::{sync::mpsc::unbounded_channel, time::sleep};
@@ -24,14 +25,25 @@ struct StressTestHttpDriver;
 impl HttpDriver for StressTestHttpDriver {
     /// This driver receives `get("X,Y")` where X and Y are numbers, and returns the string X
     /// after sleeping for Y milliseconds.
-    async fn get(url: &str, _headers: HttpHeaders<'_>) -> Result<String, Error> {
-        let captures = Regex::new("^(\\d+),(\\d+)").unwrap().captures(url).unwrap();
+    async fn request(
+        spec: RequestSpec<'_>,
+        _proxy: Option<&str>,
+        _resolve: &im::HashMap<String, SocketAddr>,
+    ) -> Result<HttpResponse, Error> {
+        let captures = Regex::new("^(\\d+),(\\d+)")
+            .unwrap()
+            .captures(spec.url)
+            .unwrap();
 
         let result = captures.get(1).unwrap().as_str().to_string();
         let sleep_duration_millis = captures.get(2).unwrap().as_str().parse::<u64>().unwrap();
 
         sleep(Duration::from_millis(sleep_duration_millis)).await;
-        Ok(result)
+
+        Ok(HttpResponse {
+            body: result,
+            ..Default::default()
+        })
     }
 }
 